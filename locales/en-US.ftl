@@ -12,15 +12,173 @@ home-notfound = Home directory not found.
 fail-contact = Failed to retrieve contact information.
 field-error = Failed to retrieve fields.
 auth-error = Authentication failed.
-input-eror = Failed to input.
+input-error = Failed to input.
 no-option = Please specify an option.
 fail-google-contacts = Unable to retrieve data from Google Contacts.
 fail-addressbook = Unable to retrieve data from .addressbook.
-update-mode = The data is being updated. Please enter [g] if you prefer the data from Google Contacts, or enter [a] if you prefer the data from .addressbook.
+update-mode = The data differs. Enter [g] to use the data from Google Contacts, [a] to use the data from .addressbook, [m] to choose each field, [e] to edit both in $EDITOR, [s] to skip, or [q] to cancel. Enter [G], [A] or [S] to apply the choice to all remaining contacts, including those that exist on only one side.
 update-done = The contact information has been updated.
 update-error = An error occurred while updating the contacts.
 update-success-google-contacts = Updated the data for Google Contacts.
 update-fail-google-contacts = Failed to update the data for Google Contacts
-add-g-or-delete-a-mode = Please input [a] if you want to delete data that exists only in .addressbook, or input [g] if you want to add it to Google Contacts.
-add-a-or-delete-g-mode = Please input [g] if you want to delete data that exists only in Google Contacts, or input [a] if you want to add it to .addressbook.
+add-g-or-delete-a-mode = Please input [a] if you want to delete data that exists only in .addressbook, or input [g] if you want to add it to Google Contacts. Enter [q] to cancel.
+add-a-or-delete-g-mode = Please input [g] if you want to delete data that exists only in Google Contacts, or input [a] if you want to add it to .addressbook. Enter [q] to cancel.
 write-complete = Address book writing has been completed.
+app-usage-auth-status = Show the account, scopes and expiry of the cached token
+app-usage-auth-revoke = Revoke the cached token at Google and delete the token cache
+auth-status-none = No cached token. Run init or sync to authenticate.
+auth-status-error = Failed to read the token cache.
+auth-status-account = Account
+auth-status-scopes = Scopes
+auth-status-expiry = Expires at
+auth-status-expired = expired
+auth-status-refresh = Refresh token
+revoke-complete = The token has been revoked and the token cache has been deleted.
+revoke-error = Failed to revoke the token.
+app-usage-strict = Refuse to run when the credentials or token cache are readable by other users
+app-usage-offline = Do not access Google Contacts; use the contacts cached by the last fetch and queue changes made by add, edit and rm until the next sync
+migrated-file = Moved from the old configuration directory
+migrate-error = Failed to migrate the old configuration directory
+app-usage-config-show = Show the effective configuration (defaults merged with config.toml)
+config-error = Invalid configuration
+backup-error = Failed to back up the address book
+app-usage-config = Read the configuration from PATH instead of config.toml in the configuration directory
+app-usage-account = Use the token cache of the account NAME (overrides google.account)
+app-usage-addressbook = Use the address book at PATH (overrides addressbook.path)
+app-usage-verbose = Show detailed progress messages
+app-usage-quiet = Show only errors and prompts
+app-usage-yes = Answer yes to confirmation prompts
+app-usage-help = Show this help, or the help of a command (also: help COMMAND)
+help-usage = Usage
+help-commands = Commands
+help-options = Options
+help-global-options = Global options
+cli-incomplete-command = Missing subcommand for
+cli-unknown-command = Unknown command
+cli-unknown-option = Unknown option
+cli-missing-value = Missing value for option
+cli-unexpected-value = Option does not take a value
+cli-missing-argument = Missing argument
+cli-unexpected-argument = Unexpected argument
+cli-conflicting-options = Options cannot be used together
+cli-help-hint = Run "gcontacts --help" for usage.
+config-loaded = Loaded configuration
+config-defaults = No configuration file; using the defaults.
+fetched-contacts = Contacts fetched from Google Contacts
+backup-created = Backed up the address book to
+app-usage-init-merge = Keep the existing entries, lists and Fcc values, and only add Google contacts whose address is missing
+merge-kept = Entries kept from the address book
+merge-added = Entries added from Google Contacts
+app-usage-report = Print a summary of the run in FORMAT (json) to standard error
+app-usage-report-file = Write the summary of the run to PATH instead of standard error (implies --report json)
+cli-invalid-value = Invalid option value
+app-usage-diff = Show field-level differences between $HOME/.addressbook and Google Contacts without changing either
+app-usage-diff-format = Output format: text (colored unified diff, default) or json
+diff-none = No differences.
+diff-error = Failed to write the differences
+app-usage-status = Show the last sync time, contact counts, local edits since the last sync and token validity
+state-error = Failed to save or read the sync state
+status-addressbook = Address book
+status-last-sync = Last sync
+status-never = never
+status-addressbook-contacts = Contacts in the address book
+status-google-contacts = Contacts in Google Contacts
+status-pending = Local edits since the last sync
+status-added = added
+status-modified = modified
+status-removed = removed
+status-token = Token
+status-token-valid = valid
+status-token-refreshable = expired (will be refreshed on the next run)
+status-token-expired = expired (authentication required)
+status-token-none = none (authentication required)
+status-cache = Contacts cache
+status-outbox = Pending offline changes
+invalid-input = Invalid input. Please enter [g], [a] or [q].
+invalid-input-conflict = Invalid input. Please enter [g], [a], [m], [e], [s], [q], [G], [A] or [S].
+merge-field = Field
+merge-field-prompt = Enter [g] or [a] to use that value, "=" followed by a new value (e.g. =Taro), [s] to skip this contact, or [q] to cancel.
+invalid-input-field = Invalid input. Please enter [g], [a], =VALUE, [s] or [q].
+invalid-field-value = Invalid value. Values cannot contain tabs, and a nickname or email must be non-empty without spaces.
+app-usage-sync-tui = Review every planned change in a full-screen table before applying it
+tui-not-terminal = --tui requires a terminal for standard input and output.
+tui-error = Failed to run the terminal UI.
+tui-title = gcontacts sync: review changes
+tui-column-kind = Kind
+tui-column-action = Change
+tui-column-email = Email
+tui-kind-google-only = Google only
+tui-kind-addressbook-only = .addressbook only
+tui-kind-conflict = Conflict
+tui-action-undecided = (undecided)
+tui-action-skip = Skip
+tui-action-add-google = Add to Google
+tui-action-update-google = Update Google
+tui-action-delete-google = Delete from Google
+tui-action-add-addressbook = Add to .addressbook
+tui-action-update-addressbook = Update .addressbook
+tui-action-delete-addressbook = Delete from .addressbook
+tui-help = ↑↓/jk move  y accept  n reject  g Google  a .addressbook  u undo  Y accept rest  Enter confirm  q quit
+tui-confirm-title = Apply the following changes? Undecided items are not changed.
+tui-confirm-prompt = Enter [y] to apply, [q] to quit without changes, or any other key to return to the list.
+edit-header = Edit the values and save the file. The saved values are written to both Google Contacts and .addressbook. Lines starting with # are ignored.
+edit-error = Failed to run the editor
+edit-invalid = The edited contact is invalid
+edit-retry = Edit again? [Y/n]
+app-usage-add = Add a contact to both Google Contacts and .addressbook
+app-usage-edit = Edit the contact NICK in both Google Contacts and .addressbook (opens $EDITOR unless field options are given)
+app-usage-rm = Delete the contact NICK from both Google Contacts and .addressbook
+app-usage-show = Show the contact NICK in .addressbook and Google Contacts
+app-usage-query = Search cached contacts by nickname, name, email or organization and print them in mutt's query format
+cache-error = Failed to save or read the contacts cache
+app-usage-refresh = Fetch the contacts from Google Contacts and update the cache without changing .addressbook
+cache-missing = No contacts cache. Run `gcontacts refresh` while online first.
+cache-loaded = Using the contacts cache fetched at
+cache-refreshed = Updated the contacts cache. Contacts
+offline-unsupported = --offline cannot be used with this command
+outbox-error = Failed to save or read the pending offline changes
+outbox-queued = Google Contacts will be updated by the next online sync.
+outbox-replaying = Applying pending offline changes
+outbox-replayed = Applied the pending change to Google Contacts
+outbox-replay-error = Failed to apply a pending change to Google Contacts (it will be retried by the next sync)
+outbox-conflict-exists = Discarded a pending change because a contact with this email address was added in Google Contacts
+outbox-conflict-changed = Discarded a pending change because the contact was changed in Google Contacts
+outbox-conflict-deleted = Discarded a pending change because the contact was deleted in Google Contacts
+app-usage-contact-name = Set the name
+app-usage-contact-email = Set the email address (required for add)
+app-usage-contact-nick = Set the nickname (add: generated from the name by default)
+app-usage-contact-fcc = Set the Fcc (add: fields.fcc by default)
+app-usage-contact-comment = Set the comment
+contact-not-found = No contact with this nickname in .addressbook
+contact-exists = A contact with this email address already exists
+nickname-taken = This nickname is already used by another contact
+contact-added = Added the contact
+contact-updated = Updated the contact
+contact-removed = Deleted the contact
+contact-unchanged = The contact was not changed.
+rm-confirm = Delete this contact from Google Contacts and .addressbook? [y/N]
+show-not-in-google = not found
+cli-missing-option = Missing required option
+app-usage-export = Write the Google contacts to a file in another application's format without changing either side
+app-usage-export-format = Output format: vcard (default), mutt-aliases, abook, vdir, ldif, google-csv, json or ndjson
+app-usage-export-vcard-version = vCard version: 4.0 (RFC 6350, default) or 3.0 (RFC 2426)
+app-usage-export-output = Write to the file PATH instead of standard output
+app-usage-export-output-dir = Write one file per contact into the directory DIR (vdir)
+app-usage-export-all = Also export the contacts excluded by labels.include and labels.exclude
+export-error = Failed to write the exported contacts
+export-written = Exported the contacts to
+app-usage-import = Create the contacts of a vCard (2.1, 3.0 or 4.0), Google CSV or JSON file in Google Contacts and add them to .addressbook
+app-usage-import-format = Input format: vcard (default), google-csv, json or ndjson
+app-usage-import-update = Also update the Google contacts that share an email address with a card (default: skip them)
+import-error = Failed to read the vCard file
+import-no-email = Skipped a card without an email address
+import-exists = Skipped a card matching an existing contact (use --update to update it)
+import-complete = Imported cards
+import-created = created
+import-updated = updated
+import-skipped = skipped
+import-rows-added = Rows added to .addressbook
+import-rows-updated = Rows updated in .addressbook
+export-output-dir-unsupported = The output format does not support writing a directory
+export-files-updated = Files written
+export-files-removed = Stale files removed
@@ -12,15 +12,173 @@ home-notfound = ホームディレクトリが見つかりません。
 fail-contact = 連絡先情報の取得に失敗しました。
 field-error = フィールドの取得に失敗しました。
 auth-error = 認証に失敗しました。
-input-eror = 入力に失敗しました。
+input-error = 入力に失敗しました。
 no-option = オプションを指定してください。
 fail-google-contacts = Google Contactsのデータを取得できません。
 fail-addressbook = .addressbookからデータを取得できません。
-update-mode = データが更新されています。Google Contactsのデータを優先する場合は[g]を、.addressbookのデータを優先する場合は[a]を入力してください。
+update-mode = データが異なります。Google Contactsのデータを優先する場合は[g]を、.addressbookのデータを優先する場合は[a]を、フィールドごとに選択する場合は[m]を、$EDITORで編集する場合は[e]を、スキップする場合は[s]を、中断する場合は[q]を入力してください。[G]、[A]、[S]を入力すると、一方にのみ存在する連絡先を含め、残りのすべての連絡先に同じ選択を適用します。
 update-done = 連絡先が更新されました。
 update-error = 連絡先の更新中にエラーが発生しました。
 update-success-google-contacts = Google Contactsのデータを更新しました。
 update-fail-google-contacts = Google Contactsのデータを更新に失敗しました。
-add-g-or-delete-a-mode = .addressbookのみに存在するデータがあります。.addressbookから削除する場合は[a]を、Google Contactsに追加する場合は[g]を入力してください。
-add-a-or-delete-g-mode = Google Contactsのみに存在するデータがあります。Google Contactsから削除する場合は[g]を、.addressbookに追加する場合は[a]を入力してください。
+add-g-or-delete-a-mode = .addressbookのみに存在するデータがあります。.addressbookから削除する場合は[a]を、Google Contactsに追加する場合は[g]を、中断する場合は[q]を入力してください。
+add-a-or-delete-g-mode = Google Contactsのみに存在するデータがあります。Google Contactsから削除する場合は[g]を、.addressbookに追加する場合は[a]を、中断する場合は[q]を入力してください。
 write-complete = アドレス帳の書き込みが終了しました。
+app-usage-auth-status = キャッシュされたトークンのアカウント、スコープ、有効期限を表示する
+app-usage-auth-revoke = キャッシュされたトークンをGoogleで失効させ、トークンキャッシュを削除する
+auth-status-none = キャッシュされたトークンがありません。initまたはsyncを実行して認証してください。
+auth-status-error = トークンキャッシュの読み込みに失敗しました。
+auth-status-account = アカウント
+auth-status-scopes = スコープ
+auth-status-expiry = 有効期限
+auth-status-expired = 期限切れ
+auth-status-refresh = リフレッシュトークン
+revoke-complete = トークンを失効させ、トークンキャッシュを削除しました。
+revoke-error = トークンの失効に失敗しました。
+app-usage-strict = 認証情報やトークンキャッシュが他のユーザーから読める場合は実行を中止する
+app-usage-offline = Google Contactsにアクセスせず、最後に取得した連絡先のキャッシュを使用する（add、edit、rmの変更は次のsyncまで送信待ちにする）
+migrated-file = 旧設定ディレクトリから移動しました
+migrate-error = 旧設定ディレクトリの移行に失敗しました
+app-usage-config-show = 既定値とconfig.tomlを組み合わせた、実際に使用される設定を表示する
+config-error = 設定に誤りがあります
+backup-error = アドレス帳のバックアップに失敗しました
+app-usage-config = 設定ディレクトリのconfig.tomlの代わりにPATHから設定を読み込む
+app-usage-account = アカウントNAMEのトークンキャッシュを使用する（google.accountより優先）
+app-usage-addressbook = PATHのアドレス帳を使用する（addressbook.pathより優先）
+app-usage-verbose = 詳細な進行状況を表示する
+app-usage-quiet = エラーと入力の確認のみを表示する
+app-usage-yes = 確認に対してすべて「はい」と答える
+app-usage-help = このヘルプ、またはコマンドのヘルプを表示する（help COMMANDも可）
+help-usage = 使用方法
+help-commands = コマンド
+help-options = オプション
+help-global-options = 共通オプション
+cli-incomplete-command = サブコマンドが指定されていません
+cli-unknown-command = 不明なコマンドです
+cli-unknown-option = 不明なオプションです
+cli-missing-value = オプションの値が指定されていません
+cli-unexpected-value = このオプションは値を取りません
+cli-missing-argument = 引数が指定されていません
+cli-unexpected-argument = 余分な引数があります
+cli-conflicting-options = 同時に指定できないオプションです
+cli-help-hint = 使用方法は "gcontacts --help" で確認できます。
+config-loaded = 設定を読み込みました
+config-defaults = 設定ファイルが無いため、既定値を使用します。
+fetched-contacts = Google Contactsから取得した連絡先の数
+backup-created = アドレス帳をバックアップしました
+app-usage-init-merge = 既存の行、リスト、Fccを残し、アドレス帳に無いGoogle Contactsの連絡先だけを追加する
+merge-kept = アドレス帳から残した行の数
+merge-added = Google Contactsから追加した行の数
+app-usage-report = 実行結果の概要をFORMAT（json）の形式で標準エラー出力に出力する
+app-usage-report-file = 実行結果の概要を標準エラー出力の代わりにPATHに書き込む（--report jsonを含む）
+cli-invalid-value = オプションの値が正しくありません
+app-usage-diff = $HOME/.addressbookとGoogle Contactsの差分をフィールド単位で表示する（どちらも変更しない）
+app-usage-diff-format = 出力形式: text（色付きのunified形式、既定）またはjson
+diff-none = 差分はありません。
+diff-error = 差分の出力に失敗しました
+app-usage-status = 最後の同期の時刻、連絡先の数、最後の同期の後のローカルの変更、トークンの有効性を表示する
+state-error = 同期の状態の保存または読み込みに失敗しました
+status-addressbook = アドレス帳
+status-last-sync = 最後の同期
+status-never = なし
+status-addressbook-contacts = アドレス帳の連絡先
+status-google-contacts = Google Contactsの連絡先
+status-pending = 最後の同期の後のローカルの変更
+status-added = 追加
+status-modified = 変更
+status-removed = 削除
+status-token = トークン
+status-token-valid = 有効
+status-token-refreshable = 期限切れ（次回の実行時に更新されます）
+status-token-expired = 期限切れ（認証が必要です）
+status-token-none = なし（認証が必要です）
+status-cache = 連絡先のキャッシュ
+status-outbox = 送信待ちのオフラインの変更
+invalid-input = 入力が正しくありません。[g]、[a]、[q]のいずれかを入力してください。
+invalid-input-conflict = 入力が正しくありません。[g]、[a]、[m]、[e]、[s]、[q]、[G]、[A]、[S]のいずれかを入力してください。
+merge-field = フィールド
+merge-field-prompt = その値を使用する場合は[g]または[a]を、新しい値を入力する場合は「=」に続けて値を（例: =Taro）、この連絡先をスキップする場合は[s]を、中断する場合は[q]を入力してください。
+invalid-input-field = 入力が正しくありません。[g]、[a]、=値、[s]、[q]のいずれかを入力してください。
+invalid-field-value = 値が正しくありません。値にタブを含めることはできず、ニックネームとメールアドレスは空白を含まない空でない値である必要があります。
+app-usage-sync-tui = 予定されているすべての変更を全画面の一覧で確認してから反映する
+tui-not-terminal = --tuiを使用するには、標準入力と標準出力が端末である必要があります。
+tui-error = 端末のUIの実行に失敗しました。
+tui-title = gcontacts sync: 変更の確認
+tui-column-kind = 種類
+tui-column-action = 変更
+tui-column-email = メールアドレス
+tui-kind-google-only = Googleのみ
+tui-kind-addressbook-only = .addressbookのみ
+tui-kind-conflict = 競合
+tui-action-undecided = （未選択）
+tui-action-skip = スキップ
+tui-action-add-google = Googleに追加
+tui-action-update-google = Googleを更新
+tui-action-delete-google = Googleから削除
+tui-action-add-addressbook = .addressbookに追加
+tui-action-update-addressbook = .addressbookを更新
+tui-action-delete-addressbook = .addressbookから削除
+tui-help = ↑↓/jk 移動  y 承認  n 拒否  g Google  a .addressbook  u 取消  Y 残りを承認  Enter 確認  q 中断
+tui-confirm-title = 次の変更を反映しますか？未選択の項目は変更しません。
+tui-confirm-prompt = 反映する場合は[y]を、変更せずに終了する場合は[q]を、一覧に戻る場合はその他のキーを入力してください。
+edit-header = 値を編集してファイルを保存してください。保存した値はGoogle Contactsと.addressbookの両方に書き込まれます。#で始まる行は無視されます。
+edit-error = エディタの実行に失敗しました
+edit-invalid = 編集した連絡先が正しくありません
+edit-retry = もう一度編集しますか？ [Y/n]
+app-usage-add = Google Contactsと.addressbookの両方に連絡先を追加する
+app-usage-edit = Google Contactsと.addressbookの両方で連絡先NICKを編集する（フィールドのオプションが無い場合は$EDITORを開く）
+app-usage-rm = Google Contactsと.addressbookの両方から連絡先NICKを削除する
+app-usage-show = .addressbookとGoogle Contactsの連絡先NICKを表示する
+app-usage-query = キャッシュした連絡先をニックネーム、名前、メールアドレス、所属組織名で検索し、muttの問い合わせの形式で表示する
+cache-error = 連絡先のキャッシュの保存または読み込みに失敗しました
+app-usage-refresh = Google Contactsから連絡先を取得してキャッシュを更新する（.addressbookは変更しない）
+cache-missing = 連絡先のキャッシュがありません。先にオンラインで`gcontacts refresh`を実行してください。
+cache-loaded = 次の時刻に取得した連絡先のキャッシュを使用します
+cache-refreshed = 連絡先のキャッシュを更新しました。連絡先の数
+offline-unsupported = このコマンドでは--offlineを使用できません
+outbox-error = 送信待ちのオフラインの変更の保存または読み込みに失敗しました
+outbox-queued = Google Contactsは次にオンラインでsyncしたときに更新されます。
+outbox-replaying = 送信待ちのオフラインの変更を反映します
+outbox-replayed = 送信待ちの変更をGoogle Contactsに反映しました
+outbox-replay-error = 送信待ちの変更をGoogle Contactsに反映できませんでした（次のsyncで再試行します）
+outbox-conflict-exists = Google Contactsにこのメールアドレスの連絡先が追加されていたため、送信待ちの変更を破棄しました
+outbox-conflict-changed = Google Contactsで連絡先が変更されていたため、送信待ちの変更を破棄しました
+outbox-conflict-deleted = Google Contactsで連絡先が削除されていたため、送信待ちの変更を破棄しました
+app-usage-contact-name = 名前を指定する
+app-usage-contact-email = メールアドレスを指定する（addでは必須）
+app-usage-contact-nick = ニックネームを指定する（addの既定は名前から作成）
+app-usage-contact-fcc = Fccを指定する（addの既定はfields.fcc）
+app-usage-contact-comment = コメントを指定する
+contact-not-found = このニックネームの連絡先は.addressbookにありません
+contact-exists = このメールアドレスの連絡先は既に存在します
+nickname-taken = このニックネームは他の連絡先で使用されています
+contact-added = 連絡先を追加しました
+contact-updated = 連絡先を更新しました
+contact-removed = 連絡先を削除しました
+contact-unchanged = 連絡先は変更されませんでした。
+rm-confirm = この連絡先をGoogle Contactsと.addressbookから削除しますか？ [y/N]
+show-not-in-google = 見つかりません
+cli-missing-option = 必須のオプションが指定されていません
+app-usage-export = Google Contactsの連絡先をほかのアプリケーションの形式でファイルに書き出す（どちらも変更しない）
+app-usage-export-format = 書き出す形式: vcard（既定）、mutt-aliases、abook、vdir、ldif、google-csv、json、ndjson
+app-usage-export-vcard-version = vCardのバージョン: 4.0（RFC 6350、既定）または3.0（RFC 2426）
+app-usage-export-output = 標準出力の代わりにファイルPATHに書き出す
+app-usage-export-output-dir = 連絡先ごとのファイルをディレクトリDIRに書き出す（vdir）
+app-usage-export-all = labels.includeとlabels.excludeにより対象外となった連絡先も書き出す
+export-error = 連絡先の書き出しに失敗しました
+export-written = 連絡先を書き出しました
+app-usage-import = vCard（2.1、3.0、4.0）、Google CSV、JSONのファイルの連絡先をGoogle Contactsに作成し、.addressbookに追加する
+app-usage-import-format = 取り込む形式: vcard（既定）、google-csv、json、ndjson
+app-usage-import-update = メールアドレスが一致するGoogle Contactsの連絡先も更新する（既定では取り込まない）
+import-error = vCardファイルの読み込みに失敗しました
+import-no-email = メールアドレスの無い連絡先を取り込みませんでした
+import-exists = 既存の連絡先と一致する連絡先を取り込みませんでした（--updateで更新します）
+import-complete = 取り込んだ連絡先
+import-created = 作成
+import-updated = 更新
+import-skipped = スキップ
+import-rows-added = .addressbookに追加した行
+import-rows-updated = .addressbookで更新した行
+export-output-dir-unsupported = この書き出す形式はディレクトリへの書き出しに対応していません
+export-files-updated = 書き込んだファイル
+export-files-removed = 削除した古いファイル
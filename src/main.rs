@@ -17,36 +17,65 @@ use base64::{engine::general_purpose, Engine as _};
 use csv::WriterBuilder; // CSVファイルを書き込むため
 use fluent::{bundle::FluentBundle, FluentResource}; // ローカライゼーション機能を提供するfluentクレート関連モジュール
 use google_people1::{
-    api::Biography, api::EmailAddress, api::Name, api::Nickname, api::Person, FieldMask,
-    PeopleService,
+    api::Biography, api::ContactGroup, api::ContactGroupMembership, api::EmailAddress,
+    api::Membership, api::Name, api::Nickname, api::Person, FieldMask, PeopleService,
 }; // Google People APIを使用するため
 use hyper::client::{Client, HttpConnector}; // HTTPクライアント操作用
 use hyper_rustls::HttpsConnector; // HTTPSサポート用
 use intl_memoizer::concurrent::IntlLangMemoizer; // 国際化機能を提供するintl_memoizerクレートのモジュール
 use quoted_printable::decode as qp_decode; // Quoted-Printableエンコーディングをデコードするための関数 `decode` を `qp_decode` としてインポート。Quoted-Printableエンコードされた文字列のデコードに使用。
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet}; // メールアドレスの集合や連絡先グループの対応を扱うため
 use std::env; // 環境変数を扱うための 'env' モジュールをインポート
-use std::fs::File; // ファイル操作を行うための `File` クラスをインポート。ファイルの読み書きに使用。
-use std::io::{self, BufRead}; // 入出力機能のための 'io' モジュールをインポート
-use std::path::Path; // ファイルパスを扱うための 'Path' モジュールをインポート
+use std::fs::{self, File}; // ファイル操作を行うための `File` クラスをインポート。ファイルの読み書きに使用。
+use std::io::{self, BufRead, IsTerminal}; // 入出力機能のための 'io' モジュールをインポート
+use std::path::{Path, PathBuf}; // ファイルパスを扱うための 'Path' モジュールをインポート
+use std::slice; // 1つの連絡先をスライスとして渡すため
 use std::str; // 文字列のスライス操作を行うための `str` モジュールをインポート。文字列操作に使用。
 use std::str::FromStr; // 文字列を型に変換するため // Base64エンコーディングのデコード操作を行うための `base64` クレートの一部をインポート。一般的なBase64デコード用途に使用。
 
+mod mod_abook; // 'mod_abook' モジュールをインポート。連絡先をabookのアドレス帳ファイルの形式で書き出す機能を提供します。
 mod mod_auth;
+mod mod_backup; // 'mod_backup' モジュールをインポート。アドレス帳を上書きする前のバックアップを提供します。
+mod mod_cache; // 'mod_cache' モジュールをインポート。取得した連絡先のキャッシュを提供します。
+mod mod_cli; // 'mod_cli' モジュールをインポート。コマンドライン引数の解析を提供します。
+mod mod_config; // 'mod_config' モジュールをインポート。設定ファイル（config.toml）の読み込みと検証を提供します。
+mod mod_diff; // 'mod_diff' モジュールをインポート。.addressbookとGoogle Contactsの差分の表示を提供します。
+mod mod_dirs; // 'mod_dirs' モジュールをインポート。設定ファイルや状態ファイルの場所を決定します。
+mod mod_editor; // 'mod_editor' モジュールをインポート。連絡先を$EDITORで編集する機能を提供します。
+mod mod_exit; // 'mod_exit' モジュールをインポート。実行結果に応じた終了コードを定義します。
+mod mod_export; // 'mod_export' モジュールをインポート。連絡先を書き出す形式と書き出し先を提供します。
 mod mod_fluent; // 'mod_fluent' モジュールをインポート。Fluent (国際化とローカリゼーション) ライブラリ関連の機能を提供します。
+mod mod_google_csv; // 'mod_google_csv' モジュールをインポート。連絡先をGoogle CSVの形式で読み書きする機能を提供します。
+mod mod_import; // 'mod_import' モジュールをインポート。連絡先を取り込むファイルの形式を提供します。
+mod mod_json; // 'mod_json' モジュールをインポート。連絡先を正規化したJSONの形式で読み書きする機能を提供します。
+mod mod_ldif; // 'mod_ldif' モジュールをインポート。連絡先をLDIFの形式で書き出す機能を提供します。
 mod mod_locale; // 'mod_locale' モジュールをインポート。ロケールと言語設定に関連する機能を提供します。 // 'mod_auth' モジュールをインポート。認証プロセスに関連する機能を提供します。
+mod mod_mutt; // 'mod_mutt' モジュールをインポート。連絡先をmuttのエイリアスファイルの形式で書き出す機能を提供します。
+mod mod_outbox; // 'mod_outbox' モジュールをインポート。オフラインで行った変更の送信待ちを提供します。
+mod mod_perm; // 'mod_perm' モジュールをインポート。認証情報とキャッシュのパーミッション検査を提供します。
+mod mod_report; // 'mod_report' モジュールをインポート。実行結果の報告（--report）を提供します。
+mod mod_state; // 'mod_state' モジュールをインポート。最後の同期の時刻とアドレス帳のスナップショットを提供します。
+#[cfg(test)]
+mod mod_test; // 'mod_test' モジュールをインポート。テストで共通して使用する連絡先と一時ファイルの補助関数を提供します。
+mod mod_token_storage; // 'mod_token_storage' モジュールをインポート。トークンの保存先（ファイル、暗号化ファイル、キーリング）を提供します。
+mod mod_tui; // 'mod_tui' モジュールをインポート。同期の変更を全画面で確認するTUIを提供します。
+mod mod_vcard; // 'mod_vcard' モジュールをインポート。連絡先をvCard形式で書き出す機能を提供します。
 
-// ユーザ選択
-enum Select {
-    Init, // 初期化オプション。例えば、初めてのデータ同期や設定の初期化に使用。
-    Sync, // 同期オプション。データの同期や更新に使用。
-}
-
+#[derive(Clone, Copy)]
 enum UpdateSource {
     FromGoogle,      // 更新のソースとしてGoogleを選択。
     FromAddressBook, // 更新のソースとしてアドレス帳を選択。
 }
 
+// 両方に存在する連絡先の競合の解決方法
+#[derive(Clone)]
+enum ConflictChoice {
+    Source(UpdateSource), // 一方のデータを採用する
+    Merge(APerson),       // フィールドごとに選択した値で両方を更新する
+    Skip,                 // 解決せずに次の連絡先へ進む
+    Cancel,               // 以降の確認を行わない
+}
+
 // .addressbookの各行に格納されているデータ
 #[derive(PartialEq, Eq)] // remove_related_apersons関数に必要。PartialEqトレイトを実装する。
 #[derive(Clone)] // ここでCloneトレイトを導出する
@@ -63,23 +92,89 @@ struct APerson {
 /// この関数は、アプリケーションの一般的な説明を出力します。FluentBundleを利用して、
 /// 国際化されたアプリケーションの説明を取得し、表示します。FluentBundleによって指定された
 /// 言語でアプリケーションの説明をフェッチし、表示します。
+/// コマンドが指定された場合は、そのコマンドの使用方法とオプションを表示します。
 ///
 /// # 引数
 /// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
-fn print_help(bundle: &FluentBundle<FluentResource, IntlLangMemoizer>) {
-    // アプリケーションの全体的な説明を表示
-    println!("Application Description:");
-    // Fluentバンドルを使用して、アプリケーションの説明を国際化対応の言語で取得し表示
+/// * `command` - 使用方法を表示するコマンド。`None`の場合はすべてのコマンドを表示する。
+fn print_help(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    command: Option<mod_cli::Command>,
+) {
+    match command {
+        None => {
+            // アプリケーションの全体的な説明を表示
+            println!("Application Description:");
+            // Fluentバンドルを使用して、アプリケーションの説明を国際化対応の言語で取得し表示
+            println!(
+                "\t{}\n",
+                mod_fluent::get_translation(bundle, "app-description")
+            );
+            println!(
+                "{}: {} [OPTIONS] COMMAND\n",
+                mod_fluent::get_translation(bundle, "help-usage"),
+                env!("CARGO_PKG_NAME")
+            );
+
+            // コマンドの一覧
+            println!("{}:", mod_fluent::get_translation(bundle, "help-commands"));
+            for spec in mod_cli::COMMANDS {
+                println!("\t{}", command_usage(spec));
+                println!("\t{}\n", mod_fluent::get_translation(bundle, spec.help_id));
+            }
+        }
+        Some(command) => {
+            // コマンドの使用方法と説明
+            let spec = command.spec();
+            println!(
+                "{}: {}\n",
+                mod_fluent::get_translation(bundle, "help-usage"),
+                command_usage(spec)
+            );
+            println!("\t{}\n", mod_fluent::get_translation(bundle, spec.help_id));
+
+            // コマンド固有のオプション
+            if !spec.options.is_empty() {
+                println!("{}:", mod_fluent::get_translation(bundle, "help-options"));
+                for option in spec.options {
+                    println!("\t{}", mod_cli::option_usage(option));
+                    println!(
+                        "\t{}\n",
+                        mod_fluent::get_translation(bundle, option.help_id)
+                    );
+                }
+            }
+        }
+    }
+
+    // すべてのコマンドで使用できるオプション
     println!(
-        "\t{}",
-        mod_fluent::get_translation(bundle, "app-description")
+        "{}:",
+        mod_fluent::get_translation(bundle, "help-global-options")
     );
-    // 追加の詳細説明や使用方法のためのプレースホルダー
-    // ここに他の詳細な説明や使用方法を記述する
-    println!("\t{} init", env!("CARGO_PKG_NAME"));
-    println!("\t{}\n", mod_fluent::get_translation(bundle, "app-usage-init"));
-    println!("\t{} sync", env!("CARGO_PKG_NAME"));
-    println!("\t{}\n", mod_fluent::get_translation(bundle, "app-usage-sync"));
+    for option in mod_cli::GLOBAL_OPTIONS {
+        println!("\t{}", mod_cli::option_usage(option));
+        println!(
+            "\t{}\n",
+            mod_fluent::get_translation(bundle, option.help_id)
+        );
+    }
+}
+
+/// コマンドの使用方法（"gcontacts sync [OPTIONS]"など）を返す関数。
+///
+/// # 引数
+/// * `spec` - コマンドの定義。
+///
+/// # 戻り値
+/// コマンド名、オプション、位置引数を含む使用方法の文字列。
+fn command_usage(spec: &mod_cli::CommandSpec) -> String {
+    let mut usage = format!("{} {} [OPTIONS]", env!("CARGO_PKG_NAME"), spec.name);
+    for argument in spec.arguments {
+        usage.push(' ');
+        usage.push_str(argument);
+    }
+    usage
 }
 
 /// 文字列内で最初に数字が現れる部分を見つけ、文字列部分と数値部分に分割する。
@@ -96,7 +191,7 @@ fn split_string_and_number(s: &str) -> (String, u32) {
     // 文字列の各文字に対してループを行う
     for (index, character) in s.char_indices() {
         // 文字が数字かどうかをチェック
-        if character.is_digit(10) {
+        if character.is_ascii_digit() {
             // 数字が見つかった場合、そのインデックスを保存しループを抜ける
             num_start_index = Some(index);
             break;
@@ -124,6 +219,7 @@ fn split_string_and_number(s: &str) -> (String, u32) {
 /// * `name` - 名前の文字列。
 /// * `email_count` - メールアドレスの数。
 /// * `existing_nicknames` - 既に存在するニックネームのリストへの可変参照。
+/// * `template` - ニックネームのテンプレート（設定の`nickname.template`）。
 ///
 /// # 戻り値
 /// 生成されたニックネームの文字列。
@@ -131,13 +227,10 @@ fn generate_nickname(
     name: &str,
     email_count: usize,
     existing_nicknames: &mut Vec<String>,
+    template: &str,
 ) -> String {
-    // 名前の最後の部分を取得し、基本的なニックネームを作成
-    let last_name_part = name
-        .split_whitespace()
-        .last()
-        .unwrap_or("Unknown")
-        .to_string();
+    // テンプレートに従って基本的なニックネームを作成
+    let last_name_part = mod_config::nickname_base(name, template);
     let base_nickname;
     let mut counter;
 
@@ -145,7 +238,7 @@ fn generate_nickname(
     if !existing_nicknames.is_empty() {
         (base_nickname, counter) = split_string_and_number(existing_nicknames[0].as_str());
     } else {
-        // 既存のニックネームがない場合は、名前の最後の部分を基本ニックネームとして使用
+        // 既存のニックネームがない場合は、テンプレートから作成したものを基本ニックネームとして使用
         base_nickname = last_name_part;
         counter = 0;
     }
@@ -169,6 +262,261 @@ fn generate_nickname(
     }
 }
 
+/// Googleの連絡先から、.addressbookの初期化で書き込む行を作成する関数。
+///
+/// メールアドレスごとに1行を作成し、ニックネームは`generate_nickname`で割り当てます
+/// （Google Contactsのニックネームがある場合はそれを基本とし、メールアドレスが複数ある場合は番号を付けます）。
+/// 名前と所属組織のどちらも無い連絡先は対象外です。muttのエイリアスの書き出しでも同じ割り当てを使用します。
+///
+/// # 引数
+/// * `person` - Googleの連絡先。
+/// * `config` - 名前とコメントのフィールドの対応、Fccの既定値、ニックネームのテンプレートの設定。
+///
+/// # 戻り値
+/// 作成したAPersonオブジェクトのベクター（ほかの連絡先とのニックネームの重複は考慮しない）。
+fn addressbook_rows(person: &Person, config: &mod_config::Config) -> Vec<APerson> {
+    // 名前が存在する場合のみ処理
+    let has_names = person.names.as_ref().is_some_and(|n| !n.is_empty());
+    let has_organizations = person.organizations.as_ref().is_some_and(|o| !o.is_empty());
+    if !has_names && !has_organizations {
+        return Vec::new();
+    }
+
+    // 生成されたニックネームを格納するVec（Google Contactsのニックネームを基本とする）
+    let mut existing_nicknames = Vec::new();
+    let nickname_from_g = get_gcontact_nickname(person);
+    if !nickname_from_g.is_empty() {
+        existing_nicknames.push(nickname_from_g);
+    }
+
+    // 名前か会社を取得する
+    let name = get_gcontact_name(person, &config.fields.name);
+
+    // メモ欄の内容を取得する（コメントを同期しない場合は空）
+    let memo = if config.fields.comment {
+        get_gcontact_biography(person)
+    } else {
+        "".to_string()
+    };
+
+    let emails = person.email_addresses.as_deref().unwrap_or_default();
+    emails
+        .iter()
+        .map(|email| APerson {
+            nickname: generate_nickname(
+                &name,
+                emails.len(),
+                &mut existing_nicknames,
+                &config.nickname.template,
+            ),
+            name: name.clone(),
+            email: email.value.clone().unwrap_or_default(),
+            fcc: config.fields.fcc.clone(),
+            biography: memo.clone(),
+        })
+        .collect()
+}
+
+/// Googleの連絡先から、.addressbookの初期化と同じ行を作成する関数。
+///
+/// 連絡先の順に`addressbook_rows`で行を作成し、前の行と重複するニックネームには`unique_nickname`で番号を付けます。
+///
+/// # 引数
+/// * `gpersons` - Googleの連絡先。
+/// * `config` - ニックネームとフィールドの対応の設定。
+///
+/// # 戻り値
+/// 連絡先ごとの行のベクター。
+fn allocate_addressbook_rows(
+    gpersons: &[Person],
+    config: &mod_config::Config,
+) -> Vec<Vec<APerson>> {
+    let mut taken_nicknames = HashSet::new();
+    gpersons
+        .iter()
+        .map(|person| {
+            let mut rows = addressbook_rows(person, config);
+            for aperson in &mut rows {
+                aperson.nickname = unique_nickname(&aperson.nickname, &taken_nicknames);
+                taken_nicknames.insert(aperson.nickname.clone());
+            }
+            rows
+        })
+        .collect()
+}
+
+/// Googleの連絡先に、書き出すニックネームを対応付ける関数。
+///
+/// ニックネームには、.addressbookの初期化で最初の行に使用するもの（`allocate_addressbook_rows`）を使用します。
+/// 行を作成しない連絡先（名前もメールアドレスも無い連絡先など）のニックネームは空になります。
+///
+/// # 引数
+/// * `gpersons` - 書き出すGoogleの連絡先。
+/// * `config` - ニックネームとフィールドの対応の設定。
+///
+/// # 戻り値
+/// 連絡先とニックネームのタプルのベクター。
+fn nicknamed_gpersons<'a>(
+    gpersons: &'a [Person],
+    config: &mod_config::Config,
+) -> Vec<(&'a Person, String)> {
+    gpersons
+        .iter()
+        .zip(allocate_addressbook_rows(gpersons, config))
+        .map(|(person, rows)| {
+            let nickname = rows.into_iter().next().map(|r| r.nickname);
+            (person, nickname.unwrap_or_default())
+        })
+        .collect()
+}
+
+/// Googleの連絡先から、muttのエイリアスファイルの内容を作成する関数。
+///
+/// メールアドレスごとのエイリアスには、.addressbookの初期化と同じニックネーム（`allocate_addressbook_rows`）を使用するため、
+/// Alpineとmuttで同じ名前で連絡先を指定できます。メールアドレスが複数ある連絡先には、番号の無いニックネームで
+/// すべてのアドレスをまとめたエイリアスを、連絡先グループ（ラベル）にはラベル名でメンバーの最初のアドレスを
+/// まとめたエイリアスを追加します。エイリアスの名前が重複する場合は番号を付けます。
+///
+/// # 引数
+/// * `gpersons` - 書き出すGoogleの連絡先。
+/// * `labels` - 連絡先グループのリソース名とラベル名の対応。
+/// * `config` - ニックネームとフィールドの対応の設定。
+///
+/// # 戻り値
+/// エイリアスファイルの内容。
+fn mutt_aliases(
+    gpersons: &[Person],
+    labels: &BTreeMap<String, String>,
+    config: &mod_config::Config,
+) -> String {
+    let rows = allocate_addressbook_rows(gpersons, config);
+    let mut taken: HashSet<String> = HashSet::new();
+    let mut unique_alias = |name: &str| {
+        let alias = unique_nickname(&mod_mutt::alias_name(name), &taken);
+        taken.insert(alias.clone());
+        alias
+    };
+
+    // メールアドレスごとのエイリアスの名前を先に確保し、まとめたエイリアスの名前と重複しないようにする
+    // （使用できない文字を置き換えた結果が同じになる場合も番号を付ける）
+    let mut aliases: Vec<Vec<String>> = Vec::new();
+    for apeople in &rows {
+        let names = apeople
+            .iter()
+            .map(|aperson| unique_alias(&aperson.nickname))
+            .collect();
+        aliases.push(names);
+    }
+
+    let mut data = String::new();
+    for (apeople, names) in rows.iter().zip(&aliases) {
+        let addresses: Vec<String> = apeople
+            .iter()
+            .map(|aperson| mod_mutt::address(&aperson.name, &aperson.email))
+            .collect();
+        for (alias, address) in names.iter().zip(&addresses) {
+            data.push_str(&mod_mutt::alias_line(alias, slice::from_ref(address)));
+        }
+        if let [first, _, ..] = apeople.as_slice() {
+            // 番号を除いたニックネーム（"Smith01" → "Smith"）
+            let (base, _) = split_string_and_number(&first.nickname);
+            data.push_str(&mod_mutt::alias_line(&unique_alias(&base), &addresses));
+        }
+    }
+
+    for (resource_name, label) in labels {
+        let addresses: Vec<String> = gpersons
+            .iter()
+            .zip(&rows)
+            .filter(|(person, _)| {
+                person.memberships.iter().flatten().any(|m| {
+                    m.contact_group_membership
+                        .as_ref()
+                        .and_then(|g| g.contact_group_resource_name.as_ref())
+                        == Some(resource_name)
+                })
+            })
+            .filter_map(|(_, apeople)| apeople.first())
+            .map(|aperson| mod_mutt::address(&aperson.name, &aperson.email))
+            .collect();
+        if !addresses.is_empty() {
+            data.push_str(&mod_mutt::alias_line(&unique_alias(label), &addresses));
+        }
+    }
+    data
+}
+
+/// 既存のニックネームと重複しないニックネームを返す関数。
+///
+/// 重複する場合は、末尾に2から始まる番号を付けます（例: "Smith" → "Smith2"）。
+///
+/// # 引数
+/// * `nickname` - 候補のニックネーム。
+/// * `taken` - 既に使用されているニックネームの集合。
+///
+/// # 戻り値
+/// 重複しないニックネーム。
+fn unique_nickname(nickname: &str, taken: &HashSet<String>) -> String {
+    if !taken.contains(nickname) {
+        return nickname.to_string();
+    }
+    let mut counter = 2;
+    loop {
+        let candidate = format!("{}{}", nickname, counter);
+        if !taken.contains(&candidate) {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
+
+/// .addressbookの行がリスト（複数のメールアドレスをまとめたもの）かどうかを判定する関数。
+///
+/// Alpineのアドレス帳では、リストのアドレス欄は"(a@example.com,b@example.com)"のように括弧で囲まれます。
+///
+/// # 引数
+/// * `aperson` - 判定するAPersonオブジェクトへの参照。
+///
+/// # 戻り値
+/// リストの場合は`true`。
+fn is_addressbook_list(aperson: &APerson) -> bool {
+    let email = aperson.email.trim();
+    email.starts_with('(') && email.ends_with(')')
+}
+
+/// `import --update`で更新した連絡先の値で、メールアドレスが一致する.addressbookの行を書き換える関数。
+///
+/// 名前は取り込んだ連絡先に名前がある場合、コメントは`fields.comment`が有効な場合に置き換えます。
+/// ニックネームとFccはJSONで指定された場合だけ置き換え、それ以外は行の値を残します。
+///
+/// # 引数
+/// * `row` - 書き換える行。
+/// * `name` - 取り込んだ連絡先の名前。
+/// * `memo` - 取り込んだ連絡先のメモ（コメントを置き換えない場合は`None`）。
+/// * `values` - JSONで指定された行の値。
+/// * `taken_nicknames` - 使用されているニックネーム。変更したニックネームを追加する。
+fn apply_imported_row(
+    row: &mut APerson,
+    name: &str,
+    memo: Option<&str>,
+    values: mod_vcard::RowValues,
+    taken_nicknames: &mut HashSet<String>,
+) {
+    if !name.is_empty() {
+        row.name = name.to_string();
+    }
+    if let Some(memo) = memo {
+        row.biography = memo.to_string();
+    }
+    if let Some(nickname) = values.nickname.filter(|n| *n != row.nickname) {
+        row.nickname = unique_nickname(&nickname, taken_nicknames);
+        taken_nicknames.insert(row.nickname.clone());
+    }
+    if let Some(fcc) = values.fcc {
+        row.fcc = fcc;
+    }
+}
+
 /// 文字列内でエンコードされた部分をデコードする。
 ///
 /// この関数は、与えられた文字列をチェックし、Base64またはQuoted-Printableで
@@ -226,7 +574,7 @@ fn get_decoded_apersons(
     fields: Vec<&str>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // 各フィールドをデコードし、`APerson` 構造体に変換します。
-    let nickname = decode_if_encoded(fields.get(0).unwrap_or(&""))?;
+    let nickname = decode_if_encoded(fields.first().unwrap_or(&""))?;
     let name = decode_if_encoded(fields.get(1).unwrap_or(&""))?;
     let email = decode_if_encoded(fields.get(2).unwrap_or(&""))?;
     let fcc = decode_if_encoded(fields.get(3).unwrap_or(&""))?;
@@ -257,7 +605,7 @@ fn get_decoded_apersons(
 /// # 戻り値
 /// `Result<(), Box<dyn std::error::Error>>` - 成功した場合はOk(())、失敗した場合はエラー。
 fn convert_line_to_aperson(
-    mut persons: &mut Vec<APerson>,
+    persons: &mut Vec<APerson>,
     combined_line: &mut String,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // タブで区切られたフィールドに分割
@@ -273,7 +621,7 @@ fn convert_line_to_aperson(
     }
 
     // 各フィールドをデコードし、`APerson` 構造体に変換
-    get_decoded_apersons(&mut persons, fields)?;
+    get_decoded_apersons(persons, fields)?;
     // 結合された行をクリアして、次の行の処理に備える
     combined_line.clear();
 
@@ -335,25 +683,28 @@ fn load_addressbook_data(file_path: &Path) -> Result<Vec<APerson>, Box<dyn std::
     Ok(persons)
 }
 
-/// Googleの連絡先を更新する非同期関数。
+/// APersonオブジェクトの値でGoogleのPersonオブジェクトを作成する関数。
 ///
-/// 既存のGoogleの連絡先（Personオブジェクト）を更新するか、新しい連絡先を作成します。
-/// 更新するには、既存のPersonオブジェクトの参照とAPersonオブジェクトが必要です。
+/// 既存のPersonオブジェクトがある場合は、リソース名やetag、各フィールドのメタデータを保ったまま値を置き換えます。
+/// メールアドレスは`previous_email`の要素だけを置き換え、連絡先の他のメールアドレス（.addressbookの別の行）は残します。
+/// 無い場合は新しい連絡先として作成します。
 ///
 /// # 引数
 /// * `gperson_option` - 既存のGoogleの連絡先のOption参照。Noneの場合は新しい連絡先を作成。
-/// * `aperson` - 更新するためのAPersonオブジェクトの参照。
-/// * `service` - PeopleServiceの参照。Google People APIへのリクエストに使用。
+/// * `aperson` - 値をコピーするAPersonオブジェクトの参照。
+/// * `previous_email` - 置き換えるメールアドレス（変更前の.addressbookの行のメールアドレス）。
+/// * `fields` - フィールドの対応の設定。コメントを同期しない場合はメモを変更しない。
 ///
 /// # 戻り値
-/// `Result<(), Box<dyn std::error::Error>>` - 成功した場合はOk(())、失敗した場合はエラー。
-async fn update_google_contacts(
+/// 作成したPersonオブジェクト。
+fn build_gperson(
     gperson_option: Option<&Person>,
     aperson: &APerson,
-    service: &PeopleService<HttpsConnector<HttpConnector>>,
-) -> Result<(), Box<dyn std::error::Error>> {
+    previous_email: &str,
+    fields: &mod_config::FieldsConfig,
+) -> Person {
     // 新しいPersonインスタンスを作成するか、既存の参照を使用して更新
-    let new_gperson = match gperson_option {
+    match gperson_option {
         Some(person) => {
             // 既存のデータをコピーし、必要なフィールドのみを更新
             let mut updated_person = person.clone();
@@ -361,11 +712,11 @@ async fn update_google_contacts(
             let existing_metadata = person
                 .nicknames
                 .as_ref()
-                .and_then(|n| n.get(0).and_then(|nn| nn.metadata.clone()));
+                .and_then(|n| n.first().and_then(|nn| nn.metadata.clone()));
             let existing_type = person
                 .nicknames
                 .as_ref()
-                .and_then(|n| n.get(0).and_then(|nn| nn.type_.clone()));
+                .and_then(|n| n.first().and_then(|nn| nn.type_.clone()));
 
             updated_person.nicknames = Some(vec![Nickname {
                 value: Some(aperson.nickname.clone()),
@@ -378,59 +729,59 @@ async fn update_google_contacts(
             let existing_display_name_last_first = person
                 .names
                 .as_ref()
-                .and_then(|n| n.get(0).and_then(|nn| nn.display_name_last_first.clone()));
+                .and_then(|n| n.first().and_then(|nn| nn.display_name_last_first.clone()));
             let existing_family_name = person
                 .names
                 .as_ref()
-                .and_then(|n| n.get(0).and_then(|nn| nn.family_name.clone()));
+                .and_then(|n| n.first().and_then(|nn| nn.family_name.clone()));
             let existing_given_name = person
                 .names
                 .as_ref()
-                .and_then(|n| n.get(0).and_then(|nn| nn.given_name.clone()));
+                .and_then(|n| n.first().and_then(|nn| nn.given_name.clone()));
             let existing_honorific_prefix = person
                 .names
                 .as_ref()
-                .and_then(|n| n.get(0).and_then(|nn| nn.honorific_prefix.clone()));
+                .and_then(|n| n.first().and_then(|nn| nn.honorific_prefix.clone()));
             let existing_honorific_suffix = person
                 .names
                 .as_ref()
-                .and_then(|n| n.get(0).and_then(|nn| nn.honorific_suffix.clone()));
+                .and_then(|n| n.first().and_then(|nn| nn.honorific_suffix.clone()));
             let existing_metadata = person
                 .names
                 .as_ref()
-                .and_then(|n| n.get(0).and_then(|nn| nn.metadata.clone()));
+                .and_then(|n| n.first().and_then(|nn| nn.metadata.clone()));
             let existing_middle_name = person
                 .names
                 .as_ref()
-                .and_then(|n| n.get(0).and_then(|nn| nn.middle_name.clone()));
+                .and_then(|n| n.first().and_then(|nn| nn.middle_name.clone()));
             let existing_phonetic_family_name = person
                 .names
                 .as_ref()
-                .and_then(|n| n.get(0).and_then(|nn| nn.phonetic_family_name.clone()));
+                .and_then(|n| n.first().and_then(|nn| nn.phonetic_family_name.clone()));
             let existing_phonetic_full_name = person
                 .names
                 .as_ref()
-                .and_then(|n| n.get(0).and_then(|nn| nn.phonetic_full_name.clone()));
+                .and_then(|n| n.first().and_then(|nn| nn.phonetic_full_name.clone()));
             let existing_phonetic_given_name = person
                 .names
                 .as_ref()
-                .and_then(|n| n.get(0).and_then(|nn| nn.phonetic_given_name.clone()));
-            let existing_phonetic_honorific_prefix = person
-                .names
-                .as_ref()
-                .and_then(|n| n.get(0).and_then(|nn| nn.phonetic_honorific_prefix.clone()));
-            let existing_phonetic_honorific_suffix = person
-                .names
-                .as_ref()
-                .and_then(|n| n.get(0).and_then(|nn| nn.phonetic_honorific_suffix.clone()));
+                .and_then(|n| n.first().and_then(|nn| nn.phonetic_given_name.clone()));
+            let existing_phonetic_honorific_prefix = person.names.as_ref().and_then(|n| {
+                n.first()
+                    .and_then(|nn| nn.phonetic_honorific_prefix.clone())
+            });
+            let existing_phonetic_honorific_suffix = person.names.as_ref().and_then(|n| {
+                n.first()
+                    .and_then(|nn| nn.phonetic_honorific_suffix.clone())
+            });
             let existing_phonetic_middle_name = person
                 .names
                 .as_ref()
-                .and_then(|n| n.get(0).and_then(|nn| nn.phonetic_middle_name.clone()));
+                .and_then(|n| n.first().and_then(|nn| nn.phonetic_middle_name.clone()));
             let existing_unstructured_name = person
                 .names
                 .as_ref()
-                .and_then(|n| n.get(0).and_then(|nn| nn.unstructured_name.clone()));
+                .and_then(|n| n.first().and_then(|nn| nn.unstructured_name.clone()));
             updated_person.names = Some(vec![Name {
                 display_name: Some(aperson.name.clone()),
                 display_name_last_first: existing_display_name_last_first,
@@ -449,56 +800,44 @@ async fn update_google_contacts(
                 unstructured_name: existing_unstructured_name,
             }]);
 
-            let existing_metadata = person
-                .email_addresses
-                .as_ref()
-                .and_then(|n| n.get(0).and_then(|nn| nn.metadata.clone()));
-            let existing_type_ = person
-                .email_addresses
-                .as_ref()
-                .and_then(|n| n.get(0).and_then(|nn| nn.type_.clone()));
-            let existing_formatted_type = person
-                .email_addresses
-                .as_ref()
-                .and_then(|n| n.get(0).and_then(|nn| nn.formatted_type.clone()));
-            let existing_display_name = person
-                .email_addresses
-                .as_ref()
-                .and_then(|n| n.get(0).and_then(|nn| nn.display_name.clone()));
-            updated_person.email_addresses = Some(vec![EmailAddress {
-                value: Some(aperson.email.clone()),
-                metadata: existing_metadata,
-                type_: existing_type_,
-                formatted_type: existing_formatted_type,
-                display_name: existing_display_name,
-            }]);
+            // 変更前のメールアドレスの要素だけを、メタデータや種類を保ったまま置き換える
+            let mut emails = person.email_addresses.clone().unwrap_or_default();
+            let previous = emails.iter_mut().find(|e| {
+                e.value
+                    .as_deref()
+                    .is_some_and(|v| v.eq_ignore_ascii_case(previous_email))
+            });
+            match previous {
+                Some(email) => email.value = Some(aperson.email.clone()),
+                None => emails.insert(
+                    0,
+                    EmailAddress {
+                        value: Some(aperson.email.clone()),
+                        ..Default::default()
+                    },
+                ),
+            }
+            updated_person.email_addresses = Some(emails);
 
-            let existing_metadata = person
-                .biographies
-                .as_ref()
-                .and_then(|n| n.get(0).and_then(|nn| nn.metadata.clone()));
-            let existing_content_type = person
-                .biographies
-                .as_ref()
-                .and_then(|n| n.get(0).and_then(|nn| nn.content_type.clone()));
-            updated_person.biographies = Some(vec![Biography {
-                value: Some(aperson.biography.clone()),
-                metadata: existing_metadata,
-                content_type: existing_content_type,
-            }]);
+            if fields.comment {
+                let existing_metadata = person
+                    .biographies
+                    .as_ref()
+                    .and_then(|n| n.first().and_then(|nn| nn.metadata.clone()));
+                let existing_content_type = person
+                    .biographies
+                    .as_ref()
+                    .and_then(|n| n.first().and_then(|nn| nn.content_type.clone()));
+                updated_person.biographies = Some(vec![Biography {
+                    value: Some(aperson.biography.clone()),
+                    metadata: existing_metadata,
+                    content_type: existing_content_type,
+                }]);
+            }
             updated_person
         }
         None => {
-            // 新しいPersonオブジェクトを作成
-            let mut new_person = Person::default();
-
-            // 各フィールドの値をAPersonからコピー
-            new_person.nicknames = Some(vec![Nickname {
-                value: Some(aperson.nickname.clone()),
-                metadata: None,
-                type_: None,
-            }]);
-
+            // 名前を名と姓に分割
             let first_name;
             let last_name;
             let words: Vec<&str> = aperson.name.split_whitespace().collect();
@@ -513,41 +852,79 @@ async fn update_google_contacts(
                 last_name = "";
             }
 
-            new_person.names = Some(vec![Name {
-                display_name: Some(aperson.name.clone()),
-                display_name_last_first: None,
-                family_name: Some(last_name.to_string()),
-                given_name: Some(first_name.to_string()),
-                honorific_prefix: None,
-                honorific_suffix: None,
-                metadata: None,
-                middle_name: None,
-                phonetic_family_name: None,
-                phonetic_full_name: None,
-                phonetic_given_name: None,
-                phonetic_honorific_prefix: None,
-                phonetic_honorific_suffix: None,
-                phonetic_middle_name: None,
-                unstructured_name: None,
-            }]);
-            new_person.email_addresses = Some(vec![EmailAddress {
-                value: Some(aperson.email.clone()),
-                metadata: None,
-                type_: None,
-                formatted_type: None,
-                display_name: None,
-            }]);
-            new_person.biographies = Some(vec![Biography {
-                value: Some(aperson.biography.clone()),
-                metadata: None,
-                content_type: None,
-            }]);
-            new_person
+            // 新しいPersonオブジェクトを作成し、各フィールドの値をAPersonからコピー
+            Person {
+                nicknames: Some(vec![Nickname {
+                    value: Some(aperson.nickname.clone()),
+                    metadata: None,
+                    type_: None,
+                }]),
+                names: Some(vec![Name {
+                    display_name: Some(aperson.name.clone()),
+                    display_name_last_first: None,
+                    family_name: Some(last_name.to_string()),
+                    given_name: Some(first_name.to_string()),
+                    honorific_prefix: None,
+                    honorific_suffix: None,
+                    metadata: None,
+                    middle_name: None,
+                    phonetic_family_name: None,
+                    phonetic_full_name: None,
+                    phonetic_given_name: None,
+                    phonetic_honorific_prefix: None,
+                    phonetic_honorific_suffix: None,
+                    phonetic_middle_name: None,
+                    unstructured_name: None,
+                }]),
+                email_addresses: Some(vec![EmailAddress {
+                    value: Some(aperson.email.clone()),
+                    metadata: None,
+                    type_: None,
+                    formatted_type: None,
+                    display_name: None,
+                }]),
+                biographies: fields.comment.then(|| {
+                    vec![Biography {
+                        value: Some(aperson.biography.clone()),
+                        metadata: None,
+                        content_type: None,
+                    }]
+                }),
+                ..Default::default()
+            }
         }
-    };
+    }
+}
+
+/// Googleの連絡先を更新する非同期関数。
+///
+/// 既存のGoogleの連絡先（Personオブジェクト）を更新するか、新しい連絡先を作成します。
+/// 更新するには、既存のPersonオブジェクトの参照とAPersonオブジェクトが必要です。
+///
+/// # 引数
+/// * `gperson_option` - 既存のGoogleの連絡先のOption参照。Noneの場合は新しい連絡先を作成。
+/// * `aperson` - 更新するためのAPersonオブジェクトの参照。
+/// * `previous_email` - 置き換えるメールアドレス（`build_gperson`を参照）。
+/// * `fields` - フィールドの対応の設定。コメントを同期しない場合はメモを変更しない。
+/// * `service` - PeopleServiceの参照。Google People APIへのリクエストに使用。
+///
+/// # 戻り値
+/// `Result<(), Box<dyn std::error::Error>>` - 成功した場合はOk(())、失敗した場合はエラー。
+async fn update_google_contacts(
+    gperson_option: Option<&Person>,
+    aperson: &APerson,
+    previous_email: &str,
+    fields: &mod_config::FieldsConfig,
+    service: &PeopleService<HttpsConnector<HttpConnector>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let new_gperson = build_gperson(gperson_option, aperson, previous_email, fields);
 
-    // 更新するフィールドのマスクを設定
-    let field_mask = FieldMask::from_str("nicknames,names,emailAddresses,biographies").unwrap();
+    // 更新するフィールドのマスクを設定（コメントを同期しない場合はメモを変更しない）
+    let field_mask = if fields.comment {
+        FieldMask::from_str("nicknames,names,emailAddresses,biographies").unwrap()
+    } else {
+        FieldMask::from_str("nicknames,names,emailAddresses").unwrap()
+    };
 
     // Personオブジェクトのresource_nameがあれば、Google People APIを使用して更新
     if let Some(resource_name) = new_gperson.resource_name.as_ref() {
@@ -555,6 +932,7 @@ async fn update_google_contacts(
             .people()
             .update_contact(new_gperson.clone(), resource_name)
             .update_person_fields(field_mask)
+            .add_scope(mod_auth::AccessLevel::ReadWrite.scope())
             .doit()
             .await?;
     } else {
@@ -563,12 +941,139 @@ async fn update_google_contacts(
             .people()
             .create_contact(new_gperson.clone())
             .person_fields(field_mask)
+            .add_scope(mod_auth::AccessLevel::ReadWrite.scope())
             .doit()
             .await?;
     }
     Ok(())
 }
 
+/// 取り込んだ連絡先をGoogle Contactsに作成、または更新するときのフィールドのマスク
+const IMPORT_PERSON_FIELDS: &str = "names,nicknames,emailAddresses,phoneNumbers,addresses,\
+                                    organizations,birthdays,biographies,memberships";
+
+/// vCardから読み込んだ連絡先を、Google Contactsに作成する連絡先に変換する関数。
+///
+/// CATEGORIESのラベル名は、同じ名前の連絡先グループ（ユーザーが作成したもの）への所属に変換します。
+/// 存在しないラベル名は無視します。
+///
+/// # 引数
+/// * `card` - 読み込んだvCard。
+/// * `labels` - 連絡先グループのリソース名とラベル名の対応。
+///
+/// # 戻り値
+/// 作成したPersonオブジェクト。
+fn imported_gperson(card: &mod_vcard::Card, labels: &BTreeMap<String, String>) -> Person {
+    let mut person = card.person.clone();
+    // JSONのリソース名は照合にのみ使用する
+    person.resource_name = None;
+    let memberships: Vec<Membership> = card
+        .categories
+        .iter()
+        .filter_map(|category| labels.iter().find(|(_, label)| *label == category))
+        .map(|(resource_name, _)| Membership {
+            contact_group_membership: Some(ContactGroupMembership {
+                contact_group_resource_name: Some(resource_name.clone()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .collect();
+    if !memberships.is_empty() {
+        person.memberships = Some(memberships);
+    }
+    person
+}
+
+/// 既存のGoogleの連絡先に、取り込んだ連絡先の値を反映する関数。
+///
+/// 取り込んだ連絡先にある項目（名前、電話番号など）は置き換えます。
+/// メールアドレスと連絡先グループは、既存のものを残して足りないものだけを追加します。
+///
+/// # 引数
+/// * `gperson` - 既存のGoogleの連絡先。
+/// * `imported` - 取り込んだ連絡先。
+///
+/// # 戻り値
+/// 更新後のPersonオブジェクト（etagは既存の連絡先のもの）。
+fn merge_imported_gperson(gperson: &Person, imported: &Person) -> Person {
+    let mut merged = gperson.clone();
+    fn replace<T: Clone>(target: &mut Option<Vec<T>>, source: &Option<Vec<T>>) {
+        if source.as_ref().is_some_and(|values| !values.is_empty()) {
+            target.clone_from(source);
+        }
+    }
+    replace(&mut merged.names, &imported.names);
+    replace(&mut merged.nicknames, &imported.nicknames);
+    replace(&mut merged.phone_numbers, &imported.phone_numbers);
+    replace(&mut merged.addresses, &imported.addresses);
+    replace(&mut merged.organizations, &imported.organizations);
+    replace(&mut merged.birthdays, &imported.birthdays);
+    replace(&mut merged.biographies, &imported.biographies);
+
+    let emails = merged.email_addresses.get_or_insert_with(Vec::new);
+    for email in imported.email_addresses.iter().flatten() {
+        let exists = emails.iter().any(|e| {
+            e.value.as_deref().map(str::to_lowercase)
+                == email.value.as_deref().map(str::to_lowercase)
+        });
+        if !exists {
+            emails.push(email.clone());
+        }
+    }
+    let group = |m: &Membership| {
+        m.contact_group_membership
+            .as_ref()
+            .and_then(|g| g.contact_group_resource_name.clone())
+    };
+    let memberships = merged.memberships.get_or_insert_with(Vec::new);
+    for membership in imported.memberships.iter().flatten() {
+        if !memberships.iter().any(|m| group(m) == group(membership)) {
+            memberships.push(membership.clone());
+        }
+    }
+    merged
+}
+
+/// 取り込んだ連絡先をGoogle Contactsに作成、または既存の連絡先を更新する非同期関数。
+///
+/// # 引数
+/// * `gperson_option` - 既存のGoogleの連絡先のOption参照。Noneの場合は新しい連絡先を作成。
+/// * `imported` - 取り込んだ連絡先。
+/// * `service` - PeopleServiceの参照。Google People APIへのリクエストに使用。
+///
+/// # 戻り値
+/// `Result<Person, Box<dyn std::error::Error>>` - 成功した場合は作成または更新した連絡先、失敗した場合はエラー。
+async fn import_google_contact(
+    gperson_option: Option<&Person>,
+    imported: &Person,
+    service: &PeopleService<HttpsConnector<HttpConnector>>,
+) -> Result<Person, Box<dyn std::error::Error>> {
+    let field_mask = FieldMask::from_str(IMPORT_PERSON_FIELDS).unwrap();
+    let (_, person) = match gperson_option {
+        Some(gperson) => {
+            let resource_name = gperson.resource_name.clone().unwrap_or_default();
+            service
+                .people()
+                .update_contact(merge_imported_gperson(gperson, imported), &resource_name)
+                .update_person_fields(field_mask)
+                .add_scope(mod_auth::AccessLevel::ReadWrite.scope())
+                .doit()
+                .await?
+        }
+        None => {
+            service
+                .people()
+                .create_contact(imported.clone())
+                .person_fields(field_mask)
+                .add_scope(mod_auth::AccessLevel::ReadWrite.scope())
+                .doit()
+                .await?
+        }
+    };
+    Ok(person)
+}
+
 /// 特定のGoogleのPersonオブジェクトを削除する非同期関数。
 ///
 /// この関数は、Google People APIを使用してGoogleの連絡先リストから特定のPersonオブジェクトを削除します。
@@ -592,6 +1097,7 @@ async fn remove_related_gperson(
         service
             .people()
             .delete_contact(resource_name)
+            .add_scope(mod_auth::AccessLevel::ReadWrite.scope())
             .doit()
             .await?;
     } else {
@@ -606,50 +1112,678 @@ async fn remove_related_gperson(
     Ok(())
 }
 
-/// 特定のメールアドレスを持つGoogleのPersonオブジェクトを取得する。
-///
-/// 与えられたメールアドレスと一致するメールアドレスを持つPersonオブジェクトを`gpersons`ベクターから探し出し、
-/// それらに関連するPersonオブジェクトの参照のベクターを返します。
+/// 連絡先から1つのメールアドレスを取り除いたPersonオブジェクトを作成する関数。
 ///
 /// # 引数
-/// * `gpersons` - GoogleのPersonオブジェクトのベクターへの参照。
-/// * `email` - 検索するメールアドレスの参照。
+/// * `gperson` - Googleの連絡先。
+/// * `email` - 取り除くメールアドレス（大文字と小文字を区別しない）。
 ///
 /// # 戻り値
-/// `Vec<&'a Person>` - 与えられたメールアドレスを持つPersonオブジェクトの参照のベクター。
-fn get_related_gpersons<'a>(gpersons: &'a Vec<Person>, email: &String) -> Vec<&'a Person> {
-    // gpersonsベクターをイテレートし、条件に合致するPersonオブジェクトの参照をフィルタリング
-    let related_persons = gpersons
-        .iter()
-        .filter(
-            |&p|
-        // Personオブジェクトのemail_addressesフィールドをチェック
-        p.email_addresses.as_ref().unwrap_or(&Vec::new())
-        // email_addresses内の各EmailAddressオブジェクトに対して、
-        // 与えられたemailと一致するかどうかを確認
-        .iter()
-        .any(|e| e.value.as_ref() == Some(email)), // 条件に合致するPersonオブジェクトの参照をベクターとして収集
-        )
-        .collect::<Vec<&Person>>();
+/// メールアドレスを取り除いたPersonオブジェクト。
+fn without_email(gperson: &Person, email: &str) -> Person {
+    let mut updated = gperson.clone();
+    if let Some(emails) = updated.email_addresses.as_mut() {
+        emails.retain(|e| {
+            !e.value
+                .as_deref()
+                .is_some_and(|v| v.eq_ignore_ascii_case(email))
+        });
+    }
+    updated
+}
 
-    // 関連するPersonオブジェクトの参照のベクターを返す
-    related_persons
+/// 連絡先に、指定したメールアドレスの他にメールアドレスがあるかどうかを判定する関数。
+fn has_other_emails(gperson: &Person, email: &str) -> bool {
+    gperson
+        .email_addresses
+        .iter()
+        .flatten()
+        .filter_map(|e| e.value.as_deref())
+        .any(|v| !v.eq_ignore_ascii_case(email))
 }
 
-/// 特定のメールアドレスに関連する `APerson` オブジェクトの参照を取得する。
+/// Googleの連絡先から1つのメールアドレスを取り除く非同期関数。
 ///
-/// 与えられたメールアドレスと一致する `APerson` オブジェクトを `people` ベクターから探し出し、
-/// それらに関連する `APerson` オブジェクトの参照のベクターを返します。
+/// rmで複数のメールアドレスを持つ連絡先の1行を削除する場合に、連絡先の他のメールアドレスを残すために使用します。
 ///
 /// # 引数
-/// * `people` - `APerson` オブジェクトのベクターへの参照。
-/// * `email_to_find` - 検索するメールアドレス。
+/// * `gperson` - 更新するPersonオブジェクトへの参照。
+/// * `email` - 取り除くメールアドレス。
+/// * `service` - Google People APIにアクセスするためのPeopleServiceオブジェクトへの参照。
 ///
 /// # 戻り値
-/// `Vec<&'a APerson>` - 与えられたメールアドレスを持つ `APerson` オブジェクトの参照のベクター。
-fn get_related_apersons<'a>(people: &'a Vec<APerson>, email_to_find: &str) -> Vec<&'a APerson> {
-    // `people` ベクターをイテレートし、条件に合致する `APerson` オブジェクトの参照をフィルタリング
-    people
+/// 成功した場合はOk(())、失敗した場合はエラー。
+async fn remove_gperson_email(
+    gperson: &Person,
+    email: &str,
+    service: &PeopleService<HttpsConnector<HttpConnector>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(resource_name) = gperson.resource_name.as_ref() else {
+        return Err(Box::new(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "resource name is empty.",
+        )));
+    };
+    service
+        .people()
+        .update_contact(without_email(gperson, email), resource_name)
+        .update_person_fields(FieldMask::from_str("emailAddresses").unwrap())
+        .add_scope(mod_auth::AccessLevel::ReadWrite.scope())
+        .doit()
+        .await?;
+    Ok(())
+}
+
+/// Google Contactsからすべての連絡先を取得する非同期関数。
+///
+/// 設定の`google.page_size`件ずつ、次のページが無くなるまで取得します。
+///
+/// # 引数
+/// * `service` - Google People APIにアクセスするためのPeopleServiceオブジェクトへの参照。
+/// * `config` - 取得するフィールドとページサイズの設定。
+/// * `scope` - リクエストに使用するOAuth2スコープ。
+///
+/// # 戻り値
+/// 成功した場合はPersonオブジェクトのベクター、失敗した場合はエラー。
+async fn fetch_gpersons(
+    service: &PeopleService<HttpsConnector<HttpConnector>>,
+    config: &mod_config::Config,
+    scope: &str,
+) -> Result<Vec<Person>, Box<dyn std::error::Error>> {
+    let field_mask = FieldMask::from_str(&mod_config::Config::person_fields()).unwrap();
+
+    let mut gpersons = Vec::new();
+    let mut page_token: Option<String> = None;
+    loop {
+        let mut call = service
+            .people()
+            .connections_list("people/me")
+            .page_size(config.google.page_size)
+            .person_fields(field_mask.clone())
+            .add_scope(scope);
+        if let Some(token) = &page_token {
+            call = call.page_token(token);
+        }
+
+        // resultsは(Response<Body>, ListConnectionsResponse)のタプル
+        let results = call.doit().await?;
+        gpersons.extend(results.1.connections.unwrap_or_default());
+
+        // 次のページがあれば続けて取得する
+        match results.1.next_page_token {
+            Some(token) if !token.is_empty() => page_token = Some(token),
+            _ => break,
+        }
+    }
+
+    Ok(gpersons)
+}
+
+/// Google People APIを使用して連絡先グループ（ラベル）の一覧を取得する非同期関数。
+///
+/// # 引数
+/// * `service` - Google People APIにアクセスするためのPeopleServiceオブジェクトへの参照。
+/// * `scope` - リクエストに使用するOAuth2スコープ。
+///
+/// # 戻り値
+/// 成功した場合はContactGroupオブジェクトのベクター、失敗した場合はエラー。
+async fn fetch_contact_groups(
+    service: &PeopleService<HttpsConnector<HttpConnector>>,
+    scope: &str,
+) -> Result<Vec<ContactGroup>, Box<dyn std::error::Error>> {
+    let results = service
+        .contact_groups()
+        .list()
+        .page_size(1000)
+        .add_scope(scope)
+        .doit()
+        .await?;
+    Ok(results.1.contact_groups.unwrap_or_default())
+}
+
+/// ユーザーが作成した連絡先グループのリソース名とラベル名の対応を返す関数。
+///
+/// "My Contacts"などのシステムグループは含めません。書き出しでvCardのCATEGORIESなどに使用します。
+fn group_labels(groups: &[ContactGroup]) -> BTreeMap<String, String> {
+    groups
+        .iter()
+        .filter(|g| g.group_type.as_deref() == Some("USER_CONTACT_GROUP"))
+        .filter_map(|g| {
+            let label = g.formatted_name.as_ref().or(g.name.as_ref())?;
+            Some((g.resource_name.clone()?, label.clone()))
+        })
+        .collect()
+}
+
+/// ラベル（連絡先グループ）の設定に従って連絡先を振り分ける関数。
+///
+/// `include`が空でない場合はそのいずれかのラベルが付いた連絡先のみを、`exclude`のラベルが付いていない場合に対象とします。
+/// ラベルはグループ名、表示名（"My Contacts"など）、リソース名（"contactGroups/..."）のいずれでも指定できます。
+///
+/// # 引数
+/// * `gpersons` - 振り分けるPersonオブジェクトのベクター。
+/// * `groups` - 連絡先グループの一覧。
+/// * `labels` - ラベルの設定。
+///
+/// # 戻り値
+/// 成功した場合は対象の連絡先と対象外の連絡先のタプル、存在しないラベルが指定された場合はエラー。
+fn filter_gpersons_by_labels(
+    gpersons: Vec<Person>,
+    groups: &[ContactGroup],
+    labels: &mod_config::LabelsConfig,
+) -> Result<(Vec<Person>, Vec<Person>), Box<dyn std::error::Error>> {
+    // ラベルが指定されていなければすべて対象
+    if labels.include.is_empty() && labels.exclude.is_empty() {
+        return Ok((gpersons, Vec::new()));
+    }
+
+    // 指定されたラベルを連絡先グループのリソース名に変換する
+    let resolve = |names: &[String]| -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+        names
+            .iter()
+            .map(|label| {
+                groups
+                    .iter()
+                    .find(|g| {
+                        g.name.as_ref() == Some(label)
+                            || g.formatted_name.as_ref() == Some(label)
+                            || g.resource_name.as_ref() == Some(label)
+                    })
+                    .and_then(|g| g.resource_name.clone())
+                    .ok_or_else(|| format!("label not found: {}", label).into())
+            })
+            .collect()
+    };
+    let include = resolve(&labels.include)?;
+    let exclude = resolve(&labels.exclude)?;
+
+    Ok(gpersons.into_iter().partition(|person| {
+        // この連絡先が所属する連絡先グループ
+        let memberships: Vec<&String> = person
+            .memberships
+            .iter()
+            .flatten()
+            .filter_map(|m| {
+                m.contact_group_membership
+                    .as_ref()?
+                    .contact_group_resource_name
+                    .as_ref()
+            })
+            .collect();
+        (include.is_empty() || memberships.iter().any(|g| include.contains(*g)))
+            && !memberships.iter().any(|g| exclude.contains(*g))
+    }))
+}
+
+/// 連絡先と連絡先グループを取得し、ラベルの設定に従って振り分けたキャッシュを作成する非同期関数。
+///
+/// # 引数
+/// * `service` - Google People APIにアクセスするためのPeopleServiceオブジェクトへの参照。
+/// * `config` - 取得するフィールド、ページサイズ、ラベルの設定。
+/// * `scope` - リクエストに使用するOAuth2スコープ。
+///
+/// # 戻り値
+/// 成功した場合は取得した連絡先のキャッシュ（まだ保存しない）、失敗した場合はエラー。
+async fn fetch_contacts(
+    service: &PeopleService<HttpsConnector<HttpConnector>>,
+    config: &mod_config::Config,
+    scope: &str,
+) -> Result<mod_cache::ContactsCache, Box<dyn std::error::Error>> {
+    let gpersons = fetch_gpersons(service, config, scope).await?;
+    let groups = fetch_contact_groups(service, scope).await?;
+    let (gpersons, hidden_gpersons) = filter_gpersons_by_labels(gpersons, &groups, &config.labels)?;
+    Ok(mod_cache::ContactsCache::new(
+        gpersons,
+        hidden_gpersons,
+        group_labels(&groups),
+    ))
+}
+
+/// エラーメッセージを表示し、実行結果の報告を出力して終了する関数。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+/// * `report` - 出力する実行結果の報告。
+/// * `code` - 終了コード。
+/// * `message_id` - エラーメッセージのFluentメッセージID。
+/// * `error` - エラーの内容。
+fn exit_with_error(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    report: &mod_report::Report,
+    code: mod_exit::ExitCode,
+    message_id: &str,
+    error: &dyn std::fmt::Display,
+) -> ! {
+    let message = format!(
+        "{}: {}",
+        mod_fluent::get_translation(bundle, message_id),
+        error
+    );
+    eprintln!("{}", message);
+    report.exit(code, Some(&message))
+}
+
+/// アドレス帳のバックアップを作成する関数。失敗した場合はエラーメッセージを表示して終了する。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+/// * `report` - 終了する場合に出力する実行結果の報告。
+/// * `addressbook_path` - バックアップするアドレス帳のパス。
+/// * `retention` - 保持するバックアップの数。
+/// * `verbose` - 作成したバックアップのパスを表示するかどうか。
+fn backup_addressbook_or_exit(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    report: &mod_report::Report,
+    addressbook_path: &Path,
+    retention: usize,
+    verbose: bool,
+) {
+    match mod_backup::backup_addressbook(addressbook_path, retention) {
+        Ok(Some(backup_path)) if verbose => eprintln!(
+            "{}: {}",
+            mod_fluent::get_translation(bundle, "backup-created"),
+            backup_path.display()
+        ),
+        Ok(_) => {}
+        Err(e) => exit_with_error(
+            bundle,
+            report,
+            mod_exit::ExitCode::Failure,
+            "backup-error",
+            &e,
+        ),
+    }
+}
+
+/// GoogleのPersonオブジェクトが持つすべてのメールアドレスを取得する関数。
+///
+/// # 引数
+/// * `gpersons` - GoogleのPersonオブジェクトのスライス。
+///
+/// # 戻り値
+/// メールアドレスのHashSet。
+fn get_gperson_emails(gpersons: &[Person]) -> HashSet<String> {
+    gpersons
+        .iter()
+        .flat_map(|p| p.email_addresses.iter().flatten())
+        .filter_map(|e| e.value.clone())
+        .collect()
+}
+
+/// .addressbookのニックネームがGoogle Contactsのニックネームと同じとみなせるかどうかを判定する関数。
+///
+/// .addressbookに格納されているニックネームはそのまま使わず、末尾の数字を取り除いて比較します。
+/// generate_nickname()で作られるニックネーム（名前とテンプレートから決まるもの）と同じ場合は、
+/// Google Contactsのニックネームと同じとみなします。
+///
+/// # 引数
+/// * `aperson` - .addressbookのAPersonオブジェクトへの参照。
+/// * `gnickname` - Google Contactsのニックネーム。
+/// * `template` - ニックネームのテンプレート。
+///
+/// # 戻り値
+/// 同じとみなせる場合は`true`。
+fn nickname_matches(aperson: &APerson, gnickname: &str, template: &str) -> bool {
+    let anickname = split_string_and_number(&aperson.nickname).0;
+    anickname == gnickname || anickname == mod_config::nickname_base(&aperson.name, template)
+}
+
+/// .addressbookの行を差分の比較に使用する値に変換する関数。
+///
+/// # 引数
+/// * `aperson` - .addressbookのAPersonオブジェクトへの参照。
+///
+/// # 戻り値
+/// `mod_diff::Record` - 変換した値。
+fn addressbook_record(aperson: &APerson) -> mod_diff::Record {
+    mod_diff::Record {
+        nickname: aperson.nickname.clone(),
+        name: aperson.name.clone(),
+        email: aperson.email.clone(),
+        fcc: aperson.fcc.clone(),
+        comment: aperson.biography.clone(),
+    }
+}
+
+/// 同期で変更する可能性のある連絡先（一方にのみ存在するか、値が異なる連絡先）。
+struct SyncItem<'a> {
+    aperson: Option<&'a APerson>, // .addressbookの行（Google Contactsにのみ存在する場合は`None`）
+    gperson: Option<&'a Person>, // Google Contactsの連絡先（.addressbookにのみ存在する場合は`None`）
+    diff: mod_diff::ContactDiff, // フィールドごとの差分
+}
+
+/// .addressbookとGoogle Contactsの差分をメールアドレスごとに求め、同期で変更する可能性のある連絡先の一覧を作成する関数。
+///
+/// メールアドレスで連絡先を照合し、ニックネームの末尾の数字や自動で作成されたニックネーム、コメントを同期しない設定を考慮して比較します。
+/// ラベルの設定により対象外となった連絡先のメールアドレスは、.addressbookにのみ存在するものとして扱いません。
+///
+/// # 引数
+/// * `apeople` - .addressbookのAPersonオブジェクトのスライス。
+/// * `gpersons` - 同期の対象となるGoogleのPersonオブジェクトのスライス。
+/// * `hidden_gpersons` - ラベルの設定により対象外となったGoogleのPersonオブジェクトのスライス。
+/// * `config` - フィールドの対応とニックネームの設定。
+///
+/// # 戻り値
+/// メールアドレス順に並べた`SyncItem`のベクター。
+fn plan_sync<'a>(
+    apeople: &'a [APerson],
+    gpersons: &'a [Person],
+    hidden_gpersons: &[Person],
+    config: &mod_config::Config,
+) -> Vec<SyncItem<'a>> {
+    let hidden_emails = get_gperson_emails(hidden_gpersons);
+    let gperson_emails = get_gperson_emails(gpersons);
+    let aperson_emails: HashSet<String> = apeople.iter().map(|ap| ap.email.clone()).collect();
+    let all_emails: BTreeSet<&String> = gperson_emails.union(&aperson_emails).collect();
+
+    // Google Contactsの値を.addressbookの行と同じ形にする
+    let google_record = |gperson: &Person, email: &str, fcc: &str, abiography: Option<&str>| {
+        mod_diff::Record {
+            nickname: get_gcontact_nickname(gperson),
+            name: get_gcontact_name(gperson, &config.fields.name),
+            email: email.to_string(),
+            fcc: fcc.to_string(),
+            // コメントを同期しない場合は.addressbookの値（追加する場合は空）を使う
+            comment: if config.fields.comment {
+                get_gcontact_biography(gperson)
+            } else {
+                abiography.unwrap_or_default().to_string()
+            },
+        }
+    };
+    let mut items = Vec::new();
+    for email in all_emails {
+        if email.is_empty() {
+            continue;
+        }
+        let related_apeople = get_related_apersons(apeople, email);
+        let related_gpersons = get_related_gpersons(gpersons, email);
+
+        match related_apeople.first() {
+            // 両方に存在する
+            Some(aperson) if !related_gpersons.is_empty() => {
+                for gperson in related_gpersons {
+                    // Fccは同期で変更しないので.addressbookの値と同じとする
+                    let google =
+                        google_record(gperson, email, &aperson.fcc, Some(&aperson.biography));
+                    let mut changed = Vec::new();
+                    if !nickname_matches(aperson, &google.nickname, &config.nickname.template) {
+                        changed.push("nickname");
+                    }
+                    if aperson.name != google.name {
+                        changed.push("name");
+                    }
+                    if aperson.fcc != google.fcc {
+                        changed.push("fcc");
+                    }
+                    if aperson.biography != google.comment {
+                        changed.push("comment");
+                    }
+                    if !changed.is_empty() {
+                        items.push(SyncItem {
+                            aperson: Some(aperson),
+                            gperson: Some(gperson),
+                            diff: mod_diff::ContactDiff {
+                                email: email.clone(),
+                                addressbook: Some(addressbook_record(aperson)),
+                                google: Some(google),
+                                changed,
+                            },
+                        });
+                    }
+                }
+            }
+            // .addressbookにのみ存在する
+            Some(_) => {
+                if hidden_emails.contains(email) {
+                    continue;
+                }
+                for aperson in related_apeople {
+                    items.push(SyncItem {
+                        aperson: Some(aperson),
+                        gperson: None,
+                        diff: mod_diff::ContactDiff {
+                            email: email.clone(),
+                            addressbook: Some(addressbook_record(aperson)),
+                            google: None,
+                            changed: Vec::new(),
+                        },
+                    });
+                }
+            }
+            // Google Contactsにのみ存在する（.addressbookに追加する場合のFccは設定の値）
+            None => {
+                for gperson in related_gpersons {
+                    items.push(SyncItem {
+                        aperson: None,
+                        gperson: Some(gperson),
+                        diff: mod_diff::ContactDiff {
+                            email: email.clone(),
+                            addressbook: None,
+                            google: Some(google_record(gperson, email, &config.fields.fcc, None)),
+                            changed: Vec::new(),
+                        },
+                    });
+                }
+            }
+        }
+    }
+    items
+}
+
+/// 統合した値をGoogle Contactsに反映した結果に応じて、.addressbookの行を置き換える関数。
+///
+/// Google Contactsの更新に失敗した場合は、両者が食い違わないように.addressbookの行も変更せず、
+/// 失敗を実行結果の報告に記録します。
+///
+/// # 引数
+/// * `result` - Google Contactsの更新の結果。
+/// * `email` - 連絡先のメールアドレス。
+/// * `aperson` - 置き換える.addressbookの行。
+/// * `merged` - 統合した値。
+/// * `remove_apeople` - .addressbookから削除する行のベクター。
+/// * `add_apeople` - .addressbookに追加する行のベクター。
+/// * `report` - 実行結果の報告。
+///
+/// # 戻り値
+/// Google Contactsの更新に成功した場合はOk(())、失敗した場合はそのエラー。
+fn apply_merged_contact(
+    result: Result<(), Box<dyn std::error::Error>>,
+    email: &str,
+    aperson: &APerson,
+    merged: APerson,
+    remove_apeople: &mut Vec<APerson>,
+    add_apeople: &mut Vec<APerson>,
+    report: &mut mod_report::Report,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Err(e) = result {
+        report.record_error(
+            email,
+            mod_report::Side::Google,
+            mod_report::Action::Update,
+            e.as_ref(),
+        );
+        return Err(e);
+    }
+
+    report.record(mod_report::Side::Google, mod_report::Action::Update);
+    remove_apeople.push(aperson.clone());
+    add_apeople.push(merged);
+    report.record(mod_report::Side::AddressBook, mod_report::Action::Update);
+    Ok(())
+}
+
+/// TUIで選択された解決方法を、確認の入力と同じ解決方法に変換する関数。
+///
+/// 一方にのみ存在する連絡先の確認では、`g`/`a`は変更する側（削除する側、または追加される側の逆）を表すため、
+/// 合わせる側を表すTUIの選択とは逆になります。
+///
+/// # 引数
+/// * `decision` - TUIで選択された解決方法。
+/// * `one_sided` - 一方にのみ存在する連絡先かどうか。
+///
+/// # 戻り値
+/// `ConflictChoice` - 対応する解決方法。変更しない場合は`Skip`。
+fn tui_choice(decision: mod_tui::Decision, one_sided: bool) -> ConflictChoice {
+    match (decision, one_sided) {
+        (mod_tui::Decision::Google, false) | (mod_tui::Decision::AddressBook, true) => {
+            ConflictChoice::Source(UpdateSource::FromGoogle)
+        }
+        (mod_tui::Decision::AddressBook, false) | (mod_tui::Decision::Google, true) => {
+            ConflictChoice::Source(UpdateSource::FromAddressBook)
+        }
+        (mod_tui::Decision::Undecided | mod_tui::Decision::Reject, _) => ConflictChoice::Skip,
+    }
+}
+
+/// 残りのすべての連絡先に適用する解決方法を、一方にのみ存在する連絡先の解決方法に変換する関数。
+///
+/// `G`はGoogle Contactsの状態に、`A`は.addressbookの状態に合わせるものとして、TUIの選択と同じように変換します。
+///
+/// # 引数
+/// * `choice` - 両方に存在する連絡先の確認で大文字のG/A/Sにより選択された解決方法。
+///
+/// # 戻り値
+/// `ConflictChoice` - 対応する解決方法。変更しない場合は`Skip`。
+fn one_sided_choice(choice: &ConflictChoice) -> ConflictChoice {
+    match choice {
+        ConflictChoice::Source(UpdateSource::FromGoogle) => {
+            tui_choice(mod_tui::Decision::Google, true)
+        }
+        ConflictChoice::Source(UpdateSource::FromAddressBook) => {
+            tui_choice(mod_tui::Decision::AddressBook, true)
+        }
+        _ => ConflictChoice::Skip,
+    }
+}
+
+/// 同期で変更する連絡先をTUIで確認させ、項目ごとの解決方法を取得する関数。
+///
+/// 一方にのみ存在する連絡先は、もう一方に追加することを提案します。
+/// 値が異なる連絡先は、設定で優先する側が決まっている場合はその側を、決まっていない場合はGoogle Contactsを提案します。
+/// 標準入力と標準出力が端末でない場合や端末の操作に失敗した場合は、エラーメッセージを表示して終了します。
+/// 中断された場合は、どちらも変更せず、同期の状態も保存せずに、すべての項目を未解決として終了します。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+/// * `report` - 終了時に出力する実行結果の報告。
+/// * `plan` - 同期で変更する可能性のある連絡先。
+/// * `config` - 競合の解決の設定。
+///
+/// # 戻り値
+/// 項目ごとの解決方法。
+fn review_sync_plan(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    report: &mut mod_report::Report,
+    plan: &[SyncItem],
+    config: &mod_config::Config,
+) -> Vec<mod_tui::Decision> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        let message = mod_fluent::get_translation(bundle, "tui-not-terminal");
+        eprintln!("{}", message);
+        report.exit(mod_exit::ExitCode::Usage, Some(&message));
+    }
+
+    let items: Vec<mod_tui::Item> = plan
+        .iter()
+        .map(|item| mod_tui::Item {
+            diff: &item.diff,
+            proposed: match (item.aperson, item.gperson, config.sync.conflict_policy) {
+                (None, _, _) => mod_tui::Decision::Google,
+                (_, None, _) => mod_tui::Decision::AddressBook,
+                (_, _, mod_config::ConflictPolicy::AddressBook) => mod_tui::Decision::AddressBook,
+                _ => mod_tui::Decision::Google,
+            },
+        })
+        .collect();
+
+    match mod_tui::run(bundle, &items) {
+        Ok(Some(decisions)) => decisions,
+        Ok(None) => {
+            println!("{}", mod_fluent::get_translation(bundle, "op-cancel"));
+            for _ in plan {
+                report.record_unresolved();
+            }
+            report.finish();
+        }
+        Err(e) => exit_with_error(bundle, report, mod_exit::ExitCode::Failure, "tui-error", &e),
+    }
+}
+
+/// コマンド固有のオプションの値を解析する関数。不正な値の場合はエラーメッセージを表示して終了する。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+/// * `cli` - 解析したコマンドライン。
+/// * `name` - オプションの名前。
+///
+/// # 戻り値
+/// オプションが指定された場合は解析した値、指定されていない場合は`None`。
+fn parse_option_value<T: FromStr>(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    cli: &mod_cli::Cli,
+    name: &str,
+) -> Option<T> {
+    let value = cli.value(name)?;
+    match value.parse::<T>() {
+        Ok(parsed) => Some(parsed),
+        Err(_) => {
+            eprintln!(
+                "{}: --{} {}",
+                mod_fluent::get_translation(bundle, "cli-invalid-value"),
+                name,
+                value
+            );
+            eprintln!("{}", mod_fluent::get_translation(bundle, "cli-help-hint"));
+            mod_exit::ExitCode::Usage.exit();
+        }
+    }
+}
+
+/// 特定のメールアドレスを持つGoogleのPersonオブジェクトを取得する。
+///
+/// 与えられたメールアドレスと一致するメールアドレスを持つPersonオブジェクトを`gpersons`ベクターから探し出し、
+/// それらに関連するPersonオブジェクトの参照のベクターを返します。
+///
+/// # 引数
+/// * `gpersons` - GoogleのPersonオブジェクトのベクターへの参照。
+/// * `email` - 検索するメールアドレスの参照。
+///
+/// # 戻り値
+/// `Vec<&'a Person>` - 与えられたメールアドレスを持つPersonオブジェクトの参照のベクター。
+fn get_related_gpersons<'a>(gpersons: &'a [Person], email: &String) -> Vec<&'a Person> {
+    // gpersonsベクターをイテレートし、条件に合致するPersonオブジェクトの参照をフィルタリング
+    let related_persons = gpersons
+        .iter()
+        .filter(
+            |&p|
+        // Personオブジェクトのemail_addressesフィールドをチェック
+        p.email_addresses.as_ref().unwrap_or(&Vec::new())
+        // email_addresses内の各EmailAddressオブジェクトに対して、
+        // 与えられたemailと一致するかどうかを確認
+        .iter()
+        .any(|e| e.value.as_ref() == Some(email)), // 条件に合致するPersonオブジェクトの参照をベクターとして収集
+        )
+        .collect::<Vec<&Person>>();
+
+    // 関連するPersonオブジェクトの参照のベクターを返す
+    related_persons
+}
+
+/// 特定のメールアドレスに関連する `APerson` オブジェクトの参照を取得する。
+///
+/// 与えられたメールアドレスと一致する `APerson` オブジェクトを `people` ベクターから探し出し、
+/// それらに関連する `APerson` オブジェクトの参照のベクターを返します。
+///
+/// # 引数
+/// * `people` - `APerson` オブジェクトのベクターへの参照。
+/// * `email_to_find` - 検索するメールアドレス。
+///
+/// # 戻り値
+/// `Vec<&'a APerson>` - 与えられたメールアドレスを持つ `APerson` オブジェクトの参照のベクター。
+fn get_related_apersons<'a>(people: &'a [APerson], email_to_find: &str) -> Vec<&'a APerson> {
+    // `people` ベクターをイテレートし、条件に合致する `APerson` オブジェクトの参照をフィルタリング
+    people
         .iter()
         // `APerson` オブジェクトの email フィールドが `email_to_find` と一致するか確認
         .filter(|person| person.email == email_to_find)
@@ -665,11 +1799,11 @@ fn get_related_apersons<'a>(people: &'a Vec<APerson>, email_to_find: &str) -> Ve
 /// # 引数
 /// * `apeople` - `APerson` オブジェクトのベクターへの可変参照。
 /// * `related_apeople` - 削除する `APerson` オブジェクトの参照のベクター。
-fn remove_related_apersons(apeople: &mut Vec<APerson>, related_apeople: &Vec<APerson>) {
+fn remove_related_apersons(apeople: &mut Vec<APerson>, related_apeople: &[APerson]) {
     // `apeople` ベクターから `related_apeople` に含まれるオブジェクトを削除
     apeople.retain(|ap|
         // `related_apeople` に含まれていない `APerson` オブジェクトだけを保持
-        !related_apeople.contains(&ap));
+        !related_apeople.contains(ap));
 }
 
 /// 特定の `APerson` オブジェクトに関連するGoogleのPersonオブジェクトを検索する関数。
@@ -686,34 +1820,34 @@ fn remove_related_apersons(apeople: &mut Vec<APerson>, related_apeople: &Vec<APe
 /// `Option<Vec<Person>>` - 条件に一致するPersonオブジェクトのベクター。
 /// 一致するオブジェクトがない場合は `None` を返す。
 #[allow(dead_code)] // この関数は未使用でも警告を出さない
-fn get_gpersons_from_aperson(gpersons: &Vec<Person>, aperson: &APerson) -> Option<Vec<Person>> {
+fn get_gpersons_from_aperson(gpersons: &[Person], aperson: &APerson) -> Option<Vec<Person>> {
     // 条件に一致するPersonオブジェクトをフィルタリングして収集
     let filtered_gpersons: Vec<Person> = gpersons
         .iter()
         .filter(|gperson| {
             // Email と Nickname の条件をチェック
-            let email_match = gperson.email_addresses.as_ref().map_or(false, |emails| {
+            let email_match = gperson.email_addresses.as_ref().is_some_and(|emails| {
                 emails
                     .iter()
                     .any(|email| email.value.as_ref() == Some(&aperson.email))
             });
-            let nickname_match = gperson.nicknames.as_ref().map_or(false, |nicknames| {
+            let nickname_match = gperson.nicknames.as_ref().is_some_and(|nicknames| {
                 nicknames
                     .iter()
                     .any(|nickname| nickname.value.as_ref() == Some(&aperson.nickname))
             });
 
             // Name または Organization の条件をチェック
-            let name_match = gperson.names.as_ref().map_or(false, |names| {
+            let name_match = gperson.names.as_ref().is_some_and(|names| {
                 names
                     .iter()
                     .any(|name| name.display_name.as_ref() == Some(&aperson.name))
-            }) || gperson.organizations.as_ref().map_or(false, |orgs| {
+            }) || gperson.organizations.as_ref().is_some_and(|orgs| {
                 orgs.iter()
                     .any(|org| org.name.as_ref() == Some(&aperson.name))
             });
 
-            let biography_match = gperson.biographies.as_ref().map_or(false, |biographies| {
+            let biography_match = gperson.biographies.as_ref().is_some_and(|biographies| {
                 biographies
                     .iter()
                     .any(|bio| bio.value.as_ref() == Some(&aperson.biography))
@@ -735,31 +1869,40 @@ fn get_gpersons_from_aperson(gpersons: &Vec<Person>, aperson: &APerson) -> Optio
 
 /// GoogleのPersonオブジェクトから名前を取得する関数。
 ///
-/// この関数は、指定されたGoogleのPersonオブジェクトから名前を抽出します。Personオブジェクトに名前が存在する場合、
-/// 最初に見つかった名前を返します。名前が存在しない場合は、代わりに所属組織名を返します。どちらも存在しない場合は空文字列を返します。
+/// この関数は、指定されたGoogleのPersonオブジェクトから名前を抽出します。`fields`の順にフィールドを確認し、
+/// 名前（names）が存在する場合は最初に見つかった名前を、所属組織名（organizations）が存在する場合は最初の組織名を返します。
+/// どれも存在しない場合は空文字列を返します。
 ///
 /// # 引数
 /// * `person` - 名前を取得するGoogleのPersonオブジェクトへの参照。
+/// * `fields` - 名前として使用するフィールドの優先順位（設定の`fields.name`）。
 ///
 /// # 戻り値
 /// `String` - 取得した名前。名前または所属組織名が存在しない場合は空文字列。
-fn get_gcontact_name(person: &Person) -> String {
-    // Personオブジェクトのnamesフィールドを確認し、名前が存在するかチェック
-    if let Some(names) = &person.names {
-        // 名前のリストが空でない場合、最初の名前を使用
-        if let Some(name) = names.get(0) {
-            if let Some(display_name) = &name.display_name {
-                return display_name.clone();
+fn get_gcontact_name(person: &Person, fields: &[mod_config::NameField]) -> String {
+    for field in fields {
+        match field {
+            mod_config::NameField::Names => {
+                // Personオブジェクトのnamesフィールドを確認し、名前が存在するかチェック
+                if let Some(names) = &person.names {
+                    // 名前のリストが空でない場合、最初の名前を使用
+                    if let Some(name) = names.first() {
+                        if let Some(display_name) = &name.display_name {
+                            return display_name.clone();
+                        }
+                    }
+                }
             }
-        }
-    }
-
-    // 名前が空の場合、Personオブジェクトのorganizationsフィールドを確認
-    if let Some(organizations) = &person.organizations {
-        // 所属組織が存在する場合
-        if let Some(organization) = organizations.get(0) {
-            if let Some(name) = &organization.name {
-                return name.clone();
+            mod_config::NameField::Organizations => {
+                // Personオブジェクトのorganizationsフィールドを確認
+                if let Some(organizations) = &person.organizations {
+                    // 所属組織が存在する場合
+                    if let Some(organization) = organizations.first() {
+                        if let Some(name) = &organization.name {
+                            return name.clone();
+                        }
+                    }
+                }
             }
         }
     }
@@ -782,7 +1925,7 @@ fn get_gcontact_name(person: &Person) -> String {
 fn get_gcontact_nickname(person: &Person) -> String {
     if let Some(nicknames) = &person.nicknames {
         // ニックネームのリストが空でない場合、最初のニックネームを使用
-        if let Some(nickname) = nicknames.get(0) {
+        if let Some(nickname) = nicknames.first() {
             if let Some(value) = &nickname.value {
                 return value.clone();
             }
@@ -807,7 +1950,7 @@ fn get_gcontact_nickname(person: &Person) -> String {
 fn get_gcontact_biography(person: &Person) -> String {
     if let Some(biographies) = &person.biographies {
         // バイオグラフィのリストが空でない場合、最初のバイオグラフィを使用
-        if let Some(biography) = biographies.get(0) {
+        if let Some(biography) = biographies.first() {
             if let Some(value) = &biography.value {
                 return value.clone();
             }
@@ -818,48 +1961,1450 @@ fn get_gcontact_biography(person: &Person) -> String {
     "".to_string()
 }
 
-/// ユーザー入力に基づいてデータ更新のソースを選択する関数。
+/// 認証状態を表示する非同期関数。
 ///
-/// この関数は、ユーザーにGoogle Contactsと.addressbookのどちらをデータ更新のソースとして使用するかを尋ね、
-/// 入力に基づいて適切な `UpdateSource` 列挙型を返します。ユーザーが 'g' を入力した場合は `UpdateSource::FromGoogle` を、
-/// 'a' を入力した場合は `UpdateSource::FromAddressBook` を返します。入力が 'g' または 'a' 以外の場合は、
-/// オペレーションをキャンセルします。
+/// トークンキャッシュに保存されている各トークンについて、アカウント、許可されているスコープ、
+/// 有効期限を表示します。アカウントはアクセストークンが有効な場合のみ問い合わせます。
 ///
 /// # 引数
 /// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
-///
-/// # 戻り値
-/// `UpdateSource` - ユーザーが選択したデータ更新のソース。
-fn input_select_source(bundle: &FluentBundle<FluentResource, IntlLangMemoizer>) -> UpdateSource {
-    // デフォルトのデータソースをGoogleに設定
-    let mut source = UpdateSource::FromGoogle;
-
-    // ユーザー入力を取得するためのバッファ
-    let mut input = String::new();
-    // 標準入力からの読み取りを試み、エラーがあれば処理を終了
-    if let Err(e) = io::stdin().read_line(&mut input) {
+/// * `config` - トークンの保存先とアカウントの設定。
+async fn print_auth_status(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    config: &mod_config::Config,
+) {
+    // トークンキャッシュを読み込む
+    let tokens = mod_auth::load_cached_tokens(config).unwrap_or_else(|e| {
         eprintln!(
             "{}: {}",
-            mod_fluent::get_translation(&bundle, "input-error"),
+            mod_fluent::get_translation(bundle, "auth-status-error"),
             e
         );
-        std::process::exit(0);
-    }
+        mod_exit::ExitCode::Auth.exit();
+    });
+
+    // トークンが無ければ未認証
+    if tokens.is_empty() {
+        println!(
+            "{}",
+            mod_fluent::get_translation(bundle, "auth-status-none")
+        );
+        return;
+    }
+
+    for cached in &tokens {
+        // アクセストークンが期限切れでなければアカウントを問い合わせる
+        let account = match cached.token.access_token.as_ref() {
+            Some(access_token) if !cached.token.is_expired() => {
+                mod_auth::lookup_account(access_token).await
+            }
+            _ => None,
+        };
+        let expiry = match cached.token.expires_at {
+            Some(expires_at) if cached.token.is_expired() => format!(
+                "{} ({})",
+                expires_at,
+                mod_fluent::get_translation(bundle, "auth-status-expired")
+            ),
+            Some(expires_at) => expires_at.to_string(),
+            None => "-".to_string(),
+        };
+
+        println!(
+            "{}: {}",
+            mod_fluent::get_translation(bundle, "auth-status-account"),
+            account.unwrap_or_else(|| "-".to_string())
+        );
+        println!(
+            "{}: {}",
+            mod_fluent::get_translation(bundle, "auth-status-scopes"),
+            cached.scopes.join(" ")
+        );
+        println!(
+            "{}: {}",
+            mod_fluent::get_translation(bundle, "auth-status-expiry"),
+            expiry
+        );
+        println!(
+            "{}: {}\n",
+            mod_fluent::get_translation(bundle, "auth-status-refresh"),
+            if cached.token.refresh_token.is_some() {
+                "yes"
+            } else {
+                "no"
+            }
+        );
+    }
+}
+
+/// APersonオブジェクトをスナップショットに保存する形に変換する関数。
+///
+/// # 引数
+/// * `apeople` - 変換するAPersonオブジェクトのスライス。
+///
+/// # 戻り値
+/// スナップショットの行のベクター。メールアドレスの無い行は含めない。
+fn to_state_entries(apeople: &[APerson]) -> Vec<mod_state::Entry> {
+    apeople
+        .iter()
+        .filter(|ap| !ap.email.is_empty())
+        .map(to_state_entry)
+        .collect()
+}
+
+/// APersonオブジェクトをスナップショットや送信待ちの変更に保存する形に変換する関数。
+fn to_state_entry(aperson: &APerson) -> mod_state::Entry {
+    mod_state::Entry {
+        nickname: aperson.nickname.clone(),
+        name: aperson.name.clone(),
+        email: aperson.email.clone(),
+        fcc: aperson.fcc.clone(),
+        comment: aperson.biography.clone(),
+    }
+}
+
+/// スナップショットや送信待ちの変更に保存した行をAPersonオブジェクトに変換する関数。
+fn from_state_entry(entry: &mod_state::Entry) -> APerson {
+    APerson {
+        nickname: entry.nickname.clone(),
+        name: entry.name.clone(),
+        email: entry.email.clone(),
+        fcc: entry.fcc.clone(),
+        biography: entry.comment.clone(),
+    }
+}
+
+/// 同期の時刻と、同期した直後のアドレス帳の内容を保存する関数。
+///
+/// `status`コマンドで、最後の同期の時刻と、その後の.addressbookの変更を表示するために使用します。
+/// 保存に失敗しても同期の結果には影響しないため、警告を表示して続行します。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+/// * `config` - アカウントの設定。
+/// * `command` - 実行したコマンドの名前。
+/// * `addressbook_path` - 同期したアドレス帳のパス。
+fn save_sync_state(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    config: &mod_config::Config,
+    command: &str,
+    addressbook_path: &Path,
+) {
+    let result = load_addressbook_data(addressbook_path).and_then(|apeople| {
+        let state = mod_state::SyncState {
+            time: chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+            command: command.to_string(),
+            addressbook: addressbook_path.to_path_buf(),
+            entries: to_state_entries(&apeople),
+        };
+        Ok(mod_state::save(&config.google.account, &state)?)
+    });
+    if let Err(e) = result {
+        eprintln!(
+            "{}: {}",
+            mod_fluent::get_translation(bundle, "state-error"),
+            e
+        );
+    }
+}
+
+/// 同期の状態の概要を表示する非同期関数。
+///
+/// アドレス帳のパス、最後の同期の時刻、両側の連絡先の数、最後の同期の後の.addressbookの変更、
+/// トークンの有効性、連絡先のキャッシュの時刻を表示します。連絡先は同期と同じ方法で読み込みます。
+/// Google Contactsの連絡先は、使用できるトークンがキャッシュされている場合のみ取得します（新たに認証を求めない）。
+/// `offline`の場合は取得せず、キャッシュした連絡先を数えます。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+/// * `config` - アドレス帳のパス、トークンの保存先とアカウントの設定。
+/// * `offline` - Google Contactsにアクセスしないかどうか（`--offline`）。
+///
+/// # 戻り値
+/// 問題が無ければ`Success`、取得できない項目があった場合は最初の問題に対応する終了コード。
+async fn print_status(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    config: &mod_config::Config,
+    offline: bool,
+) -> mod_exit::ExitCode {
+    let mut code: Option<mod_exit::ExitCode> = None;
+    let label = |id: &str| mod_fluent::get_translation(bundle, id);
+    let addressbook_path = config.addressbook.path.as_path();
+
+    println!(
+        "{}: {}",
+        label("status-addressbook"),
+        addressbook_path.display()
+    );
+
+    // 最後の同期
+    let state = mod_state::load(&config.google.account).unwrap_or_else(|e| {
+        eprintln!("{}: {}", label("state-error"), e);
+        code.get_or_insert(mod_exit::ExitCode::Parse);
+        None
+    });
+    match &state {
+        Some(state) => println!(
+            "{}: {} ({})",
+            label("status-last-sync"),
+            state.time,
+            state.command
+        ),
+        None => println!("{}: {}", label("status-last-sync"), label("status-never")),
+    }
+
+    // .addressbookの連絡先（同期と同じく読み込む）
+    let entries = match load_addressbook_data(addressbook_path) {
+        Ok(apeople) => Some(to_state_entries(&apeople)),
+        Err(e) => {
+            eprintln!("{}: {}", label("fail-addressbook"), e);
+            code.get_or_insert(mod_exit::classify_read_error(e.as_ref()));
+            None
+        }
+    };
+    match &entries {
+        Some(entries) => println!(
+            "{}: {}",
+            label("status-addressbook-contacts"),
+            entries.len()
+        ),
+        None => println!("{}: -", label("status-addressbook-contacts")),
+    }
+
+    // 最後の同期の後の.addressbookの変更（別のアドレス帳のスナップショットとは比較しない）
+    match (&state, &entries) {
+        (Some(state), Some(entries)) if state.addressbook == addressbook_path => {
+            let pending = mod_state::pending_edits(&state.entries, entries);
+            println!(
+                "{}: {} ({} {}, {} {}, {} {})",
+                label("status-pending"),
+                pending.total(),
+                label("status-added"),
+                pending.added,
+                label("status-modified"),
+                pending.modified,
+                label("status-removed"),
+                pending.removed
+            );
+        }
+        _ => println!("{}: -", label("status-pending")),
+    }
+
+    // トークンの有効性
+    let scope = mod_auth::AccessLevel::ReadOnly.scope();
+    let tokens = mod_auth::load_cached_tokens(config).unwrap_or_else(|e| {
+        eprintln!("{}: {}", label("auth-status-error"), e);
+        code.get_or_insert(mod_exit::ExitCode::Auth);
+        Vec::new()
+    });
+    let valid = tokens
+        .iter()
+        .filter(|t| !t.token.is_expired())
+        .filter_map(|t| t.token.expires_at)
+        .max();
+    let token_status = if let Some(expires_at) = valid {
+        format!(
+            "{} ({} {})",
+            label("status-token-valid"),
+            label("auth-status-expiry"),
+            expires_at
+        )
+    } else if tokens.iter().any(|t| t.token.refresh_token.is_some()) {
+        label("status-token-refreshable")
+    } else if tokens.is_empty() {
+        label("status-token-none")
+    } else {
+        label("status-token-expired")
+    };
+    println!("{}: {}", label("status-token"), token_status);
+
+    // 連絡先のキャッシュ
+    let cache = mod_cache::load(&config.google.account).unwrap_or_else(|e| {
+        eprintln!("{}: {}", label("cache-error"), e);
+        code.get_or_insert(mod_exit::ExitCode::Parse);
+        None
+    });
+    match &cache {
+        Some(cache) => println!("{}: {}", label("status-cache"), cache.time),
+        None => println!("{}: {}", label("status-cache"), label("status-never")),
+    }
+
+    // オフラインで行った送信待ちの変更
+    match mod_outbox::load(&config.google.account) {
+        Ok(outbox) => println!("{}: {}", label("status-outbox"), outbox.len()),
+        Err(e) => {
+            eprintln!("{}: {}", label("outbox-error"), e);
+            code.get_or_insert(mod_exit::ExitCode::Parse);
+            println!("{}: -", label("status-outbox"));
+        }
+    }
+
+    // Google Contactsの連絡先（読み取り専用の権限を含むトークンが使用できる場合のみ）
+    let usable = tokens.iter().any(|t| {
+        t.scopes
+            .iter()
+            .any(|s| mod_token_storage::scope_covers(s, scope))
+            && (!t.token.is_expired() || t.token.refresh_token.is_some())
+    });
+    let google_count = if offline {
+        cache.map_or("-".to_string(), |cache| cache.split().0.len().to_string())
+    } else if usable {
+        let fetched = match (
+            mod_auth::get_auth(&mod_auth::AccessLevel::ReadOnly, config).await,
+            mod_auth::build_https_connector(),
+        ) {
+            (Ok(auth), Ok(connector)) => {
+                let service = PeopleService::new(Client::builder().build(connector), auth);
+                fetch_contacts(&service, config, scope)
+                    .await
+                    .map_err(|e| (mod_exit::classify_error(e.as_ref()), e))
+            }
+            (Err(e), _) => Err((mod_exit::ExitCode::Auth, e)),
+            (_, Err(e)) => Err((mod_exit::ExitCode::Failure, e.into())),
+        };
+        match fetched {
+            Ok(cache) => cache.split().0.len().to_string(),
+            Err((failure, e)) => {
+                eprintln!("{}: {}", label("fail-contact"), e);
+                code.get_or_insert(failure);
+                "-".to_string()
+            }
+        }
+    } else {
+        "-".to_string()
+    };
+    println!("{}: {}", label("status-google-contacts"), google_count);
+
+    code.unwrap_or(mod_exit::ExitCode::Success)
+}
+
+/// add、edit、rmでGoogle Contactsに行う変更。
+enum GoogleChange<'a> {
+    Create(&'a APerson),                      // 連絡先を作成する
+    Update(&'a Person, &'a str, &'a APerson), // 既存の連絡先の、変更前のメールアドレスの値を更新する
+    RemoveEmail(&'a Person, &'a str),         // 既存の連絡先から1つのメールアドレスを取り除く
+    Delete(&'a Person),                       // 既存の連絡先を削除する
+}
+
+/// ニックネームで.addressbookの連絡先を探す関数。見つからない場合はエラーメッセージを表示して終了する。
+///
+/// リスト（複数のメールアドレスをまとめた行）とメールアドレスの無い行は、Google Contactsに対応する連絡先が無いため対象外です。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+/// * `report` - 終了時に出力する実行結果の報告。
+/// * `apeople` - .addressbookのAPersonオブジェクトのスライス。
+/// * `nickname` - 探すニックネーム。
+///
+/// # 戻り値
+/// 見つかったAPersonオブジェクトへの参照。
+fn find_contact_or_exit<'a>(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    report: &mod_report::Report,
+    apeople: &'a [APerson],
+    nickname: &str,
+) -> &'a APerson {
+    apeople
+        .iter()
+        .find(|ap| ap.nickname == nickname && !ap.email.is_empty() && !is_addressbook_list(ap))
+        .unwrap_or_else(|| {
+            exit_with_error(
+                bundle,
+                report,
+                mod_exit::ExitCode::Failure,
+                "contact-not-found",
+                &nickname,
+            )
+        })
+}
+
+/// 連絡先のフィールドを指定するオプション（`--name`など）が指定されたかどうかを判定する関数。
+fn has_contact_options(cli: &mod_cli::Cli) -> bool {
+    ["name", "email", "nick", "fcc", "comment"]
+        .iter()
+        .any(|name| cli.value(name).is_some())
+}
+
+/// 連絡先にオプションで指定された値を反映する関数。
+///
+/// # 引数
+/// * `cli` - 解析したコマンドライン。
+/// * `aperson` - 元の連絡先。
+///
+/// # 戻り値
+/// オプションで指定されたフィールドを置き換えた連絡先。
+fn apply_contact_options(cli: &mod_cli::Cli, aperson: &APerson) -> APerson {
+    let value = |name: &str, current: &str| cli.value(name).unwrap_or(current).trim().to_string();
+    APerson {
+        nickname: value("nick", &aperson.nickname),
+        name: value("name", &aperson.name),
+        email: value("email", &aperson.email),
+        fcc: value("fcc", &aperson.fcc),
+        biography: value("comment", &aperson.biography),
+    }
+}
+
+/// 連絡先の値を検証する関数。
+///
+/// 値にタブを含めることはできず、ニックネームとメールアドレスは空白を含まない空でない値である必要があります。
+///
+/// # 引数
+/// * `aperson` - 検証する連絡先。
+///
+/// # 戻り値
+/// 不正な値がある場合は、そのフィールドのオプション名。
+fn invalid_contact_field(aperson: &APerson) -> Option<&'static str> {
+    let fields = [
+        ("nick", &aperson.nickname, true),
+        ("email", &aperson.email, true),
+        ("name", &aperson.name, false),
+        ("fcc", &aperson.fcc, false),
+        ("comment", &aperson.biography, false),
+    ];
+    fields
+        .iter()
+        .find(|(_, value, required)| {
+            value.contains('\t')
+                || (*required && (value.is_empty() || value.contains(char::is_whitespace)))
+        })
+        .map(|(name, _, _)| *name)
+}
+
+/// add、edit、rm、importで使用する.addressbookを読み込む関数。読み込めない場合はエラーメッセージを表示して終了する。
+///
+/// .addressbookが存在しない場合は空として扱います。
+/// 変更後の.addressbookはこの内容から書き込むため、メールアドレスの無い行も残します（連絡先の照合では対象外です）。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+/// * `report` - 終了時に出力する実行結果の報告。
+/// * `addressbook_path` - アドレス帳のパス。
+///
+/// # 戻り値
+/// .addressbookのAPersonオブジェクトのベクター。
+fn load_contacts_or_exit(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    report: &mod_report::Report,
+    addressbook_path: &Path,
+) -> Vec<APerson> {
+    if !addressbook_path.exists() {
+        return Vec::new();
+    }
+    load_addressbook_data(addressbook_path).unwrap_or_else(|e| {
+        exit_with_error(
+            bundle,
+            report,
+            mod_exit::classify_read_error(e.as_ref()),
+            "fail-addressbook",
+            &e,
+        )
+    })
+}
+
+/// .addressbookの新しい内容を、同じディレクトリの一時ファイルに書き込む関数。
+///
+/// 一時ファイルのパーミッションは元のファイルと同じにします。書き込みに失敗した場合は一時ファイルを削除します。
+///
+/// # 引数
+/// * `addressbook_path` - アドレス帳のパス。
+/// * `apeople` - 書き込むAPersonオブジェクトのスライス。
+///
+/// # 戻り値
+/// 成功した場合は一時ファイルのパス、失敗した場合はエラー。
+fn stage_addressbook(
+    addressbook_path: &Path,
+    apeople: &[APerson],
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let file_name = addressbook_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let staged =
+        addressbook_path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    let write = || -> Result<(), Box<dyn std::error::Error>> {
+        let mut writer = WriterBuilder::new().delimiter(b'\t').from_path(&staged)?;
+        for aperson in apeople {
+            writer.write_record([
+                &aperson.nickname,
+                &aperson.name,
+                &aperson.email,
+                &aperson.fcc,
+                &aperson.biography,
+            ])?;
+        }
+        writer.flush()?;
+        if let Ok(metadata) = fs::metadata(addressbook_path) {
+            fs::set_permissions(&staged, metadata.permissions())?;
+        }
+        Ok(())
+    };
+    match write() {
+        Ok(()) => Ok(staged),
+        Err(e) => {
+            let _ = fs::remove_file(&staged);
+            Err(e)
+        }
+    }
+}
+
+/// Google Contactsと.addressbookの連絡先を1つの操作として変更する非同期関数。
+///
+/// 新しい.addressbookを一時ファイルに書き込んでからGoogle Contactsを変更し、1つでも成功した場合は
+/// 一時ファイルで.addressbookを置き換えます（置き換える前にバックアップを作成します）。
+/// 失敗した変更はエラーメッセージを表示して報告に記録し、残りの変更を続けます（終了コードは`PartialSuccess`になります）。
+/// すべての変更に失敗した場合は、.addressbookを変更せずにエラーメッセージを表示して終了します。
+/// `service`が`None`（`--offline`）の場合は、Google Contactsの変更を送信待ちの変更として保存します。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+/// * `report` - 実行結果の報告。Google Contactsの変更を記録する。
+/// * `config` - アドレス帳のパス、フィールドの対応、バックアップの設定。
+/// * `service` - Google People APIにアクセスするためのPeopleServiceオブジェクトへの参照（オフラインの場合は`None`）。
+/// * `apeople` - 変更後の.addressbookの内容。
+/// * `changes` - Google Contactsに行う変更。
+/// * `verbose` - 作成したバックアップのパスを表示するかどうか。
+async fn commit_contact_change(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    report: &mut mod_report::Report,
+    config: &mod_config::Config,
+    service: Option<&PeopleService<HttpsConnector<HttpConnector>>>,
+    apeople: &[APerson],
+    changes: &[GoogleChange<'_>],
+    verbose: bool,
+) {
+    let addressbook_path = config.addressbook.path.as_path();
+    let staged = stage_addressbook(addressbook_path, apeople).unwrap_or_else(|e| {
+        exit_with_error(
+            bundle,
+            report,
+            mod_exit::ExitCode::Failure,
+            "write-error",
+            &e,
+        )
+    });
+
+    // オフラインの場合は、Google Contactsの変更を送信待ちの変更と連絡先のキャッシュに反映する
+    let Some(service) = service else {
+        if let Err(e) = queue_google_changes(config, changes) {
+            let _ = fs::remove_file(&staged);
+            exit_with_error(
+                bundle,
+                report,
+                mod_exit::ExitCode::Failure,
+                "outbox-error",
+                &e,
+            );
+        }
+        install_staged_addressbook(bundle, report, config, &staged, verbose);
+        return;
+    };
+
+    let mut succeeded = false;
+    let mut errors = Vec::new();
+    for change in changes {
+        let (email, action, result) = match change {
+            GoogleChange::Create(aperson) => (
+                aperson.email.as_str(),
+                mod_report::Action::Add,
+                update_google_contacts(None, aperson, "", &config.fields, service).await,
+            ),
+            GoogleChange::Update(person, previous_email, aperson) => (
+                aperson.email.as_str(),
+                mod_report::Action::Update,
+                update_google_contacts(
+                    Some(person),
+                    aperson,
+                    previous_email,
+                    &config.fields,
+                    service,
+                )
+                .await,
+            ),
+            GoogleChange::RemoveEmail(person, email) => (
+                *email,
+                mod_report::Action::Update,
+                remove_gperson_email(person, email, service).await,
+            ),
+            GoogleChange::Delete(person) => (
+                person
+                    .email_addresses
+                    .iter()
+                    .flatten()
+                    .find_map(|e| e.value.as_deref())
+                    .unwrap_or_default(),
+                mod_report::Action::Delete,
+                remove_related_gperson(person, service).await,
+            ),
+        };
+        match result {
+            Ok(()) => {
+                succeeded = true;
+                report.record(mod_report::Side::Google, action);
+            }
+            Err(e) => {
+                report.record_error(email, mod_report::Side::Google, action, e.as_ref());
+                errors.push(e);
+            }
+        }
+    }
+
+    // すべて失敗した場合は.addressbookを変更しない
+    if !succeeded && !errors.is_empty() {
+        let _ = fs::remove_file(&staged);
+        let first = errors.remove(0);
+        for e in &errors {
+            eprintln!(
+                "{}: {}",
+                mod_fluent::get_translation(bundle, "update-fail-google-contacts"),
+                e
+            );
+        }
+        exit_with_error(
+            bundle,
+            report,
+            mod_exit::classify_error(first.as_ref()),
+            "update-fail-google-contacts",
+            &first,
+        );
+    }
+
+    // 一部が成功した場合は、失敗した変更を表示して.addressbookを置き換える（次のsyncで差分として表示される）
+    for e in &errors {
+        eprintln!(
+            "{}: {}",
+            mod_fluent::get_translation(bundle, "update-fail-google-contacts"),
+            e
+        );
+    }
+    install_staged_addressbook(bundle, report, config, &staged, verbose);
+}
+
+/// 一時ファイルに書き込んだ.addressbookで、.addressbookを置き換える関数。
+///
+/// 置き換える前にバックアップを作成します。失敗した場合はエラーメッセージを表示して終了します。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+/// * `report` - 終了時に出力する実行結果の報告。
+/// * `config` - アドレス帳のパスとバックアップの設定。
+/// * `staged` - 新しい.addressbookを書き込んだ一時ファイル。
+/// * `verbose` - 作成したバックアップのパスを表示するかどうか。
+fn install_staged_addressbook(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    report: &mod_report::Report,
+    config: &mod_config::Config,
+    staged: &Path,
+    verbose: bool,
+) {
+    let addressbook_path = config.addressbook.path.as_path();
+    backup_addressbook_or_exit(
+        bundle,
+        report,
+        addressbook_path,
+        config.backup.retention,
+        verbose,
+    );
+    if let Err(e) = fs::rename(staged, addressbook_path) {
+        let _ = fs::remove_file(staged);
+        exit_with_error(
+            bundle,
+            report,
+            mod_exit::ExitCode::Failure,
+            "write-error",
+            &e,
+        );
+    }
+}
+
+/// オフラインで行ったGoogle Contactsの変更を、送信待ちの変更と連絡先のキャッシュに反映する関数。
+///
+/// 続けてオフラインで実行するコマンドが変更後の連絡先を参照できるように、キャッシュも変更します。
+/// オフラインで作成した連絡先には、キャッシュで仮のリソース名（`outbox/<番号>`）を使用します。
+///
+/// # 引数
+/// * `config` - アカウントとフィールドの対応の設定。
+/// * `changes` - Google Contactsに行う変更。
+///
+/// # 戻り値
+/// 成功した場合はOk(())、読み込みや書き込みに失敗した場合はエラー。
+fn queue_google_changes(
+    config: &mod_config::Config,
+    changes: &[GoogleChange<'_>],
+) -> io::Result<()> {
+    let account = &config.google.account;
+    let mut outbox = mod_outbox::load(account)?;
+    let mut cache = mod_cache::load(account)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no contacts cache"))?;
+
+    for change in changes {
+        match change {
+            GoogleChange::Create(aperson) => {
+                let id = mod_outbox::next_id(&outbox);
+                let mut gperson = build_gperson(None, aperson, "", &config.fields);
+                gperson.resource_name = Some(mod_outbox::placeholder(id));
+                cache.contacts.push(gperson);
+                mod_outbox::record(
+                    &mut outbox,
+                    mod_outbox::Mutation::Create {
+                        id,
+                        contact: to_state_entry(aperson),
+                    },
+                );
+            }
+            GoogleChange::Update(gperson, previous_email, aperson) => {
+                let updated = build_gperson(Some(gperson), aperson, previous_email, &config.fields);
+                for cached in cache.contacts.iter_mut() {
+                    if cached.resource_name == gperson.resource_name {
+                        *cached = updated.clone();
+                    }
+                }
+                mod_outbox::record(
+                    &mut outbox,
+                    mod_outbox::Mutation::Update {
+                        resource_name: gperson.resource_name.clone().unwrap_or_default(),
+                        etag: gperson.etag.clone().unwrap_or_default(),
+                        email: previous_email.to_string(),
+                        contact: to_state_entry(aperson),
+                    },
+                );
+            }
+            GoogleChange::RemoveEmail(gperson, email) => {
+                let updated = without_email(gperson, email);
+                for cached in cache.contacts.iter_mut() {
+                    if cached.resource_name == gperson.resource_name {
+                        *cached = updated.clone();
+                    }
+                }
+                mod_outbox::record(
+                    &mut outbox,
+                    mod_outbox::Mutation::RemoveEmail {
+                        resource_name: gperson.resource_name.clone().unwrap_or_default(),
+                        etag: gperson.etag.clone().unwrap_or_default(),
+                        email: email.to_string(),
+                    },
+                );
+            }
+            GoogleChange::Delete(gperson) => {
+                cache
+                    .contacts
+                    .retain(|cached| cached.resource_name != gperson.resource_name);
+                let email = gperson
+                    .email_addresses
+                    .iter()
+                    .flatten()
+                    .find_map(|e| e.value.clone())
+                    .unwrap_or_default();
+                mod_outbox::record(
+                    &mut outbox,
+                    mod_outbox::Mutation::Delete {
+                        resource_name: gperson.resource_name.clone().unwrap_or_default(),
+                        etag: gperson.etag.clone().unwrap_or_default(),
+                        email,
+                    },
+                );
+            }
+        }
+    }
+
+    // キャッシュを先に保存し、送信待ちの変更を保存できた場合のみ変更を確定する
+    mod_cache::save(account, &cache)?;
+    mod_outbox::save(account, &outbox)
+}
+
+/// 送信待ちの変更をGoogle People APIで反映する処理。
+struct GoogleTarget<'a> {
+    fields: &'a mod_config::FieldsConfig,
+    service: &'a PeopleService<HttpsConnector<HttpConnector>>,
+}
+
+#[async_trait::async_trait(?Send)]
+impl mod_outbox::Target for GoogleTarget<'_> {
+    async fn create(&self, contact: &mod_state::Entry) -> Result<(), Box<dyn std::error::Error>> {
+        let aperson = from_state_entry(contact);
+        update_google_contacts(None, &aperson, "", self.fields, self.service).await
+    }
+
+    async fn update(
+        &self,
+        gperson: &Person,
+        previous_email: &str,
+        contact: &mod_state::Entry,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let aperson = from_state_entry(contact);
+        update_google_contacts(
+            Some(gperson),
+            &aperson,
+            previous_email,
+            self.fields,
+            self.service,
+        )
+        .await
+    }
+
+    async fn remove_email(
+        &self,
+        gperson: &Person,
+        email: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        remove_gperson_email(gperson, email, self.service).await
+    }
+
+    async fn delete(&self, gperson: &Person) -> Result<(), Box<dyn std::error::Error>> {
+        remove_related_gperson(gperson, self.service).await
+    }
+}
+
+/// 送信待ちの変更をGoogle Contactsに反映する非同期関数。
+///
+/// 各変更を現在のGoogle Contactsの連絡先と照合して競合を検出します。更新と削除は連絡先が削除されているか
+/// etagがオフラインで変更した時点から変わっている場合、作成は同じメールアドレスの連絡先が既にある場合に
+/// 競合とし、反映せずに警告を表示して破棄します（.addressbookには変更が残っているため、続く同期で差分として扱われます）。
+/// 反映した変更は1つずつ送信待ちから取り除き、反映に失敗した場合は残りの変更を保存したまま終了します。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+/// * `report` - 実行結果の報告。Google Contactsの変更を記録する。
+/// * `config` - アカウントとフィールドの対応の設定。
+/// * `service` - Google People APIにアクセスするためのPeopleServiceオブジェクトへの参照。
+/// * `gpersons` - 現在のGoogle Contactsのすべての連絡先（ラベルの設定により対象外となった連絡先を含む）。
+/// * `verbose` - 反映した変更を表示するかどうか。
+///
+/// # 戻り値
+/// 送信待ちの変更があった場合は`true`（Google Contactsの連絡先を取得し直す必要がある）。
+async fn replay_outbox(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    report: &mut mod_report::Report,
+    config: &mod_config::Config,
+    service: &PeopleService<HttpsConnector<HttpConnector>>,
+    gpersons: &[Person],
+    verbose: bool,
+) -> bool {
+    let account = &config.google.account;
+    let mut outbox = mod_outbox::load(account).unwrap_or_else(|e| {
+        exit_with_error(
+            bundle,
+            report,
+            mod_exit::ExitCode::Parse,
+            "outbox-error",
+            &e,
+        )
+    });
+    if outbox.is_empty() {
+        return false;
+    }
+    if verbose {
+        eprintln!(
+            "{}: {}",
+            mod_fluent::get_translation(bundle, "outbox-replaying"),
+            outbox.len()
+        );
+    }
+
+    let target = GoogleTarget {
+        fields: &config.fields,
+        service,
+    };
+    while let Some((email, replayed)) =
+        mod_outbox::replay_first(&mut outbox, gpersons, &target).await
+    {
+        match replayed {
+            Ok(mod_outbox::Replayed::Applied(action)) => {
+                report.record(mod_report::Side::Google, action);
+                if verbose {
+                    eprintln!(
+                        "{}: {}",
+                        mod_fluent::get_translation(bundle, "outbox-replayed"),
+                        email
+                    );
+                }
+            }
+            Ok(mod_outbox::Replayed::Conflict(message_id)) => eprintln!(
+                "{}: {}",
+                mod_fluent::get_translation(bundle, message_id),
+                email
+            ),
+            Err((action, e)) => {
+                // 反映できなかった変更と残りの変更は、次の同期まで送信待ちのままにする
+                report.record_error(&email, mod_report::Side::Google, action, e.as_ref());
+                exit_with_error(
+                    bundle,
+                    report,
+                    mod_exit::classify_error(e.as_ref()),
+                    "outbox-replay-error",
+                    &e,
+                );
+            }
+        }
+
+        if let Err(e) = mod_outbox::save(account, &outbox) {
+            exit_with_error(
+                bundle,
+                report,
+                mod_exit::ExitCode::Failure,
+                "outbox-error",
+                &e,
+            );
+        }
+    }
+    true
+}
+
+/// Google Contactsから連絡先を取得し、ラベルの設定に従って絞り込む非同期関数。
+///
+/// 取得した連絡先はキャッシュに保存します。`refresh`以外では、保存に失敗しても警告を表示して続行します。
+/// 取得に失敗した場合はエラーメッセージを表示して終了します。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+/// * `report` - 終了時に出力する実行結果の報告。
+/// * `service` - Google People APIにアクセスするためのPeopleServiceオブジェクトへの参照。
+/// * `config` - 取得するフィールド、ページサイズ、ラベル、アカウントの設定。
+/// * `sel` - 実行中のコマンド。
+/// * `verbose` - 取得した連絡先の数を表示するかどうか。
+///
+/// # 戻り値
+/// 取得した連絡先のキャッシュ。
+async fn fetch_contacts_or_exit(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    report: &mod_report::Report,
+    service: &PeopleService<HttpsConnector<HttpConnector>>,
+    config: &mod_config::Config,
+    sel: mod_cli::Command,
+    verbose: bool,
+) -> mod_cache::ContactsCache {
+    let scope = sel.access_level().scope();
+    let cache = fetch_contacts(service, config, scope)
+        .await
+        .unwrap_or_else(|e| {
+            exit_with_error(
+                bundle,
+                report,
+                mod_exit::classify_error(e.as_ref()),
+                "fail-contact",
+                &e,
+            )
+        });
+
+    if let Err(e) = mod_cache::save(&config.google.account, &cache) {
+        if sel == mod_cli::Command::Refresh {
+            exit_with_error(
+                bundle,
+                report,
+                mod_exit::ExitCode::Failure,
+                "cache-error",
+                &e,
+            );
+        }
+        eprintln!(
+            "{}: {}",
+            mod_fluent::get_translation(bundle, "cache-error"),
+            e
+        );
+    }
+
+    if verbose {
+        eprintln!(
+            "{}: {}",
+            mod_fluent::get_translation(bundle, "fetched-contacts"),
+            cache.contacts.len() - cache.hidden.len()
+        );
+    }
+    cache
+}
+
+/// 最後の同期のスナップショットに、add、edit、rmによる連絡先の変更を反映する関数。
+///
+/// これらのコマンドはGoogle Contactsと.addressbookの両方を変更するため、
+/// `status`コマンドで未同期の変更として数えないようにします。同期の時刻は変更しません。
+/// まだ同期していない場合や、別のアドレス帳のスナップショットの場合は何もしません。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+/// * `config` - アカウントとアドレス帳のパスの設定。
+/// * `before` - 変更前の連絡先（追加の場合は`None`）。
+/// * `after` - 変更後の連絡先（削除の場合は`None`）。
+fn update_sync_state_entry(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    config: &mod_config::Config,
+    before: Option<&APerson>,
+    after: Option<&APerson>,
+) {
+    let result = mod_state::load(&config.google.account).and_then(|state| match state {
+        Some(mut state) if state.addressbook == config.addressbook.path => {
+            if let Some(entry) = before.and_then(|ap| to_state_entries(slice::from_ref(ap)).pop()) {
+                if let Some(index) = state.entries.iter().position(|e| *e == entry) {
+                    state.entries.remove(index);
+                }
+            }
+            if let Some(after) = after {
+                state
+                    .entries
+                    .extend(to_state_entries(slice::from_ref(after)));
+            }
+            mod_state::save(&config.google.account, &state)
+        }
+        _ => Ok(()),
+    });
+    if let Err(e) = result {
+        eprintln!(
+            "{}: {}",
+            mod_fluent::get_translation(bundle, "state-error"),
+            e
+        );
+    }
+}
+
+/// 連絡先の.addressbookとGoogle Contactsの値を表示する関数。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+/// * `aperson` - .addressbookの連絡先。
+/// * `related_gpersons` - 同じメールアドレスを持つGoogle Contactsの連絡先。
+/// * `config` - フィールドの対応の設定。
+fn print_contact(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    aperson: &APerson,
+    related_gpersons: &[&Person],
+    config: &mod_config::Config,
+) {
+    println!(".addressbook:");
+    println!("  nickname: {}", aperson.nickname);
+    println!("  name: {}", aperson.name);
+    println!("  email: {}", aperson.email);
+    println!("  fcc: {}", aperson.fcc);
+    println!("  comment: {}", aperson.biography);
+
+    if related_gpersons.is_empty() {
+        println!(
+            "Google Contacts: {}",
+            mod_fluent::get_translation(bundle, "show-not-in-google")
+        );
+    }
+    for gperson in related_gpersons {
+        let emails: Vec<&str> = gperson
+            .email_addresses
+            .iter()
+            .flatten()
+            .filter_map(|e| e.value.as_deref())
+            .collect();
+        println!(
+            "Google Contacts ({}):",
+            gperson.resource_name.as_deref().unwrap_or_default()
+        );
+        println!("  nickname: {}", get_gcontact_nickname(gperson));
+        println!(
+            "  name: {}",
+            get_gcontact_name(gperson, &config.fields.name)
+        );
+        println!("  email: {}", emails.join(", "));
+        println!("  comment: {}", get_gcontact_biography(gperson));
+    }
+}
+
+/// 連絡先のキャッシュを読み込む関数。キャッシュが無い場合や読み込めない場合はエラーメッセージを表示して終了する。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+/// * `report` - 終了時に出力する実行結果の報告。
+/// * `config` - アカウントの設定。
+///
+/// # 戻り値
+/// 読み込んだキャッシュ。
+fn load_contacts_cache_or_exit(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    report: &mod_report::Report,
+    config: &mod_config::Config,
+) -> mod_cache::ContactsCache {
+    match mod_cache::load(&config.google.account) {
+        Ok(Some(cache)) => cache,
+        Ok(None) => {
+            let message = mod_fluent::get_translation(bundle, "cache-missing");
+            eprintln!("{}", message);
+            report.exit(mod_exit::ExitCode::Failure, Some(&message));
+        }
+        Err(e) => exit_with_error(bundle, report, mod_exit::ExitCode::Parse, "cache-error", &e),
+    }
+}
+
+/// 連絡先を検索し、muttの`query_command`の形式で表示する関数。
+///
+/// ニックネーム、名前、メールアドレス、所属組織名のいずれかに検索文字列を含む連絡先を、大文字と小文字を
+/// 区別せずに検索します。1行目は空行で、続けて一致した連絡先のメールアドレスごとに
+/// `メールアドレス<TAB>名前<TAB>コメント`の行を表示します。
+///
+/// # 引数
+/// * `query` - 検索文字列。
+/// * `gpersons` - 検索するGoogle Contactsの連絡先。
+/// * `config` - フィールドの対応の設定。
+///
+fn print_query_results(query: &str, gpersons: &[Person], config: &mod_config::Config) {
+    let query = query.to_lowercase();
+    let contains = |value: &str| value.to_lowercase().contains(&query);
+    // タブと改行はmuttの形式の区切りになるので空白に置き換える
+    let flatten = |value: String| value.replace(['\t', '\r', '\n'], " ");
+
+    println!();
+    for gperson in gpersons {
+        let emails: Vec<&str> = gperson
+            .email_addresses
+            .iter()
+            .flatten()
+            .filter_map(|e| e.value.as_deref())
+            .filter(|e| !e.is_empty())
+            .collect();
+        let name = get_gcontact_name(gperson, &config.fields.name);
+        let matched = contains(&get_gcontact_nickname(gperson))
+            || contains(&name)
+            || emails.iter().any(|e| contains(e))
+            || gperson
+                .organizations
+                .iter()
+                .flatten()
+                .filter_map(|o| o.name.as_deref())
+                .any(contains);
+        if !matched {
+            continue;
+        }
+
+        let name = flatten(name);
+        let comment = flatten(get_gcontact_biography(gperson));
+        for email in emails {
+            println!("{}\t{}\t{}", email, name, comment);
+        }
+    }
+}
+
+/// 標準入力から1行読み込む関数。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+///
+/// # 戻り値
+/// 読み込んだ行（末尾の改行は除く）。入力の終わりに達した場合や読み込みに失敗した場合は`None`。
+fn read_input_line(bundle: &FluentBundle<FluentResource, IntlLangMemoizer>) -> Option<String> {
+    let mut input = String::new();
+    match io::stdin().read_line(&mut input) {
+        Ok(0) => None,
+        Ok(_) => Some(input.trim_end_matches(['\r', '\n']).to_string()),
+        Err(e) => {
+            eprintln!(
+                "{}: {}",
+                mod_fluent::get_translation(bundle, "input-error"),
+                e
+            );
+            None
+        }
+    }
+}
+
+/// ユーザー入力に基づいてデータ更新のソースを選択する関数。
+///
+/// この関数は、ユーザーにGoogle Contactsと.addressbookのどちらをデータ更新のソースとして使用するかを尋ね、
+/// 入力に基づいて適切な `UpdateSource` 列挙型を返します。ユーザーが 'g' を入力した場合は `UpdateSource::FromGoogle` を、
+/// 'a' を入力した場合は `UpdateSource::FromAddressBook` を返します。'q' を入力した場合や、
+/// 入力を読み取れない場合は、オペレーションをキャンセルします。それ以外の入力の場合は再入力させます。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+///
+/// # 戻り値
+/// `Option<UpdateSource>` - ユーザーが選択したデータ更新のソース。キャンセルされた場合は`None`。
+fn input_select_source(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+) -> Option<UpdateSource> {
+    loop {
+        // 標準入力からの読み取りを試み、入力が無ければキャンセルする
+        let input = read_input_line(bundle)?;
+
+        // ユーザー入力により、Google Contactsまたは.addressbookのどちらのデータを優先するか決定
+        match input.trim().to_lowercase().as_str() {
+            // Google Contactsを優先し、.addressbookを更新する
+            "g" => return Some(UpdateSource::FromGoogle),
+            // .addressbookを優先し、Google Contactsを更新する
+            "a" => return Some(UpdateSource::FromAddressBook),
+            "q" => {
+                println!("{}", mod_fluent::get_translation(bundle, "op-cancel"));
+                return None;
+            }
+            _ => println!("{}", mod_fluent::get_translation(bundle, "invalid-input")),
+        }
+    }
+}
+
+/// 両方に存在する連絡先の競合の解決方法を入力させる関数。
+///
+/// 'g'/'a'で一方のデータを採用し、'm'でフィールドごとに値を選択します。's'は解決せずに次の連絡先へ進みます。
+/// 'e'で両方の値を$EDITORで編集します。
+/// 大文字の'G'/'A'/'S'は、一方にのみ存在する連絡先を含め、残りのすべての連絡先に同じ解決方法を適用します（`apply_to_all`に設定します）。
+/// 'q'を入力した場合や入力を読み取れない場合はキャンセルし、それ以外の入力の場合は再入力させます。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+/// * `local` - .addressbookのAPersonオブジェクトへの参照。
+/// * `google` - Google Contactsの値を.addressbookの行と同じ形にしたAPersonオブジェクトへの参照。
+/// * `comment` - コメントを同期するかどうか。
+/// * `apply_to_all` - 残りのすべての競合に適用する解決方法の設定先。
+///
+/// # 戻り値
+/// `ConflictChoice` - 選択された解決方法。
+fn input_conflict_resolution(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    local: &APerson,
+    google: &APerson,
+    comment: bool,
+    apply_to_all: &mut Option<ConflictChoice>,
+) -> ConflictChoice {
+    loop {
+        let input = match read_input_line(bundle) {
+            Some(input) => input,
+            None => return ConflictChoice::Cancel,
+        };
+
+        let choice = match input.trim() {
+            "g" | "G" => ConflictChoice::Source(UpdateSource::FromGoogle),
+            "a" | "A" => ConflictChoice::Source(UpdateSource::FromAddressBook),
+            "s" | "S" => ConflictChoice::Skip,
+            "m" => return input_merged_fields(bundle, local, google, comment),
+            "e" => match edit_conflict(bundle, local, google) {
+                Some(edited) => return ConflictChoice::Merge(edited),
+                None => {
+                    // 編集を中止した場合は、もう一度選択させる
+                    println!("{}", mod_fluent::get_translation(bundle, "update-mode"));
+                    continue;
+                }
+            },
+            "q" => {
+                println!("{}", mod_fluent::get_translation(bundle, "op-cancel"));
+                return ConflictChoice::Cancel;
+            }
+            _ => {
+                println!(
+                    "{}",
+                    mod_fluent::get_translation(bundle, "invalid-input-conflict")
+                );
+                continue;
+            }
+        };
+
+        // 大文字の場合は残りのすべての競合に適用する
+        if input.trim().chars().all(|c| c.is_ascii_uppercase()) {
+            *apply_to_all = Some(choice.clone());
+        }
+        return choice;
+    }
+}
+
+/// フィールドごとに値を選択させ、統合した連絡先を作成する関数。
+///
+/// 値が異なるフィールド（ニックネーム、名前、コメント）ごとに、'g'/'a'でどちらかの値を選択するか、
+/// '='に続けて新しい値を入力させます。メールアドレスとFccは.addressbookの値を使用します。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+/// * `local` - .addressbookのAPersonオブジェクトへの参照。
+/// * `google` - Google Contactsの値を.addressbookの行と同じ形にしたAPersonオブジェクトへの参照。
+/// * `comment` - コメントを同期するかどうか。同期しない場合はコメントを選択させない。
+///
+/// # 戻り値
+/// `ConflictChoice` - 統合した連絡先（`Merge`）、またはフィールドの入力で選択されたスキップかキャンセル。
+fn input_merged_fields(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    local: &APerson,
+    google: &APerson,
+    comment: bool,
+) -> ConflictChoice {
+    let mut merged = local.clone();
+
+    // ニックネーム
+    if local.nickname != google.nickname {
+        merged.nickname =
+            match input_field_value(bundle, "nickname", &local.nickname, &google.nickname) {
+                Ok(value) => value,
+                Err(choice) => return choice,
+            };
+    }
+    // 名前
+    if local.name != google.name {
+        merged.name = match input_field_value(bundle, "name", &local.name, &google.name) {
+            Ok(value) => value,
+            Err(choice) => return choice,
+        };
+    }
+    // コメント
+    if comment && local.biography != google.biography {
+        merged.biography =
+            match input_field_value(bundle, "comment", &local.biography, &google.biography) {
+                Ok(value) => value,
+                Err(choice) => return choice,
+            };
+    }
+
+    ConflictChoice::Merge(merged)
+}
+
+/// 競合する連絡先を$EDITORで編集させる関数。
+///
+/// .addressbookの値を編集できる文書を作成し、値が異なるフィールドにはGoogle Contactsの値をコメントとして表示します。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+/// * `local` - .addressbookのAPersonオブジェクトへの参照。
+/// * `google` - Google Contactsの値を.addressbookの行と同じ形にしたAPersonオブジェクトへの参照。
+///
+/// # 戻り値
+/// 編集した連絡先。編集を中止した場合やエディタを起動できない場合は`None`。
+fn edit_conflict(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    local: &APerson,
+    google: &APerson,
+) -> Option<APerson> {
+    let header = [
+        mod_fluent::get_translation(bundle, "edit-header"),
+        format!("email: {}", local.email),
+    ];
+    edit_contact_document(
+        bundle,
+        &header,
+        local,
+        &["nickname", "name", "fcc", "comment"],
+        Some(("Google Contacts", google)),
+    )
+}
+
+/// 連絡先を1行に1フィールドの文書として$EDITORで編集させる関数。
+///
+/// 保存した文書が不正な場合は、エラーを表示して再編集するかどうかを確認します。再編集では前回の内容を開きます。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+/// * `header` - 文書の先頭にコメントとして表示する行。
+/// * `base` - 編集する連絡先。
+/// * `fields` - 編集できるフィールドの名前。
+/// * `alternative` - 比較のためにコメントとして表示する見出しと連絡先。
+///
+/// # 戻り値
+/// 編集した連絡先。編集を中止した場合やエディタを起動できない場合は`None`。
+fn edit_contact_document(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    header: &[String],
+    base: &APerson,
+    fields: &[&str],
+    alternative: Option<(&str, &APerson)>,
+) -> Option<APerson> {
+    let base = addressbook_record(base);
+    let alternative = alternative.map(|(label, aperson)| (label, addressbook_record(aperson)));
+    let mut text = mod_editor::format_document(
+        header,
+        &base,
+        fields,
+        alternative.as_ref().map(|(label, record)| (*label, record)),
+    );
+
+    loop {
+        text = match mod_editor::edit(&text) {
+            Ok(edited) => edited,
+            Err(e) => {
+                eprintln!(
+                    "{}: {}",
+                    mod_fluent::get_translation(bundle, "edit-error"),
+                    e
+                );
+                return None;
+            }
+        };
 
-    // ユーザー入力により、Google Contactsまたは.addressbookのどちらのデータを優先するか決定
-    if (input.trim().to_lowercase() != "g") && (input.trim().to_lowercase() != "a") {
-        println!("{}", mod_fluent::get_translation(&bundle, "op-cancel"));
-        std::process::exit(0);
-    } else if input.trim().to_lowercase() == "g" {
-        // Google Contactsを優先し、.addressbookを更新する
-        source = UpdateSource::FromGoogle;
-    } else if input.trim().to_lowercase() == "a" {
-        // .addressbookを優先し、Google Contactsを更新する
-        source = UpdateSource::FromAddressBook;
+        match mod_editor::parse_document(&text, &base, fields) {
+            Ok(record) => {
+                return Some(APerson {
+                    nickname: record.nickname,
+                    name: record.name,
+                    email: record.email,
+                    fcc: record.fcc,
+                    biography: record.comment,
+                })
+            }
+            Err(e) => {
+                eprintln!(
+                    "{}: {}",
+                    mod_fluent::get_translation(bundle, "edit-invalid"),
+                    e
+                );
+                // 再編集するかどうかを確認する（既定は再編集する）
+                println!("{}", mod_fluent::get_translation(bundle, "edit-retry"));
+                match read_input_line(bundle) {
+                    Some(input) if !matches!(input.trim(), "n" | "N" | "q") => {}
+                    _ => return None,
+                }
+            }
+        }
     }
+}
+
+/// 1つのフィールドの値を入力させる関数。
+///
+/// 'g'/'a'でどちらかの値を、'='に続けて新しい値を入力します。's'はこの連絡先をスキップし、'q'はキャンセルします。
+/// 新しい値にタブを含めることはできず、ニックネームは空白を含まない空でない値である必要があります。
+/// 不正な入力の場合は再入力させます。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+/// * `field` - フィールドの名前。
+/// * `local` - .addressbookの値。
+/// * `google` - Google Contactsの値。
+///
+/// # 戻り値
+/// 成功した場合は選択された値、スキップまたはキャンセルされた場合は対応する`ConflictChoice`。
+fn input_field_value(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    field: &str,
+    local: &str,
+    google: &str,
+) -> Result<String, ConflictChoice> {
+    loop {
+        println!(
+            "{}: {}",
+            mod_fluent::get_translation(bundle, "merge-field"),
+            field
+        );
+        println!("  [g] Google Contacts: {}", google);
+        println!("  [a] .addressbook   : {}", local);
+        println!(
+            "{}",
+            mod_fluent::get_translation(bundle, "merge-field-prompt")
+        );
+
+        let input = read_input_line(bundle).ok_or(ConflictChoice::Cancel)?;
+        match input.trim() {
+            "g" => return Ok(google.to_string()),
+            "a" => return Ok(local.to_string()),
+            "s" => return Err(ConflictChoice::Skip),
+            "q" => {
+                println!("{}", mod_fluent::get_translation(bundle, "op-cancel"));
+                return Err(ConflictChoice::Cancel);
+            }
+            _ => {}
+        }
 
-    // 選択されたデータソースを返す
-    source
+        // '='に続けて入力された新しい値
+        match input.trim_start().strip_prefix('=').map(str::trim) {
+            Some(value)
+                if !value.contains('\t')
+                    && (field != "nickname"
+                        || (!value.is_empty() && !value.contains(char::is_whitespace))) =>
+            {
+                return Ok(value.to_string())
+            }
+            Some(_) => println!(
+                "{}",
+                mod_fluent::get_translation(bundle, "invalid-field-value")
+            ),
+            None => println!(
+                "{}",
+                mod_fluent::get_translation(bundle, "invalid-input-field")
+            ),
+        }
+    }
 }
 
 // 非同期のメイン関数
@@ -871,497 +3416,1595 @@ async fn main() {
     // Fluentバンドルを初期化
     let bundle = mod_fluent::init_fluent_bundle(&locale);
 
-    // コマンドライン引数を取得
-    let args: Vec<String> = env::args().collect();
+    // コマンドライン引数を取得（プログラム名を除く）
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    // 動作モード
-    let sel;
+    // コマンドライン引数を解析する
+    let cli = mod_cli::parse(&args).unwrap_or_else(|e| {
+        match e.detail() {
+            Some(detail) => eprintln!(
+                "{}: {}",
+                mod_fluent::get_translation(&bundle, e.message_id()),
+                detail
+            ),
+            None => eprintln!("{}", mod_fluent::get_translation(&bundle, e.message_id())),
+        }
+        eprintln!("{}", mod_fluent::get_translation(&bundle, "cli-help-hint"));
+        mod_exit::ExitCode::Usage.exit();
+    });
 
-    // --help オプションのチェック
-    if args.contains(&"--help".to_string()) {
+    // --help オプションまたはhelpコマンドのチェック
+    if cli.help {
         // ヘルプメッセージを表示
-        print_help(&bundle);
+        print_help(&bundle, cli.command);
         std::process::exit(0);
-    } else if args.contains(&"init".to_string()) {
-        // Google Contactsからダウンロードして.addressbookを上書き
-        sel = Select::Init;
-    } else if args.contains(&"sync".to_string()) {
-        // Google Concatcsのデータと同期
-        sel = Select::Sync;
+    }
+
+    // 動作モード
+    let sel = cli.command.unwrap();
+
+    // 実行結果の報告（--reportと--report-fileのどちらも指定されていない場合は出力しない）
+    // --report-fileだけが指定された場合はJSONで書き込む
+    let report_path = cli.value("report-file").map(PathBuf::from);
+    let report_format = parse_option_value::<mod_report::ReportFormat>(&bundle, &cli, "report")
+        .or(report_path.as_ref().map(|_| mod_report::ReportFormat::Json));
+    let mut report = mod_report::Report::new(sel.spec().name, report_format, report_path);
+
+    // 差分の出力形式（既定は色付きのunified形式）
+    let diff_format = match sel {
+        mod_cli::Command::Diff => parse_option_value(&bundle, &cli, "format"),
+        _ => None,
+    }
+    .unwrap_or(mod_diff::DiffFormat::Text);
+
+    // 書き出す形式（既定はvCard 4.0）
+    let (export_format, vcard_version) = match sel {
+        mod_cli::Command::Export => (
+            parse_option_value(&bundle, &cli, "format"),
+            parse_option_value(&bundle, &cli, "vcard-version"),
+        ),
+        _ => (None, None),
+    };
+    let export_format = export_format.unwrap_or(mod_export::ExportFormat::VCard);
+    let vcard_version = vcard_version.unwrap_or(mod_vcard::Version::V4);
+
+    // 取り込むファイルの形式（既定はvCard）
+    let import_format = match sel {
+        mod_cli::Command::Import => parse_option_value(&bundle, &cli, "format"),
+        _ => None,
+    }
+    .unwrap_or(mod_import::ImportFormat::VCard);
+
+    // --output-dirは連絡先ごとのファイルに書き出せる形式でのみ使用できる
+    if cli.value("output-dir").is_some() && !export_format.supports_directory() {
+        eprintln!(
+            "{}: --output-dir",
+            mod_fluent::get_translation(&bundle, "export-output-dir-unsupported")
+        );
+        eprintln!("{}", mod_fluent::get_translation(&bundle, "cli-help-hint"));
+        report.exit(mod_exit::ExitCode::Usage, None);
+    }
+    if cli.value("output-dir").is_none() && export_format.requires_directory() {
+        eprintln!(
+            "{}: --output-dir",
+            mod_fluent::get_translation(&bundle, "cli-missing-option")
+        );
+        eprintln!("{}", mod_fluent::get_translation(&bundle, "cli-help-hint"));
+        report.exit(mod_exit::ExitCode::Usage, None);
+    }
+
+    // addではメールアドレスの指定が必須
+    if sel == mod_cli::Command::Add && cli.value("email").is_none() {
+        eprintln!(
+            "{}: --email",
+            mod_fluent::get_translation(&bundle, "cli-missing-option")
+        );
+        eprintln!("{}", mod_fluent::get_translation(&bundle, "cli-help-hint"));
+        report.exit(mod_exit::ExitCode::Usage, None);
+    }
+
+    // --offlineはキャッシュした連絡先だけで実行できるコマンドでのみ使用できる
+    let offline = cli.global.offline;
+    if offline && !sel.works_offline() {
+        eprintln!(
+            "{}: {}",
+            mod_fluent::get_translation(&bundle, "offline-unsupported"),
+            sel.spec().name
+        );
+        eprintln!("{}", mod_fluent::get_translation(&bundle, "cli-help-hint"));
+        report.exit(mod_exit::ExitCode::Usage, None);
+    }
+
+    // エラー以外のメッセージを表示しない、または詳細なメッセージを表示する
+    let quiet = cli.global.quiet;
+    let verbose = cli.global.verbose;
+
+    // 設定ファイルを読み込む
+    let mut config = mod_config::load(cli.global.config.as_deref()).unwrap_or_else(|e| {
+        exit_with_error(
+            &bundle,
+            &report,
+            mod_exit::ExitCode::Parse,
+            "config-error",
+            &e,
+        )
+    });
+    // コマンドラインの指定で設定を上書きする
+    if let Err(e) = config.apply_overrides(
+        cli.global.account.as_deref(),
+        cli.global.addressbook.as_deref(),
+        cli.global.strict,
+    ) {
+        exit_with_error(
+            &bundle,
+            &report,
+            mod_exit::ExitCode::Usage,
+            "config-error",
+            &e,
+        );
+    }
+    // 設定ファイルでロケールが指定されている場合はFluentバンドルを作り直す
+    let bundle = if config.output.locale.is_empty() {
+        bundle
     } else {
-        eprintln!("{}", mod_fluent::get_translation(&bundle, "no-option"));
-        std::process::exit(1);
+        mod_fluent::init_fluent_bundle(&config.output.locale)
+    };
+    if verbose {
+        match &config.source {
+            Some(path) => eprintln!(
+                "{}: {}",
+                mod_fluent::get_translation(&bundle, "config-loaded"),
+                path.display()
+            ),
+            None => eprintln!(
+                "{}",
+                mod_fluent::get_translation(&bundle, "config-defaults")
+            ),
+        }
+    }
+
+    // 設定の表示は他の処理を必要としないので、ここで処理して終了する
+    if let mod_cli::Command::ConfigShow = sel {
+        println!("{}", config);
+        std::process::exit(0);
+    }
+
+    // 旧バージョンの~/.gcontactsから設定ディレクトリと状態ディレクトリへ移行する
+    match mod_dirs::migrate_legacy_dir() {
+        Ok(migrated) => {
+            for path in migrated.iter().filter(|_| !quiet) {
+                println!(
+                    "{}: {}",
+                    mod_fluent::get_translation(&bundle, "migrated-file"),
+                    path.display()
+                );
+            }
+        }
+        Err(e) => exit_with_error(
+            &bundle,
+            &report,
+            mod_exit::ExitCode::Failure,
+            "migrate-error",
+            &e,
+        ),
+    }
+
+    // 同期の状態の表示は新たな認証を必要としないので、ここで処理して終了する
+    if let mod_cli::Command::Status = sel {
+        print_status(&bundle, &config, offline).await.exit();
+    }
+
+    // 問い合わせはキャッシュがあればGoogle Contactsにアクセスせずに処理して終了する
+    // （キャッシュが無いか古い場合は連絡先を取得してキャッシュを更新する。--offlineの場合は常にキャッシュを使用する）
+    if let mod_cli::Command::Query = sel {
+        let cache = if offline {
+            Some(load_contacts_cache_or_exit(&bundle, &report, &config))
+        } else {
+            mod_cache::load(&config.google.account).unwrap_or_else(|e| {
+                eprintln!(
+                    "{}: {}",
+                    mod_fluent::get_translation(&bundle, "cache-error"),
+                    e
+                );
+                None
+            })
+        };
+        match cache {
+            Some(cache) if offline || !cache.is_stale(config.cache.max_age) => {
+                print_query_results(&cli.arguments[0], &cache.contacts, &config);
+                std::process::exit(0);
+            }
+            _ => {}
+        }
+    }
+
+    // 認証関連の操作はGoogle Contactsへのアクセスを必要としないので、ここで処理して終了する
+    if let mod_cli::Command::AuthStatus | mod_cli::Command::AuthRevoke = sel {
+        if let Err(e) = mod_auth::check_secret_files(&config) {
+            exit_with_error(&bundle, &report, mod_exit::ExitCode::Auth, "auth-error", &e);
+        }
+    }
+    match sel {
+        mod_cli::Command::AuthStatus => {
+            print_auth_status(&bundle, &config).await;
+            std::process::exit(0);
+        }
+        mod_cli::Command::AuthRevoke => match mod_auth::revoke(&config).await {
+            Ok(_) => {
+                if !quiet {
+                    println!(
+                        "{}",
+                        mod_fluent::get_translation(&bundle, "revoke-complete")
+                    );
+                }
+                std::process::exit(0);
+            }
+            Err(e) => exit_with_error(
+                &bundle,
+                &report,
+                mod_exit::ExitCode::Auth,
+                "revoke-error",
+                &e,
+            ),
+        },
+        _ => {}
     }
 
-    // 認証が成功した場合の処理を続行
-    let auth = match mod_auth::get_auth().await {
-        Ok(a) => a,
-        Err(e) => {
-            eprintln!(
-                "{}: {}",
-                mod_fluent::get_translation(&bundle, "auth-error"),
-                e
-            );
-            std::process::exit(1);
+    // --offlineの場合はGoogle Contactsにアクセスせず、キャッシュした連絡先を使用する
+    let (service, cache) = if offline {
+        let cache = load_contacts_cache_or_exit(&bundle, &report, &config);
+        if verbose {
+            eprintln!(
+                "{}: {}",
+                mod_fluent::get_translation(&bundle, "cache-loaded"),
+                cache.time
+            );
+        }
+        (None, cache)
+    } else {
+        // 認証が成功した場合の処理を続行
+        let auth = match mod_auth::get_auth(&sel.access_level(), &config).await {
+            Ok(a) => a,
+            Err(e) => exit_with_error(&bundle, &report, mod_exit::ExitCode::Auth, "auth-error", &e),
+        };
+
+        // PeopleService（Google People APIクライアント）を初期化
+        let connector = mod_auth::build_https_connector().unwrap_or_else(|e| {
+            exit_with_error(
+                &bundle,
+                &report,
+                mod_exit::ExitCode::Failure,
+                "auth-error",
+                &e,
+            )
+        });
+        let service = PeopleService::new(Client::builder().build(connector), auth);
+
+        // Google People APIを使用して連絡先情報を取得し、ラベルの設定に従って絞り込む
+        let cache = fetch_contacts_or_exit(&bundle, &report, &service, &config, sel, verbose).await;
+        (Some(service), cache)
+    };
+    // hidden_gpersonsはラベルの設定により対象外となった連絡先
+    // group_labelsはユーザーが作成した連絡先グループのリソース名とラベル名の対応
+    let group_labels = cache.labels.clone();
+    let (gpersons, hidden_gpersons) = cache.split();
+
+    // CSVファイルの保存場所を指定
+    let addressbook_path = config.addressbook.path.clone();
+
+    // ユーザの選択に応じた処理を行なう
+    match sel {
+        mod_cli::Command::Init => {
+            // Google Contactsからダウンロードし、.addressbookに上書きする
+            // --mergeの場合は既存の.addressbookの内容を残し、足りない連絡先だけを追加する
+            let merge = cli.flag("merge");
+
+            // 既存の.addressbookを残す場合は読み込む
+            // メールアドレスの無い行もそのまま残し、先頭の空行などから作られる空の要素だけを除く
+            let apeople: Vec<APerson> = if merge && addressbook_path.exists() {
+                load_addressbook_data(addressbook_path.as_path())
+                    .unwrap_or_else(|e| {
+                        exit_with_error(
+                            &bundle,
+                            &report,
+                            mod_exit::classify_read_error(e.as_ref()),
+                            "fail-addressbook",
+                            &e,
+                        )
+                    })
+                    .into_iter()
+                    .filter(|ap| {
+                        !(ap.nickname.is_empty()
+                            && ap.name.is_empty()
+                            && ap.email.is_empty()
+                            && ap.fcc.is_empty()
+                            && ap.biography.is_empty())
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+
+            // 既存のメールアドレスとニックネーム
+            // リスト（"(a@example.com, b@example.com)"）のメンバーは個別の連絡先として扱わない
+            let local_emails: HashSet<String> = apeople
+                .iter()
+                .filter(|ap| !ap.email.is_empty() && !is_addressbook_list(ap))
+                .map(|ap| ap.email.clone())
+                .collect();
+            let mut taken_nicknames: HashSet<String> =
+                apeople.iter().map(|ap| ap.nickname.clone()).collect();
+            let mut added_count = 0;
+
+            // ファイルが存在するかチェックし、存在する場合は上書き確認する
+            // --yesの場合や、既存の内容を残す--mergeの場合は確認しない
+            if Path::new(&addressbook_path).exists() && !cli.global.yes && !merge {
+                println!(
+                    "{}",
+                    mod_fluent::get_translation(&bundle, "overwrite-or-not")
+                );
+                let mut input = String::new();
+                if let Err(e) = io::stdin().read_line(&mut input) {
+                    exit_with_error(
+                        &bundle,
+                        &report,
+                        mod_exit::ExitCode::Failure,
+                        "input-error",
+                        &e,
+                    );
+                };
+
+                // y以外を選択していたらキャンセル（同期のキャンセルと同じ終了コード）
+                if input.trim().to_lowercase() != "y" {
+                    println!("{}", mod_fluent::get_translation(&bundle, "op-cancel"));
+                    report.record_unresolved();
+                    report.finish();
+                }
+            }
+
+            // 既存の.addressbookの内容をそのまま残す
+            let mut rows: Vec<APerson> = apeople.clone();
+
+            // 取得した連絡先情報に基づいて処理
+            for person in &gpersons {
+                // 各メールアドレスにニックネームを割り当ててCSVに書き込む
+                for mut aperson in addressbook_rows(person, &config) {
+                    if merge {
+                        // 既に.addressbookにあるメールアドレスは既存の行を残す
+                        if local_emails.contains(&aperson.email) {
+                            continue;
+                        }
+                        added_count += 1;
+                    }
+                    // 既存のニックネームや前の行と重複しないようにする（エイリアスの書き出しと同じ）
+                    aperson.nickname = unique_nickname(&aperson.nickname, &taken_nicknames);
+                    taken_nicknames.insert(aperson.nickname.clone());
+
+                    rows.push(aperson);
+                    report.record(mod_report::Side::AddressBook, mod_report::Action::Add);
+                }
+            }
+
+            // 一時ファイルに書き込み、バックアップを作成してから.addressbookを置き換える
+            let staged = stage_addressbook(&addressbook_path, &rows).unwrap_or_else(|e| {
+                exit_with_error(
+                    &bundle,
+                    &report,
+                    mod_exit::ExitCode::Failure,
+                    "init-error",
+                    &e,
+                )
+            });
+            install_staged_addressbook(&bundle, &report, &config, &staged, verbose);
+
+            // 書き込み完了メッセージを表示
+            if !quiet {
+                if merge {
+                    println!(
+                        "{}: {}",
+                        mod_fluent::get_translation(&bundle, "merge-kept"),
+                        apeople.len()
+                    );
+                    println!(
+                        "{}: {}",
+                        mod_fluent::get_translation(&bundle, "merge-added"),
+                        added_count
+                    );
+                }
+                println!(
+                    "{}",
+                    mod_fluent::get_translation(&bundle, "export-complete")
+                );
+            }
+
+            // 同期の時刻と、書き込んだアドレス帳の内容を保存する
+            save_sync_state(&bundle, &config, sel.spec().name, &addressbook_path);
+        }
+
+        mod_cli::Command::AuthStatus
+        | mod_cli::Command::AuthRevoke
+        | mod_cli::Command::ConfigShow
+        | mod_cli::Command::Status => unreachable!(),
+
+        mod_cli::Command::Diff => {
+            // .addressbookとGoogle Contactsの差分を表示する（どちらも変更しない）
+            let apeople: Vec<APerson> = load_addressbook_data(addressbook_path.as_path())
+                .unwrap_or_else(|e| {
+                    exit_with_error(
+                        &bundle,
+                        &report,
+                        mod_exit::classify_read_error(e.as_ref()),
+                        "fail-addressbook",
+                        &e,
+                    )
+                })
+                .into_iter()
+                .filter(|ap| !ap.email.is_empty())
+                .collect();
+
+            let diffs: Vec<mod_diff::ContactDiff> =
+                plan_sync(&apeople, &gpersons, &hidden_gpersons, &config)
+                    .into_iter()
+                    .map(|item| item.diff)
+                    .collect();
+            let result = match diff_format {
+                mod_diff::DiffFormat::Text => mod_diff::write_unified(
+                    &mut io::stdout(),
+                    &diffs,
+                    &addressbook_path.display().to_string(),
+                    mod_diff::use_color(),
+                ),
+                mod_diff::DiffFormat::Json => {
+                    println!("{}", mod_diff::to_json(&diffs));
+                    Ok(())
+                }
+            };
+            if let Err(e) = result {
+                exit_with_error(
+                    &bundle,
+                    &report,
+                    mod_exit::ExitCode::Failure,
+                    "diff-error",
+                    &e,
+                );
+            }
+            if diffs.is_empty() && diff_format == mod_diff::DiffFormat::Text && !quiet {
+                println!("{}", mod_fluent::get_translation(&bundle, "diff-none"));
+            }
+        }
+
+        mod_cli::Command::Add => {
+            // Google Contactsと.addressbookの両方に連絡先を追加する
+            let mut apeople = load_contacts_or_exit(&bundle, &report, &addressbook_path);
+            let all_gpersons: Vec<Person> =
+                gpersons.iter().chain(&hidden_gpersons).cloned().collect();
+
+            let email = cli.value("email").unwrap_or_default().trim().to_string();
+
+            // 同じメールアドレスの連絡先が既にある場合は追加しない
+            if apeople.iter().any(|ap| ap.email == email)
+                || !get_related_gpersons(&all_gpersons, &email).is_empty()
+            {
+                exit_with_error(
+                    &bundle,
+                    &report,
+                    mod_exit::ExitCode::Failure,
+                    "contact-exists",
+                    &email,
+                );
+            }
+
+            // ニックネームが指定されていない場合は、テンプレート（名前が無い場合はメールアドレスのローカル部）から作成する
+            let taken_nicknames: HashSet<String> =
+                apeople.iter().map(|ap| ap.nickname.clone()).collect();
+            let name = cli.value("name").unwrap_or_default().trim().to_string();
+            let nickname = match cli.value("nick") {
+                Some(nickname) => nickname.trim().to_string(),
+                None => {
+                    let base = mod_config::nickname_base(&name, &config.nickname.template);
+                    let base = if base.is_empty() {
+                        email.split('@').next().unwrap_or_default().to_string()
+                    } else {
+                        base
+                    };
+                    unique_nickname(&base, &taken_nicknames)
+                }
+            };
+            if taken_nicknames.contains(&nickname) {
+                exit_with_error(
+                    &bundle,
+                    &report,
+                    mod_exit::ExitCode::Failure,
+                    "nickname-taken",
+                    &nickname,
+                );
+            }
+
+            let aperson = APerson {
+                nickname,
+                name,
+                email,
+                fcc: cli
+                    .value("fcc")
+                    .map(|fcc| fcc.trim().to_string())
+                    .unwrap_or_else(|| config.fields.fcc.clone()),
+                biography: cli.value("comment").unwrap_or_default().trim().to_string(),
+            };
+            if let Some(field) = invalid_contact_field(&aperson) {
+                exit_with_error(
+                    &bundle,
+                    &report,
+                    mod_exit::ExitCode::Usage,
+                    "invalid-field-value",
+                    &format!("--{}", field),
+                );
+            }
+
+            apeople.push(aperson.clone());
+            commit_contact_change(
+                &bundle,
+                &mut report,
+                &config,
+                service.as_ref(),
+                &apeople,
+                &[GoogleChange::Create(&aperson)],
+                verbose,
+            )
+            .await;
+            report.record(mod_report::Side::AddressBook, mod_report::Action::Add);
+            update_sync_state_entry(&bundle, &config, None, Some(&aperson));
+
+            if !quiet {
+                println!(
+                    "{}: {}",
+                    mod_fluent::get_translation(&bundle, "contact-added"),
+                    aperson.nickname
+                );
+                if offline {
+                    println!("{}", mod_fluent::get_translation(&bundle, "outbox-queued"));
+                }
+            }
+        }
+
+        mod_cli::Command::Edit => {
+            // Google Contactsと.addressbookの両方の連絡先を編集する
+            let mut apeople = load_contacts_or_exit(&bundle, &report, &addressbook_path);
+            let all_gpersons: Vec<Person> =
+                gpersons.iter().chain(&hidden_gpersons).cloned().collect();
+            let before =
+                find_contact_or_exit(&bundle, &report, &apeople, &cli.arguments[0]).clone();
+
+            // オプションで値が指定されていない場合は$EDITORで編集する
+            let after = if has_contact_options(&cli) {
+                apply_contact_options(&cli, &before)
+            } else {
+                let header = [mod_fluent::get_translation(&bundle, "edit-header")];
+                match edit_contact_document(
+                    &bundle,
+                    &header,
+                    &before,
+                    &["nickname", "name", "email", "fcc", "comment"],
+                    None,
+                ) {
+                    Some(after) => after,
+                    None => {
+                        println!("{}", mod_fluent::get_translation(&bundle, "op-cancel"));
+                        report.record_unresolved();
+                        report.finish();
+                    }
+                }
+            };
+            if let Some(field) = invalid_contact_field(&after) {
+                exit_with_error(
+                    &bundle,
+                    &report,
+                    mod_exit::ExitCode::Usage,
+                    "invalid-field-value",
+                    &format!("--{}", field),
+                );
+            }
+            if after == before {
+                if !quiet {
+                    println!(
+                        "{}",
+                        mod_fluent::get_translation(&bundle, "contact-unchanged")
+                    );
+                }
+                report.finish();
+            }
+
+            // 変更後のニックネームとメールアドレスは他の連絡先と重複させない
+            let others = || apeople.iter().filter(|ap| **ap != before);
+            if others().any(|ap| ap.nickname == after.nickname) {
+                exit_with_error(
+                    &bundle,
+                    &report,
+                    mod_exit::ExitCode::Failure,
+                    "nickname-taken",
+                    &after.nickname,
+                );
+            }
+            if after.email != before.email
+                && (others().any(|ap| ap.email == after.email)
+                    || !get_related_gpersons(&all_gpersons, &after.email).is_empty())
+            {
+                exit_with_error(
+                    &bundle,
+                    &report,
+                    mod_exit::ExitCode::Failure,
+                    "contact-exists",
+                    &after.email,
+                );
+            }
+
+            // Google Contactsに対応する連絡先が無い場合は作成する
+            let related_gpersons = get_related_gpersons(&all_gpersons, &before.email);
+            let changes: Vec<GoogleChange> = if related_gpersons.is_empty() {
+                vec![GoogleChange::Create(&after)]
+            } else {
+                related_gpersons
+                    .iter()
+                    .map(|gperson| GoogleChange::Update(gperson, &before.email, &after))
+                    .collect()
+            };
+
+            for aperson in apeople.iter_mut().filter(|ap| **ap == before) {
+                *aperson = after.clone();
+            }
+            commit_contact_change(
+                &bundle,
+                &mut report,
+                &config,
+                service.as_ref(),
+                &apeople,
+                &changes,
+                verbose,
+            )
+            .await;
+            report.record(mod_report::Side::AddressBook, mod_report::Action::Update);
+            update_sync_state_entry(&bundle, &config, Some(&before), Some(&after));
+
+            if !quiet {
+                println!(
+                    "{}: {}",
+                    mod_fluent::get_translation(&bundle, "contact-updated"),
+                    after.nickname
+                );
+                if offline {
+                    println!("{}", mod_fluent::get_translation(&bundle, "outbox-queued"));
+                }
+            }
+        }
+
+        mod_cli::Command::Remove => {
+            // Google Contactsと.addressbookの両方から連絡先を削除する
+            let mut apeople = load_contacts_or_exit(&bundle, &report, &addressbook_path);
+            let all_gpersons: Vec<Person> =
+                gpersons.iter().chain(&hidden_gpersons).cloned().collect();
+            let target =
+                find_contact_or_exit(&bundle, &report, &apeople, &cli.arguments[0]).clone();
+            let related_gpersons = get_related_gpersons(&all_gpersons, &target.email);
+
+            // --yesの場合は確認しない
+            if !cli.global.yes {
+                print_contact(&bundle, &target, &related_gpersons, &config);
+                println!("{}", mod_fluent::get_translation(&bundle, "rm-confirm"));
+                let input = read_input_line(&bundle).unwrap_or_default();
+                if !matches!(input.trim(), "y" | "Y") {
+                    println!("{}", mod_fluent::get_translation(&bundle, "op-cancel"));
+                    report.record_unresolved();
+                    report.finish();
+                }
+            }
+
+            // 他のメールアドレス（.addressbookの別の行）がある連絡先は、このメールアドレスだけを取り除く
+            apeople.retain(|ap| *ap != target);
+            let changes: Vec<GoogleChange> = related_gpersons
+                .iter()
+                .map(|gperson| {
+                    if has_other_emails(gperson, &target.email) {
+                        GoogleChange::RemoveEmail(gperson, &target.email)
+                    } else {
+                        GoogleChange::Delete(gperson)
+                    }
+                })
+                .collect();
+            commit_contact_change(
+                &bundle,
+                &mut report,
+                &config,
+                service.as_ref(),
+                &apeople,
+                &changes,
+                verbose,
+            )
+            .await;
+            report.record(mod_report::Side::AddressBook, mod_report::Action::Delete);
+            update_sync_state_entry(&bundle, &config, Some(&target), None);
+
+            if !quiet {
+                println!(
+                    "{}: {}",
+                    mod_fluent::get_translation(&bundle, "contact-removed"),
+                    target.nickname
+                );
+                if offline {
+                    println!("{}", mod_fluent::get_translation(&bundle, "outbox-queued"));
+                }
+            }
+        }
+
+        mod_cli::Command::Query => {
+            // 取得した連絡先を検索する（キャッシュが無かった場合）
+            let all_gpersons: Vec<Person> =
+                gpersons.iter().chain(&hidden_gpersons).cloned().collect();
+            print_query_results(&cli.arguments[0], &all_gpersons, &config);
+        }
+
+        mod_cli::Command::Refresh => {
+            // 取得した連絡先はキャッシュに保存済み
+            if !quiet {
+                println!(
+                    "{}: {}",
+                    mod_fluent::get_translation(&bundle, "cache-refreshed"),
+                    gpersons.len() + hidden_gpersons.len()
+                );
+            }
+        }
+
+        mod_cli::Command::Export => {
+            // Google Contactsの連絡先をほかのアプリケーションの形式で書き出す（どちらも変更しない）
+            // ラベルの設定により対象外となった連絡先は、--allを指定した場合のみ書き出す
+            let export_gpersons: Vec<Person> = if cli.flag("all") {
+                gpersons.iter().chain(&hidden_gpersons).cloned().collect()
+            } else {
+                gpersons.clone()
+            };
+            let output_dir = cli.value("output-dir").map(Path::new);
+            let output = cli.value("output").map(Path::new);
+            let card = |p: &Person| mod_vcard::write_card(p, &group_labels, vcard_version);
+            let mut changes = None;
+            let written = match export_format {
+                mod_export::ExportFormat::VCard => match output_dir {
+                    Some(dir) => {
+                        let files: Vec<(String, String)> = export_gpersons
+                            .iter()
+                            .map(|p| (format!("{}.vcf", mod_vcard::uid(p)), card(p)))
+                            .collect();
+                        mod_export::write_directory(dir, &files)
+                    }
+                    None => {
+                        let data: String = export_gpersons.iter().map(card).collect();
+                        mod_export::write_output(output, &data)
+                    }
+                },
+                mod_export::ExportFormat::MuttAliases => {
+                    let data = mutt_aliases(&export_gpersons, &group_labels, &config);
+                    mod_export::write_output(output, &data)
+                }
+                mod_export::ExportFormat::Abook => {
+                    let entries = nicknamed_gpersons(&export_gpersons, &config);
+                    let data = mod_abook::write_addressbook(&entries, &group_labels);
+                    mod_export::write_output(output, &data)
+                }
+                mod_export::ExportFormat::GoogleCsv => {
+                    mod_google_csv::write_csv(&export_gpersons, &group_labels)
+                        .and_then(|data| mod_export::write_output(output, &data))
+                }
+                mod_export::ExportFormat::Json | mod_export::ExportFormat::Ndjson => {
+                    // メールアドレスに対応する.addressbookの行のニックネームとFcc
+                    let apeople = load_contacts_or_exit(&bundle, &report, &addressbook_path);
+                    let rows: HashMap<String, (String, String)> = apeople
+                        .into_iter()
+                        .filter(|ap| !ap.email.is_empty() && !is_addressbook_list(ap))
+                        .map(|ap| (ap.email.to_lowercase(), (ap.nickname, ap.fcc)))
+                        .collect();
+                    let contacts: Vec<mod_json::Contact> = export_gpersons
+                        .iter()
+                        .map(|p| mod_json::Contact::from_person(p, &group_labels, &rows))
+                        .collect();
+                    let data = match export_format {
+                        mod_export::ExportFormat::Json => mod_json::write_json(contacts),
+                        _ => mod_json::write_ndjson(&contacts),
+                    };
+                    data.map_err(io::Error::from)
+                        .and_then(|data| mod_export::write_output(output, &data))
+                }
+                mod_export::ExportFormat::Ldif => {
+                    let entries = nicknamed_gpersons(&export_gpersons, &config);
+                    let data = mod_ldif::write_ldif(&entries, &config.ldif.dn);
+                    mod_export::write_output(output, &data)
+                }
+                mod_export::ExportFormat::Vdir => {
+                    // 再度書き出した場合は、変更されたファイルのみを書き込み、削除された連絡先のファイルを削除する
+                    let files: Vec<(String, String)> = export_gpersons
+                        .iter()
+                        .map(|p| (format!("{}.vcf", mod_vcard::uid(p)), card(p)))
+                        .collect();
+                    let dir = output_dir.unwrap_or(Path::new("."));
+                    mod_export::sync_directory(dir, &files, "vcf").map(|c| changes = Some(c))
+                }
+            };
+            if let Err(e) = written {
+                exit_with_error(
+                    &bundle,
+                    &report,
+                    mod_exit::ExitCode::Failure,
+                    "export-error",
+                    &e,
+                );
+            }
+
+            // 標準出力に書き出した場合は、書き出したデータにメッセージを混ぜない
+            if let (false, Some(path)) = (quiet, output_dir.or(output)) {
+                println!(
+                    "{}: {} ({})",
+                    mod_fluent::get_translation(&bundle, "export-written"),
+                    path.display(),
+                    export_gpersons.len()
+                );
+            }
+            if let (false, Some((updated, removed))) = (quiet, changes) {
+                println!(
+                    "{}: {}",
+                    mod_fluent::get_translation(&bundle, "export-files-updated"),
+                    updated
+                );
+                println!(
+                    "{}: {}",
+                    mod_fluent::get_translation(&bundle, "export-files-removed"),
+                    removed
+                );
+            }
         }
-    };
-
-    // PeopleService（Google People APIクライアント）を初期化
-    let service = PeopleService::new(
-        Client::builder().build(HttpsConnector::with_native_roots()),
-        auth,
-    );
 
-    // Google People APIから取得するフィールドを設定
-    let field_mask =
-        FieldMask::from_str("nicknames,names,organizations,emailAddresses,biographies").unwrap(); // 失敗したらパニック
+        mod_cli::Command::Import => {
+            // vCardやGoogle CSVのファイルの連絡先をGoogle Contactsと.addressbookに取り込む
+            // --offlineはmainの最初で拒否しているため、Google Contactsに接続している
+            let Some(service) = service else {
+                unreachable!()
+            };
+            let path = Path::new(&cli.arguments[0]);
+            let data = fs::read(path).unwrap_or_else(|e| {
+                exit_with_error(
+                    &bundle,
+                    &report,
+                    mod_exit::ExitCode::Failure,
+                    "import-error",
+                    &e,
+                )
+            });
+            let cards = mod_import::parse(import_format, &data).unwrap_or_else(|e| {
+                exit_with_error(
+                    &bundle,
+                    &report,
+                    mod_exit::ExitCode::Parse,
+                    "import-error",
+                    &e,
+                )
+            });
 
-    // Google People APIを使用して連絡先情報を取得
-    // resultsは(Response<Body>, ListConnectionsResponse)のタプル
-    let results = service
-        .people()
-        .connections_list("people/me")
-        .page_size(1000)
-        .person_fields(field_mask.clone())
-        .doit()
-        .await
-        .unwrap_or_else(|e| {
-            eprintln!(
-                "{}: {}",
-                mod_fluent::get_translation(&bundle, "fail-contact"),
-                e
-            );
-            std::process::exit(1);
-        });
+            let mut apeople = load_contacts_or_exit(&bundle, &report, &addressbook_path);
+            // 同じファイルの後の連絡先が照合できるように、作成や更新をした連絡先で置き換える
+            let mut all_gpersons: Vec<Person> =
+                gpersons.iter().chain(&hidden_gpersons).cloned().collect();
+            let update = cli.flag("update");
 
-    // CSVファイルの保存場所を指定
-    let home_dir = dirs::home_dir().unwrap_or_else(|| {
-        eprintln!("{}", mod_fluent::get_translation(&bundle, "home-notfound"));
-        std::process::exit(1);
-    });
+            // 既存のメールアドレス（大文字と小文字を区別しない）とニックネーム（初期化の--mergeと同じく重複を避ける）
+            let mut local_emails: HashSet<String> = apeople
+                .iter()
+                .filter(|ap| !ap.email.is_empty() && !is_addressbook_list(ap))
+                .map(|ap| ap.email.to_lowercase())
+                .collect();
+            let mut taken_nicknames: HashSet<String> =
+                apeople.iter().map(|ap| ap.nickname.clone()).collect();
 
-    let addressbook_path = home_dir.join(".addressbook");
+            let mut added: Vec<APerson> = Vec::new();
+            // 書き換えた.addressbookの行（変更前と変更後）
+            let mut rewritten: Vec<(APerson, APerson)> = Vec::new();
+            let (mut created, mut updated, mut skipped) = (0, 0, 0);
+            let mut failure = None;
+            for card in &cards {
+                let emails = card.emails();
+                let imported = imported_gperson(card, &group_labels);
 
-    // ユーザの選択に応じた処理を行なう
-    match sel {
-        Select::Init => {
-            // Google Contactsからダウンロードし、.addressbookに上書きする
-            // ファイルが存在するかチェックし、存在する場合は上書き確認する
-            if Path::new(&addressbook_path).exists() {
-                println!(
-                    "{}",
-                    mod_fluent::get_translation(&bundle, "overwrite-or-not")
-                );
-                let mut input = String::new();
-                if let Err(e) = io::stdin().read_line(&mut input) {
+                // メールアドレスの無い連絡先は照合できず、.addressbookにも追加できないため取り込まない
+                let Some(first_email) = emails.first().map(|e| e.to_string()) else {
                     eprintln!(
                         "{}: {}",
-                        mod_fluent::get_translation(&bundle, "input-error"),
-                        e
+                        mod_fluent::get_translation(&bundle, "import-no-email"),
+                        get_gcontact_name(&imported, &config.fields.name)
                     );
-                    std::process::exit(1);
+                    skipped += 1;
+                    continue;
                 };
 
-                // y以外を選択していたらキャンセル
-                if input.trim().to_lowercase() != "y" {
-                    println!("{}", mod_fluent::get_translation(&bundle, "op-cancel"));
-                    std::process::exit(1);
+                // リソース名か、いずれかのメールアドレス（大文字と小文字を区別しない）が一致する既存の連絡先
+                let related_index = card
+                    .person
+                    .resource_name
+                    .as_ref()
+                    .and_then(|r| {
+                        all_gpersons
+                            .iter()
+                            .position(|gperson| gperson.resource_name.as_ref() == Some(r))
+                    })
+                    .or_else(|| {
+                        emails.iter().find_map(|email| {
+                            let email = email.to_lowercase();
+                            all_gpersons.iter().position(|gperson| {
+                                gperson
+                                    .email_addresses
+                                    .iter()
+                                    .flatten()
+                                    .filter_map(|e| e.value.as_deref())
+                                    .any(|value| value.to_lowercase() == email)
+                            })
+                        })
+                    });
+                let related = related_index.map(|index| &all_gpersons[index]);
+                let action = match related {
+                    Some(_) if !update => {
+                        if verbose {
+                            eprintln!(
+                                "{}: {}",
+                                mod_fluent::get_translation(&bundle, "import-exists"),
+                                first_email
+                            );
+                        }
+                        skipped += 1;
+                        continue;
+                    }
+                    Some(_) => mod_report::Action::Update,
+                    None => mod_report::Action::Add,
+                };
+                let gperson = match import_google_contact(related, &imported, &service).await {
+                    Ok(gperson) => gperson,
+                    Err(e) => {
+                        // 取り込んだ連絡先の.addressbookの行は書き込んでから終了する
+                        report.record_error(
+                            &first_email,
+                            mod_report::Side::Google,
+                            action,
+                            e.as_ref(),
+                        );
+                        failure = Some(e);
+                        break;
+                    }
+                };
+                report.record(mod_report::Side::Google, action);
+                if let Some(index) = related_index {
+                    all_gpersons[index] = gperson;
+                    updated += 1;
+                } else {
+                    all_gpersons.push(gperson);
+                    created += 1;
                 }
-            }
 
-            // CSVファイルライター（タブ区切り）を初期化
-            let mut writer = WriterBuilder::new()
-                .delimiter(b'\t')
-                .from_path(addressbook_path)
-                .unwrap_or_else(|e| {
-                    eprintln!(
-                        "{}: {}",
-                        mod_fluent::get_translation(&bundle, "init-error"),
-                        e
+                // .addressbookに無いメールアドレスの行を追加する（ニックネームは初期化と同じく作成する）
+                // 更新した連絡先は、メールアドレスが一致する既存の行も取り込んだ値で書き換える
+                let name = get_gcontact_name(&imported, &config.fields.name);
+                let memo = if config.fields.comment {
+                    get_gcontact_biography(&imported)
+                } else {
+                    "".to_string()
+                };
+                let mut existing_nicknames = Vec::new();
+                let nickname_from_card = get_gcontact_nickname(&imported);
+                if !nickname_from_card.is_empty() {
+                    existing_nicknames.push(nickname_from_card);
+                }
+                for email in &emails {
+                    let nickname = generate_nickname(
+                        &name,
+                        emails.len(),
+                        &mut existing_nicknames,
+                        &config.nickname.template,
                     );
-                    std::process::exit(1);
-                });
-
-            // 取得した連絡先情報に基づいて処理
-            if let Some(connections) = results.1.connections {
-                for person in connections {
-                    // 生成されたニックネームを格納するVec
-                    let mut existing_nicknames = Vec::new();
-
-                    // Google Contactsから各人物の名前と会社を取得する
-                    let default_names = Vec::new();
-                    let default_organizations = Vec::new();
-                    let default_emails = Vec::new();
-
-                    let names = person.names.as_ref().unwrap_or(&default_names);
-                    let organizations = person
-                        .organizations
-                        .as_ref()
-                        .unwrap_or(&default_organizations);
-                    let emails = person.email_addresses.as_ref().unwrap_or(&default_emails);
-
-                    // 名前が存在する場合のみ処理
-                    if !names.is_empty() || !organizations.is_empty() {
-                        // ニックネームを取得する
-                        let nickname_from_g = get_gcontact_nickname(&person);
-                        if !nickname_from_g.is_empty() {
-                            existing_nicknames.push(nickname_from_g);
+                    // JSONで指定されたニックネームとFccを使用する
+                    let values = card.rows.get(&email.to_lowercase()).cloned();
+                    let values = values.unwrap_or_default();
+                    if !local_emails.insert(email.to_lowercase()) {
+                        if related_index.is_none() {
+                            continue;
                         }
-
-                        // 名前か会社を取得する
-                        let name = get_gcontact_name(&person);
-
-                        // メモ欄の内容を取得する
-                        let memo = get_gcontact_biography(&person);
-
-                        let email_count = emails.len();
-
-                        // 各メールアドレスにニックネームを割り当ててCSVに書き込む
-                        for email in emails {
-                            let email_default = "".to_string();
-                            let email_address = email.value.as_ref().unwrap_or(&email_default);
-                            let nickname =
-                                generate_nickname(&name, email_count, &mut existing_nicknames);
-                            if let Err(e) = writer.write_record(&[
-                                &nickname,
-                                &name,
-                                &email_address,
-                                &"".to_string(),
-                                &memo,
-                            ]) {
-                                eprintln!(
-                                    "{}: {}",
-                                    mod_fluent::get_translation(&bundle, "write-error"),
-                                    e
-                                );
-                                std::process::exit(1);
+                        let memo = config.fields.comment.then_some(memo.as_str());
+                        let same_email = |ap: &&mut APerson| {
+                            !is_addressbook_list(ap)
+                                && ap.email.to_lowercase() == email.to_lowercase()
+                        };
+                        // 同じファイルで先に追加した行は、追加する値を書き換える
+                        if let Some(row) = added.iter_mut().find(same_email) {
+                            apply_imported_row(row, &name, memo, values, &mut taken_nicknames);
+                        } else if let Some(row) = apeople.iter_mut().find(same_email) {
+                            let before = row.clone();
+                            apply_imported_row(row, &name, memo, values, &mut taken_nicknames);
+                            if *row != before {
+                                rewritten.push((before, row.clone()));
                             }
                         }
+                        continue;
                     }
+                    // 名前が無い場合はメールアドレスのローカル部から作成する
+                    let nickname = match values.nickname {
+                        Some(nickname) => nickname,
+                        None if nickname.is_empty() => {
+                            email.split('@').next().unwrap_or_default().to_string()
+                        }
+                        None => nickname,
+                    };
+                    let nickname = unique_nickname(&nickname, &taken_nicknames);
+                    taken_nicknames.insert(nickname.clone());
+                    added.push(APerson {
+                        nickname,
+                        name: name.clone(),
+                        email: email.to_string(),
+                        fcc: values.fcc.unwrap_or_else(|| config.fields.fcc.clone()),
+                        biography: memo.clone(),
+                    });
                 }
-            };
+            }
 
-            // CSVファイルへの書き込みを完了
-            if let Err(e) = writer.flush() {
-                eprintln!(
-                    "{}: {}",
-                    mod_fluent::get_translation(&bundle, "flush-error"),
-                    e
+            if !added.is_empty() || !rewritten.is_empty() {
+                apeople.extend(added.iter().cloned());
+                let staged = stage_addressbook(&addressbook_path, &apeople).unwrap_or_else(|e| {
+                    exit_with_error(
+                        &bundle,
+                        &report,
+                        mod_exit::ExitCode::Failure,
+                        "write-error",
+                        &e,
+                    )
+                });
+                install_staged_addressbook(&bundle, &report, &config, &staged, verbose);
+                for (before, after) in &rewritten {
+                    report.record(mod_report::Side::AddressBook, mod_report::Action::Update);
+                    update_sync_state_entry(&bundle, &config, Some(before), Some(after));
+                }
+                for aperson in &added {
+                    report.record(mod_report::Side::AddressBook, mod_report::Action::Add);
+                    update_sync_state_entry(&bundle, &config, None, Some(aperson));
+                }
+            }
+            if let Some(e) = failure {
+                exit_with_error(
+                    &bundle,
+                    &report,
+                    mod_exit::classify_error(e.as_ref()),
+                    "update-fail-google-contacts",
+                    &e,
                 );
-                std::process::exit(1);
-            };
+            }
 
-            // 書き込み完了メッセージを表示
-            println!(
-                "{}",
-                mod_fluent::get_translation(&bundle, "export-complete")
-            );
+            if !quiet {
+                let label = |id: &str| mod_fluent::get_translation(&bundle, id);
+                println!(
+                    "{}: {} ({} {}, {} {}, {} {})",
+                    label("import-complete"),
+                    cards.len(),
+                    label("import-created"),
+                    created,
+                    label("import-updated"),
+                    updated,
+                    label("import-skipped"),
+                    skipped
+                );
+                println!("{}: {}", label("import-rows-added"), added.len());
+                println!("{}: {}", label("import-rows-updated"), rewritten.len());
+            }
+        }
+
+        mod_cli::Command::Show => {
+            // .addressbookとGoogle Contactsの連絡先を表示する（どちらも変更しない）
+            let apeople = load_contacts_or_exit(&bundle, &report, &addressbook_path);
+            let all_gpersons: Vec<Person> =
+                gpersons.iter().chain(&hidden_gpersons).cloned().collect();
+            let aperson = find_contact_or_exit(&bundle, &report, &apeople, &cli.arguments[0]);
+            let related_gpersons = get_related_gpersons(&all_gpersons, &aperson.email);
+            print_contact(&bundle, aperson, &related_gpersons, &config);
         }
 
-        Select::Sync => {
+        mod_cli::Command::Sync => {
             // Google Contactsと.adressbookを同期する
+            // --offlineはmainの最初で拒否しているため、Google Contactsに接続している
+            let Some(service) = service else {
+                unreachable!()
+            };
+
+            // オフラインで行った変更を先にGoogle Contactsに反映し、反映した場合は連絡先を取得し直す
+            let all_gpersons: Vec<Person> =
+                gpersons.iter().chain(&hidden_gpersons).cloned().collect();
+            let replayed = replay_outbox(
+                &bundle,
+                &mut report,
+                &config,
+                &service,
+                &all_gpersons,
+                verbose,
+            )
+            .await;
+            let (gpersons, hidden_gpersons) = if replayed {
+                fetch_contacts_or_exit(&bundle, &report, &service, &config, sel, verbose)
+                    .await
+                    .split()
+            } else {
+                (gpersons, hidden_gpersons)
+            };
+
+            // 変更を全画面で確認してから同期する
+            let tui = cli.flag("tui");
 
             // .addressbook書き込みフラグ
             let mut apeople_diarty = false;
 
+            // 入力でキャンセルされた場合は、以降の確認を行わず未解決の競合として数える
+            let mut cancelled = false;
+
+            // 残りのすべての連絡先に適用する解決方法（大文字のG/A/Sで選択される）
+            let mut apply_to_all: Option<ConflictChoice> = None;
+
             // .addressbookからデータを全て取得
             let mut apeople =
                 load_addressbook_data(addressbook_path.as_path()).unwrap_or_else(|e| {
-                    eprintln!(
-                        "{}: {}",
-                        mod_fluent::get_translation(&bundle, "fail-addressbook"),
-                        e
-                    );
-                    std::process::exit(1);
+                    exit_with_error(
+                        &bundle,
+                        &report,
+                        mod_exit::classify_read_error(e.as_ref()),
+                        "fail-addressbook",
+                        &e,
+                    )
                 });
 
-            // Google Contactsからデータを全て取得
-            let gpersons = results.1.connections.unwrap_or_else(|| {
-                eprintln!(
-                    "{}",
-                    mod_fluent::get_translation(&bundle, "fail-google-contacts")
-                );
-                std::process::exit(1);
-            });
-
-            // .addressbookのメールアドレスと比較するためのHashSet
-            let mut gperson_emails: HashSet<String> = HashSet::new();
-            for gperson in &gpersons {
-                // gpersonのメールアドレスのvecを取得
-                let emails = gperson.email_addresses.clone().unwrap_or(Vec::new());
-
-                // メールアドレスのvecからメールアドレスを取得
-                for email in emails {
-                    // メールアドレスをStringに変換
-                    let email_str = match email.value {
-                        Some(s) => s,
-                        None => continue,
-                    };
-                    gperson_emails.insert(email_str);
-                }
+            // Google Contactsからデータを取得できなかった場合は同期しない
+            if gpersons.is_empty() {
+                let message = mod_fluent::get_translation(&bundle, "fail-google-contacts");
+                eprintln!("{}", message);
+                report.exit(mod_exit::ExitCode::Failure, Some(&message));
             }
 
-            // Google Contactsのメールアドレスと比較するためのHashSet
-            let aperson_emails: HashSet<String> =
-                apeople.iter().map(|ap| ap.email.clone()).collect();
+            // 同期で変更する可能性のある連絡先をメールアドレス順に求める
+            let plan = plan_sync(&apeople, &gpersons, &hidden_gpersons, &config);
 
-            // 一方にのみ存在するメールアドレスを特定
-            let unique_to_gpersons = gperson_emails.difference(&aperson_emails);
-            let unique_to_apeople = aperson_emails.difference(&gperson_emails);
-            // 両方に存在するメールアドレスを特定
-            let common_emails = aperson_emails.intersection(&gperson_emails);
+            // --tuiの場合は、Google Contactsを変更する前にすべての項目の解決方法を全画面で選択させる
+            let decisions = if tui && !plan.is_empty() {
+                Some(review_sync_plan(&bundle, &mut report, &plan, &config))
+            } else {
+                None
+            };
 
             // 追加/削除するAPersonを保持しておく
             let mut related_remove_apeople = Vec::new();
             let mut related_add_apeople = Vec::new();
 
-            for email in unique_to_gpersons {
-                // このメールアドレスはGoogle Contactsにのみ存在し、.addressbookには存在しない。
-                if email.is_empty() {
-                    continue;
-                }
+            for (index, item) in plan.iter().enumerate() {
+                let email = &item.diff.email;
 
-                // Google Contactsの中でこのメールアドレスを持つ人々
-                let related_gpersons = get_related_gpersons(&gpersons, email);
+                // TUIで選択された解決方法（TUIを使用しない場合は`None`）
+                let decision = decisions.as_ref().map(|decisions| decisions[index]);
 
-                let mut source;
-                for gperson in &related_gpersons {
-                    // .addressbookに新規登録するか、Google Contactsから削除するかを入力させる
-                    println!(
-                        "{}",
-                        mod_fluent::get_translation(&bundle, "add-a-or-delete-g-mode")
-                    );
-                    let gnickname = get_gcontact_nickname(gperson);
-                    let gname = get_gcontact_name(gperson);
-                    let gbiography = get_gcontact_biography(gperson);
-                    println!(
-                        "Google Contacts   :{}/{}/{}/{}",
-                        gnickname, gname, email, gbiography
-                    );
+                match (item.aperson, item.gperson) {
+                    (None, Some(gperson)) => {
+                        // このメールアドレスはGoogle Contactsにのみ存在し、.addressbookには存在しない。
+                        if cancelled {
+                            report.record_unresolved();
+                            continue;
+                        }
 
-                    // ソース選択の入力
-                    source = input_select_source(&bundle);
-
-                    // ユーザ入力に従って分岐
-                    match source {
-                        UpdateSource::FromGoogle => {
-                            // Google Contactsから削除する
-                            match remove_related_gperson(&gperson, &service).await {
-                                Ok(()) => {
-                                    println!(
-                                        "{}",
-                                        mod_fluent::get_translation(
-                                            &bundle,
-                                            "update-success-google-contacts"
-                                        )
-                                    );
+                        let gnickname = get_gcontact_nickname(gperson);
+                        let gname = get_gcontact_name(gperson, &config.fields.name);
+                        let gbiography = if config.fields.comment {
+                            get_gcontact_biography(gperson)
+                        } else {
+                            "".to_string()
+                        };
+
+                        // TUIで選択された場合や、残りのすべてに適用する解決方法が選択された場合は確認しない
+                        let choice = decision
+                            .map(|d| tui_choice(d, true))
+                            .or_else(|| apply_to_all.as_ref().map(one_sided_choice));
+                        let source = match choice {
+                            Some(ConflictChoice::Source(source)) => source,
+                            Some(_) => {
+                                // TUIまたは大文字のSで変更しないことが選択された
+                                report.record_unresolved();
+                                continue;
+                            }
+                            None => {
+                                // .addressbookに新規登録するか、Google Contactsから削除するかを入力させる
+                                println!(
+                                    "{}",
+                                    mod_fluent::get_translation(&bundle, "add-a-or-delete-g-mode")
+                                );
+                                println!(
+                                    "Google Contacts   :{}/{}/{}/{}",
+                                    gnickname, gname, email, gbiography
+                                );
+
+                                // ソース選択の入力
+                                match input_select_source(&bundle) {
+                                    Some(source) => source,
+                                    None => {
+                                        cancelled = true;
+                                        report.record_unresolved();
+                                        continue;
+                                    }
                                 }
-                                Err(e) => {
-                                    eprintln!(
-                                        "{}: {}",
-                                        mod_fluent::get_translation(
-                                            &bundle,
-                                            "update-fail-google-contacts"
-                                        ),
-                                        e
-                                    );
-                                    std::process::exit(1);
+                            }
+                        };
+
+                        // ユーザ入力に従って分岐
+                        match source {
+                            UpdateSource::FromGoogle => {
+                                // Google Contactsから削除する
+                                match remove_related_gperson(gperson, &service).await {
+                                    Ok(()) => {
+                                        report.record(
+                                            mod_report::Side::Google,
+                                            mod_report::Action::Delete,
+                                        );
+                                        if !quiet {
+                                            println!(
+                                                "{}",
+                                                mod_fluent::get_translation(
+                                                    &bundle,
+                                                    "update-success-google-contacts"
+                                                )
+                                            );
+                                        }
+                                    }
+                                    Err(e) => {
+                                        // 失敗した連絡先を記録し、他の連絡先の同期を続ける
+                                        eprintln!(
+                                            "{}: {}",
+                                            mod_fluent::get_translation(
+                                                &bundle,
+                                                "update-fail-google-contacts"
+                                            ),
+                                            e
+                                        );
+                                        report.record_error(
+                                            email,
+                                            mod_report::Side::Google,
+                                            mod_report::Action::Delete,
+                                            e.as_ref(),
+                                        );
+                                    }
                                 }
                             }
-                        }
-                        UpdateSource::FromAddressBook => {
-                            // .addressbookに追加する
-                            let mut existing_nicknames = Vec::new();
-                            let nickname = generate_nickname(&gname, 1, &mut existing_nicknames);
-                            // .addressbookに新しく追加する
-                            related_add_apeople.push(APerson {
-                                nickname: nickname.to_owned(),
-                                name: gname.to_owned(),
-                                email: email.to_owned(),
-                                fcc: "".to_string(),
-                                biography: gbiography.to_owned(),
-                            });
-
-                            apeople_diarty = true;
+                            UpdateSource::FromAddressBook => {
+                                // .addressbookに追加する
+                                let mut existing_nicknames = Vec::new();
+                                let nickname = generate_nickname(
+                                    &gname,
+                                    1,
+                                    &mut existing_nicknames,
+                                    &config.nickname.template,
+                                );
+                                // .addressbookに新しく追加する
+                                related_add_apeople.push(APerson {
+                                    nickname: nickname.to_owned(),
+                                    name: gname.to_owned(),
+                                    email: email.to_owned(),
+                                    fcc: config.fields.fcc.clone(),
+                                    biography: gbiography.to_owned(),
+                                });
+                                report
+                                    .record(mod_report::Side::AddressBook, mod_report::Action::Add);
+
+                                apeople_diarty = true;
+                            }
                         }
                     }
-                }
-            }
 
-            for email in unique_to_apeople {
-                // このメールアドレスは.addressbookにのみ存在し、Google Contactsには存在しない。
-                if email.is_empty() {
-                    continue;
-                }
+                    (Some(aperson), None) => {
+                        // このメールアドレスは.addressbookにのみ存在し、Google Contactsには存在しない。
+                        if cancelled {
+                            report.record_unresolved();
+                            continue;
+                        }
 
-                // このメールアドレスを持つ人物を.addressbookから探す
-                let related_apeople = get_related_apersons(&apeople, email);
-                let mut source;
-                for aperson in &related_apeople {
-                    // Google Contactsに新規登録するか、.addressbookから削除するかを入力させる
-                    println!(
-                        "{}",
-                        mod_fluent::get_translation(&bundle, "add-g-or-delete-a-mode")
-                    );
-                    println!(
-                        ".addressbook   :{}/{}/{}/{}",
-                        aperson.nickname, aperson.name, aperson.email, aperson.biography
-                    );
+                        // TUIで選択された場合や、残りのすべてに適用する解決方法が選択された場合は確認しない
+                        let choice = decision
+                            .map(|d| tui_choice(d, true))
+                            .or_else(|| apply_to_all.as_ref().map(one_sided_choice));
+                        let source = match choice {
+                            Some(ConflictChoice::Source(source)) => source,
+                            Some(_) => {
+                                // TUIまたは大文字のSで変更しないことが選択された
+                                report.record_unresolved();
+                                continue;
+                            }
+                            None => {
+                                // Google Contactsに新規登録するか、.addressbookから削除するかを入力させる
+                                println!(
+                                    "{}",
+                                    mod_fluent::get_translation(&bundle, "add-g-or-delete-a-mode")
+                                );
+                                println!(
+                                    ".addressbook   :{}/{}/{}/{}",
+                                    aperson.nickname,
+                                    aperson.name,
+                                    aperson.email,
+                                    aperson.biography
+                                );
 
-                    // ソース選択の入力
-                    source = input_select_source(&bundle);
-
-                    // ユーザ入力に従って分岐
-                    match source {
-                        UpdateSource::FromGoogle => {
-                            match update_google_contacts(None, &aperson, &service).await {
-                                Ok(()) => {
-                                    println!(
-                                        "{}",
-                                        mod_fluent::get_translation(
-                                            &bundle,
-                                            "update-success-google-contacts"
-                                        )
-                                    );
+                                // ソース選択の入力
+                                match input_select_source(&bundle) {
+                                    Some(source) => source,
+                                    None => {
+                                        cancelled = true;
+                                        report.record_unresolved();
+                                        continue;
+                                    }
                                 }
-                                Err(e) => {
-                                    eprintln!(
-                                        "{}: {}",
-                                        mod_fluent::get_translation(
-                                            &bundle,
-                                            "update-fail-google-contacts"
-                                        ),
-                                        e
-                                    );
-                                    std::process::exit(1);
+                            }
+                        };
+
+                        // ユーザ入力に従って分岐
+                        match source {
+                            UpdateSource::FromGoogle => {
+                                match update_google_contacts(
+                                    None,
+                                    aperson,
+                                    "",
+                                    &config.fields,
+                                    &service,
+                                )
+                                .await
+                                {
+                                    Ok(()) => {
+                                        report.record(
+                                            mod_report::Side::Google,
+                                            mod_report::Action::Add,
+                                        );
+                                        if !quiet {
+                                            println!(
+                                                "{}",
+                                                mod_fluent::get_translation(
+                                                    &bundle,
+                                                    "update-success-google-contacts"
+                                                )
+                                            );
+                                        }
+                                    }
+                                    Err(e) => {
+                                        // 失敗した連絡先を記録し、他の連絡先の同期を続ける
+                                        eprintln!(
+                                            "{}: {}",
+                                            mod_fluent::get_translation(
+                                                &bundle,
+                                                "update-fail-google-contacts"
+                                            ),
+                                            e
+                                        );
+                                        report.record_error(
+                                            email,
+                                            mod_report::Side::Google,
+                                            mod_report::Action::Add,
+                                            e.as_ref(),
+                                        );
+                                    }
                                 }
                             }
-                        }
-                        UpdateSource::FromAddressBook => {
-                            // .addressbookから削除する
-                            // 関連するAPersonオブジェクトのキーを収集
-                            related_remove_apeople.push(APerson {
-                                nickname: aperson.nickname.to_owned(),
-                                name: aperson.name.to_owned(),
-                                email: aperson.email.to_owned(),
-                                fcc: aperson.fcc.to_owned(),
-                                biography: aperson.biography.to_owned(),
-                            });
-
-                            apeople_diarty = true;
+                            UpdateSource::FromAddressBook => {
+                                // .addressbookから削除する
+                                related_remove_apeople.push(aperson.clone());
+                                report.record(
+                                    mod_report::Side::AddressBook,
+                                    mod_report::Action::Delete,
+                                );
+
+                                apeople_diarty = true;
+                            }
                         }
                     }
-                }
-            }
 
-            for email in common_emails {
-                // このメールアドレスは両者共通に存在する
-                let aperson = match apeople.iter().find(|&ap| &ap.email == email) {
-                    Some(s) => s,
-                    None => continue,
-                };
+                    (Some(aperson), Some(person)) => {
+                        // このメールアドレスは両者共通に存在し、データが異なる
+                        // 名前を取得
+                        let gname = get_gcontact_name(person, &config.fields.name);
 
-                // このメールアドレスを持つGoogle Contactsの要素だけループする
-                let related_persons = get_related_gpersons(&gpersons, email);
-                for person in related_persons {
-                    // 名前を取得
-                    let gname = get_gcontact_name(person);
-
-                    // ニックネームを取得する
-                    let gnickname = get_gcontact_nickname(person);
-
-                    // メモを取得する
-                    let gbiography = get_gcontact_biography(person);
-
-                    // .addressbookに格納されているニックネームはそのまま使わず、
-                    // 末尾の数字を取り除き、
-                    // generate_nickname()で作ったニックネームと同じ場合はGoogle Contactsと同じとする
-                    let mut anickname = &split_string_and_number(&aperson.nickname).0;
-                    let last_name_part = &aperson
-                        .name
-                        .split_whitespace()
-                        .last()
-                        .unwrap_or("Unknown")
-                        .to_string();
-                    if anickname == last_name_part {
-                        anickname = &gnickname;
-                    }
+                        // ニックネームを取得する
+                        let gnickname = get_gcontact_nickname(person);
 
-                    if aperson.name == gname {
-                        // メールアドレスと名前が同じ
-                        if (anickname == &gnickname) && (aperson.biography == gbiography) {
-                            // ニックネームもメモも同じ
-                            // 他のpersonのループを続ける
-                            continue;
-                        }
-                    }
+                        // メモを取得する（コメントを同期しない場合は.addressbookの値を使う）
+                        let gbiography = if config.fields.comment {
+                            get_gcontact_biography(person)
+                        } else {
+                            aperson.biography.clone()
+                        };
 
-                    // ここまで来たらデータを更新する
-                    let source;
+                        // 設定で優先する側が決まっている場合や、残りのすべてに適用する解決方法が選択された場合、
+                        // TUIで選択された場合は確認しない
+                        let ask = config.sync.conflict_policy == mod_config::ConflictPolicy::Ask
+                            && apply_to_all.is_none()
+                            && decision.is_none();
 
-                    // Google Contactsと.addressbookのどちらを優先するか入力させる
-                    println!("{}", mod_fluent::get_translation(&bundle, "update-mode"));
-                    println!("Google Contacts:{}/{}/{}", gname, gnickname, gbiography);
-                    println!(
-                        ".addressbook   :{}/{}/{}",
-                        aperson.name, aperson.nickname, aperson.biography
-                    );
+                        // キャンセル後は確認せずに未解決として数える
+                        if cancelled && ask {
+                            report.record_unresolved();
+                            continue;
+                        }
 
-                    source = input_select_source(&bundle);
-
-                    // 既存の人物を更新する
-                    match source {
-                        UpdateSource::FromGoogle => {
-                            // .addressbookを更新する
-                            let mut existing_nicknames = Vec::new();
-                            let nickname = generate_nickname(&gname, 1, &mut existing_nicknames);
-                            // related_remove_apeopleとrelated_add_apeopleにapersonを渡すと
-                            // apeopleが不変の参照と可変の参照の同時使用によりエラーになる
-                            // .addressbookから該当する値を消す
-                            related_remove_apeople.push(APerson {
-                                nickname: aperson.nickname.to_owned(),
-                                name: aperson.name.to_owned(),
-                                email: aperson.email.to_owned(),
-                                fcc: aperson.fcc.to_owned(),
-                                biography: aperson.biography.to_owned(),
-                            });
-                            // .addressbookに新しく追加する
-                            related_add_apeople.push(APerson {
-                                nickname: nickname.to_owned(),
-                                name: gname.to_owned(),
-                                email: email.to_owned(),
-                                fcc: aperson.fcc.to_owned(), // 元のfccと同じ
-                                biography: gbiography.to_owned(),
-                            });
-
-                            apeople_diarty = true;
+                        // Google Contactsと.addressbookのどちらを優先するか入力させる
+                        if decision.is_none() {
+                            if ask {
+                                println!("{}", mod_fluent::get_translation(&bundle, "update-mode"));
+                            }
+                            println!("Google Contacts:{}/{}/{}", gname, gnickname, gbiography);
+                            println!(
+                                ".addressbook   :{}/{}/{}",
+                                aperson.name, aperson.nickname, aperson.biography
+                            );
                         }
-                        UpdateSource::FromAddressBook => {
-                            // Google Contactsを更新する
-                            match update_google_contacts(Some(person), &aperson, &service).await {
-                                Ok(()) => {
-                                    println!(
-                                        "{}",
-                                        mod_fluent::get_translation(
-                                            &bundle,
-                                            "update-success-google-contacts"
+
+                        let choice = match (decision, &apply_to_all, config.sync.conflict_policy) {
+                            (Some(decision), _, _) => tui_choice(decision, false),
+                            (None, Some(choice), _) => choice.clone(),
+                            (None, None, mod_config::ConflictPolicy::Ask) => {
+                                // フィールドごとの選択で使用するGoogle Contactsの値
+                                // ニックネームが無い場合は同期で作成するものと同じにする
+                                let google = APerson {
+                                    nickname: if gnickname.is_empty() {
+                                        generate_nickname(
+                                            &gname,
+                                            1,
+                                            &mut Vec::new(),
+                                            &config.nickname.template,
                                         )
-                                    );
+                                    } else {
+                                        gnickname.clone()
+                                    },
+                                    name: gname.clone(),
+                                    email: email.to_owned(),
+                                    fcc: aperson.fcc.clone(),
+                                    biography: gbiography.clone(),
+                                };
+                                input_conflict_resolution(
+                                    &bundle,
+                                    aperson,
+                                    &google,
+                                    config.fields.comment,
+                                    &mut apply_to_all,
+                                )
+                            }
+                            (None, None, mod_config::ConflictPolicy::Google) => {
+                                ConflictChoice::Source(UpdateSource::FromGoogle)
+                            }
+                            (None, None, mod_config::ConflictPolicy::AddressBook) => {
+                                ConflictChoice::Source(UpdateSource::FromAddressBook)
+                            }
+                        };
+
+                        // 既存の人物を更新する
+                        match choice {
+                            ConflictChoice::Skip => {
+                                // 解決せずに次へ進む
+                                report.record_unresolved();
+                            }
+                            ConflictChoice::Cancel => {
+                                cancelled = true;
+                                report.record_unresolved();
+                            }
+                            ConflictChoice::Merge(merged) => {
+                                // 統合した値でGoogle Contactsを更新し、成功した場合のみ.addressbookも更新する
+                                let result = update_google_contacts(
+                                    Some(person),
+                                    &merged,
+                                    email,
+                                    &config.fields,
+                                    &service,
+                                )
+                                .await;
+                                match apply_merged_contact(
+                                    result,
+                                    email,
+                                    aperson,
+                                    merged,
+                                    &mut related_remove_apeople,
+                                    &mut related_add_apeople,
+                                    &mut report,
+                                ) {
+                                    Ok(()) => {
+                                        apeople_diarty = true;
+                                        if !quiet {
+                                            println!(
+                                                "{}",
+                                                mod_fluent::get_translation(
+                                                    &bundle,
+                                                    "update-success-google-contacts"
+                                                )
+                                            );
+                                        }
+                                    }
+                                    Err(e) => {
+                                        // 失敗した連絡先を記録し、他の連絡先の同期を続ける
+                                        eprintln!(
+                                            "{}: {}",
+                                            mod_fluent::get_translation(
+                                                &bundle,
+                                                "update-fail-google-contacts"
+                                            ),
+                                            e
+                                        );
+                                    }
                                 }
-                                Err(e) => {
-                                    eprintln!(
-                                        "{}: {}",
-                                        mod_fluent::get_translation(
-                                            &bundle,
-                                            "update-fail-google-contacts"
-                                        ),
-                                        e
-                                    );
-                                    std::process::exit(1);
+                            }
+                            ConflictChoice::Source(UpdateSource::FromGoogle) => {
+                                // .addressbookを更新する
+                                let mut existing_nicknames = Vec::new();
+                                let nickname = generate_nickname(
+                                    &gname,
+                                    1,
+                                    &mut existing_nicknames,
+                                    &config.nickname.template,
+                                );
+                                // .addressbookから該当する値を消す
+                                related_remove_apeople.push(aperson.clone());
+                                // .addressbookに新しく追加する
+                                related_add_apeople.push(APerson {
+                                    nickname: nickname.to_owned(),
+                                    name: gname.to_owned(),
+                                    email: email.to_owned(),
+                                    fcc: aperson.fcc.to_owned(), // 元のfccと同じ
+                                    biography: gbiography.to_owned(),
+                                });
+                                report.record(
+                                    mod_report::Side::AddressBook,
+                                    mod_report::Action::Update,
+                                );
+
+                                apeople_diarty = true;
+                            }
+                            ConflictChoice::Source(UpdateSource::FromAddressBook) => {
+                                // Google Contactsを更新する
+                                match update_google_contacts(
+                                    Some(person),
+                                    aperson,
+                                    &aperson.email,
+                                    &config.fields,
+                                    &service,
+                                )
+                                .await
+                                {
+                                    Ok(()) => {
+                                        report.record(
+                                            mod_report::Side::Google,
+                                            mod_report::Action::Update,
+                                        );
+                                        if !quiet {
+                                            println!(
+                                                "{}",
+                                                mod_fluent::get_translation(
+                                                    &bundle,
+                                                    "update-success-google-contacts"
+                                                )
+                                            );
+                                        }
+                                    }
+                                    Err(e) => {
+                                        // 失敗した連絡先を記録し、他の連絡先の同期を続ける
+                                        eprintln!(
+                                            "{}: {}",
+                                            mod_fluent::get_translation(
+                                                &bundle,
+                                                "update-fail-google-contacts"
+                                            ),
+                                            e
+                                        );
+                                        report.record_error(
+                                            email,
+                                            mod_report::Side::Google,
+                                            mod_report::Action::Update,
+                                            e.as_ref(),
+                                        );
+                                    }
                                 }
                             }
                         }
                     }
+
+                    (None, None) => {}
                 }
             }
 
@@ -1373,53 +5016,285 @@ async fn main() {
                     apeople.append(&mut related_add_apeople);
                 }
 
-                // apeopleを.addressbookに書き込む
-                // CSVファイルライター（タブ区切り）を初期化
-                let mut writer = WriterBuilder::new()
-                    .delimiter(b'\t')
-                    .from_path(addressbook_path)
-                    .unwrap_or_else(|e| {
-                        eprintln!(
-                            "{}: {}",
-                            mod_fluent::get_translation(&bundle, "init-error"),
-                            e
-                        );
-                        std::process::exit(1);
-                    });
+                // apeopleを一時ファイルに書き込み、バックアップを作成してから.addressbookを置き換える
+                let staged = stage_addressbook(&addressbook_path, &apeople).unwrap_or_else(|e| {
+                    exit_with_error(
+                        &bundle,
+                        &report,
+                        mod_exit::ExitCode::Failure,
+                        "write-error",
+                        &e,
+                    )
+                });
+                install_staged_addressbook(&bundle, &report, &config, &staged, verbose);
 
-                // 各apersonをCSVに書き込む
-                for aperson in &apeople {
-                    if !aperson.email.is_empty() {
-                        if let Err(e) = writer.write_record(&[
-                            &aperson.nickname,
-                            &aperson.name,
-                            &aperson.email,
-                            &aperson.fcc,
-                            &aperson.biography,
-                        ]) {
-                            eprintln!(
-                                "{}: {}",
-                                mod_fluent::get_translation(&bundle, "write-error"),
-                                e
-                            );
-                            std::process::exit(1);
-                        }
-                    }
+                // 書き込み完了メッセージを表示
+                if !quiet {
+                    println!("{}", mod_fluent::get_translation(&bundle, "write-complete"));
                 }
+            }
 
-                // CSVファイルへの書き込みを完了
-                if let Err(e) = writer.flush() {
-                    eprintln!(
-                        "{}: {}",
-                        mod_fluent::get_translation(&bundle, "flush-error"),
-                        e
-                    );
-                    std::process::exit(1);
-                };
-
-                // 書き込み完了メッセージを表示
-                println!("{}", mod_fluent::get_translation(&bundle, "write-complete"));
+            // 同期の時刻と、同期した直後のアドレス帳の内容を保存する
+            // 入力でキャンセルされた場合は未解決の差分が残っているため、最後の同期として記録しない
+            if !cancelled {
+                save_sync_state(&bundle, &config, sel.spec().name, &addressbook_path);
             }
         }
     }
+
+    // 実行結果の報告を出力し、結果に応じた終了コードで終了する
+    report.finish();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_string_and_number_separates_the_trailing_number() {
+        assert_eq!(split_string_and_number("Smith02"), ("Smith".to_string(), 2));
+        assert_eq!(split_string_and_number("Smith"), ("Smith".to_string(), 0));
+    }
+
+    #[test]
+    fn generate_nickname_uses_the_template_and_numbers_multiple_emails() {
+        let mut existing = Vec::new();
+        assert_eq!(
+            generate_nickname("Jane Smith", 1, &mut existing, "{last}"),
+            "Smith"
+        );
+
+        let mut existing = Vec::new();
+        assert_eq!(
+            generate_nickname("Jane Smith", 2, &mut existing, "{first}"),
+            "Jane01"
+        );
+        assert_eq!(
+            generate_nickname("Jane Smith", 2, &mut existing, "{first}"),
+            "Jane02"
+        );
+        assert_eq!(existing, vec!["Jane01", "Jane02"]);
+    }
+
+    #[test]
+    fn apply_to_all_matches_the_chosen_side_for_one_sided_contacts() {
+        // Gはメールアドレスが片方にしか無い連絡先もGoogle Contactsの状態に合わせる（[a]の入力と同じ）
+        assert!(matches!(
+            one_sided_choice(&ConflictChoice::Source(UpdateSource::FromGoogle)),
+            ConflictChoice::Source(UpdateSource::FromAddressBook)
+        ));
+        assert!(matches!(
+            one_sided_choice(&ConflictChoice::Source(UpdateSource::FromAddressBook)),
+            ConflictChoice::Source(UpdateSource::FromGoogle)
+        ));
+        assert!(matches!(
+            one_sided_choice(&ConflictChoice::Skip),
+            ConflictChoice::Skip
+        ));
+    }
+
+    /// テスト用の.addressbookの行を作成する。
+    fn aperson(nickname: &str, name: &str) -> APerson {
+        APerson {
+            nickname: nickname.to_string(),
+            name: name.to_string(),
+            email: "taro@example.com".to_string(),
+            fcc: String::new(),
+            biography: String::new(),
+        }
+    }
+
+    #[test]
+    fn merged_contact_replaces_the_row_only_when_google_was_updated() {
+        let original = aperson("Tanaka", "Tanaka Taro");
+        let merged = aperson("Tanaka", "Taro Tanaka");
+
+        // Google Contactsの更新に失敗した場合は.addressbookを変更しない
+        let mut report = mod_report::Report::new("sync", None, None);
+        let (mut remove, mut add) = (Vec::new(), Vec::new());
+        let failed = apply_merged_contact(
+            Err(std::io::Error::other("503 Service Unavailable").into()),
+            &original.email,
+            &original,
+            merged.clone(),
+            &mut remove,
+            &mut add,
+            &mut report,
+        );
+        assert!(failed.is_err());
+        assert!(remove.is_empty() && add.is_empty());
+        assert_eq!(report.outcome(), mod_exit::ExitCode::Failure);
+
+        let mut report = mod_report::Report::new("sync", None, None);
+        apply_merged_contact(
+            Ok(()),
+            &original.email,
+            &original,
+            merged.clone(),
+            &mut remove,
+            &mut add,
+            &mut report,
+        )
+        .unwrap();
+        assert!(remove == vec![original] && add == vec![merged]);
+        assert_eq!(report.outcome(), mod_exit::ExitCode::Success);
+    }
+
+    #[test]
+    fn unique_nickname_appends_the_first_free_number() {
+        let taken: HashSet<String> = ["Smith", "Smith2", "Jones"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(unique_nickname("Brown", &taken), "Brown");
+        assert_eq!(unique_nickname("Jones", &taken), "Jones2");
+        assert_eq!(unique_nickname("Smith", &taken), "Smith3");
+    }
+
+    #[test]
+    fn updating_one_row_keeps_the_other_emails_of_the_contact() {
+        let config = mod_config::Config::defaults().unwrap();
+        let person = gperson(
+            "Taro Tanaka",
+            &["taro@example.org", "Taro@Example.com"],
+            None,
+        );
+        let mut row = aperson("Tanaka", "Tanaka Taro");
+        row.email = "taro@example.net".to_string();
+
+        let updated = build_gperson(Some(&person), &row, "taro@example.com", &config.fields);
+        let emails: Vec<&str> = updated
+            .email_addresses
+            .iter()
+            .flatten()
+            .filter_map(|e| e.value.as_deref())
+            .collect();
+        assert_eq!(emails, ["taro@example.org", "taro@example.net"]);
+        let name = updated.names.as_ref().unwrap()[0].display_name.as_deref();
+        assert_eq!(name, Some("Tanaka Taro"));
+    }
+
+    #[test]
+    fn removing_one_row_drops_only_its_email() {
+        let person = gperson(
+            "Taro Tanaka",
+            &["taro@example.org", "taro@example.com"],
+            None,
+        );
+        assert!(has_other_emails(&person, "TARO@example.com"));
+        let updated = without_email(&person, "TARO@example.com");
+        let emails: Vec<&str> = updated
+            .email_addresses
+            .iter()
+            .flatten()
+            .filter_map(|e| e.value.as_deref())
+            .collect();
+        assert_eq!(emails, ["taro@example.org"]);
+        assert!(!has_other_emails(&updated, "taro@example.org"));
+    }
+
+    #[test]
+    fn imported_update_rewrites_the_matching_row() {
+        let mut taken: HashSet<String> = ["Tanaka", "taro"].iter().map(|s| s.to_string()).collect();
+        let mut row = aperson("Tanaka", "Tanaka Taro");
+        row.fcc = "=sent".to_string();
+        row.biography = "old memo".to_string();
+
+        // 名前とコメントを置き換え、指定の無いニックネームとFccは残す
+        apply_imported_row(
+            &mut row,
+            "Taro Tanaka",
+            Some("new memo"),
+            mod_vcard::RowValues::default(),
+            &mut taken,
+        );
+        assert_eq!(row.name, "Taro Tanaka");
+        assert_eq!(row.biography, "new memo");
+        assert_eq!(row.nickname, "Tanaka");
+        assert_eq!(row.fcc, "=sent");
+
+        // JSONで指定されたニックネームは重複しないように番号を付ける
+        let values = mod_vcard::RowValues {
+            nickname: Some("taro".to_string()),
+            fcc: Some("=work".to_string()),
+        };
+        apply_imported_row(&mut row, "", None, values, &mut taken);
+        assert_eq!(row.name, "Taro Tanaka");
+        assert_eq!(row.biography, "new memo");
+        assert_eq!(row.nickname, "taro2");
+        assert_eq!(row.fcc, "=work");
+        assert!(taken.contains("taro2"));
+    }
+
+    /// テスト用の連絡先を作成する。
+    fn gperson(name: &str, emails: &[&str], group: Option<&str>) -> Person {
+        serde_json::from_value(serde_json::json!({
+            "names": [{"displayName": name}],
+            "emailAddresses": emails.iter().map(|e| serde_json::json!({"value": e})).collect::<Vec<_>>(),
+            "memberships": group.map(|g| vec![serde_json::json!({
+                "contactGroupMembership": {"contactGroupResourceName": g}
+            })]),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn mutt_aliases_are_unique() {
+        let config = mod_config::Config::defaults().unwrap();
+        let gpersons = vec![
+            gperson(
+                "Jane Smith",
+                &["jane@example.com"],
+                Some("contactGroups/smith"),
+            ),
+            gperson(
+                "John Smith",
+                &["john@example.com", "john@example.org"],
+                None,
+            ),
+        ];
+        let labels = BTreeMap::from([("contactGroups/smith".to_string(), "Smith".to_string())]);
+
+        let data = mutt_aliases(&gpersons, &labels, &config);
+        let names: Vec<&str> = data
+            .lines()
+            .map(|line| line.split(' ').nth(1).unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["Smith", "Smith01", "Smith02", "Smith2", "Smith3"]
+        );
+        assert!(data.starts_with("alias Smith Jane Smith <jane@example.com>\n"));
+        assert!(data.contains(
+            "alias Smith2 John Smith <john@example.com>, John Smith <john@example.org>\n"
+        ));
+        assert!(data.ends_with("alias Smith3 Jane Smith <jane@example.com>\n"));
+    }
+
+    #[test]
+    fn staged_addressbook_keeps_rows_without_an_email() {
+        let dir = mod_test::temp_path("stage");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("addressbook");
+        let row = |nickname: &str, email: &str| APerson {
+            nickname: nickname.to_string(),
+            name: "Name".to_string(),
+            email: email.to_string(),
+            fcc: String::new(),
+            biography: String::new(),
+        };
+        let apeople = vec![row("smith", "smith@example.com"), row("group", "")];
+
+        let staged = stage_addressbook(&path, &apeople).unwrap();
+        fs::rename(&staged, &path).unwrap();
+        let loaded = load_addressbook_data(&path).unwrap();
+        let nicknames: Vec<&str> = loaded
+            .iter()
+            .filter(|ap| !ap.nickname.is_empty())
+            .map(|ap| ap.nickname.as_str())
+            .collect();
+        assert_eq!(nicknames, vec!["smith", "group"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,256 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! 連絡先をabookのアドレス帳ファイル（`~/.abook/addressbook`、INI形式）の形式で書き出す
+
+use google_people1::api::Person; // Google Contactsの連絡先を扱うため
+use std::collections::BTreeMap; // 連絡先グループのリソース名とラベル名の対応を扱うため
+
+/// アドレス帳ファイルの先頭に書き出す`[format]`セクション
+const HEADER: &str = "# abook addressbook file\n\n[format]\nprogram=abook\nversion=0.6.1\n\n";
+
+/// 値を1行の値として書き出せる形式に変換する。
+///
+/// INI形式では値に改行を含められないため、改行を空白に置き換えます。
+fn value(value: &str) -> String {
+    value
+        .trim()
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Google Contactsの電話番号の種類を、abookの電話番号のフィールド名に変換する。
+fn phone_field(type_: Option<&str>) -> &'static str {
+    match type_ {
+        Some("work") => "workphone",
+        Some("mobile") | Some("workMobile") => "mobile",
+        Some("homeFax") | Some("workFax") | Some("otherFax") => "fax",
+        _ => "phone",
+    }
+}
+
+/// 1つの連絡先のエントリーを作成する。
+///
+/// # 引数
+/// * `index` - エントリーの番号（セクション名）。
+/// * `person` - Googleの連絡先。
+/// * `nickname` - 書き出すニックネーム（`nick`）。空の場合は書き出しません。
+/// * `labels` - 連絡先グループのリソース名とラベル名の対応。
+///
+/// # 戻り値
+/// エントリーの内容。名前として書き出せる値が無い場合は`None`。
+fn write_entry(
+    index: usize,
+    person: &Person,
+    nickname: &str,
+    labels: &BTreeMap<String, String>,
+) -> Option<String> {
+    let organization = person.organizations.iter().flatten().next();
+    let emails: Vec<&str> = person
+        .email_addresses
+        .iter()
+        .flatten()
+        .filter_map(|e| e.value.as_deref())
+        .filter(|v| !v.is_empty())
+        .collect();
+
+    // 名前は必須のため、名前が無い場合は所属組織名かメールアドレスを使用する
+    let name = person
+        .names
+        .iter()
+        .flatten()
+        .find_map(|n| n.display_name.as_deref())
+        .or_else(|| organization.and_then(|o| o.name.as_deref()))
+        .or(emails.first().copied())
+        .map(value)
+        .filter(|v| !v.is_empty())?;
+
+    let mut fields: Vec<(&str, String)> = vec![("name", name)];
+    if !emails.is_empty() {
+        // abookでは複数のメールアドレスをカンマで区切る
+        fields.push(("email", emails.join(",")));
+    }
+
+    // 住所（最初の住所のみ。番地が複数行の場合は2行目以降をaddress2に書き出す）
+    if let Some(a) = person.addresses.iter().flatten().next() {
+        let street = a.street_address.as_deref().unwrap_or_default();
+        let (address, address2) = street.split_once('\n').unwrap_or((street, ""));
+        for (key, v) in [
+            ("address", address),
+            ("address2", address2),
+            ("city", a.city.as_deref().unwrap_or_default()),
+            ("state", a.region.as_deref().unwrap_or_default()),
+            ("zip", a.postal_code.as_deref().unwrap_or_default()),
+            ("country", a.country.as_deref().unwrap_or_default()),
+        ] {
+            fields.push((key, value(v)));
+        }
+    }
+
+    // 電話番号（種類ごとに最初の番号のみ）
+    for p in person.phone_numbers.iter().flatten() {
+        let Some(number) = p.value.as_deref().filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        let key = phone_field(p.type_.as_deref());
+        if !fields.iter().any(|(k, _)| *k == key) {
+            fields.push((key, value(number)));
+        }
+    }
+
+    if !nickname.is_empty() {
+        fields.push(("nick", value(nickname)));
+    }
+
+    // メモ
+    if let Some(note) = person
+        .biographies
+        .iter()
+        .flatten()
+        .find_map(|b| b.value.as_deref())
+    {
+        fields.push(("notes", value(note)));
+    }
+
+    // 誕生日（abookのanniversaryは年が無い場合に"--MM-DD"の形式を使用する）
+    let date = person
+        .birthdays
+        .iter()
+        .flatten()
+        .find_map(|b| b.date.as_ref());
+    if let Some((month, day)) = date.and_then(|d| Some((d.month?, d.day?))) {
+        let anniversary = match date.and_then(|d| d.year) {
+            Some(year) => format!("{:04}-{:02}-{:02}", year, month, day),
+            None => format!("--{:02}-{:02}", month, day),
+        };
+        fields.push(("anniversary", anniversary));
+    }
+
+    // 連絡先グループ（ユーザーが作成したグループのみ）
+    let groups: Vec<String> = person
+        .memberships
+        .iter()
+        .flatten()
+        .filter_map(|m| {
+            let group = m.contact_group_membership.as_ref()?;
+            labels.get(group.contact_group_resource_name.as_ref()?)
+        })
+        .map(|label| value(&label.replace(',', " ")))
+        .collect();
+    if !groups.is_empty() {
+        fields.push(("groups", groups.join(",")));
+    }
+
+    let mut entry = format!("[{}]\n", index);
+    for (key, v) in fields.iter().filter(|(_, v)| !v.is_empty()) {
+        entry.push_str(&format!("{}={}\n", key, v));
+    }
+    entry.push('\n');
+    Some(entry)
+}
+
+/// Googleの連絡先から、abookのアドレス帳ファイルの内容を作成する関数。
+///
+/// 名前、所属組織名、メールアドレスのいずれも無い連絡先は書き出しません。
+///
+/// # 引数
+/// * `entries` - Googleの連絡先と、書き出すニックネームのタプルのスライス。
+/// * `labels` - 連絡先グループのリソース名とラベル名の対応。
+///
+/// # 戻り値
+/// アドレス帳ファイルの内容。
+pub fn write_addressbook(
+    entries: &[(&Person, String)],
+    labels: &BTreeMap<String, String>,
+) -> String {
+    let mut data = HEADER.to_string();
+    let mut index = 0;
+    for (person, nickname) in entries {
+        if let Some(entry) = write_entry(index, person, nickname, labels) {
+            data.push_str(&entry);
+            index += 1;
+        }
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mod_test;
+
+    /// テスト用の連絡先を作成する。
+    fn person() -> Person {
+        mod_test::person()
+            .name("Taro Tanaka")
+            .email("taro@example.com")
+            .email("taro@example.org")
+            .address("1-2-3 Chiyoda\nRoom 401", "Tokyo", "100-0001")
+            .phone("03-1234-5678", "work")
+            .phone("090-1234-5678", "mobile")
+            .phone("03-9999-9999", "work")
+            .biography("line 1\n\n  line 2  ")
+            .birthday(4, 1)
+            .group("contactGroups/abc")
+            .group("contactGroups/myContacts")
+            .build()
+    }
+
+    #[test]
+    fn joins_multiline_values_into_one_line() {
+        assert_eq!(value("  line 1\r\n\n line 2 \n"), "line 1 line 2");
+    }
+
+    #[test]
+    fn writes_an_entry_for_each_contact() {
+        let labels = BTreeMap::from([(
+            "contactGroups/abc".to_string(),
+            "Friends, Family".to_string(),
+        )]);
+        let person = person();
+        let data = write_addressbook(&[(&person, "taro".to_string())], &labels);
+        assert!(data.starts_with(HEADER));
+        let entry = &data[HEADER.len()..];
+        assert_eq!(
+            entry,
+            "[0]\n\
+             name=Taro Tanaka\n\
+             email=taro@example.com,taro@example.org\n\
+             address=1-2-3 Chiyoda\n\
+             address2=Room 401\n\
+             city=Tokyo\n\
+             zip=100-0001\n\
+             workphone=03-1234-5678\n\
+             mobile=090-1234-5678\n\
+             nick=taro\n\
+             notes=line 1 line 2\n\
+             anniversary=--04-01\n\
+             groups=Friends  Family\n\n"
+        );
+    }
+
+    #[test]
+    fn skips_contacts_without_a_name_and_numbers_the_rest() {
+        let nameless = Person::default();
+        let organization = mod_test::person().organization("Example Inc.").build();
+        let data = write_addressbook(
+            &[(&nameless, String::new()), (&organization, String::new())],
+            &BTreeMap::new(),
+        );
+        assert_eq!(&data[HEADER.len()..], "[0]\nname=Example Inc.\n\n");
+    }
+}
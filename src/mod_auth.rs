@@ -12,42 +12,240 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::mod_config::Config; // トークンの保存先やアカウントの設定
+use crate::mod_dirs; // 認証情報とトークンキャッシュの場所を決定するため
+use crate::mod_perm; // 認証情報とトークンキャッシュのパーミッションを検査するため
+use crate::mod_token_storage::{
+    self, CachedToken, EncryptedFileStore, FileStore, KeyringStore, MemoryStore, TokenCache,
+    TokenStorageKind,
+}; // トークンの保存先を切り替えるため
 use hyper::client::{Client, HttpConnector}; // HTTPクライアント操作用
-use hyper_rustls::HttpsConnector;
+use hyper::{header, Body, Method, Request}; // トークン失効・照会リクエストの組み立て用
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder}; // HTTPSサポート用
+use std::path::PathBuf; // ファイルパスを扱うため
 /// Google APIへのOAuth2認証を行う
 use yup_oauth2::{
-    authenticator::Authenticator, read_application_secret, InstalledFlowAuthenticator,
-    InstalledFlowReturnMethod,
-}; // OAuth2認証のためのモジュール // HTTPSサポート用
+    authenticator::Authenticator, read_application_secret, ApplicationSecret,
+    InstalledFlowAuthenticator, InstalledFlowReturnMethod,
+}; // OAuth2認証のためのモジュール
+
+/// Googleのトークン失効エンドポイントの既定値
+const DEFAULT_REVOKE_ENDPOINT: &str = "https://oauth2.googleapis.com/revoke";
+/// Googleのトークン情報エンドポイントの既定値
+const DEFAULT_TOKENINFO_ENDPOINT: &str = "https://oauth2.googleapis.com/tokeninfo";
+
+/// 連絡先の読み取り専用スコープ
+pub const SCOPE_CONTACTS_READONLY: &str = "https://www.googleapis.com/auth/contacts.readonly";
+/// 連絡先の読み書きスコープ
+pub const SCOPE_CONTACTS: &str = "https://www.googleapis.com/auth/contacts";
+
+/// コマンドが必要とするGoogle Contactsへのアクセス権限。
+///
+/// 読み取りだけのコマンドには読み取り専用スコープを要求し、
+/// Google Contactsを変更するときだけ読み書きスコープを要求します。
+pub enum AccessLevel {
+    ReadOnly,  // 連絡先の取得のみ
+    ReadWrite, // 連絡先の作成、更新、削除
+}
+
+impl AccessLevel {
+    /// アクセス権限に対応するOAuth2スコープを返す。
+    pub fn scope(&self) -> &'static str {
+        match self {
+            AccessLevel::ReadOnly => SCOPE_CONTACTS_READONLY,
+            AccessLevel::ReadWrite => SCOPE_CONTACTS,
+        }
+    }
+}
+
+/// HTTPS対応のコネクタを構築する関数。
+///
+/// システムのルート証明書を使用し、HTTPSでのみ接続します。
+///
+/// # 戻り値
+/// 成功した場合は`HttpsConnector<HttpConnector>`、ルート証明書の読み込みに失敗した場合はエラー。
+pub fn build_https_connector() -> std::io::Result<HttpsConnector<HttpConnector>> {
+    Ok(HttpsConnectorBuilder::new()
+        .with_native_roots()?
+        .https_only()
+        .enable_http1()
+        .build())
+}
+
+/// URLがループバックアドレス（`localhost`、`127.0.0.1`、`::1`）への`http://`のURLかどうかを返す。
+fn is_loopback_http(url: &str) -> bool {
+    let Ok(uri) = url.parse::<hyper::Uri>() else {
+        return false;
+    };
+    uri.scheme_str() == Some("http")
+        && matches!(
+            uri.host(),
+            Some("localhost" | "127.0.0.1" | "[::1]" | "::1")
+        )
+}
+
+/// トークン失効・照会エンドポイントに接続するコネクタを構築する関数。
+///
+/// 既定のエンドポイントにはHTTPSでのみ接続します。テストのために環境変数で
+/// ループバックアドレスの`http://`のURLが指定された場合に限り、HTTPを許可します。
+///
+/// # 引数
+/// * `url` - 接続するエンドポイントのURL。
+///
+/// # 戻り値
+/// 成功した場合は`HttpsConnector<HttpConnector>`、ルート証明書の読み込みに失敗した場合はエラー。
+fn build_endpoint_connector(url: &str) -> std::io::Result<HttpsConnector<HttpConnector>> {
+    if !is_loopback_http(url) {
+        return build_https_connector();
+    }
+    Ok(HttpsConnectorBuilder::new()
+        .with_native_roots()?
+        .https_or_http()
+        .enable_http1()
+        .build())
+}
+
+/// トークンキャッシュファイルのパスを取得する関数。
+///
+/// トークンキャッシュは状態ディレクトリに保存します。アカウントが設定されている場合は
+/// アカウントごとに別のファイルを使用します。
+///
+/// # 引数
+/// * `config` - トークンの保存先とアカウントの設定。
+///
+/// # 戻り値
+/// 成功した場合はトークンキャッシュファイルのパス。保存先がファイルでない場合は`None`。
+/// 状態ディレクトリのパスを決定できない場合はエラー。
+pub fn get_token_cache_path(
+    config: &Config,
+) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    match config.auth.token_storage.file_name(&config.google.account) {
+        Some(file_name) => Ok(Some(mod_dirs::state_dir()?.join(file_name))),
+        None => Ok(None),
+    }
+}
+
+/// 設定された保存先のトークンキャッシュを開く関数。
+///
+/// 暗号化ファイルを使用する場合は、環境変数`GCONTACTS_TOKEN_PASSPHRASE`、
+/// または端末から入力されたパスフレーズを使用します。暗号化ファイルがまだ無い場合は、確認のために2回入力させます。
+///
+/// # 引数
+/// * `config` - トークンの保存先とアカウントの設定。
+///
+/// # 戻り値
+/// 成功した場合は`TokenCache`、パスフレーズの入力に失敗したか一致しない場合などはエラー。
+pub fn open_token_cache(config: &Config) -> Result<TokenCache, Box<dyn std::error::Error>> {
+    let store: Box<dyn mod_token_storage::BlobStore> = match config.auth.token_storage {
+        TokenStorageKind::File => Box::new(FileStore::new(get_token_cache_path(config)?.unwrap())),
+        TokenStorageKind::Encrypted => {
+            let path = get_token_cache_path(config)?.unwrap();
+            let passphrase = match std::env::var("GCONTACTS_TOKEN_PASSPHRASE") {
+                Ok(p) if !p.is_empty() => p,
+                _ => {
+                    let passphrase =
+                        mod_token_storage::read_passphrase("Token cache passphrase: ")?;
+                    // 初めて暗号化する場合は、入力ミスで復号できなくならないように再入力させる
+                    if !path.exists()
+                        && mod_token_storage::read_passphrase("Confirm passphrase: ")? != passphrase
+                    {
+                        return Err("the passphrases do not match".into());
+                    }
+                    passphrase
+                }
+            };
+            Box::new(EncryptedFileStore::new(path, passphrase))
+        }
+        TokenStorageKind::Keyring => Box::new(KeyringStore::new(&config.google.account)?),
+        TokenStorageKind::Memory => Box::new(MemoryStore::default()),
+    };
+
+    Ok(TokenCache::new(store))
+}
+
+/// 設定ディレクトリ、状態ディレクトリ、`client_secret.json`、トークンキャッシュファイルの
+/// パーミッションと所有者を検査する関数。
+///
+/// ディレクトリが存在しない場合は作成し、ディレクトリのパーミッションを0700にします。ファイルは0600であることを期待し、グループやその他のユーザーが
+/// アクセスできる場合や、他のユーザーが所有している場合は警告を表示します。ファイルのパーミッションは変更しません
+/// （トークンキャッシュファイルは書き込むたびに0600にします）。
+/// 設定の`auth.strict`が`true`の場合は、これらの問題が見つかった時点でエラーを返します。
+///
+/// # 引数
+/// * `config` - トークンの保存先と`strict`の設定。
+///
+/// # 戻り値
+/// 成功した場合はOk(())、`strict`で問題が見つかった場合や検査に失敗した場合はエラー。
+pub fn check_secret_files(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let strict = config.auth.strict;
+
+    mod_dirs::ensure_dirs()?;
+
+    mod_perm::check_private(&mod_dirs::config_dir()?, mod_perm::PRIVATE_DIR_MODE, strict)?;
+    mod_perm::check_private(&mod_dirs::state_dir()?, mod_perm::PRIVATE_DIR_MODE, strict)?;
+    mod_perm::check_private(
+        &mod_dirs::client_secret_path()?,
+        mod_perm::PRIVATE_FILE_MODE,
+        strict,
+    )?;
+    if let Some(token_cache_file) = get_token_cache_path(config)? {
+        mod_perm::check_private(&token_cache_file, mod_perm::PRIVATE_FILE_MODE, strict)?;
+    }
+
+    Ok(())
+}
+
+/// 指定されたトークンキャッシュを使用するOAuth2認証フローを構築する非同期関数。
+///
+/// # 引数
+/// * `secret` - Google APIの認証情報。
+/// * `cache` - トークンの保存先。
+///
+/// # 戻り値
+/// 成功した場合は`Authenticator<HttpsConnector<HttpConnector>>`、失敗した場合はエラー。
+async fn build_authenticator(
+    secret: ApplicationSecret,
+    cache: TokenCache,
+) -> Result<Authenticator<HttpsConnector<HttpConnector>>, Box<dyn std::error::Error>> {
+    // HTTPS対応のHTTPクライアントを構築
+    let client = Client::builder().build(build_https_connector()?);
+
+    let auth = InstalledFlowAuthenticator::builder(secret, InstalledFlowReturnMethod::HTTPRedirect)
+        .with_storage(Box::new(cache))
+        .hyper_client(client)
+        .build()
+        .await?;
+
+    Ok(auth)
+}
 
 /// Google APIの認証プロセスを実行し、認証情報を取得する非同期関数。
 ///
-/// この関数はユーザーのホームディレクトリからプロジェクト固有のディレクトリを作成し、
-/// そこにGoogle APIの認証情報とトークンキャッシュを保存します。まず、プロジェクトのディレクトリが存在するかを確認し、
-/// 存在しない場合は新しく作成します。次に、`client_secret.json` と `token_cache.json` ファイルのパスを設定し、
-/// `client_secret.json` からGoogle APIの認証情報を読み込みます。その後、HTTPS対応のHTTPクライアントを構築し、
-/// OAuth2認証フローを構築して返します。
+/// この関数は設定ディレクトリの`client_secret.json`（または環境変数`GCONTACTS_CLIENT_SECRET`で指定されたファイル）
+/// からGoogle APIの認証情報を読み込みます。トークンキャッシュは状態ディレクトリに保存します。その後、HTTPS対応のHTTPクライアントを構築し、
+/// 設定された保存先のトークンキャッシュを使用するOAuth2認証フローを構築します。最後に`access`に対応するスコープのトークンを取得してから返します。
+///
+/// 読み書きスコープのトークンはキャッシュに別途保存されるため、読み取り専用で認証した後でも
+/// Google Contactsを変更するリクエストの時点で読み書きスコープへの同意を求めます。
+///
+/// 認証情報を読み込む前と、トークンを取得した後に`check_secret_files`でパーミッションを検査します。
+///
+/// # 引数
+/// * `access` - コマンドが最初に必要とするアクセス権限。
+/// * `config` - トークンの保存先、アカウント、`strict`の設定。
 ///
 /// # 戻り値
 /// 成功した場合は`Result`型で`Authenticator<HttpsConnector<HttpConnector>>`を返し、
 /// エラーが発生した場合は`Box<dyn std::error::Error>`を返します。
 pub async fn get_auth(
+    access: &AccessLevel,
+    config: &Config,
 ) -> Result<Authenticator<HttpsConnector<HttpConnector>>, Box<dyn std::error::Error>> {
-    // ユーザーのホームディレクトリを取得
-    let home_dir = dirs::home_dir().expect("Home directory not found");
-    // Rustプロジェクトの名前を動的に取得
-    let project_name = env!("CARGO_PKG_NAME");
-    // プロジェクトのディレクトリパスを作成
-    let project_dir = home_dir.join(format!(".{}", project_name));
-
-    // プロジェクトディレクトリが存在するかチェックし、存在しない場合は作成する
-    if !project_dir.exists() {
-        std::fs::create_dir(&project_dir)?;
-    }
+    // 認証情報のファイルパスを設定
+    let secret_file = mod_dirs::client_secret_path()?;
 
-    // 認証情報とトークンキャッシュのファイルパスを設定
-    let secret_file = project_dir.join("client_secret.json");
-    let token_cache_file = project_dir.join("token_cache.json");
+    // 認証情報とトークンキャッシュが他のユーザーから読めないことを確認
+    check_secret_files(config)?;
 
     // `secret_file` のパスをクローンし`secret_file_path`に保存
     // これにより、所有権が移された後もファイルパスを使用できる
@@ -65,15 +263,208 @@ pub async fn get_auth(
         }?, // `?` 演算子は`Result`型から`Ok`の値を抽出し、`Err`の場合は呼び出し元の関数にエラーを返す
     };
 
-    // HTTPS対応のHTTPクライアントを構築
-    let client = Client::builder().build(HttpsConnector::with_native_roots());
+    // OAuth2認証フローを構築する
+    let auth = build_authenticator(secret, open_token_cache(config)?).await?;
 
-    // OAuth2認証フローを構築して返す
-    let auth = InstalledFlowAuthenticator::builder(secret, InstalledFlowReturnMethod::HTTPRedirect)
-        .persist_tokens_to_disk(token_cache_file)
-        .hyper_client(client)
-        .build()
-        .await?;
+    // 必要なスコープのトークンを先に取得し、認証の失敗をここで検出する
+    auth.token(&[access.scope()]).await?;
+
+    // 新しく作成されたトークンキャッシュも含めてパーミッションを確認
+    check_secret_files(config)?;
 
     Ok(auth)
 }
+
+/// トークンキャッシュを読み込む関数。
+///
+/// 設定された保存先からトークンのリストを読み込みます。
+/// トークンが保存されていない場合は空のリストを返します。
+///
+/// # 引数
+/// * `config` - トークンの保存先とアカウントの設定。
+///
+/// # 戻り値
+/// 成功した場合は`CachedToken`のベクター、読み込みや解析に失敗した場合はエラー。
+pub fn load_cached_tokens(config: &Config) -> Result<Vec<CachedToken>, Box<dyn std::error::Error>> {
+    Ok(open_token_cache(config)?.load_tokens()?)
+}
+
+/// 環境変数で上書きされたエンドポイントURLを取得する関数。
+///
+/// # 引数
+/// * `var` - 上書きに使用する環境変数名。
+/// * `default` - 環境変数が設定されていない場合のURL。
+///
+/// # 戻り値
+/// 使用するエンドポイントのURL。
+fn endpoint_from_env(var: &str, default: &str) -> String {
+    match std::env::var(var) {
+        Ok(url) if !url.is_empty() => url,
+        _ => default.to_string(),
+    }
+}
+
+/// トークン失効エンドポイントのURLを取得する関数。
+///
+/// 環境変数`GCONTACTS_REVOKE_ENDPOINT`が設定されている場合はその値を使用します。
+/// オフラインのテストではローカルのモックサーバーを指定できます。
+pub fn revoke_endpoint() -> String {
+    endpoint_from_env("GCONTACTS_REVOKE_ENDPOINT", DEFAULT_REVOKE_ENDPOINT)
+}
+
+/// トークン情報エンドポイントのURLを取得する関数。
+///
+/// 環境変数`GCONTACTS_TOKENINFO_ENDPOINT`が設定されている場合はその値を使用します。
+pub fn tokeninfo_endpoint() -> String {
+    endpoint_from_env("GCONTACTS_TOKENINFO_ENDPOINT", DEFAULT_TOKENINFO_ENDPOINT)
+}
+
+/// アクセストークンに紐付くアカウントのメールアドレスを問い合わせる非同期関数。
+///
+/// トークン情報エンドポイントにアクセストークンを送り、応答の`email`フィールドを返します。
+/// トークンが期限切れの場合や、メールアドレスを参照するスコープが許可されていない場合は`None`を返します。
+///
+/// # 引数
+/// * `access_token` - 問い合わせるアクセストークン。
+///
+/// # 戻り値
+/// アカウントのメールアドレス。取得できなかった場合は`None`。
+pub async fn lookup_account(access_token: &str) -> Option<String> {
+    let endpoint = tokeninfo_endpoint();
+    let client = Client::builder().build::<_, Body>(build_endpoint_connector(&endpoint).ok()?);
+    let uri = format!(
+        "{}?access_token={}",
+        endpoint,
+        form_urlencoded::byte_serialize(access_token.as_bytes()).collect::<String>()
+    );
+    let res = client.get(uri.parse().ok()?).await.ok()?;
+    if !res.status().is_success() {
+        return None;
+    }
+    let body = hyper::body::to_bytes(res.into_body()).await.ok()?;
+    let info: serde_json::Value = serde_json::from_slice(&body).ok()?;
+    info["email"].as_str().map(|s| s.to_string())
+}
+
+/// トークンを失効させる非同期関数。
+///
+/// トークン失効エンドポイントに`token`をフォーム形式でPOSTします。
+///
+/// # 引数
+/// * `token` - 失効させるリフレッシュトークンまたはアクセストークン。
+///
+/// # 戻り値
+/// 成功した場合はOk(())、エンドポイントがエラーを返した場合はエラー。
+pub async fn revoke_token(token: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let endpoint = revoke_endpoint();
+    let client = Client::builder().build::<_, Body>(build_endpoint_connector(&endpoint)?);
+    let body = format!(
+        "token={}",
+        form_urlencoded::byte_serialize(token.as_bytes()).collect::<String>()
+    );
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(endpoint)
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(body))?;
+
+    let res = client.request(req).await?;
+    let status = res.status();
+    if !status.is_success() {
+        // エンドポイントからのエラー応答をそのままメッセージに含める
+        let body = hyper::body::to_bytes(res.into_body()).await?;
+        return Err(Box::new(std::io::Error::other(format!(
+            "{}: {}",
+            status,
+            String::from_utf8_lossy(&body)
+        ))));
+    }
+
+    Ok(())
+}
+
+/// キャッシュされているすべてのトークンを失効させ、トークンキャッシュを削除する非同期関数。
+///
+/// リフレッシュトークンがあればそれを、無ければアクセストークンを失効させます。
+/// すべての失効が成功した場合のみキャッシュファイルを削除します。
+///
+/// # 引数
+/// * `config` - トークンの保存先とアカウントの設定。
+///
+/// # 戻り値
+/// 成功した場合は失効させたトークンの数、失敗した場合はエラー。
+pub async fn revoke(config: &Config) -> Result<usize, Box<dyn std::error::Error>> {
+    let cache = open_token_cache(config)?;
+    let tokens = cache.load_tokens()?;
+
+    let mut revoked = 0;
+    for cached in &tokens {
+        // リフレッシュトークンを失効させると、関連するアクセストークンも無効になる
+        let token = cached
+            .token
+            .refresh_token
+            .as_ref()
+            .or(cached.token.access_token.as_ref());
+        if let Some(token) = token {
+            revoke_token(token).await?;
+            revoked += 1;
+        }
+    }
+
+    // トークンキャッシュを削除
+    cache.clear()?;
+
+    Ok(revoked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// テスト用のトークン（有効期限なし）を作成する。
+    fn token(access_token: &str) -> CachedToken {
+        CachedToken {
+            scopes: vec![SCOPE_CONTACTS_READONLY.to_string()],
+            token: serde_json::from_value(serde_json::json!({
+                "access_token": access_token,
+                "refresh_token": "refresh",
+                "expires_at": null,
+                "id_token": null,
+            }))
+            .unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn authenticator_uses_the_token_in_the_memory_store() {
+        let cache = TokenCache::new(Box::new(MemoryStore::default()));
+        cache.save_tokens(&[token("cached-access")]).unwrap();
+
+        // キャッシュに有効なトークンがあるので、ネットワークには接続しない
+        let auth = build_authenticator(ApplicationSecret::default(), cache)
+            .await
+            .unwrap();
+        let access = auth.token(&[AccessLevel::ReadOnly.scope()]).await.unwrap();
+        assert_eq!(access.token(), Some("cached-access"));
+    }
+
+    #[test]
+    fn memory_storage_keeps_no_file() {
+        assert_eq!(TokenStorageKind::Memory.file_name(""), None);
+        assert_eq!(
+            TokenStorageKind::File.file_name("work").as_deref(),
+            Some("token_cache.work.json")
+        );
+    }
+
+    #[test]
+    fn http_is_allowed_only_for_loopback_endpoints() {
+        assert!(is_loopback_http("http://localhost:8080/revoke"));
+        assert!(is_loopback_http("http://127.0.0.1:8080/revoke"));
+        assert!(is_loopback_http("http://[::1]:8080/revoke"));
+        assert!(!is_loopback_http("http://example.com/revoke"));
+        assert!(!is_loopback_http("https://localhost/revoke"));
+        assert!(!is_loopback_http(DEFAULT_REVOKE_ENDPOINT));
+        assert!(!is_loopback_http("not a url"));
+    }
+}
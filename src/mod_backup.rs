@@ -0,0 +1,91 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! アドレス帳を上書きする前にバックアップを作成する
+
+use crate::mod_dirs; // バックアップを状態ディレクトリに保存するため
+use crate::mod_perm; // バックアップディレクトリをパーミッション0700で作成するため
+use std::fs; // ファイルシステム操作のための標準ライブラリのモジュール
+use std::io; // エラーを返すための 'io' モジュール
+use std::path::{Path, PathBuf}; // ファイルパスを扱うため
+
+/// バックアップファイル名の接頭辞
+const BACKUP_PREFIX: &str = "addressbook-";
+
+/// バックアップを保存するディレクトリのパスを取得する関数。
+///
+/// # 戻り値
+/// 成功した場合は状態ディレクトリの`backups`のパス、ホームディレクトリが見つからない場合はエラー。
+pub fn backup_dir() -> io::Result<PathBuf> {
+    Ok(mod_dirs::state_dir()?.join("backups"))
+}
+
+/// アドレス帳のバックアップを作成し、古いバックアップを削除する関数。
+///
+/// バックアップは`addressbook-YYYYmmddHHMMSS`という名前で状態ディレクトリの`backups`に保存し、
+/// 新しいものから`retention`個だけ残します。`retention`が0の場合やアドレス帳が存在しない場合は何もしません。
+///
+/// # 引数
+/// * `addressbook_path` - バックアップするアドレス帳のパス。
+/// * `retention` - 保持するバックアップの数。
+///
+/// # 戻り値
+/// 成功した場合は作成したバックアップのパス（作成しなかった場合は`None`）、失敗した場合はエラー。
+pub fn backup_addressbook(
+    addressbook_path: &Path,
+    retention: usize,
+) -> io::Result<Option<PathBuf>> {
+    if retention == 0 || !addressbook_path.exists() {
+        return Ok(None);
+    }
+
+    let dir = backup_dir()?;
+    mod_perm::create_private_dir(&dir)?;
+
+    // 同じ秒に複数回実行された場合は連番を付ける
+    let timestamp = chrono::Local::now().format("%Y%m%d%H%M%S").to_string();
+    let mut backup_path = dir.join(format!("{}{}", BACKUP_PREFIX, timestamp));
+    let mut counter = 1;
+    while backup_path.exists() {
+        backup_path = dir.join(format!("{}{}-{}", BACKUP_PREFIX, timestamp, counter));
+        counter += 1;
+    }
+    fs::copy(addressbook_path, &backup_path)?;
+
+    prune_backups(&dir, retention)?;
+
+    Ok(Some(backup_path))
+}
+
+/// 新しいものから`retention`個を残して古いバックアップを削除する関数。
+fn prune_backups(dir: &Path, retention: usize) -> io::Result<()> {
+    let mut backups: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(BACKUP_PREFIX))
+        })
+        .collect();
+
+    // ファイル名に日時が含まれるので、名前の降順が新しい順になる
+    backups.sort();
+    backups.reverse();
+    for old in backups.iter().skip(retention) {
+        fs::remove_file(old)?;
+    }
+
+    Ok(())
+}
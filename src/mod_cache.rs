@@ -0,0 +1,208 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Google Contactsから取得した連絡先をキャッシュし、Google Contactsにアクセスせずに使用できるようにする
+
+use crate::mod_dirs; // 状態ディレクトリに保存するため
+use crate::mod_perm; // 連絡先を含むファイルをパーミッション0600で書き込むため
+use google_people1::api::Person; // キャッシュする連絡先
+use serde::{Deserialize, Serialize}; // JSON形式で保存するため
+use std::collections::BTreeMap; // 連絡先グループのリソース名とラベル名の対応
+use std::fs; // ファイルシステム操作のための標準ライブラリのモジュール
+use std::io; // エラーを返すための 'io' モジュール
+use std::path::PathBuf; // ファイルパスを扱うため
+
+/// 取得した連絡先のキャッシュ。
+#[derive(Serialize, Deserialize)]
+pub struct ContactsCache {
+    pub time: String,          // 取得した時刻（RFC 3339）
+    pub contacts: Vec<Person>, // 取得したすべての連絡先（etagを含む）
+    pub hidden: Vec<String>,   // ラベルの設定により対象外となった連絡先のリソース名
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>, // ユーザーが作成した連絡先グループのリソース名とラベル名
+}
+
+impl ContactsCache {
+    /// 同期の対象となる連絡先と、ラベルの設定により対象外となった連絡先を作成する。
+    ///
+    /// # 引数
+    /// * `gpersons` - 同期の対象となる連絡先。
+    /// * `hidden_gpersons` - ラベルの設定により対象外となった連絡先。
+    /// * `labels` - ユーザーが作成した連絡先グループのリソース名とラベル名の対応。
+    pub fn new(
+        gpersons: Vec<Person>,
+        hidden_gpersons: Vec<Person>,
+        labels: BTreeMap<String, String>,
+    ) -> Self {
+        let hidden = hidden_gpersons
+            .iter()
+            .filter_map(|p| p.resource_name.clone())
+            .collect();
+        let mut contacts = gpersons;
+        contacts.extend(hidden_gpersons);
+        ContactsCache {
+            time: chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+            contacts,
+            hidden,
+            labels,
+        }
+    }
+
+    /// キャッシュが古くなったかどうかを返す。
+    ///
+    /// # 引数
+    /// * `max_age` - キャッシュを使用できる秒数（0の場合は古くならない）。
+    ///
+    /// # 戻り値
+    /// 取得してから`max_age`秒より長く経過した場合、または取得した時刻を解析できない場合は`true`。
+    pub fn is_stale(&self, max_age: u64) -> bool {
+        self.is_stale_at(max_age, chrono::Local::now().fixed_offset())
+    }
+
+    /// `now`の時点でキャッシュが古くなっているかどうかを返す。
+    fn is_stale_at(&self, max_age: u64, now: chrono::DateTime<chrono::FixedOffset>) -> bool {
+        if max_age == 0 {
+            return false;
+        }
+        match chrono::DateTime::parse_from_rfc3339(&self.time) {
+            Ok(time) => (now - time).num_seconds().max(0) as u64 > max_age,
+            Err(_) => true,
+        }
+    }
+
+    /// キャッシュした連絡先を、同期の対象となる連絡先とラベルの設定により対象外となった連絡先に分ける。
+    ///
+    /// ラベルの設定は、キャッシュを作成した時点の設定が適用されます。
+    ///
+    /// # 戻り値
+    /// 同期の対象となる連絡先と、対象外となった連絡先のタプル。
+    pub fn split(self) -> (Vec<Person>, Vec<Person>) {
+        let hidden = self.hidden;
+        self.contacts.into_iter().partition(|p| {
+            !p.resource_name
+                .as_ref()
+                .is_some_and(|name| hidden.contains(name))
+        })
+    }
+}
+
+/// キャッシュを保存するファイルのパスを取得する関数。
+///
+/// トークンキャッシュと同じく、アカウントごとに別のファイル（`contacts_cache.<account>.json`）に保存します。
+///
+/// # 引数
+/// * `account` - アカウント名。空の場合は既定のアカウント。
+///
+/// # 戻り値
+/// 成功した場合はファイルのパス、ホームディレクトリが見つからない場合はエラー。
+pub fn cache_path(account: &str) -> io::Result<PathBuf> {
+    let file_name = if account.is_empty() {
+        "contacts_cache.json".to_string()
+    } else {
+        format!("contacts_cache.{}.json", account)
+    };
+    Ok(mod_dirs::state_dir()?.join(file_name))
+}
+
+/// キャッシュを読み込む関数。
+///
+/// # 引数
+/// * `account` - アカウント名。
+///
+/// # 戻り値
+/// 成功した場合はキャッシュ（まだ取得していない場合は`None`）、読み込みや解析に失敗した場合はエラー。
+pub fn load(account: &str) -> io::Result<Option<ContactsCache>> {
+    let data = match fs::read(cache_path(account)?) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    serde_json::from_slice(&data)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// キャッシュを保存する関数。
+///
+/// 連絡先を含むため、所有者以外が読めないパーミッション（0600）で書き込みます。
+///
+/// # 引数
+/// * `account` - アカウント名。
+/// * `cache` - 保存するキャッシュ。
+///
+/// # 戻り値
+/// 成功した場合はOk(())、書き込みに失敗した場合はエラー。
+pub fn save(account: &str, cache: &ContactsCache) -> io::Result<()> {
+    mod_dirs::ensure_dirs()?;
+    let data = serde_json::to_vec(cache)?;
+    mod_perm::write_private_file(&cache_path(account)?, &data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// リソース名だけを持つ連絡先を作成する。
+    fn person(resource_name: &str) -> Person {
+        Person {
+            resource_name: Some(resource_name.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// 指定した時刻に取得したキャッシュを作成する。
+    fn cache_at(time: &str) -> ContactsCache {
+        ContactsCache {
+            time: time.to_string(),
+            contacts: Vec::new(),
+            hidden: Vec::new(),
+            labels: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn becomes_stale_only_after_max_age() {
+        let cache = cache_at("2024-01-01T00:00:00+09:00");
+        let now = |s: &str| chrono::DateTime::parse_from_rfc3339(s).unwrap();
+
+        assert!(!cache.is_stale_at(60, now("2024-01-01T00:01:00+09:00")));
+        assert!(cache.is_stale_at(60, now("2024-01-01T00:01:01+09:00")));
+        // 別のタイムゾーンの時刻でも経過時間で比較する
+        assert!(!cache.is_stale_at(60, now("2023-12-31T15:00:30+00:00")));
+        // 時計が戻った場合は古くならない
+        assert!(!cache.is_stale_at(60, now("2023-12-31T00:00:00+09:00")));
+        // 0の場合は古くならない
+        assert!(!cache.is_stale_at(0, now("2030-01-01T00:00:00+09:00")));
+        // 時刻を解析できない場合は古いものとして扱う
+        assert!(cache_at("yesterday").is_stale_at(60, now("2024-01-01T00:00:00+09:00")));
+    }
+
+    #[test]
+    fn splits_hidden_contacts_from_the_others() {
+        let cache = ContactsCache::new(
+            vec![person("people/1"), Person::default()],
+            vec![person("people/2")],
+            BTreeMap::new(),
+        );
+        assert_eq!(cache.hidden, vec!["people/2"]);
+        assert!(!cache.is_stale(60));
+
+        let (visible, hidden) = cache.split();
+        let names = |people: &[Person]| -> Vec<Option<String>> {
+            people.iter().map(|p| p.resource_name.clone()).collect()
+        };
+        assert_eq!(names(&visible), vec![Some("people/1".to_string()), None]);
+        assert_eq!(names(&hidden), vec![Some("people/2".to_string())]);
+    }
+}
@@ -0,0 +1,770 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! コマンドライン引数をサブコマンド、グローバルオプション、コマンドごとのオプションに解析する
+
+use crate::mod_auth::AccessLevel; // コマンドが必要とするアクセス権限
+use std::collections::BTreeMap; // コマンドごとのオプションの値を保持するため
+use std::path::PathBuf; // ファイルパスを扱うため
+
+/// コマンド。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Command {
+    Init,       // Google Contactsのデータで.addressbookを初期化する
+    Sync,       // Google Contactsと.addressbookを同期する
+    Diff,       // Google Contactsと.addressbookの差分を表示する
+    Status,     // 同期の状態の概要を表示する
+    Add,        // 連絡先を追加する
+    Edit,       // 連絡先を編集する
+    Remove,     // 連絡先を削除する
+    Show,       // 連絡先を表示する
+    Query,      // 連絡先を検索し、muttの形式で表示する
+    Refresh,    // 連絡先のキャッシュを更新する
+    Export,     // 連絡先をほかのアプリケーションの形式で書き出す
+    Import,     // ほかのアプリケーションの形式の連絡先を取り込む
+    AuthStatus, // 認証状態を表示する
+    AuthRevoke, // トークンを失効させる
+    ConfigShow, // 実際に使用される設定を表示する
+}
+
+impl Command {
+    /// コマンドが最初に必要とするGoogle Contactsへのアクセス権限を返す。
+    ///
+    /// 同期はGoogle Contactsを変更するため、読み書きスコープを要求する。
+    pub fn access_level(&self) -> AccessLevel {
+        match self {
+            Command::Init | Command::Diff | Command::Show => AccessLevel::ReadOnly,
+            Command::Query | Command::Refresh | Command::Export => AccessLevel::ReadOnly,
+            Command::Sync | Command::Add | Command::Edit | Command::Remove | Command::Import => {
+                AccessLevel::ReadWrite
+            }
+            Command::Status | Command::AuthStatus | Command::AuthRevoke | Command::ConfigShow => {
+                AccessLevel::ReadOnly
+            }
+        }
+    }
+
+    /// `--offline`を指定して、キャッシュした連絡先だけで実行できるかどうかを返す。
+    ///
+    /// add、edit、rmはGoogle Contactsの変更を送信待ちの変更として保存し、次のsyncで反映する。
+    /// importは送信待ちの変更に保存できない項目（電話番号など）を含むため、オフラインでは実行できない。
+    pub fn works_offline(&self) -> bool {
+        !matches!(
+            self,
+            Command::Sync
+                | Command::Refresh
+                | Command::Import
+                | Command::AuthStatus
+                | Command::AuthRevoke
+        )
+    }
+
+    /// コマンドの定義を返す。
+    pub fn spec(&self) -> &'static CommandSpec {
+        COMMANDS.iter().find(|spec| spec.command == *self).unwrap()
+    }
+}
+
+/// オプションの定義。
+pub struct OptionSpec {
+    pub name: &'static str,          // 長い名前（先頭の"--"を除く）
+    pub short: Option<char>,         // 短い名前（先頭の"-"を除く）
+    pub value: Option<&'static str>, // 値の表示名。値を取らない場合は`None`
+    pub help_id: &'static str,       // 説明のFluentメッセージID
+}
+
+/// コマンドの定義。
+pub struct CommandSpec {
+    pub command: Command,                   // コマンド
+    pub name: &'static str,                 // コマンド名（サブコマンドは空白で区切る）
+    pub arguments: &'static [&'static str], // 必須の位置引数の表示名
+    pub options: &'static [OptionSpec],     // コマンド固有のオプション
+    pub help_id: &'static str,              // 説明のFluentメッセージID
+}
+
+/// すべてのコマンドで使用できるオプション
+pub const GLOBAL_OPTIONS: &[OptionSpec] = &[
+    OptionSpec {
+        name: "config",
+        short: None,
+        value: Some("PATH"),
+        help_id: "app-usage-config",
+    },
+    OptionSpec {
+        name: "account",
+        short: None,
+        value: Some("NAME"),
+        help_id: "app-usage-account",
+    },
+    OptionSpec {
+        name: "addressbook",
+        short: None,
+        value: Some("PATH"),
+        help_id: "app-usage-addressbook",
+    },
+    OptionSpec {
+        name: "verbose",
+        short: Some('v'),
+        value: None,
+        help_id: "app-usage-verbose",
+    },
+    OptionSpec {
+        name: "quiet",
+        short: Some('q'),
+        value: None,
+        help_id: "app-usage-quiet",
+    },
+    OptionSpec {
+        name: "yes",
+        short: Some('y'),
+        value: None,
+        help_id: "app-usage-yes",
+    },
+    OptionSpec {
+        name: "strict",
+        short: None,
+        value: None,
+        help_id: "app-usage-strict",
+    },
+    OptionSpec {
+        name: "offline",
+        short: None,
+        value: None,
+        help_id: "app-usage-offline",
+    },
+    OptionSpec {
+        name: "help",
+        short: Some('h'),
+        value: None,
+        help_id: "app-usage-help",
+    },
+];
+
+/// 実行結果の報告を出力するオプション
+const REPORT_OPTION: OptionSpec = OptionSpec {
+    name: "report",
+    short: None,
+    value: Some("FORMAT"),
+    help_id: "app-usage-report",
+};
+
+/// 実行結果の報告を書き込むファイルを指定するオプション
+const REPORT_FILE_OPTION: OptionSpec = OptionSpec {
+    name: "report-file",
+    short: None,
+    value: Some("PATH"),
+    help_id: "app-usage-report-file",
+};
+
+/// 連絡先のフィールドと実行結果の報告を指定するオプション（addとedit）
+const CONTACT_OPTIONS: &[OptionSpec] = &[
+    OptionSpec {
+        name: "name",
+        short: None,
+        value: Some("NAME"),
+        help_id: "app-usage-contact-name",
+    },
+    OptionSpec {
+        name: "email",
+        short: None,
+        value: Some("EMAIL"),
+        help_id: "app-usage-contact-email",
+    },
+    OptionSpec {
+        name: "nick",
+        short: None,
+        value: Some("NICK"),
+        help_id: "app-usage-contact-nick",
+    },
+    OptionSpec {
+        name: "fcc",
+        short: None,
+        value: Some("FCC"),
+        help_id: "app-usage-contact-fcc",
+    },
+    OptionSpec {
+        name: "comment",
+        short: None,
+        value: Some("TEXT"),
+        help_id: "app-usage-contact-comment",
+    },
+    REPORT_OPTION,
+    REPORT_FILE_OPTION,
+];
+
+/// コマンドの一覧（ヘルプに表示する順）
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        command: Command::Init,
+        name: "init",
+        arguments: &[],
+        options: &[
+            OptionSpec {
+                name: "merge",
+                short: None,
+                value: None,
+                help_id: "app-usage-init-merge",
+            },
+            REPORT_OPTION,
+            REPORT_FILE_OPTION,
+        ],
+        help_id: "app-usage-init",
+    },
+    CommandSpec {
+        command: Command::Sync,
+        name: "sync",
+        arguments: &[],
+        options: &[
+            OptionSpec {
+                name: "tui",
+                short: None,
+                value: None,
+                help_id: "app-usage-sync-tui",
+            },
+            REPORT_OPTION,
+            REPORT_FILE_OPTION,
+        ],
+        help_id: "app-usage-sync",
+    },
+    CommandSpec {
+        command: Command::Diff,
+        name: "diff",
+        arguments: &[],
+        options: &[OptionSpec {
+            name: "format",
+            short: None,
+            value: Some("FORMAT"),
+            help_id: "app-usage-diff-format",
+        }],
+        help_id: "app-usage-diff",
+    },
+    CommandSpec {
+        command: Command::Status,
+        name: "status",
+        arguments: &[],
+        options: &[],
+        help_id: "app-usage-status",
+    },
+    CommandSpec {
+        command: Command::Add,
+        name: "add",
+        arguments: &[],
+        options: CONTACT_OPTIONS,
+        help_id: "app-usage-add",
+    },
+    CommandSpec {
+        command: Command::Edit,
+        name: "edit",
+        arguments: &["NICK"],
+        options: CONTACT_OPTIONS,
+        help_id: "app-usage-edit",
+    },
+    CommandSpec {
+        command: Command::Remove,
+        name: "rm",
+        arguments: &["NICK"],
+        options: &[REPORT_OPTION, REPORT_FILE_OPTION],
+        help_id: "app-usage-rm",
+    },
+    CommandSpec {
+        command: Command::Show,
+        name: "show",
+        arguments: &["NICK"],
+        options: &[],
+        help_id: "app-usage-show",
+    },
+    CommandSpec {
+        command: Command::Query,
+        name: "query",
+        arguments: &["STRING"],
+        options: &[],
+        help_id: "app-usage-query",
+    },
+    CommandSpec {
+        command: Command::Refresh,
+        name: "refresh",
+        arguments: &[],
+        options: &[],
+        help_id: "app-usage-refresh",
+    },
+    CommandSpec {
+        command: Command::Export,
+        name: "export",
+        arguments: &[],
+        options: &[
+            OptionSpec {
+                name: "format",
+                short: None,
+                value: Some("FORMAT"),
+                help_id: "app-usage-export-format",
+            },
+            OptionSpec {
+                name: "vcard-version",
+                short: None,
+                value: Some("VERSION"),
+                help_id: "app-usage-export-vcard-version",
+            },
+            OptionSpec {
+                name: "output",
+                short: Some('o'),
+                value: Some("PATH"),
+                help_id: "app-usage-export-output",
+            },
+            OptionSpec {
+                name: "output-dir",
+                short: None,
+                value: Some("DIR"),
+                help_id: "app-usage-export-output-dir",
+            },
+            OptionSpec {
+                name: "all",
+                short: None,
+                value: None,
+                help_id: "app-usage-export-all",
+            },
+        ],
+        help_id: "app-usage-export",
+    },
+    CommandSpec {
+        command: Command::Import,
+        name: "import",
+        arguments: &["FILE"],
+        options: &[
+            OptionSpec {
+                name: "format",
+                short: None,
+                value: Some("FORMAT"),
+                help_id: "app-usage-import-format",
+            },
+            OptionSpec {
+                name: "update",
+                short: None,
+                value: None,
+                help_id: "app-usage-import-update",
+            },
+            REPORT_OPTION,
+            REPORT_FILE_OPTION,
+        ],
+        help_id: "app-usage-import",
+    },
+    CommandSpec {
+        command: Command::AuthStatus,
+        name: "auth status",
+        arguments: &[],
+        options: &[],
+        help_id: "app-usage-auth-status",
+    },
+    CommandSpec {
+        command: Command::AuthRevoke,
+        name: "auth revoke",
+        arguments: &[],
+        options: &[],
+        help_id: "app-usage-auth-revoke",
+    },
+    CommandSpec {
+        command: Command::ConfigShow,
+        name: "config show",
+        arguments: &[],
+        options: &[],
+        help_id: "app-usage-config-show",
+    },
+];
+
+/// すべてのコマンドで使用できるオプションの値。
+#[derive(Default)]
+pub struct GlobalOptions {
+    pub config: Option<PathBuf>,      // 設定ファイルのパス
+    pub account: Option<String>,      // アカウント名
+    pub addressbook: Option<PathBuf>, // アドレス帳のパス
+    pub verbose: bool,                // 詳細なメッセージを表示する
+    pub quiet: bool,                  // エラー以外のメッセージを表示しない
+    pub yes: bool,                    // 確認をすべて「はい」として扱う
+    pub strict: bool,                 // パーミッションに問題がある場合にエラーとする
+    pub offline: bool,                // Google Contactsにアクセスせず、キャッシュを使用する
+}
+
+/// 解析したコマンドライン。
+pub struct Cli {
+    pub command: Option<Command>, // コマンド（ヘルプの表示のみの場合は`None`の場合がある）
+    pub help: bool,               // ヘルプを表示する
+    pub global: GlobalOptions,    // グローバルオプション
+    pub arguments: Vec<String>,   // 位置引数
+    options: BTreeMap<&'static str, Option<String>>, // コマンド固有のオプション
+}
+
+impl Cli {
+    /// 値を取らないコマンド固有のオプションが指定されたかどうかを返す。
+    pub fn flag(&self, name: &str) -> bool {
+        self.options.contains_key(name)
+    }
+
+    /// 値を取るコマンド固有のオプションの値を返す。
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.options.get(name).and_then(|v| v.as_deref())
+    }
+}
+
+/// コマンドライン引数の解析エラー。
+#[derive(Debug, PartialEq, Eq)]
+pub enum CliError {
+    MissingCommand,                     // コマンドが指定されていない
+    IncompleteCommand(String),          // サブコマンドが指定されていない
+    UnknownCommand(String),             // 不明なコマンド
+    UnknownOption(String),              // 不明なオプション
+    MissingValue(String),               // オプションの値が指定されていない
+    UnexpectedValue(String),            // 値を取らないオプションに値が指定された
+    MissingArgument(String),            // 必須の位置引数が指定されていない
+    UnexpectedArgument(String),         // 余分な位置引数
+    ConflictingOptions(String, String), // 同時に指定できないオプション
+}
+
+impl CliError {
+    /// エラーメッセージのFluentメッセージIDを返す。
+    pub fn message_id(&self) -> &'static str {
+        match self {
+            CliError::MissingCommand => "no-option",
+            CliError::IncompleteCommand(_) => "cli-incomplete-command",
+            CliError::UnknownCommand(_) => "cli-unknown-command",
+            CliError::UnknownOption(_) => "cli-unknown-option",
+            CliError::MissingValue(_) => "cli-missing-value",
+            CliError::UnexpectedValue(_) => "cli-unexpected-value",
+            CliError::MissingArgument(_) => "cli-missing-argument",
+            CliError::UnexpectedArgument(_) => "cli-unexpected-argument",
+            CliError::ConflictingOptions(_, _) => "cli-conflicting-options",
+        }
+    }
+
+    /// エラーの対象（コマンド名、オプション名など）を返す。
+    pub fn detail(&self) -> Option<String> {
+        match self {
+            CliError::MissingCommand => None,
+            CliError::IncompleteCommand(s)
+            | CliError::UnknownCommand(s)
+            | CliError::UnknownOption(s)
+            | CliError::MissingValue(s)
+            | CliError::UnexpectedValue(s)
+            | CliError::MissingArgument(s)
+            | CliError::UnexpectedArgument(s) => Some(s.clone()),
+            CliError::ConflictingOptions(a, b) => Some(format!("{} {}", a, b)),
+        }
+    }
+}
+
+/// オプションの表示名（"--name VALUE"）を返す。
+pub fn option_usage(option: &OptionSpec) -> String {
+    let mut usage = String::new();
+    if let Some(short) = option.short {
+        usage.push_str(&format!("-{}, ", short));
+    }
+    usage.push_str("--");
+    usage.push_str(option.name);
+    if let Some(value) = option.value {
+        usage.push(' ');
+        usage.push_str(value);
+    }
+    usage
+}
+
+/// コマンドライン引数を解析する関数。
+///
+/// 最初の位置引数（`auth`と`config`は2つ目まで）をコマンドとして解釈し、残りの位置引数をコマンドの引数とします。
+/// グローバルオプションはどこにでも指定でき、コマンド固有のオプションはコマンドの後に指定します。
+/// オプションの値は`--name value`と`--name=value`のどちらでも指定できます。`--`以降はすべて位置引数として扱います。
+/// `help`コマンド、`--help`、`-h`が指定された場合は、コマンドや引数の不足をエラーにしません。
+///
+/// # 引数
+/// * `args` - プログラム名を除いたコマンドライン引数。
+///
+/// # 戻り値
+/// 成功した場合は解析したコマンドライン、不明なコマンドやオプションがある場合はエラー。
+pub fn parse(args: &[String]) -> Result<Cli, CliError> {
+    let mut cli = Cli {
+        command: None,
+        help: false,
+        global: GlobalOptions::default(),
+        arguments: Vec::new(),
+        options: BTreeMap::new(),
+    };
+    let mut spec: Option<&'static CommandSpec> = None;
+    let mut words: Vec<&str> = Vec::new(); // 解釈中のコマンド名
+    let mut only_positional = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        // オプション
+        if !only_positional && arg == "--" {
+            only_positional = true;
+            continue;
+        }
+        if !only_positional && arg.starts_with('-') && arg.len() > 1 {
+            let (option, inline_value) = find_option(arg, spec)?;
+            let value = match (option.value, inline_value) {
+                (Some(_), Some(v)) => Some(v),
+                (Some(_), None) => match iter.next() {
+                    Some(v) => Some(v.clone()),
+                    None => return Err(CliError::MissingValue(arg.clone())),
+                },
+                (None, Some(_)) => return Err(CliError::UnexpectedValue(arg.clone())),
+                (None, None) => None,
+            };
+            apply_option(&mut cli, option, value);
+            continue;
+        }
+
+        // コマンドが決まっていれば、コマンドの引数
+        if spec.is_some() {
+            cli.arguments.push(arg.clone());
+            continue;
+        }
+
+        // 先頭の"help"はヘルプの表示
+        if words.is_empty() && !cli.help && arg == "help" {
+            cli.help = true;
+            continue;
+        }
+
+        // コマンド名を解釈する
+        words.push(arg);
+        let name = words.join(" ");
+        if let Some(found) = COMMANDS.iter().find(|c| c.name == name) {
+            spec = Some(found);
+            cli.command = Some(found.command);
+            words.clear();
+        } else if !COMMANDS
+            .iter()
+            .any(|c| c.name.starts_with(&format!("{} ", name)))
+        {
+            return Err(CliError::UnknownCommand(name));
+        }
+    }
+
+    // ヘルプの表示では、コマンドや引数の不足をエラーにしない
+    if cli.help {
+        return Ok(cli);
+    }
+
+    let spec = match spec {
+        Some(spec) => spec,
+        None if words.is_empty() => return Err(CliError::MissingCommand),
+        None => return Err(CliError::IncompleteCommand(words.join(" "))),
+    };
+
+    // 位置引数の数を確認する
+    if cli.arguments.len() < spec.arguments.len() {
+        return Err(CliError::MissingArgument(
+            spec.arguments[cli.arguments.len()].to_string(),
+        ));
+    }
+    if let Some(extra) = cli.arguments.get(spec.arguments.len()) {
+        return Err(CliError::UnexpectedArgument(extra.clone()));
+    }
+
+    if cli.global.verbose && cli.global.quiet {
+        return Err(CliError::ConflictingOptions(
+            "--verbose".to_string(),
+            "--quiet".to_string(),
+        ));
+    }
+    if cli.options.contains_key("output") && cli.options.contains_key("output-dir") {
+        return Err(CliError::ConflictingOptions(
+            "--output".to_string(),
+            "--output-dir".to_string(),
+        ));
+    }
+
+    Ok(cli)
+}
+
+/// オプションの定義を探す。
+///
+/// # 戻り値
+/// オプションの定義と、`--name=value`形式で指定された値のタプル。見つからない場合はエラー。
+fn find_option(
+    arg: &str,
+    spec: Option<&'static CommandSpec>,
+) -> Result<(&'static OptionSpec, Option<String>), CliError> {
+    let command_options = spec.map(|s| s.options).unwrap_or(&[]);
+    let mut candidates = GLOBAL_OPTIONS.iter().chain(command_options.iter());
+
+    let found = if let Some(long) = arg.strip_prefix("--") {
+        let (name, value) = match long.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (long, None),
+        };
+        candidates.find(|o| o.name == name).map(|o| (o, value))
+    } else {
+        let short = arg.strip_prefix('-').unwrap_or(arg);
+        let mut chars = short.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => candidates.find(|o| o.short == Some(c)).map(|o| (o, None)),
+            _ => None,
+        }
+    };
+
+    found.ok_or_else(|| CliError::UnknownOption(arg.to_string()))
+}
+
+/// オプションの値をコマンドラインに反映する。
+fn apply_option(cli: &mut Cli, option: &'static OptionSpec, value: Option<String>) {
+    match option.name {
+        "config" => cli.global.config = value.map(PathBuf::from),
+        "account" => cli.global.account = value,
+        "addressbook" => cli.global.addressbook = value.map(PathBuf::from),
+        "verbose" => cli.global.verbose = true,
+        "quiet" => cli.global.quiet = true,
+        "yes" => cli.global.yes = true,
+        "strict" => cli.global.strict = true,
+        "offline" => cli.global.offline = true,
+        "help" => cli.help = true,
+        name => {
+            cli.options.insert(name, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 文字列のスライスを解析する。
+    fn parse_args(args: &[&str]) -> Result<Cli, CliError> {
+        parse(&args.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn parses_commands_arguments_and_options() {
+        let cli = parse_args(&["-v", "edit", "--name=Jane Smith", "smith", "--yes"]).unwrap();
+        assert_eq!(cli.command, Some(Command::Edit));
+        assert_eq!(cli.arguments, vec!["smith"]);
+        assert_eq!(cli.value("name"), Some("Jane Smith"));
+        assert!(cli.global.verbose);
+        assert!(cli.global.yes);
+
+        let cli = parse_args(&["auth", "status", "--account", "work"]).unwrap();
+        assert_eq!(cli.command, Some(Command::AuthStatus));
+        assert_eq!(cli.global.account.as_deref(), Some("work"));
+
+        let cli = parse_args(&["init", "--merge"]).unwrap();
+        assert!(cli.flag("merge"));
+        assert!(!cli.flag("tui"));
+
+        // "--"以降は位置引数
+        let cli = parse_args(&["query", "--", "-v"]).unwrap();
+        assert_eq!(cli.arguments, vec!["-v"]);
+        assert!(!cli.global.verbose);
+    }
+
+    #[test]
+    fn help_does_not_require_a_complete_command() {
+        assert!(parse_args(&["help"]).unwrap().help);
+        let cli = parse_args(&["edit", "--help"]).unwrap();
+        assert!(cli.help);
+        assert_eq!(cli.command, Some(Command::Edit));
+        assert!(parse_args(&["auth", "-h"]).unwrap().help);
+    }
+
+    #[test]
+    fn reports_invalid_command_lines() {
+        let error = |args: &[&str]| parse_args(args).err().unwrap();
+
+        assert_eq!(error(&[]), CliError::MissingCommand);
+        assert_eq!(
+            error(&["auth"]),
+            CliError::IncompleteCommand("auth".to_string())
+        );
+        assert_eq!(
+            error(&["frobnicate"]),
+            CliError::UnknownCommand("frobnicate".to_string())
+        );
+        // コマンド固有のオプションはコマンドの後にのみ指定できる
+        assert_eq!(
+            error(&["--merge", "init"]),
+            CliError::UnknownOption("--merge".to_string())
+        );
+        assert_eq!(
+            error(&["init", "--tui"]),
+            CliError::UnknownOption("--tui".to_string())
+        );
+        assert_eq!(
+            error(&["query", "--config"]),
+            CliError::MissingValue("--config".to_string())
+        );
+        assert_eq!(
+            error(&["sync", "--yes=no"]),
+            CliError::UnexpectedValue("--yes=no".to_string())
+        );
+        assert_eq!(
+            error(&["edit"]),
+            CliError::MissingArgument("NICK".to_string())
+        );
+        assert_eq!(
+            error(&["show", "a", "b"]),
+            CliError::UnexpectedArgument("b".to_string())
+        );
+        assert_eq!(
+            error(&["status", "-v", "-q"]),
+            CliError::ConflictingOptions("--verbose".to_string(), "--quiet".to_string())
+        );
+    }
+
+    #[test]
+    fn every_command_name_is_parsed_to_its_command() {
+        for spec in COMMANDS {
+            let mut args: Vec<&str> = spec.name.split(' ').collect();
+            args.extend(spec.arguments.iter().map(|_| "x"));
+            assert_eq!(parse_args(&args).unwrap().command, Some(spec.command));
+        }
+    }
+
+    #[test]
+    fn commands_that_change_google_contacts_request_read_write_access() {
+        for command in [
+            Command::Sync,
+            Command::Add,
+            Command::Edit,
+            Command::Remove,
+            Command::Import,
+        ] {
+            assert!(matches!(command.access_level(), AccessLevel::ReadWrite));
+        }
+        for command in [Command::Init, Command::Diff, Command::Export] {
+            assert!(matches!(command.access_level(), AccessLevel::ReadOnly));
+        }
+    }
+
+    #[test]
+    fn commands_that_record_changes_accept_report_options() {
+        for command in [
+            Command::Init,
+            Command::Sync,
+            Command::Add,
+            Command::Edit,
+            Command::Remove,
+            Command::Import,
+        ] {
+            let spec = command.spec();
+            for option in ["report", "report-file"] {
+                assert!(
+                    spec.options.iter().any(|o| o.name == option),
+                    "{} --{}",
+                    spec.name,
+                    option
+                );
+            }
+        }
+
+        let cli = parse_args(&["rm", "smith", "--report-file", "out.json"]).unwrap();
+        assert_eq!(cli.value("report-file"), Some("out.json"));
+    }
+}
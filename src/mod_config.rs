@@ -0,0 +1,871 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! 設定ファイル（config.toml）を読み込み、既定値と組み合わせた設定を提供する
+
+use crate::mod_dirs; // 設定ファイルとアドレス帳の既定の場所を取得するため
+use crate::mod_locale; // ロケールの形式を検証するため
+use crate::mod_token_storage::TokenStorageKind; // トークンの保存先の種類
+use serde::Deserialize; // 設定ファイルの内容を読み込むため
+use std::fmt; // 設定の表示とエラーメッセージのため
+use std::path::{Path, PathBuf}; // ファイルパスを扱うため
+use std::str::FromStr; // 文字列から設定値に変換するため
+use toml::{Spanned, Value}; // 設定ファイルの値とその位置を扱うため
+
+/// 設定ファイルの名前
+const CONFIG_FILE: &str = "config.toml";
+/// ニックネームのテンプレートで使用できるプレースホルダー
+const NICKNAME_PLACEHOLDERS: [&str; 3] = ["{first}", "{last}", "{name}"];
+/// LDIFのDNのテンプレートで使用できるプレースホルダー
+const LDIF_DN_PLACEHOLDERS: [&str; 3] = ["{cn}", "{mail}", "{uid}"];
+
+/// 同期時に両方のデータが異なる場合の扱い。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConflictPolicy {
+    Ask,         // どちらを優先するか毎回確認する
+    Google,      // 常にGoogle Contactsを優先する
+    AddressBook, // 常に.addressbookを優先する
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ask" => Ok(ConflictPolicy::Ask),
+            "google" => Ok(ConflictPolicy::Google),
+            "addressbook" => Ok(ConflictPolicy::AddressBook),
+            _ => Err(format!(
+                "expected one of ask, google, addressbook, got \"{}\"",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            ConflictPolicy::Ask => "ask",
+            ConflictPolicy::Google => "google",
+            ConflictPolicy::AddressBook => "addressbook",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// アドレス帳の名前欄に使用するGoogle Contactsのフィールド。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NameField {
+    Names,         // 名前（表示名）
+    Organizations, // 所属組織名
+}
+
+impl FromStr for NameField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "names" => Ok(NameField::Names),
+            "organizations" => Ok(NameField::Organizations),
+            _ => Err(format!("expected names or organizations, got \"{}\"", s)),
+        }
+    }
+}
+
+impl fmt::Display for NameField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            NameField::Names => "names",
+            NameField::Organizations => "organizations",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// [addressbook] アドレス帳に関する設定。
+pub struct AddressBookConfig {
+    pub path: PathBuf, // アドレス帳のパス
+}
+
+/// [google] Google Contactsへのアクセスに関する設定。
+pub struct GoogleConfig {
+    pub account: String, // アカウント名。アカウントごとにトークンを分けて保存する
+    pub page_size: i32,  // 連絡先を取得する際の1ページあたりの件数
+}
+
+/// [auth] 認証に関する設定。
+pub struct AuthConfig {
+    pub token_storage: TokenStorageKind, // トークンの保存先
+    pub strict: bool,                    // パーミッションに問題がある場合にエラーとするか
+}
+
+/// [sync] 同期に関する設定。
+pub struct SyncConfig {
+    pub conflict_policy: ConflictPolicy, // 両方のデータが異なる場合の扱い
+}
+
+/// [nickname] ニックネームの生成に関する設定。
+pub struct NicknameConfig {
+    pub template: String, // ニックネームのテンプレート
+}
+
+/// [labels] 対象とする連絡先グループ（ラベル）の設定。
+pub struct LabelsConfig {
+    pub include: Vec<String>, // 空でない場合、これらのラベルが付いた連絡先のみを対象とする
+    pub exclude: Vec<String>, // これらのラベルが付いた連絡先を対象外とする
+}
+
+/// [fields] Google Contactsのフィールドとアドレス帳の列の対応の設定。
+pub struct FieldsConfig {
+    pub name: Vec<NameField>, // 名前欄に使用するフィールド（先頭から順に使用）
+    pub comment: bool,        // コメント欄をGoogle Contactsのメモと同期するか
+    pub fcc: String,          // 新しく追加する行のFcc
+}
+
+/// [backup] アドレス帳のバックアップに関する設定。
+pub struct BackupConfig {
+    pub retention: usize, // 保持するバックアップの数（0の場合はバックアップしない）
+}
+
+/// [cache] 連絡先のキャッシュに関する設定。
+pub struct CacheConfig {
+    pub max_age: u64, // キャッシュを自動的に更新するまでの秒数（0の場合は自動的に更新しない）
+}
+
+/// [ldif] LDIFの書き出しに関する設定。
+pub struct LdifConfig {
+    pub dn: String, // エントリーのDNのテンプレート
+}
+
+/// [output] 出力に関する設定。
+pub struct OutputConfig {
+    pub locale: String, // 表示に使用するロケール（空の場合は環境変数LANGから決定）
+}
+
+/// 既定値、設定ファイル、環境変数を組み合わせた設定。
+pub struct Config {
+    pub source: Option<PathBuf>, // 読み込んだ設定ファイルのパス（無い場合は`None`）
+    pub addressbook: AddressBookConfig,
+    pub google: GoogleConfig,
+    pub auth: AuthConfig,
+    pub sync: SyncConfig,
+    pub nickname: NicknameConfig,
+    pub labels: LabelsConfig,
+    pub fields: FieldsConfig,
+    pub backup: BackupConfig,
+    pub cache: CacheConfig,
+    pub ldif: LdifConfig,
+    pub output: OutputConfig,
+}
+
+/// 設定の読み込みや検証のエラー。
+#[derive(Debug)]
+pub struct ConfigError {
+    pub path: PathBuf,       // 設定ファイルのパス
+    pub line: Option<usize>, // エラーのある行番号
+    pub message: String,     // エラーの内容
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.path.display(), line, self.message),
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// 既定の設定を作成する。
+    ///
+    /// # 戻り値
+    /// 成功した場合は既定の設定、ホームディレクトリが見つからない場合はエラー。
+    pub fn defaults() -> std::io::Result<Self> {
+        Ok(Config {
+            source: None,
+            addressbook: AddressBookConfig {
+                path: mod_dirs::addressbook_path()?,
+            },
+            google: GoogleConfig {
+                account: String::new(),
+                page_size: 1000,
+            },
+            auth: AuthConfig {
+                token_storage: TokenStorageKind::File,
+                strict: false,
+            },
+            sync: SyncConfig {
+                conflict_policy: ConflictPolicy::Ask,
+            },
+            nickname: NicknameConfig {
+                template: "{last}".to_string(),
+            },
+            labels: LabelsConfig {
+                include: Vec::new(),
+                exclude: Vec::new(),
+            },
+            fields: FieldsConfig {
+                name: vec![NameField::Names, NameField::Organizations],
+                comment: true,
+                fcc: String::new(),
+            },
+            backup: BackupConfig { retention: 5 },
+            cache: CacheConfig { max_age: 86400 },
+            ldif: LdifConfig {
+                dn: "cn={cn},mail={mail}".to_string(),
+            },
+            output: OutputConfig {
+                locale: String::new(),
+            },
+        })
+    }
+
+    /// コマンドラインで指定された値で設定を上書きする。
+    ///
+    /// # 引数
+    /// * `account` - `--account`で指定されたアカウント名。
+    /// * `addressbook` - `--addressbook`で指定されたアドレス帳のパス。
+    /// * `strict` - `--strict`が指定されたかどうか。
+    ///
+    /// # 戻り値
+    /// 成功した場合はOk(())、アカウント名が不正な場合はエラーメッセージ。
+    pub fn apply_overrides(
+        &mut self,
+        account: Option<&str>,
+        addressbook: Option<&Path>,
+        strict: bool,
+    ) -> Result<(), String> {
+        if let Some(account) = account {
+            validate_account(account).map_err(|e| format!("--account: {}", e))?;
+            self.google.account = account.to_string();
+        }
+        if let Some(addressbook) = addressbook {
+            self.addressbook.path = addressbook.to_path_buf();
+        }
+        if strict {
+            self.auth.strict = true;
+        }
+        Ok(())
+    }
+
+    /// Google People APIから取得するフィールドのマスクを返す。
+    ///
+    /// 同期に使用するフィールドに加えて、書き出しに使用する電話番号、住所、誕生日、連絡先グループも取得します。
+    /// 取得した連絡先はキャッシュされ、`--offline`での書き出しにも使用されます。
+    pub fn person_fields() -> String {
+        [
+            "nicknames",
+            "names",
+            "organizations",
+            "emailAddresses",
+            "biographies",
+            "memberships",
+            "phoneNumbers",
+            "addresses",
+            "birthdays",
+        ]
+        .join(",")
+    }
+}
+
+impl fmt::Display for Config {
+    /// 設定をTOMLの形式で出力する。
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let string = |s: &str| Value::String(s.to_string());
+        let strings = |v: &[String]| Value::Array(v.iter().map(|s| string(s)).collect());
+
+        match &self.source {
+            Some(path) => writeln!(f, "# {}", path.display())?,
+            None => writeln!(f, "# (defaults)")?,
+        }
+        writeln!(f, "\n[addressbook]")?;
+        writeln!(
+            f,
+            "path = {}",
+            string(&self.addressbook.path.to_string_lossy())
+        )?;
+        writeln!(f, "\n[google]")?;
+        writeln!(f, "account = {}", string(&self.google.account))?;
+        writeln!(f, "page_size = {}", self.google.page_size)?;
+        writeln!(f, "\n[auth]")?;
+        writeln!(
+            f,
+            "token_storage = {}",
+            string(self.auth.token_storage.as_str())
+        )?;
+        writeln!(f, "strict = {}", self.auth.strict)?;
+        writeln!(f, "\n[sync]")?;
+        writeln!(
+            f,
+            "conflict_policy = {}",
+            string(&self.sync.conflict_policy.to_string())
+        )?;
+        writeln!(f, "\n[nickname]")?;
+        writeln!(f, "template = {}", string(&self.nickname.template))?;
+        writeln!(f, "\n[labels]")?;
+        writeln!(f, "include = {}", strings(&self.labels.include))?;
+        writeln!(f, "exclude = {}", strings(&self.labels.exclude))?;
+        writeln!(f, "\n[fields]")?;
+        let names: Vec<String> = self.fields.name.iter().map(|n| n.to_string()).collect();
+        writeln!(f, "name = {}", strings(&names))?;
+        writeln!(
+            f,
+            "comment = {}",
+            string(if self.fields.comment {
+                "biographies"
+            } else {
+                "none"
+            })
+        )?;
+        writeln!(f, "fcc = {}", string(&self.fields.fcc))?;
+        writeln!(f, "\n[backup]")?;
+        writeln!(f, "retention = {}", self.backup.retention)?;
+        writeln!(f, "\n[cache]")?;
+        writeln!(f, "max_age = {}", self.cache.max_age)?;
+        writeln!(f, "\n[ldif]")?;
+        writeln!(f, "dn = {}", string(&self.ldif.dn))?;
+        writeln!(f, "\n[output]")?;
+        write!(f, "locale = {}", string(&self.output.locale))
+    }
+}
+
+/// 既定の設定ファイルのパスを取得する関数。
+///
+/// # 戻り値
+/// 成功した場合は設定ディレクトリの`config.toml`のパス、ホームディレクトリが見つからない場合はエラー。
+pub fn default_config_path() -> std::io::Result<PathBuf> {
+    Ok(mod_dirs::config_dir()?.join(CONFIG_FILE))
+}
+
+/// 設定を読み込む関数。
+///
+/// 既定値に設定ファイルの内容を上書きし、さらに環境変数`GCONTACTS_TOKEN_STORAGE`で上書きします。
+/// `path`が`None`の場合は既定の設定ファイルを使用し、存在しない場合は既定値のみを使用します。
+/// `path`が指定された場合、そのファイルが存在しなければエラーになります。
+///
+/// # 引数
+/// * `path` - 設定ファイルのパス。
+///
+/// # 戻り値
+/// 成功した場合は設定、ファイルの読み込み、構文、値の検証に失敗した場合はエラー。
+pub fn load(path: Option<&Path>) -> Result<Config, ConfigError> {
+    let (config_path, required) = match path {
+        Some(p) => (p.to_path_buf(), true),
+        None => (
+            default_config_path().map_err(|e| ConfigError {
+                path: PathBuf::from(CONFIG_FILE),
+                line: None,
+                message: e.to_string(),
+            })?,
+            false,
+        ),
+    };
+    let error = |line: Option<usize>, message: String| ConfigError {
+        path: config_path.clone(),
+        line,
+        message,
+    };
+
+    let mut config = Config::defaults().map_err(|e| error(None, e.to_string()))?;
+
+    match std::fs::read_to_string(&config_path) {
+        Ok(contents) => {
+            apply_contents(&mut config, &contents)
+                .map_err(|(line, message)| error(line, message))?;
+            config.source = Some(config_path.clone());
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && !required => {}
+        Err(e) => return Err(error(None, e.to_string())),
+    }
+
+    // 環境変数による上書き
+    if let Ok(kind) = std::env::var("GCONTACTS_TOKEN_STORAGE") {
+        if !kind.is_empty() {
+            config.auth.token_storage = kind.parse().map_err(|e| ConfigError {
+                path: PathBuf::from("GCONTACTS_TOKEN_STORAGE"),
+                line: None,
+                message: e,
+            })?;
+        }
+    }
+
+    Ok(config)
+}
+
+/// 設定ファイルの内容。
+///
+/// 各値は、検証に失敗した場合に行番号を示せるように、ファイル内の位置とともに読み込みます。
+/// 知らないテーブルやキーはエラーにします。
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    addressbook: AddressBookFile,
+    google: GoogleFile,
+    auth: AuthFile,
+    sync: SyncFile,
+    nickname: NicknameFile,
+    labels: LabelsFile,
+    fields: FieldsFile,
+    backup: BackupFile,
+    cache: CacheFile,
+    ldif: LdifFile,
+    output: OutputFile,
+}
+
+/// 設定ファイルの[addressbook]テーブル。
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct AddressBookFile {
+    path: Option<Spanned<String>>,
+}
+
+/// 設定ファイルの[google]テーブル。
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct GoogleFile {
+    account: Option<Spanned<String>>,
+    page_size: Option<Spanned<i64>>,
+}
+
+/// 設定ファイルの[auth]テーブル。
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct AuthFile {
+    token_storage: Option<Spanned<String>>,
+    strict: Option<bool>,
+}
+
+/// 設定ファイルの[sync]テーブル。
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct SyncFile {
+    conflict_policy: Option<Spanned<String>>,
+}
+
+/// 設定ファイルの[nickname]テーブル。
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct NicknameFile {
+    template: Option<Spanned<String>>,
+}
+
+/// 設定ファイルの[labels]テーブル。
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct LabelsFile {
+    include: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
+}
+
+/// 設定ファイルの[fields]テーブル。
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct FieldsFile {
+    name: Option<Spanned<Vec<String>>>,
+    comment: Option<Spanned<String>>,
+    fcc: Option<String>,
+}
+
+/// 設定ファイルの[backup]テーブル。
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct BackupFile {
+    retention: Option<Spanned<i64>>,
+}
+
+/// 設定ファイルの[cache]テーブル。
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct CacheFile {
+    max_age: Option<Spanned<i64>>,
+}
+
+/// 設定ファイルの[ldif]テーブル。
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct LdifFile {
+    dn: Option<Spanned<String>>,
+}
+
+/// 設定ファイルの[output]テーブル。
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct OutputFile {
+    locale: Option<Spanned<String>>,
+}
+
+/// 設定ファイルの内容を解析し、検証して設定に反映する関数。
+///
+/// # 引数
+/// * `config` - 反映先の設定。
+/// * `contents` - 設定ファイルの内容。
+///
+/// # 戻り値
+/// 成功した場合はOk(())、構文の誤り、不明なキー、不正な値がある場合は行番号とエラーメッセージ。
+fn apply_contents(config: &mut Config, contents: &str) -> Result<(), (Option<usize>, String)> {
+    // バイト位置を行番号（1から始まる）に変換する
+    let line_of = |offset: usize| contents[..offset.min(contents.len())].matches('\n').count() + 1;
+
+    let file: ConfigFile = toml::from_str(contents).map_err(|e| {
+        (
+            e.span().map(|span| line_of(span.start)),
+            e.message().to_string(),
+        )
+    })?;
+
+    // 値の位置とキーの名前を付けたエラーを作成する
+    let fail = |name: &str, value_start: usize, message: String| {
+        (Some(line_of(value_start)), format!("{}: {}", name, message))
+    };
+
+    if let Some(path) = &file.addressbook.path {
+        config.addressbook.path = expand_tilde(path.get_ref())
+            .map_err(|e| fail("addressbook.path", path.span().start, e))?;
+    }
+
+    if let Some(account) = &file.google.account {
+        validate_account(account.get_ref())
+            .map_err(|e| fail("google.account", account.span().start, e))?;
+        config.google.account = account.get_ref().clone();
+    }
+    if let Some(page_size) = &file.google.page_size {
+        let value = *page_size.get_ref();
+        if !(1..=1000).contains(&value) {
+            return Err(fail(
+                "google.page_size",
+                page_size.span().start,
+                format!("must be between 1 and 1000, got {}", value),
+            ));
+        }
+        config.google.page_size = value as i32;
+    }
+
+    if let Some(token_storage) = &file.auth.token_storage {
+        config.auth.token_storage = token_storage
+            .get_ref()
+            .parse()
+            .map_err(|e| fail("auth.token_storage", token_storage.span().start, e))?;
+    }
+    if let Some(strict) = file.auth.strict {
+        config.auth.strict = strict;
+    }
+
+    if let Some(policy) = &file.sync.conflict_policy {
+        config.sync.conflict_policy = policy
+            .get_ref()
+            .parse()
+            .map_err(|e| fail("sync.conflict_policy", policy.span().start, e))?;
+    }
+
+    if let Some(template) = &file.nickname.template {
+        validate_template(template.get_ref(), &NICKNAME_PLACEHOLDERS)
+            .map_err(|e| fail("nickname.template", template.span().start, e))?;
+        config.nickname.template = template.get_ref().clone();
+    }
+
+    if let Some(include) = &file.labels.include {
+        config.labels.include = include.clone();
+    }
+    if let Some(exclude) = &file.labels.exclude {
+        config.labels.exclude = exclude.clone();
+    }
+
+    if let Some(names) = &file.fields.name {
+        let mut fields = Vec::new();
+        for s in names.get_ref() {
+            fields.push(
+                s.parse::<NameField>()
+                    .map_err(|e| fail("fields.name", names.span().start, e))?,
+            );
+        }
+        if fields.is_empty() {
+            return Err(fail(
+                "fields.name",
+                names.span().start,
+                "must contain at least one field".to_string(),
+            ));
+        }
+        config.fields.name = fields;
+    }
+    if let Some(comment) = &file.fields.comment {
+        config.fields.comment = match comment.get_ref().as_str() {
+            "biographies" => true,
+            "none" => false,
+            s => {
+                return Err(fail(
+                    "fields.comment",
+                    comment.span().start,
+                    format!("expected biographies or none, got \"{}\"", s),
+                ))
+            }
+        };
+    }
+    if let Some(fcc) = &file.fields.fcc {
+        config.fields.fcc = fcc.clone();
+    }
+
+    if let Some(retention) = &file.backup.retention {
+        let value = *retention.get_ref();
+        if value < 0 {
+            return Err(fail(
+                "backup.retention",
+                retention.span().start,
+                format!("must not be negative, got {}", value),
+            ));
+        }
+        config.backup.retention = value as usize;
+    }
+
+    if let Some(max_age) = &file.cache.max_age {
+        let value = *max_age.get_ref();
+        if value < 0 {
+            return Err(fail(
+                "cache.max_age",
+                max_age.span().start,
+                format!("must not be negative, got {}", value),
+            ));
+        }
+        config.cache.max_age = value as u64;
+    }
+
+    if let Some(dn) = &file.ldif.dn {
+        validate_template(dn.get_ref(), &LDIF_DN_PLACEHOLDERS)
+            .map_err(|e| fail("ldif.dn", dn.span().start, e))?;
+        config.ldif.dn = dn.get_ref().clone();
+    }
+
+    if let Some(locale) = &file.output.locale {
+        let value = locale.get_ref();
+        if !value.is_empty() && !mod_locale::is_valid_locale_format(value) {
+            return Err(fail(
+                "output.locale",
+                locale.span().start,
+                format!(
+                    "expected a locale such as en-US or ja-JP, got \"{}\"",
+                    value
+                ),
+            ));
+        }
+        config.output.locale = value.clone();
+    }
+
+    Ok(())
+}
+
+/// 先頭の`~/`をホームディレクトリに展開する。
+fn expand_tilde(path: &str) -> Result<PathBuf, String> {
+    match path.strip_prefix("~/") {
+        Some(rest) => Ok(mod_dirs::home_dir().map_err(|e| e.to_string())?.join(rest)),
+        None if path == "~" => mod_dirs::home_dir().map_err(|e| e.to_string()),
+        None if path.is_empty() => Err("must not be empty".to_string()),
+        None => Ok(PathBuf::from(path)),
+    }
+}
+
+/// アカウント名を検証する。
+///
+/// アカウント名はトークンキャッシュのファイル名に使用するため、英数字、`-`、`_`、`.`、`@`のみを許可します。
+fn validate_account(account: &str) -> Result<(), String> {
+    if account.starts_with('.')
+        || !account
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.@".contains(c))
+    {
+        return Err(format!(
+            "may only contain letters, digits, '-', '_', '.' and '@', got \"{}\"",
+            account
+        ));
+    }
+    Ok(())
+}
+
+/// ニックネームやDNのテンプレートを検証する。
+///
+/// 少なくとも1つのプレースホルダーを含み、`placeholders`に無いプレースホルダーを含まないことを確認します。
+fn validate_template(template: &str, placeholders: &[&str]) -> Result<(), String> {
+    let mut rest = template;
+    let mut found = false;
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end + 1,
+            None => return Err("unclosed '{' in template".to_string()),
+        };
+        let placeholder = &rest[start..end];
+        if !placeholders.contains(&placeholder) {
+            return Err(format!(
+                "unknown placeholder {} (expected {})",
+                placeholder,
+                placeholders.join(", ")
+            ));
+        }
+        found = true;
+        rest = &rest[end..];
+    }
+    if !found {
+        return Err(format!(
+            "must contain at least one of {}",
+            placeholders.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// テンプレートに従ってニックネームの基本部分を作成する関数。
+///
+/// `{first}`は名前の最初の単語、`{last}`は最後の単語、`{name}`は空白を除いた名前全体に置き換えます。
+/// 名前が空の場合、各プレースホルダーは"Unknown"になります。
+///
+/// # 引数
+/// * `name` - 名前の文字列。
+/// * `template` - ニックネームのテンプレート。
+///
+/// # 戻り値
+/// ニックネームの基本部分。
+pub fn nickname_base(name: &str, template: &str) -> String {
+    let words: Vec<&str> = name.split_whitespace().collect();
+    let first = words.first().copied().unwrap_or("Unknown");
+    let last = words.last().copied().unwrap_or("Unknown");
+    let whole = if words.is_empty() {
+        "Unknown".to_string()
+    } else {
+        words.concat()
+    };
+
+    template
+        .replace("{first}", first)
+        .replace("{last}", last)
+        .replace("{name}", &whole)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// TOMLの文字列を既定の設定に反映する。
+    fn apply(input: &str) -> Result<Config, (Option<usize>, String)> {
+        let mut config = Config::defaults().unwrap();
+        apply_contents(&mut config, input)?;
+        Ok(config)
+    }
+
+    #[test]
+    fn applies_values_from_the_document() {
+        let config = apply(
+            "[google]\n\
+             account = \"work\"\n\
+             page_size = 200\n\
+             [sync]\n\
+             conflict_policy = \"google\"\n\
+             [nickname]\n\
+             template = \"{first}{last}\"\n\
+             [fields]\n\
+             name = [\"organizations\"]\n\
+             comment = \"none\"\n\
+             [backup]\n\
+             retention = 0\n",
+        )
+        .unwrap();
+
+        assert_eq!(config.google.account, "work");
+        assert_eq!(config.google.page_size, 200);
+        assert_eq!(config.sync.conflict_policy, ConflictPolicy::Google);
+        assert_eq!(config.nickname.template, "{first}{last}");
+        assert_eq!(config.fields.name, vec![NameField::Organizations]);
+        assert!(!config.fields.comment);
+        assert_eq!(config.backup.retention, 0);
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_invalid_values_with_their_line() {
+        let (line, message) = apply("[google]\naccount = \"a\"\ncolour = 1\n")
+            .err()
+            .unwrap();
+        assert_eq!(line, Some(3));
+        assert!(message.contains("colour"));
+
+        let (line, _) = apply("[colours]\nred = 1\n").err().unwrap();
+        assert_eq!(line, Some(1));
+
+        for input in [
+            "[google]\npage_size = 0\n",
+            "[google]\npage_size = \"10\"\n",
+            "[google]\naccount = \"../x\"\n",
+            "[nickname]\ntemplate = \"fixed\"\n",
+            "[nickname]\ntemplate = \"{middle}\"\n",
+            "[fields]\nname = []\n",
+            "[backup]\nretention = -1\n",
+            "[output]\nlocale = \"japanese\"\n",
+            "[labels]\ninclude = [1]\n",
+            "[google\n",
+        ] {
+            assert!(apply(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn reports_the_line_of_an_invalid_value() {
+        let (line, message) = apply("[backup]\n\nretention = -1\n").err().unwrap();
+        assert_eq!(line, Some(3));
+        assert!(message.starts_with("backup.retention"));
+    }
+
+    #[test]
+    fn accepts_any_valid_toml() {
+        let config = apply(
+            "google = { account = \"work\", page_size = 50 }\n\
+             [labels]\n\
+             include = [\n  \"Friends\",  # comment\n  'Family',\n]\n\
+             [fields]\n\
+             fcc = \"\"\"\n=sent\"\"\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(config.google.account, "work");
+        assert_eq!(config.google.page_size, 50);
+        assert_eq!(config.labels.include, vec!["Friends", "Family"]);
+        assert_eq!(config.fields.fcc, "=sent");
+    }
+
+    #[test]
+    fn displayed_config_can_be_read_back() {
+        let mut config = Config::defaults().unwrap();
+        config.labels.exclude = vec!["Old \"friends\"".to_string()];
+        let reread = apply(&config.to_string()).unwrap();
+        assert_eq!(reread.labels.exclude, config.labels.exclude);
+        assert_eq!(reread.ldif.dn, config.ldif.dn);
+    }
+
+    #[test]
+    fn nickname_base_follows_the_template() {
+        assert_eq!(nickname_base("John Ronald Tolkien", "{last}"), "Tolkien");
+        assert_eq!(nickname_base("John Ronald Tolkien", "{first}"), "John");
+        assert_eq!(
+            nickname_base("John Ronald Tolkien", "{name}"),
+            "JohnRonaldTolkien"
+        );
+        assert_eq!(nickname_base("  Ada  ", "{first}.{last}"), "Ada.Ada");
+        assert_eq!(nickname_base("", "{last}"), "Unknown");
+    }
+
+    #[test]
+    fn person_fields_include_the_fields_used_for_export() {
+        let fields = Config::person_fields();
+        for field in ["names", "emailAddresses", "memberships", "phoneNumbers"] {
+            assert!(fields.split(',').any(|f| f == field), "{}", field);
+        }
+    }
+}
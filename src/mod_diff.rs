@@ -0,0 +1,319 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! .addressbookとGoogle Contactsの差分をフィールド単位で表示する
+
+use serde_json::{json, Value}; // JSON形式で出力するため
+use std::env; // 環境変数NO_COLORを参照するため
+use std::io::{self, IsTerminal, Write}; // 差分を書き込み、標準出力が端末かどうかを判定するため
+use std::str::FromStr; // 文字列から出力形式に変換するため
+
+/// 表示するフィールドの名前（表示する順）
+///
+/// メールアドレスは連絡先の照合に使用するため、両方に存在する連絡先では常に同じ値になる。
+pub const FIELDS: [&str; 5] = ["nickname", "name", "email", "fcc", "comment"];
+
+/// 差分の出力形式。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DiffFormat {
+    Text, // 色付きのunified形式
+    Json, // JSON
+}
+
+impl FromStr for DiffFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(DiffFormat::Text),
+            "json" => Ok(DiffFormat::Json),
+            _ => Err(s.to_string()),
+        }
+    }
+}
+
+/// 比較する連絡先の値（.addressbookの1行に相当する）。
+pub struct Record {
+    pub nickname: String, // ニックネーム
+    pub name: String,     // 名前
+    pub email: String,    // メールアドレス
+    pub fcc: String,      // Fcc
+    pub comment: String,  // コメント（メモ）
+}
+
+impl Record {
+    /// `FIELDS`の順にフィールドの値を返す。
+    fn values(&self) -> [&str; 5] {
+        [
+            &self.nickname,
+            &self.name,
+            &self.email,
+            &self.fcc,
+            &self.comment,
+        ]
+    }
+
+    /// JSONの値に変換する。
+    fn to_json(&self) -> Value {
+        json!({
+            "nickname": self.nickname,
+            "name": self.name,
+            "email": self.email,
+            "fcc": self.fcc,
+            "comment": self.comment,
+        })
+    }
+}
+
+/// 1つの連絡先の差分。
+pub struct ContactDiff {
+    pub email: String,               // 照合に使用したメールアドレス
+    pub addressbook: Option<Record>, // .addressbookの値（Google Contactsにのみ存在する場合は`None`）
+    pub google: Option<Record>, // Google Contactsの値（.addressbookにのみ存在する場合は`None`）
+    pub changed: Vec<&'static str>, // 値が異なるフィールドの名前（両方に存在する場合のみ）
+}
+
+impl ContactDiff {
+    /// 差分の種類を返す。
+    fn status(&self) -> &'static str {
+        match (&self.addressbook, &self.google) {
+            (Some(_), Some(_)) => "changed",
+            (None, _) => "google_only",
+            (_, None) => "addressbook_only",
+        }
+    }
+}
+
+/// 色付きで出力するかどうかを判定する関数。
+///
+/// 標準出力が端末で、環境変数`NO_COLOR`が設定されていない場合に色付きで出力します。
+///
+/// # 戻り値
+/// 色付きで出力する場合は`true`。
+pub fn use_color() -> bool {
+    io::stdout().is_terminal() && env::var_os("NO_COLOR").unwrap_or_default().is_empty()
+}
+
+/// 差分をunified形式で書き込む関数。
+///
+/// `-`の行が.addressbook、`+`の行がGoogle Contactsの値です。連絡先ごとに`@@ メールアドレス @@`で区切ります。
+///
+/// # 引数
+/// * `out` - 書き込み先。
+/// * `diffs` - 連絡先ごとの差分。
+/// * `addressbook_label` - .addressbook側の見出し（パス）。
+/// * `color` - ANSIエスケープシーケンスで色付けするかどうか。
+///
+/// # 戻り値
+/// 成功した場合はOk(())、書き込みに失敗した場合はエラー。
+pub fn write_unified(
+    out: &mut impl Write,
+    diffs: &[ContactDiff],
+    addressbook_label: &str,
+    color: bool,
+) -> io::Result<()> {
+    if diffs.is_empty() {
+        return Ok(());
+    }
+
+    // 色付けする場合は行全体をエスケープシーケンスで囲む
+    let paint = |code: &str, line: String| {
+        if color {
+            format!("\x1b[{}m{}\x1b[0m", code, line)
+        } else {
+            line
+        }
+    };
+
+    writeln!(out, "{}", paint("1", format!("--- {}", addressbook_label)))?;
+    writeln!(out, "{}", paint("1", "+++ Google Contacts".to_string()))?;
+    for diff in diffs {
+        writeln!(out, "{}", paint("36", format!("@@ {} @@", diff.email)))?;
+        let avalues = diff.addressbook.as_ref().map(Record::values);
+        let gvalues = diff.google.as_ref().map(Record::values);
+        for (i, field) in FIELDS.iter().enumerate() {
+            match (avalues, gvalues) {
+                (Some(a), Some(g)) if diff.changed.contains(field) => {
+                    writeln!(out, "{}", paint("31", format!("-{}: {}", field, a[i])))?;
+                    writeln!(out, "{}", paint("32", format!("+{}: {}", field, g[i])))?;
+                }
+                (Some(a), Some(_)) => writeln!(out, " {}: {}", field, a[i])?,
+                (Some(a), None) => {
+                    writeln!(out, "{}", paint("31", format!("-{}: {}", field, a[i])))?
+                }
+                (None, Some(g)) => {
+                    writeln!(out, "{}", paint("32", format!("+{}: {}", field, g[i])))?
+                }
+                (None, None) => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 差分をJSONの値に変換する関数。
+///
+/// 連絡先ごとに、差分の種類（`changed`、`google_only`、`addressbook_only`）、両側のすべてのフィールドの値と、
+/// フィールドごとの差分を出力します。`fields`は、`changed`の場合は値が異なるフィールドのみを含みます。
+///
+/// # 引数
+/// * `diffs` - 連絡先ごとの差分。
+///
+/// # 戻り値
+/// 差分と件数の集計を含むJSONの値。
+pub fn to_json(diffs: &[ContactDiff]) -> Value {
+    let contacts: Vec<Value> = diffs
+        .iter()
+        .map(|diff| {
+            let avalues = diff.addressbook.as_ref().map(Record::values);
+            let gvalues = diff.google.as_ref().map(Record::values);
+            let fields: Vec<Value> = FIELDS
+                .iter()
+                .enumerate()
+                .filter(|(_, field)| {
+                    diff.addressbook.is_none()
+                        || diff.google.is_none()
+                        || diff.changed.contains(field)
+                })
+                .map(|(i, field)| {
+                    json!({
+                        "field": field,
+                        "addressbook": avalues.map(|v| v[i]),
+                        "google": gvalues.map(|v| v[i]),
+                    })
+                })
+                .collect();
+            json!({
+                "email": diff.email,
+                "status": diff.status(),
+                "addressbook": diff.addressbook.as_ref().map(Record::to_json),
+                "google": diff.google.as_ref().map(Record::to_json),
+                "fields": fields,
+            })
+        })
+        .collect();
+
+    let count = |status: &str| diffs.iter().filter(|d| d.status() == status).count();
+    json!({
+        "contacts": contacts,
+        "summary": {
+            "changed": count("changed"),
+            "google_only": count("google_only"),
+            "addressbook_only": count("addressbook_only"),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// テスト用の値を作成する。
+    fn record(name: &str, comment: &str) -> Record {
+        Record {
+            nickname: "Tanaka".to_string(),
+            name: name.to_string(),
+            email: "taro@example.com".to_string(),
+            fcc: "sent".to_string(),
+            comment: comment.to_string(),
+        }
+    }
+
+    /// 値が異なる連絡先、Google Contactsにのみ存在する連絡先、.addressbookにのみ存在する連絡先の差分。
+    fn diffs() -> Vec<ContactDiff> {
+        vec![
+            ContactDiff {
+                email: "taro@example.com".to_string(),
+                addressbook: Some(record("Tanaka Taro", "memo")),
+                google: Some(record("Taro Tanaka", "memo")),
+                changed: vec!["name"],
+            },
+            ContactDiff {
+                email: "hanako@example.com".to_string(),
+                addressbook: None,
+                google: Some(record("Hanako", "")),
+                changed: Vec::new(),
+            },
+            ContactDiff {
+                email: "jiro@example.com".to_string(),
+                addressbook: Some(record("Jiro", "")),
+                google: None,
+                changed: Vec::new(),
+            },
+        ]
+    }
+
+    #[test]
+    fn writes_a_unified_diff() {
+        let mut out = Vec::new();
+        write_unified(&mut out, &diffs()[..1], "addressbook", false).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "--- addressbook\n\
+             +++ Google Contacts\n\
+             @@ taro@example.com @@\n \
+             nickname: Tanaka\n\
+             -name: Tanaka Taro\n\
+             +name: Taro Tanaka\n \
+             email: taro@example.com\n \
+             fcc: sent\n \
+             comment: memo\n"
+        );
+    }
+
+    #[test]
+    fn writes_nothing_without_differences() {
+        let mut out = Vec::new();
+        write_unified(&mut out, &[], "addressbook", true).unwrap();
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn json_lists_only_changed_fields_of_changed_contacts() {
+        let json = to_json(&diffs());
+        assert_eq!(
+            json["summary"],
+            json!({"changed": 1, "google_only": 1, "addressbook_only": 1})
+        );
+
+        let contacts = json["contacts"].as_array().unwrap();
+        assert_eq!(contacts[0]["status"], "changed");
+        assert_eq!(
+            contacts[0]["fields"],
+            json!([{"field": "name", "addressbook": "Tanaka Taro", "google": "Taro Tanaka"}])
+        );
+        assert_eq!(contacts[1]["status"], "google_only");
+        assert_eq!(
+            contacts[1]["fields"].as_array().unwrap().len(),
+            FIELDS.len()
+        );
+        assert_eq!(contacts[2]["fields"][0]["google"], Value::Null);
+    }
+
+    #[test]
+    fn one_sided_contacts_show_their_email_and_fcc() {
+        let mut out = Vec::new();
+        write_unified(&mut out, &diffs()[2..], "addressbook", false).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("-email: taro@example.com\n"));
+        assert!(text.contains("-fcc: sent\n"));
+
+        let json = to_json(&diffs());
+        let contacts = json["contacts"].as_array().unwrap();
+        assert_eq!(contacts[0]["addressbook"]["fcc"], "sent");
+        assert_eq!(contacts[0]["google"]["email"], "taro@example.com");
+        assert_eq!(contacts[1]["addressbook"], Value::Null);
+        assert_eq!(contacts[2]["addressbook"]["email"], "taro@example.com");
+    }
+}
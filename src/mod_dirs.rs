@@ -0,0 +1,176 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! XDG Base Directory仕様に従って設定ファイルや状態ファイルの場所を決定する
+
+use crate::mod_perm; // ディレクトリをパーミッション0700で作成するため
+use std::env; // 環境変数を扱うための 'env' モジュール
+use std::fs; // ファイルシステム操作のための標準ライブラリのモジュール
+use std::io; // エラーを返すための 'io' モジュール
+use std::path::{Path, PathBuf}; // ファイルパスを扱うため
+
+/// 認証情報ファイルの名前
+const CLIENT_SECRET_FILE: &str = "client_secret.json";
+/// 状態ディレクトリに移動するファイルの名前
+const LEGACY_STATE_FILES: [&str; 2] = ["token_cache.json", "token_cache.enc"];
+
+/// ホームディレクトリを取得する関数。
+///
+/// # 戻り値
+/// 成功した場合はホームディレクトリのパス、見つからない場合はエラー。
+pub fn home_dir() -> io::Result<PathBuf> {
+    dirs::home_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Home directory not found"))
+}
+
+/// 環境変数から絶対パスを取得する関数。
+///
+/// XDG Base Directory仕様に従い、空の値や相対パスは無視します。
+///
+/// # 引数
+/// * `var` - 環境変数名。
+///
+/// # 戻り値
+/// 有効な絶対パスが設定されている場合はそのパス、それ以外は`None`。
+fn absolute_path_from_env(var: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+}
+
+/// 設定ディレクトリのパスを取得する関数。
+///
+/// 次の順に決定します。
+/// 1. 環境変数`GCONTACTS_CONFIG_DIR`
+/// 2. `$XDG_CONFIG_HOME/gcontacts`
+/// 3. `~/.config/gcontacts`
+///
+/// # 戻り値
+/// 成功した場合は設定ディレクトリのパス、ホームディレクトリが見つからない場合はエラー。
+pub fn config_dir() -> io::Result<PathBuf> {
+    if let Some(dir) = absolute_path_from_env("GCONTACTS_CONFIG_DIR") {
+        return Ok(dir);
+    }
+    let base = match absolute_path_from_env("XDG_CONFIG_HOME") {
+        Some(dir) => dir,
+        None => home_dir()?.join(".config"),
+    };
+    Ok(base.join(env!("CARGO_PKG_NAME")))
+}
+
+/// 状態ディレクトリのパスを取得する関数。
+///
+/// トークンキャッシュなど、実行のたびに更新されるファイルを保存します。次の順に決定します。
+/// 1. `$XDG_STATE_HOME/gcontacts`
+/// 2. `$XDG_DATA_HOME/gcontacts`
+/// 3. `~/.local/state/gcontacts`
+///
+/// # 戻り値
+/// 成功した場合は状態ディレクトリのパス、ホームディレクトリが見つからない場合はエラー。
+pub fn state_dir() -> io::Result<PathBuf> {
+    let base = match absolute_path_from_env("XDG_STATE_HOME")
+        .or_else(|| absolute_path_from_env("XDG_DATA_HOME"))
+    {
+        Some(dir) => dir,
+        None => home_dir()?.join(".local").join("state"),
+    };
+    Ok(base.join(env!("CARGO_PKG_NAME")))
+}
+
+/// `client_secret.json`のパスを取得する関数。
+///
+/// 環境変数`GCONTACTS_CLIENT_SECRET`が設定されている場合はその値を使用し、
+/// それ以外は設定ディレクトリの`client_secret.json`を使用します。
+///
+/// # 戻り値
+/// 成功した場合は`client_secret.json`のパス、ホームディレクトリが見つからない場合はエラー。
+pub fn client_secret_path() -> io::Result<PathBuf> {
+    match env::var_os("GCONTACTS_CLIENT_SECRET") {
+        Some(path) if !path.is_empty() => Ok(PathBuf::from(path)),
+        _ => Ok(config_dir()?.join(CLIENT_SECRET_FILE)),
+    }
+}
+
+/// Alpineのアドレス帳（`~/.addressbook`）のパスを取得する関数。
+///
+/// # 戻り値
+/// 成功した場合はアドレス帳のパス、ホームディレクトリが見つからない場合はエラー。
+pub fn addressbook_path() -> io::Result<PathBuf> {
+    Ok(home_dir()?.join(".addressbook"))
+}
+
+/// 設定ディレクトリと状態ディレクトリを作成する関数。
+///
+/// どちらも所有者以外がアクセスできないパーミッション（0700）で作成し、既に存在する場合もパーミッションを0700にします。
+///
+/// # 戻り値
+/// 成功した場合はOk(())、作成に失敗した場合はエラー。
+pub fn ensure_dirs() -> io::Result<()> {
+    for dir in [config_dir()?, state_dir()?] {
+        mod_perm::create_private_dir(&dir)?;
+        mod_perm::restrict_private(&dir, mod_perm::PRIVATE_DIR_MODE)?;
+    }
+    Ok(())
+}
+
+/// 旧バージョンの`~/.gcontacts`から新しいディレクトリにファイルを移動する関数。
+///
+/// `client_secret.json`は設定ディレクトリへ、トークンキャッシュは状態ディレクトリへ移動します。
+/// 移動先に同じ名前のファイルが既に存在する場合は移動しません。
+/// すべてのファイルを移動して`~/.gcontacts`が空になった場合はディレクトリを削除します。
+///
+/// # 戻り値
+/// 成功した場合は移動したファイルのパスのベクター、移動に失敗した場合はエラー。
+pub fn migrate_legacy_dir() -> io::Result<Vec<PathBuf>> {
+    let legacy_dir = home_dir()?.join(format!(".{}", env!("CARGO_PKG_NAME")));
+    if !legacy_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    ensure_dirs()?;
+
+    // 移動するファイルと移動先のリスト
+    let mut moves = vec![(CLIENT_SECRET_FILE, config_dir()?)];
+    for file_name in LEGACY_STATE_FILES {
+        moves.push((file_name, state_dir()?));
+    }
+
+    let mut migrated = Vec::new();
+    for (file_name, dest_dir) in moves {
+        let from = legacy_dir.join(file_name);
+        let to = dest_dir.join(file_name);
+        if from.exists() && !to.exists() {
+            move_file(&from, &to)?;
+            migrated.push(to);
+        }
+    }
+
+    // 空になった場合のみ旧ディレクトリを削除する
+    if fs::read_dir(&legacy_dir)?.next().is_none() {
+        fs::remove_dir(&legacy_dir)?;
+    }
+
+    Ok(migrated)
+}
+
+/// ファイルを移動する関数。
+///
+/// 異なるファイルシステム間ではリネームできないため、コピーしてから削除します。
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
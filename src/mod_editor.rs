@@ -0,0 +1,210 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! 連絡先を1行に1フィールドのテキストとして$EDITORで編集する
+
+use crate::mod_diff::Record; // 編集する連絡先の値
+use crate::mod_dirs; // 一時ファイルを状態ディレクトリに作成するため
+use crate::mod_perm; // 連絡先を含む一時ファイルをパーミッション0600で書き込むため
+use std::env; // 環境変数VISUAL、EDITORを参照するため
+use std::fmt; // エラーを表示するため
+use std::fs; // 一時ファイルの読み込みと削除のため
+use std::io; // エラーを返すための 'io' モジュール
+use std::process::Command; // エディタを起動するため
+
+/// 解析エラー。
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,     // エラーが発生した行番号（文書全体の場合は0）
+    pub message: String, // エラーの内容
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "line {}: {}", self.line, self.message)
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// フィールドの値を返す。
+fn field_value<'a>(record: &'a Record, field: &str) -> &'a str {
+    match field {
+        "nickname" => &record.nickname,
+        "name" => &record.name,
+        "email" => &record.email,
+        "fcc" => &record.fcc,
+        _ => &record.comment,
+    }
+}
+
+/// 編集する文書を作成する関数。
+///
+/// `#`で始まる行はコメントです。`alternative`を指定した場合は、値が異なるフィールドの下に
+/// もう一方の値をコメントとして表示します。
+///
+/// # 引数
+/// * `header` - 文書の先頭にコメントとして表示する行。
+/// * `record` - 編集する連絡先の値。
+/// * `fields` - 編集できるフィールドの名前（表示する順）。
+/// * `alternative` - 比較のために表示する見出しと値。
+///
+/// # 戻り値
+/// 編集する文書。
+pub fn format_document(
+    header: &[String],
+    record: &Record,
+    fields: &[&str],
+    alternative: Option<(&str, &Record)>,
+) -> String {
+    let mut text = String::new();
+    for line in header {
+        text.push_str(&format!("# {}\n", line));
+    }
+    for field in fields {
+        let value = field_value(record, field);
+        text.push_str(&format!("{}: {}\n", field, value));
+        if let Some((label, other)) = alternative {
+            let other_value = field_value(other, field);
+            if other_value != value {
+                text.push_str(&format!("#   {}: {}\n", label, other_value));
+            }
+        }
+    }
+    text
+}
+
+/// 編集した文書を解析する関数。
+///
+/// 空行とコメントを除く各行は`フィールド: 値`の形式で、`fields`のすべてのフィールドを1回ずつ含む必要があります。
+/// 値にタブを含めることはできず、ニックネームとメールアドレスは空白を含まない空でない値である必要があります。
+///
+/// # 引数
+/// * `text` - 編集した文書。
+/// * `base` - 文書に含まれないフィールドの値。
+/// * `fields` - 編集できるフィールドの名前。
+///
+/// # 戻り値
+/// 成功した場合は編集した値、文書が不正な場合はエラー。
+pub fn parse_document(text: &str, base: &Record, fields: &[&str]) -> Result<Record, ParseError> {
+    let mut record = Record {
+        nickname: base.nickname.clone(),
+        name: base.name.clone(),
+        email: base.email.clone(),
+        fcc: base.fcc.clone(),
+        comment: base.comment.clone(),
+    };
+    let mut seen: Vec<&str> = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let error = |message: String| ParseError {
+            line: index + 1,
+            message,
+        };
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once(':')
+            .ok_or_else(|| error("expected `field: value`".to_string()))?;
+        let key = key.trim();
+        let value = value.trim();
+        let field = match fields.iter().find(|f| **f == key) {
+            Some(field) => *field,
+            None => return Err(error(format!("unknown field `{}`", key))),
+        };
+        if seen.contains(&field) {
+            return Err(error(format!("duplicate field `{}`", field)));
+        }
+        seen.push(field);
+
+        if value.contains('\t') {
+            return Err(error(format!("`{}` cannot contain tabs", field)));
+        }
+        if (field == "nickname" || field == "email")
+            && (value.is_empty() || value.contains(char::is_whitespace))
+        {
+            return Err(error(format!(
+                "`{}` must be a non-empty value without spaces",
+                field
+            )));
+        }
+
+        let value = value.to_string();
+        match field {
+            "nickname" => record.nickname = value,
+            "name" => record.name = value,
+            "email" => record.email = value,
+            "fcc" => record.fcc = value,
+            _ => record.comment = value,
+        }
+    }
+
+    if let Some(missing) = fields.iter().find(|f| !seen.contains(f)) {
+        return Err(ParseError {
+            line: 0,
+            message: format!("missing field `{}`", missing),
+        });
+    }
+    Ok(record)
+}
+
+/// 使用するエディタのコマンドを返す。
+///
+/// 環境変数`VISUAL`、`EDITOR`の順に参照し、どちらも設定されていない場合は`vi`を使用します。
+fn editor_command() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+/// 文書をエディタで編集する関数。
+///
+/// 文書を状態ディレクトリの一時ファイルに書き込み、エディタを起動します。
+/// エディタのコマンドには引数を含めることができます（例: `code --wait`）。一時ファイルは編集後に削除します。
+///
+/// # 引数
+/// * `text` - 編集する文書。
+///
+/// # 戻り値
+/// 成功した場合は編集後の文書、エディタを起動できない場合や失敗した場合はエラー。
+pub fn edit(text: &str) -> io::Result<String> {
+    mod_dirs::ensure_dirs()?;
+    let path = mod_dirs::state_dir()?.join(format!("edit-{}.txt", std::process::id()));
+    mod_perm::write_private_file(&path, text.as_bytes())?;
+
+    // シェルを経由して、エディタのコマンドの引数を解釈させる
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor_command()))
+        .arg("sh")
+        .arg(&path)
+        .status();
+    let result = match status {
+        Ok(status) if status.success() => fs::read_to_string(&path),
+        Ok(status) => Err(io::Error::other(format!("editor exited with {}", status))),
+        Err(e) => Err(e),
+    };
+
+    let _ = fs::remove_file(&path);
+    result
+}
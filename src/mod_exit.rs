@@ -0,0 +1,189 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! 実行結果に応じた終了コードを定義する
+
+use std::error::Error; // エラーの種類を判定するため
+
+/// 終了コード。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExitCode {
+    Success = 0,        // 成功
+    Failure = 1,        // その他のエラー（ファイルの書き込みの失敗など）
+    Usage = 2,          // コマンドラインの誤り
+    Auth = 3,           // 認証の失敗
+    Network = 4,        // Google People APIとの通信の失敗
+    Parse = 5,          // 設定ファイルやアドレス帳の解析の失敗
+    Unresolved = 6,     // 操作がキャンセルされ、未解決の競合が残っている
+    PartialSuccess = 7, // 一部の連絡先の更新に失敗した
+}
+
+impl ExitCode {
+    /// プロセスの終了コードとしての値を返す。
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    /// 実行結果の報告に使用する名前を返す。
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExitCode::Success => "success",
+            ExitCode::Failure => "failure",
+            ExitCode::Usage => "usage",
+            ExitCode::Auth => "auth",
+            ExitCode::Network => "network",
+            ExitCode::Parse => "parse",
+            ExitCode::Unresolved => "unresolved",
+            ExitCode::PartialSuccess => "partial_success",
+        }
+    }
+
+    /// この終了コードでプロセスを終了する。
+    pub fn exit(self) -> ! {
+        std::process::exit(self.code())
+    }
+}
+
+/// Google People APIの呼び出しなどで発生したエラーの終了コードを判定する関数。
+///
+/// 認証に関するエラー（トークンを取得できない、HTTPステータス401/403）は`Auth`、
+/// 接続や通信のエラー（HTTPステータス429/5xxを含む）は`Network`、それ以外は`Failure`とします。
+///
+/// # 引数
+/// * `error` - 判定するエラー。
+///
+/// # 戻り値
+/// エラーに対応する終了コード。
+pub fn classify_error(error: &(dyn Error + 'static)) -> ExitCode {
+    if let Some(e) = error.downcast_ref::<google_people1::Error>() {
+        return match e {
+            google_people1::Error::MissingToken(_) | google_people1::Error::MissingAPIKey => {
+                ExitCode::Auth
+            }
+            google_people1::Error::HttpError(_) | google_people1::Error::Io(_) => ExitCode::Network,
+            google_people1::Error::Failure(response) => {
+                status_exit_code(response.status().as_u16())
+            }
+            google_people1::Error::BadRequest(value) => match value["error"]["code"].as_u64() {
+                Some(status) => status_exit_code(status as u16),
+                None => ExitCode::Failure,
+            },
+            _ => ExitCode::Failure,
+        };
+    }
+    if error.downcast_ref::<hyper::Error>().is_some() {
+        return ExitCode::Network;
+    }
+    if error.downcast_ref::<yup_oauth2::Error>().is_some() {
+        return ExitCode::Auth;
+    }
+    ExitCode::Failure
+}
+
+/// HTTPステータスコードに対応する終了コードを返す。
+///
+/// サーバー側の障害や混雑（5xx、429）は通信の失敗として扱う。
+fn status_exit_code(status: u16) -> ExitCode {
+    match status {
+        401 | 403 => ExitCode::Auth,
+        429 | 500..=599 => ExitCode::Network,
+        _ => ExitCode::Failure,
+    }
+}
+
+/// ファイルの読み込みで発生したエラーの終了コードを判定する関数。
+///
+/// 内容の誤り（不正なデータ、デコードの失敗など）は`Parse`、ファイルを開けないなどの入出力のエラーは`Failure`とします。
+///
+/// # 引数
+/// * `error` - 判定するエラー。
+///
+/// # 戻り値
+/// エラーに対応する終了コード。
+pub fn classify_read_error(error: &(dyn Error + 'static)) -> ExitCode {
+    match error.downcast_ref::<std::io::Error>() {
+        Some(e) if e.kind() != std::io::ErrorKind::InvalidData => ExitCode::Failure,
+        _ => ExitCode::Parse,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// HTTPステータスで失敗したGoogle People APIのエラーを作成する。
+    fn failure(status: u16) -> google_people1::Error {
+        google_people1::Error::Failure(
+            hyper::Response::builder()
+                .status(status)
+                .body(hyper::Body::empty())
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn exit_codes_have_fixed_values_and_names() {
+        for (code, value, name) in [
+            (ExitCode::Success, 0, "success"),
+            (ExitCode::Failure, 1, "failure"),
+            (ExitCode::Usage, 2, "usage"),
+            (ExitCode::Auth, 3, "auth"),
+            (ExitCode::Network, 4, "network"),
+            (ExitCode::Parse, 5, "parse"),
+            (ExitCode::Unresolved, 6, "unresolved"),
+            (ExitCode::PartialSuccess, 7, "partial_success"),
+        ] {
+            assert_eq!(code.code(), value);
+            assert_eq!(code.as_str(), name);
+        }
+    }
+
+    #[test]
+    fn google_errors_are_classified_by_their_status() {
+        assert_eq!(classify_error(&failure(401)), ExitCode::Auth);
+        assert_eq!(classify_error(&failure(403)), ExitCode::Auth);
+        assert_eq!(classify_error(&failure(429)), ExitCode::Network);
+        assert_eq!(classify_error(&failure(503)), ExitCode::Network);
+        assert_eq!(classify_error(&failure(404)), ExitCode::Failure);
+
+        let bad_request = |code: u64| {
+            google_people1::Error::BadRequest(serde_json::json!({"error": {"code": code}}))
+        };
+        assert_eq!(classify_error(&bad_request(403)), ExitCode::Auth);
+        assert_eq!(classify_error(&bad_request(400)), ExitCode::Failure);
+
+        assert_eq!(
+            classify_error(&google_people1::Error::MissingToken("no token".into())),
+            ExitCode::Auth
+        );
+        assert_eq!(
+            classify_error(&google_people1::Error::Io(std::io::Error::other("reset"))),
+            ExitCode::Network
+        );
+        assert_eq!(
+            classify_error(&std::io::Error::other("disk full")),
+            ExitCode::Failure
+        );
+    }
+
+    #[test]
+    fn read_errors_distinguish_bad_content_from_io_failures() {
+        let invalid = std::io::Error::new(std::io::ErrorKind::InvalidData, "bad vCard");
+        assert_eq!(classify_read_error(&invalid), ExitCode::Parse);
+        let missing = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+        assert_eq!(classify_read_error(&missing), ExitCode::Failure);
+        let csv_error: Box<dyn Error> = "unexpected column".into();
+        assert_eq!(classify_read_error(csv_error.as_ref()), ExitCode::Parse);
+    }
+}
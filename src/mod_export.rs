@@ -0,0 +1,204 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! 連絡先を書き出す形式と、書き出し先（標準出力、ファイル、ディレクトリ）を扱う
+
+use crate::mod_perm; // 連絡先を含むファイルをパーミッション0600で書き込むため
+use std::collections::HashSet; // 書き出したファイル名の集合を扱うため
+use std::fs; // 既存のファイルを読み込み、削除するため
+use std::io::{self, Write}; // 標準出力に書き込むため
+use std::path::Path; // ファイルパスを扱うため
+use std::str::FromStr; // 文字列から書き出す形式に変換するため
+
+/// 書き出す形式。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExportFormat {
+    VCard,       // vCard
+    MuttAliases, // muttのエイリアスファイル
+    Abook,       // abookのアドレス帳ファイル
+    Vdir,        // 連絡先ごとのvCardファイルを置くディレクトリ（khardなど）
+    Ldif,        // LDIF（Thunderbird、LDAPディレクトリ）
+    GoogleCsv,   // Google ContactsのWeb画面が読み書きするCSV
+    Json,        // 正規化した連絡先のJSON
+    Ndjson,      // 正規化した連絡先を1行に1つ書いたJSON
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vcard" => Ok(ExportFormat::VCard),
+            "mutt-aliases" => Ok(ExportFormat::MuttAliases),
+            "abook" => Ok(ExportFormat::Abook),
+            "vdir" => Ok(ExportFormat::Vdir),
+            "ldif" => Ok(ExportFormat::Ldif),
+            "google-csv" => Ok(ExportFormat::GoogleCsv),
+            "json" => Ok(ExportFormat::Json),
+            "ndjson" => Ok(ExportFormat::Ndjson),
+            _ => Err(s.to_string()),
+        }
+    }
+}
+
+impl ExportFormat {
+    /// 連絡先ごとのファイルをディレクトリに書き出せる（`--output-dir`を使用できる）かどうかを返す。
+    pub fn supports_directory(&self) -> bool {
+        matches!(self, ExportFormat::VCard | ExportFormat::Vdir)
+    }
+
+    /// ディレクトリにのみ書き出せる（`--output-dir`が必須である）かどうかを返す。
+    pub fn requires_directory(&self) -> bool {
+        matches!(self, ExportFormat::Vdir)
+    }
+}
+
+/// 書き出したデータを1つのファイル、または標準出力に書き込む関数。
+///
+/// 連絡先を含むため、ファイルは所有者以外が読めないパーミッション（0600）で書き込みます。
+///
+/// # 引数
+/// * `path` - 書き込むファイルのパス。`None`の場合は標準出力。
+/// * `data` - 書き込むデータ。
+///
+/// # 戻り値
+/// 成功した場合はOk(())、書き込みに失敗した場合はエラー。
+pub fn write_output(path: Option<&Path>, data: &str) -> io::Result<()> {
+    match path {
+        Some(path) => mod_perm::write_private_file(path, data.as_bytes()),
+        None => {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(data.as_bytes())?;
+            stdout.flush()
+        }
+    }
+}
+
+/// 連絡先ごとのファイルをディレクトリに書き込む関数。
+///
+/// ディレクトリが存在しない場合はパーミッション0700で作成します。同じ名前のファイルは置き換えます。
+///
+/// # 引数
+/// * `dir` - 書き込むディレクトリのパス。
+/// * `files` - ファイル名と内容のタプルのスライス。
+///
+/// # 戻り値
+/// 成功した場合はOk(())、作成や書き込みに失敗した場合はエラー。
+pub fn write_directory(dir: &Path, files: &[(String, String)]) -> io::Result<()> {
+    mod_perm::create_private_dir(dir)?;
+    for (name, data) in files {
+        mod_perm::write_private_file(&dir.join(name), data.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// 連絡先ごとのファイルでディレクトリを更新する関数（vdir）。
+///
+/// 内容が変わらないファイルは書き込まず、ディレクトリにある`extension`のファイルのうち
+/// `files`に無いもの（削除された連絡先）を削除します。
+///
+/// # 引数
+/// * `dir` - 更新するディレクトリのパス。
+/// * `files` - ファイル名と内容のタプルのスライス。
+/// * `extension` - 削除の対象とするファイルの拡張子（`"vcf"`など）。
+///
+/// # 戻り値
+/// 成功した場合は書き込んだファイルの数と削除したファイルの数、失敗した場合はエラー。
+pub fn sync_directory(
+    dir: &Path,
+    files: &[(String, String)],
+    extension: &str,
+) -> io::Result<(usize, usize)> {
+    mod_perm::create_private_dir(dir)?;
+
+    let mut written = 0;
+    for (name, data) in files {
+        let path = dir.join(name);
+        if fs::read(&path).is_ok_and(|current| current == data.as_bytes()) {
+            continue;
+        }
+        mod_perm::write_private_file(&path, data.as_bytes())?;
+        written += 1;
+    }
+
+    let names: HashSet<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+    let mut removed = 0;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let stale = path.is_file()
+            && path.extension().is_some_and(|e| e == extension)
+            && path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| !names.contains(n));
+        if stale {
+            fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    Ok((written, removed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mod_test::temp_path;
+    use std::path::PathBuf;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = temp_path(name);
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn files(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+        entries
+            .iter()
+            .map(|(name, data)| (name.to_string(), data.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn sync_directory_writes_changes_and_removes_stale_files() {
+        let dir = temp_dir("vdir");
+        let first = files(&[("a.vcf", "A1"), ("b.vcf", "B1"), ("c.vcf", "C1")]);
+        assert_eq!(sync_directory(&dir, &first, "vcf").unwrap(), (3, 0));
+        assert_eq!(fs::read_to_string(dir.join("a.vcf")).unwrap(), "A1");
+
+        // vdirに他のプログラムが置いたファイルは削除しない
+        fs::write(dir.join("notes.txt"), "keep").unwrap();
+
+        let second = files(&[("a.vcf", "A1"), ("b.vcf", "B2"), ("d.vcf", "D1")]);
+        assert_eq!(sync_directory(&dir, &second, "vcf").unwrap(), (2, 1));
+        assert_eq!(fs::read_to_string(dir.join("a.vcf")).unwrap(), "A1");
+        assert_eq!(fs::read_to_string(dir.join("b.vcf")).unwrap(), "B2");
+        assert_eq!(fs::read_to_string(dir.join("d.vcf")).unwrap(), "D1");
+        assert!(!dir.join("c.vcf").exists());
+        assert!(dir.join("notes.txt").exists());
+
+        // 内容が同じ場合は何も書き込まない
+        assert_eq!(sync_directory(&dir, &second, "vcf").unwrap(), (0, 0));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_directory_replaces_files_with_the_same_name() {
+        let dir = temp_dir("export-dir");
+        write_directory(&dir, &files(&[("a.vcf", "A1")])).unwrap();
+        write_directory(&dir, &files(&[("a.vcf", "A2"), ("b.vcf", "B1")])).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("a.vcf")).unwrap(), "A2");
+        assert_eq!(fs::read_to_string(dir.join("b.vcf")).unwrap(), "B1");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
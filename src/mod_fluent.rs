@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-/// ローカライゼーション（言語翻訳と地域設定の適用）機能を提供する
+//! ローカライゼーション（言語翻訳と地域設定の適用）機能を提供する
 
 use fluent::{bundle::FluentBundle, FluentResource}; // ローカライゼーション機能を提供するfluentクレート関連モジュール
 use intl_memoizer::concurrent::IntlLangMemoizer; // 国際化機能を提供するintl_memoizerクレートのモジュール
@@ -68,5 +68,7 @@ pub fn get_translation(bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
     let message = bundle.get_message(message_id).expect("Message doesn't exist.");
     let pattern = message.value().expect("Message has no value.");
     let mut errors = vec![];
-    bundle.format_pattern(&pattern, None, &mut errors).to_string()
+    bundle
+        .format_pattern(pattern, None, &mut errors)
+        .to_string()
 }
@@ -0,0 +1,707 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Google ContactsのWeb画面で読み書きできる「Google CSV」の形式で連絡先を書き出し、読み込む
+
+use crate::mod_vcard::{self, Card, ParseError}; // 読み込んだ連絡先をvCardと同じ形で取り込むため
+use csv::{ReaderBuilder, StringRecord, WriterBuilder}; // CSVを読み書きするため
+use google_people1::api::{
+    Address, Biography, EmailAddress, FieldMetadata, Name, Nickname, Organization, Person,
+    PhoneNumber,
+}; // Google Contactsの連絡先を扱うため
+use std::collections::BTreeMap; // 連絡先グループのリソース名とラベル名の対応を扱うため
+use std::io; // 書き出しのエラーを扱うため
+
+/// 1つのセルに複数の値を書く場合の区切り
+const SEPARATOR: &str = " ::: ";
+
+/// 番号の付かない列（Google Contactsが書き出す順）
+const BASE_COLUMNS: [&str; 29] = [
+    "Name",
+    "Given Name",
+    "Additional Name",
+    "Family Name",
+    "Yomi Name",
+    "Given Name Yomi",
+    "Additional Name Yomi",
+    "Family Name Yomi",
+    "Name Prefix",
+    "Name Suffix",
+    "Initials",
+    "Nickname",
+    "Short Name",
+    "Maiden Name",
+    "Birthday",
+    "Gender",
+    "Location",
+    "Billing Information",
+    "Directory Server",
+    "Mileage",
+    "Occupation",
+    "Hobby",
+    "Sensitivity",
+    "Priority",
+    "Subject",
+    "Notes",
+    "Language",
+    "Photo",
+    "Group Membership",
+];
+
+/// 番号の付く列の名前の接頭辞と、各番号の列の名前
+const EMAIL_COLUMNS: (&str, &[&str]) = ("E-mail", &["Type", "Value"]);
+const PHONE_COLUMNS: (&str, &[&str]) = ("Phone", &["Type", "Value"]);
+const ADDRESS_COLUMNS: (&str, &[&str]) = (
+    "Address",
+    &[
+        "Type",
+        "Formatted",
+        "Street",
+        "City",
+        "PO Box",
+        "Region",
+        "Postal Code",
+        "Country",
+        "Extended Address",
+    ],
+);
+const ORGANIZATION_COLUMNS: (&str, &[&str]) = (
+    "Organization",
+    &[
+        "Type",
+        "Name",
+        "Yomi Name",
+        "Title",
+        "Department",
+        "Symbol",
+        "Location",
+        "Job Description",
+    ],
+);
+
+/// システムの連絡先グループのリソース名と、Group Membershipに書き出す名前
+const SYSTEM_GROUPS: [(&str, &str); 2] = [
+    ("contactGroups/myContacts", "* myContacts"),
+    ("contactGroups/starred", "* starred"),
+];
+
+/// Google Contactsの種類（`homeFax`など）を、CSVの種類（`Home Fax`）に変換する。
+///
+/// メインの値の種類には、Google Contactsと同じく`* `を付けます。
+fn type_label(type_: Option<&str>, primary: bool) -> String {
+    let mut label = String::new();
+    for (i, c) in type_.unwrap_or_default().chars().enumerate() {
+        if i == 0 {
+            label.extend(c.to_uppercase());
+        } else if c.is_uppercase() {
+            label.push(' ');
+            label.push(c);
+        } else {
+            label.push(c);
+        }
+    }
+    if primary && !label.is_empty() {
+        label.insert_str(0, "* ");
+    }
+    label
+}
+
+/// CSVの種類（`* Home Fax`など）を、Google Contactsの種類（`homeFax`）とメインの値かどうかに変換する。
+fn parse_type(label: &str) -> (Option<String>, bool) {
+    let (label, primary) = match label.trim().strip_prefix('*') {
+        Some(rest) => (rest.trim(), true),
+        None => (label.trim(), false),
+    };
+    let mut type_ = String::new();
+    for (i, word) in label.split_whitespace().enumerate() {
+        let mut chars = word.chars();
+        if let Some(c) = chars.next() {
+            if i == 0 {
+                type_.extend(c.to_lowercase());
+            } else {
+                type_.extend(c.to_uppercase());
+            }
+            type_.push_str(chars.as_str());
+        }
+    }
+    (Some(type_).filter(|t| !t.is_empty()), primary)
+}
+
+/// メインの値かどうかを返す。
+fn is_primary(metadata: &Option<FieldMetadata>) -> bool {
+    metadata
+        .as_ref()
+        .and_then(|m| m.primary)
+        .unwrap_or_default()
+}
+
+/// 番号の付く列の名前を作成する（`E-mail 1 - Value`など）。
+fn numbered_columns(columns: (&str, &[&str]), count: usize) -> Vec<String> {
+    let (prefix, names) = columns;
+    (1..=count)
+        .flat_map(|i| {
+            names
+                .iter()
+                .map(move |name| format!("{} {} - {}", prefix, i, name))
+        })
+        .collect()
+}
+
+/// Googleの連絡先から、Google CSVの内容を作成する関数。
+///
+/// メールアドレス、電話番号、住所、所属組織の列（`E-mail 1 - Value`など）は、最も多い連絡先に合わせた数だけ書き出します。
+/// ラベルは`Group Membership`に` ::: `で区切って書き出します。
+///
+/// # 引数
+/// * `gpersons` - 書き出すGoogleの連絡先。
+/// * `labels` - 連絡先グループのリソース名とラベル名の対応。
+///
+/// # 戻り値
+/// 成功した場合はCSVの内容、書き込みに失敗した場合はエラー。
+pub fn write_csv(gpersons: &[Person], labels: &BTreeMap<String, String>) -> io::Result<String> {
+    let count = |len: fn(&Person) -> usize| gpersons.iter().map(len).max().unwrap_or(0).max(1);
+    let emails = count(|p| p.email_addresses.as_ref().map_or(0, Vec::len));
+    let phones = count(|p| p.phone_numbers.as_ref().map_or(0, Vec::len));
+    let addresses = count(|p| p.addresses.as_ref().map_or(0, Vec::len));
+    let organizations = count(|p| p.organizations.as_ref().map_or(0, Vec::len));
+
+    let mut writer = WriterBuilder::new().from_writer(Vec::new());
+    let mut header: Vec<String> = BASE_COLUMNS.iter().map(|c| c.to_string()).collect();
+    header.extend(numbered_columns(EMAIL_COLUMNS, emails));
+    header.extend(numbered_columns(PHONE_COLUMNS, phones));
+    header.extend(numbered_columns(ADDRESS_COLUMNS, addresses));
+    header.extend(numbered_columns(ORGANIZATION_COLUMNS, organizations));
+    writer.write_record(&header)?;
+
+    for person in gpersons {
+        let value = |v: &Option<String>| v.clone().unwrap_or_default();
+        let name = person.names.iter().flatten().next();
+        let name_value = |f: fn(&Name) -> &Option<String>| name.map(f).map_or(String::new(), value);
+
+        let birthday = person
+            .birthdays
+            .iter()
+            .flatten()
+            .next()
+            .and_then(mod_vcard::format_birthday);
+        let groups: Vec<&str> = person
+            .memberships
+            .iter()
+            .flatten()
+            .filter_map(|m| {
+                let resource_name = m
+                    .contact_group_membership
+                    .as_ref()?
+                    .contact_group_resource_name
+                    .as_ref()?;
+                SYSTEM_GROUPS
+                    .iter()
+                    .find(|(system, _)| system == resource_name)
+                    .map(|(_, label)| *label)
+                    .or_else(|| labels.get(resource_name).map(String::as_str))
+            })
+            .collect();
+
+        let mut record: BTreeMap<&str, String> = BTreeMap::new();
+        record.insert("Name", name_value(|n| &n.display_name));
+        record.insert("Given Name", name_value(|n| &n.given_name));
+        record.insert("Additional Name", name_value(|n| &n.middle_name));
+        record.insert("Family Name", name_value(|n| &n.family_name));
+        record.insert("Yomi Name", name_value(|n| &n.phonetic_full_name));
+        record.insert("Given Name Yomi", name_value(|n| &n.phonetic_given_name));
+        record.insert(
+            "Additional Name Yomi",
+            name_value(|n| &n.phonetic_middle_name),
+        );
+        record.insert("Family Name Yomi", name_value(|n| &n.phonetic_family_name));
+        record.insert("Name Prefix", name_value(|n| &n.honorific_prefix));
+        record.insert("Name Suffix", name_value(|n| &n.honorific_suffix));
+        record.insert(
+            "Nickname",
+            person
+                .nicknames
+                .iter()
+                .flatten()
+                .find_map(|n| n.value.clone())
+                .unwrap_or_default(),
+        );
+        record.insert("Birthday", birthday.unwrap_or_default());
+        record.insert(
+            "Notes",
+            person
+                .biographies
+                .iter()
+                .flatten()
+                .find_map(|b| b.value.clone())
+                .unwrap_or_default(),
+        );
+        record.insert("Group Membership", groups.join(SEPARATOR));
+
+        let mut row: Vec<String> = BASE_COLUMNS
+            .iter()
+            .map(|c| record.remove(c).unwrap_or_default())
+            .collect();
+        let mut push_numbered = |cells: Vec<Vec<String>>, count: usize, width: usize| {
+            for i in 0..count {
+                match cells.get(i) {
+                    Some(values) => row.extend(values.iter().cloned()),
+                    None => row.resize(row.len() + width, String::new()),
+                }
+            }
+        };
+        push_numbered(
+            person
+                .email_addresses
+                .iter()
+                .flatten()
+                .map(|e| {
+                    vec![
+                        type_label(e.type_.as_deref(), is_primary(&e.metadata)),
+                        value(&e.value),
+                    ]
+                })
+                .collect(),
+            emails,
+            EMAIL_COLUMNS.1.len(),
+        );
+        push_numbered(
+            person
+                .phone_numbers
+                .iter()
+                .flatten()
+                .map(|p| {
+                    vec![
+                        type_label(p.type_.as_deref(), is_primary(&p.metadata)),
+                        value(&p.value),
+                    ]
+                })
+                .collect(),
+            phones,
+            PHONE_COLUMNS.1.len(),
+        );
+        push_numbered(
+            person
+                .addresses
+                .iter()
+                .flatten()
+                .map(|a| {
+                    vec![
+                        type_label(a.type_.as_deref(), is_primary(&a.metadata)),
+                        value(&a.formatted_value),
+                        value(&a.street_address),
+                        value(&a.city),
+                        value(&a.po_box),
+                        value(&a.region),
+                        value(&a.postal_code),
+                        value(&a.country),
+                        value(&a.extended_address),
+                    ]
+                })
+                .collect(),
+            addresses,
+            ADDRESS_COLUMNS.1.len(),
+        );
+        push_numbered(
+            person
+                .organizations
+                .iter()
+                .flatten()
+                .map(|o| {
+                    vec![
+                        type_label(o.type_.as_deref(), false),
+                        value(&o.name),
+                        value(&o.phonetic_name),
+                        value(&o.title),
+                        value(&o.department),
+                        value(&o.symbol),
+                        value(&o.location),
+                        value(&o.job_description),
+                    ]
+                })
+                .collect(),
+            organizations,
+            ORGANIZATION_COLUMNS.1.len(),
+        );
+        writer.write_record(&row)?;
+    }
+
+    let data = writer.into_inner().map_err(|e| e.into_error())?;
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+/// 列の名前から、1行の値を取り出す。
+struct Row<'a> {
+    headers: &'a StringRecord,
+    record: &'a StringRecord,
+}
+
+impl Row<'_> {
+    /// 列の値を返す（列が無い場合や空の場合は`None`）。
+    fn get(&self, column: &str) -> Option<String> {
+        let index = self.headers.iter().position(|h| h.trim() == column)?;
+        self.record
+            .get(index)
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+    }
+
+    /// 番号の付く列の値を返す（`E-mail 1 - Value`など）。
+    fn numbered(&self, prefix: &str, number: usize, name: &str) -> Option<String> {
+        self.get(&format!("{} {} - {}", prefix, number, name))
+    }
+
+    /// 番号の付く種類と値の列（`E-mail 1 - Type`と`E-mail 1 - Value`など）から、種類と値を返す。
+    ///
+    /// 1つのセルに` ::: `で区切った複数の値を分け、種類に`* `が付いたメインの値を先頭にします。
+    fn typed_values(&self, prefix: &str) -> Vec<(Option<String>, String)> {
+        let mut values: Vec<(bool, Option<String>, String)> = Vec::new();
+        for i in self.numbers(prefix) {
+            let (type_, primary) =
+                parse_type(&self.numbered(prefix, i, "Type").unwrap_or_default());
+            for value in self
+                .numbered(prefix, i, "Value")
+                .iter()
+                .flat_map(|v| v.split(SEPARATOR))
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+            {
+                values.push((primary, type_.clone(), value.to_string()));
+            }
+        }
+        // 安定ソートのため、メインの値どうし、それ以外の値どうしの順序は変わらない
+        values.sort_by_key(|(primary, _, _)| !primary);
+        values
+            .into_iter()
+            .map(|(_, type_, value)| (type_, value))
+            .collect()
+    }
+
+    /// 番号の付く列の番号を返す（`Value`や`Name`などの列がある番号）。
+    fn numbers(&self, prefix: &str) -> Vec<usize> {
+        let mut numbers: Vec<usize> = self
+            .headers
+            .iter()
+            .filter_map(|h| {
+                let rest = h.trim().strip_prefix(prefix)?.strip_prefix(' ')?;
+                rest.split_once(" - ")?.0.parse().ok()
+            })
+            .collect();
+        numbers.sort_unstable();
+        numbers.dedup();
+        numbers
+    }
+}
+
+/// 1行を連絡先に変換する。
+fn parse_row(row: &Row) -> Card {
+    let mut person = Person::default();
+
+    // 名前
+    let mut name = Name {
+        display_name: row.get("Name"),
+        given_name: row.get("Given Name"),
+        middle_name: row.get("Additional Name"),
+        family_name: row.get("Family Name"),
+        phonetic_full_name: row.get("Yomi Name"),
+        phonetic_given_name: row.get("Given Name Yomi"),
+        phonetic_middle_name: row.get("Additional Name Yomi"),
+        phonetic_family_name: row.get("Family Name Yomi"),
+        honorific_prefix: row.get("Name Prefix"),
+        honorific_suffix: row.get("Name Suffix"),
+        ..Default::default()
+    };
+    let has_name = [
+        &name.display_name,
+        &name.given_name,
+        &name.middle_name,
+        &name.family_name,
+        &name.phonetic_full_name,
+        &name.phonetic_given_name,
+        &name.phonetic_middle_name,
+        &name.phonetic_family_name,
+        &name.honorific_prefix,
+        &name.honorific_suffix,
+    ]
+    .iter()
+    .any(|v| v.is_some());
+    if has_name {
+        if name.display_name.is_none() {
+            name.display_name = mod_vcard::compose_display_name(&name);
+        }
+        person.names = Some(vec![name]);
+    }
+
+    person.nicknames = row.get("Nickname").map(|value| {
+        vec![Nickname {
+            value: Some(value),
+            ..Default::default()
+        }]
+    });
+    person.birthdays = row
+        .get("Birthday")
+        .map(|value| vec![mod_vcard::parse_birthday(&value)]);
+    person.biographies = row.get("Notes").map(|value| {
+        vec![Biography {
+            value: Some(value),
+            content_type: Some("TEXT_PLAIN".to_string()),
+            ..Default::default()
+        }]
+    });
+
+    // メールアドレスと電話番号（1つのセルに` ::: `で区切った複数の値がある場合がある）
+    person.email_addresses = Some(row.typed_values(EMAIL_COLUMNS.0))
+        .filter(|values| !values.is_empty())
+        .map(|values| {
+            values
+                .into_iter()
+                .map(|(type_, value)| EmailAddress {
+                    value: Some(value),
+                    type_,
+                    ..Default::default()
+                })
+                .collect()
+        });
+    person.phone_numbers = Some(row.typed_values(PHONE_COLUMNS.0))
+        .filter(|values| !values.is_empty())
+        .map(|values| {
+            values
+                .into_iter()
+                .map(|(type_, value)| PhoneNumber {
+                    value: Some(value),
+                    type_,
+                    ..Default::default()
+                })
+                .collect()
+        });
+
+    // 住所（要素が無く整形済みの住所だけがある場合は、番地として扱う。メインの住所を先頭にする）
+    let mut addresses: Vec<(bool, Address)> = Vec::new();
+    for i in row.numbers(ADDRESS_COLUMNS.0) {
+        let field = |name: &str| row.numbered(ADDRESS_COLUMNS.0, i, name);
+        let (type_, primary) = parse_type(&field("Type").unwrap_or_default());
+        let mut address = Address {
+            street_address: field("Street"),
+            city: field("City"),
+            po_box: field("PO Box"),
+            region: field("Region"),
+            postal_code: field("Postal Code"),
+            country: field("Country"),
+            extended_address: field("Extended Address"),
+            type_,
+            ..Default::default()
+        };
+        let has_component = [
+            &address.street_address,
+            &address.city,
+            &address.po_box,
+            &address.region,
+            &address.postal_code,
+            &address.country,
+            &address.extended_address,
+        ]
+        .iter()
+        .any(|v| v.is_some());
+        if !has_component {
+            match field("Formatted") {
+                Some(formatted) => address.street_address = Some(formatted),
+                None => continue,
+            }
+        }
+        addresses.push((primary, address));
+    }
+    addresses.sort_by_key(|(primary, _)| !primary);
+    let addresses: Vec<Address> = addresses.into_iter().map(|(_, a)| a).collect();
+    person.addresses = Some(addresses).filter(|a| !a.is_empty());
+
+    // 所属組織
+    let organizations: Vec<Organization> = row
+        .numbers(ORGANIZATION_COLUMNS.0)
+        .into_iter()
+        .filter_map(|i| {
+            let field = |name: &str| row.numbered(ORGANIZATION_COLUMNS.0, i, name);
+            let organization = Organization {
+                name: field("Name"),
+                phonetic_name: field("Yomi Name"),
+                title: field("Title"),
+                department: field("Department"),
+                symbol: field("Symbol"),
+                location: field("Location"),
+                job_description: field("Job Description"),
+                ..Default::default()
+            };
+            let has_value = [
+                &organization.name,
+                &organization.phonetic_name,
+                &organization.title,
+                &organization.department,
+                &organization.symbol,
+                &organization.location,
+                &organization.job_description,
+            ]
+            .iter()
+            .any(|v| v.is_some());
+            has_value.then(|| Organization {
+                type_: parse_type(&field("Type").unwrap_or_default()).0,
+                ..organization
+            })
+        })
+        .collect();
+    person.organizations = Some(organizations).filter(|o| !o.is_empty());
+
+    // ラベル（`* myContacts`などのシステムの連絡先グループは除く）
+    let categories = row
+        .get("Group Membership")
+        .iter()
+        .flat_map(|v| v.split(SEPARATOR))
+        .map(str::trim)
+        .filter(|label| !label.is_empty() && !label.starts_with('*'))
+        .map(str::to_string)
+        .collect();
+
+    Card {
+        person,
+        categories,
+        ..Default::default()
+    }
+}
+
+/// Google CSVを読み込む関数。
+///
+/// 1行目の列の名前から値を取り出すため、列の順序や数はGoogle Contactsが書き出したものと異なっていてもかまいません。
+/// 1つのセルに` ::: `で区切った複数の値（メールアドレス、電話番号、ラベル）を読み込み、種類に`* `が付いた値をメインとして先頭にします。
+///
+/// # 引数
+/// * `data` - CSVファイルの内容。
+///
+/// # 戻り値
+/// 成功した場合は読み込んだ連絡先のベクター、形式が正しくない場合はエラー。
+pub fn parse(data: &str) -> Result<Vec<Card>, ParseError> {
+    let parse_error = |e: csv::Error| ParseError {
+        line: e.position().map_or(0, |p| p.line() as usize),
+        message: e.to_string(),
+    };
+
+    let mut reader = ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(data.trim_start_matches('\u{feff}').as_bytes());
+    let headers = reader.headers().map_err(parse_error)?.clone();
+    let mut cards = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(parse_error)?;
+        cards.push(parse_row(&Row {
+            headers: &headers,
+            record: &record,
+        }));
+    }
+    Ok(cards)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mod_test;
+
+    /// テスト用の連絡先を作成する。
+    fn person() -> Person {
+        mod_test::person()
+            .full_name("Tanaka, Taro", "Tanaka", "Taro")
+            .typed_email("taro@example.org", "home")
+            .primary_email("taro@example.com", "work")
+            .phone("03-1234-5678", "homeFax")
+            .biography("line 1, \"quoted\"\nline 2")
+            .group("contactGroups/myContacts")
+            .group("contactGroups/starred")
+            .group("contactGroups/abc")
+            .build()
+    }
+
+    /// 連絡先グループのリソース名とラベル名の対応。
+    fn labels() -> BTreeMap<String, String> {
+        BTreeMap::from([("contactGroups/abc".to_string(), "Friends".to_string())])
+    }
+
+    #[test]
+    fn converts_types_between_google_and_csv() {
+        assert_eq!(type_label(Some("homeFax"), true), "* Home Fax");
+        assert_eq!(type_label(None, true), "");
+        assert_eq!(
+            parse_type("* Home Fax"),
+            (Some("homeFax".to_string()), true)
+        );
+        assert_eq!(parse_type(" Work "), (Some("work".to_string()), false));
+        assert_eq!(parse_type(""), (None, false));
+    }
+
+    #[test]
+    fn round_trips_quoted_fields_and_group_membership() {
+        let data = write_csv(&[person()], &labels()).unwrap();
+        assert!(data.contains("* myContacts ::: * starred ::: Friends"));
+        assert!(data.contains("\"Tanaka, Taro\""));
+
+        let cards = parse(&data).unwrap();
+        assert_eq!(cards.len(), 1);
+        let card = &cards[0];
+        let name = &card.person.names.as_ref().unwrap()[0];
+        assert_eq!(name.display_name.as_deref(), Some("Tanaka, Taro"));
+        assert_eq!(name.family_name.as_deref(), Some("Tanaka"));
+        let note = card.person.biographies.as_ref().unwrap()[0]
+            .value
+            .as_deref();
+        assert_eq!(note, Some("line 1, \"quoted\"\nline 2"));
+        // システムの連絡先グループは取り込まない
+        assert_eq!(card.categories, vec!["Friends"]);
+        // メインのメールアドレスを先頭にする
+        assert_eq!(card.emails(), vec!["taro@example.com", "taro@example.org"]);
+        let phone = &card.person.phone_numbers.as_ref().unwrap()[0];
+        assert_eq!(phone.type_.as_deref(), Some("homeFax"));
+    }
+
+    #[test]
+    fn pads_columns_for_contacts_with_fewer_values() {
+        let other = mod_test::person().name("Hanako").build();
+        let data = write_csv(&[person(), other], &labels()).unwrap();
+        let mut reader = ReaderBuilder::new().from_reader(data.as_bytes());
+        let width = reader.headers().unwrap().len();
+        assert!(reader.records().all(|r| r.unwrap().len() == width));
+
+        let cards = parse(&data).unwrap();
+        assert_eq!(cards.len(), 2);
+        assert!(cards[1].emails().is_empty());
+        assert!(cards[1].categories.is_empty());
+    }
+
+    #[test]
+    fn reads_multiple_values_in_one_cell() {
+        let data = "Name,E-mail 1 - Type,E-mail 1 - Value,E-mail 2 - Type,E-mail 2 - Value,\
+                    Phone 1 - Type,Phone 1 - Value,Group Membership\n\
+                    Taro,Home,taro@example.org ::: taro@example.net,* Work,taro@example.com,\
+                    Mobile,090-1111-2222 ::: 090-3333-4444,Friends ::: * myContacts ::: Family\n";
+        let cards = parse(data).unwrap();
+        let card = &cards[0];
+        assert_eq!(
+            card.emails(),
+            vec!["taro@example.com", "taro@example.org", "taro@example.net"]
+        );
+        let phones: Vec<&str> = card
+            .person
+            .phone_numbers
+            .iter()
+            .flatten()
+            .filter_map(|p| p.value.as_deref())
+            .collect();
+        assert_eq!(phones, vec!["090-1111-2222", "090-3333-4444"]);
+        assert_eq!(card.categories, vec!["Friends", "Family"]);
+    }
+}
@@ -0,0 +1,61 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! 連絡先を取り込むファイルの形式を扱う
+
+use crate::mod_google_csv; // Google CSVを読み込むため
+use crate::mod_json; // JSONを読み込むため
+use crate::mod_vcard::{self, Card, ParseError}; // vCardを読み込むため
+use std::str::FromStr; // 文字列から取り込む形式に変換するため
+
+/// 取り込むファイルの形式。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImportFormat {
+    VCard,     // vCard
+    GoogleCsv, // Google ContactsのWeb画面が読み書きするCSV
+    Json,      // `export --format json`で書き出したJSON
+    Ndjson,    // `export --format ndjson`で書き出したJSON
+}
+
+impl FromStr for ImportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vcard" => Ok(ImportFormat::VCard),
+            "google-csv" => Ok(ImportFormat::GoogleCsv),
+            "json" => Ok(ImportFormat::Json),
+            "ndjson" => Ok(ImportFormat::Ndjson),
+            _ => Err(s.to_string()),
+        }
+    }
+}
+
+/// ファイルの内容を、指定された形式で読み込む関数。
+///
+/// # 引数
+/// * `format` - ファイルの形式。
+/// * `data` - ファイルの内容。vCardは値ごとのCHARSETパラメータでデコードし、それ以外はUTF-8として解釈する。
+///
+/// # 戻り値
+/// 成功した場合は読み込んだ連絡先のベクター、形式が正しくない場合はエラー。
+pub fn parse(format: ImportFormat, data: &[u8]) -> Result<Vec<Card>, ParseError> {
+    let text = || String::from_utf8_lossy(data);
+    match format {
+        ImportFormat::VCard => mod_vcard::parse(data),
+        ImportFormat::GoogleCsv => mod_google_csv::parse(&text()),
+        ImportFormat::Json => mod_json::parse(&text()),
+        ImportFormat::Ndjson => mod_json::parse_ndjson(&text()),
+    }
+}
@@ -0,0 +1,582 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! 連絡先を正規化したJSON（スクリプトから扱うための形式）で書き出し、読み込む
+//!
+//! JSONの形式（キーと値の型）はREADMEに記載しており、変更する場合は`SCHEMA_VERSION`を上げます。
+
+use crate::mod_vcard::{self, Card, ParseError, RowValues}; // 読み込んだ連絡先をvCardと同じ形で取り込むため
+use google_people1::api::{
+    Address, Biography, EmailAddress, FieldMetadata, Name, Nickname, Organization, Person,
+    PhoneNumber,
+}; // Google Contactsの連絡先を扱うため
+use serde::{Deserialize, Serialize}; // JSONとの変換のため
+use std::collections::{BTreeMap, HashMap}; // ラベル名と.addressbookの行の対応を扱うため
+
+/// JSONの形式のバージョン
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// `--format json`で書き出すドキュメント。
+#[derive(Serialize, Deserialize)]
+pub struct Document {
+    pub version: u32,           // JSONの形式のバージョン（`SCHEMA_VERSION`）
+    pub contacts: Vec<Contact>, // 連絡先
+}
+
+/// 1つの連絡先。
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Contact {
+    pub resource_name: Option<String>, // Google Contactsのリソース名（"people/c123"）
+    pub display_name: Option<String>,  // 表示名
+    pub name: ContactName,             // 名前の要素
+    pub nicknames: Vec<String>,        // Google Contactsのニックネーム
+    pub emails: Vec<ContactEmail>,     // メールアドレスと.addressbookの行
+    pub phones: Vec<ContactPhone>,     // 電話番号
+    pub addresses: Vec<ContactAddress>, // 住所
+    pub organization: Option<ContactOrganization>, // 所属組織と役職
+    pub birthday: Option<String>,      // 誕生日（`YYYY-MM-DD`、年が無い場合は`--MM-DD`）
+    pub note: Option<String>,          // メモ
+    pub labels: Vec<String>,           // ラベル名
+}
+
+/// 名前の要素。
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ContactName {
+    pub given: Option<String>,           // 名
+    pub middle: Option<String>,          // ミドルネーム
+    pub family: Option<String>,          // 姓
+    pub prefix: Option<String>,          // 敬称（前）
+    pub suffix: Option<String>,          // 敬称（後）
+    pub phonetic_given: Option<String>,  // 名の読み仮名
+    pub phonetic_middle: Option<String>, // ミドルネームの読み仮名
+    pub phonetic_family: Option<String>, // 姓の読み仮名
+}
+
+/// メールアドレスと、対応する.addressbookの行。
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ContactEmail {
+    pub address: String,          // メールアドレス
+    pub r#type: Option<String>,   // 種類（"home"、"work"、"other"など）
+    pub primary: bool,            // メインのメールアドレスかどうか
+    pub nickname: Option<String>, // .addressbookのニックネーム（行が無い場合は`null`）
+    pub fcc: Option<String>,      // .addressbookのFcc（行が無い場合は`null`）
+}
+
+/// 電話番号。
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ContactPhone {
+    pub number: String,         // 電話番号
+    pub r#type: Option<String>, // 種類（"mobile"、"home"、"work"、"homeFax"など）
+    pub primary: bool,          // メインの電話番号かどうか
+}
+
+/// 住所。
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ContactAddress {
+    pub r#type: Option<String>, // 種類（"home"、"work"、"other"など）
+    pub primary: bool,          // メインの住所かどうか
+    pub po_box: Option<String>, // 私書箱
+    pub extended_address: Option<String>, // 拡張住所（建物名など）
+    pub street_address: Option<String>, // 番地
+    pub city: Option<String>,   // 市区町村
+    pub region: Option<String>, // 都道府県
+    pub postal_code: Option<String>, // 郵便番号
+    pub country: Option<String>, // 国
+}
+
+/// 所属組織と役職。
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ContactOrganization {
+    pub name: Option<String>,       // 組織名
+    pub department: Option<String>, // 部署
+    pub title: Option<String>,      // 役職
+}
+
+/// メインの値かどうかを返す。
+fn is_primary(metadata: &Option<FieldMetadata>) -> bool {
+    metadata
+        .as_ref()
+        .and_then(|m| m.primary)
+        .unwrap_or_default()
+}
+
+/// メインの値を先頭に並べ替える（メインの値どうし、それ以外の値どうしの順序は変えない）。
+fn primary_first<T>(mut values: Vec<T>, primary: fn(&T) -> bool) -> Vec<T> {
+    values.sort_by_key(|value| !primary(value));
+    values
+}
+
+impl Contact {
+    /// Googleの連絡先から、書き出す連絡先を作成する。
+    ///
+    /// # 引数
+    /// * `person` - Googleの連絡先。
+    /// * `labels` - 連絡先グループのリソース名とラベル名の対応。
+    /// * `rows` - 小文字のメールアドレスと、.addressbookの行のニックネームとFccの対応。
+    pub fn from_person(
+        person: &Person,
+        labels: &BTreeMap<String, String>,
+        rows: &HashMap<String, (String, String)>,
+    ) -> Self {
+        let name = person.names.iter().flatten().next();
+        let name_value = |f: fn(&Name) -> &Option<String>| name.and_then(|n| f(n).clone());
+
+        Contact {
+            resource_name: person.resource_name.clone(),
+            display_name: name_value(|n| &n.display_name),
+            name: ContactName {
+                given: name_value(|n| &n.given_name),
+                middle: name_value(|n| &n.middle_name),
+                family: name_value(|n| &n.family_name),
+                prefix: name_value(|n| &n.honorific_prefix),
+                suffix: name_value(|n| &n.honorific_suffix),
+                phonetic_given: name_value(|n| &n.phonetic_given_name),
+                phonetic_middle: name_value(|n| &n.phonetic_middle_name),
+                phonetic_family: name_value(|n| &n.phonetic_family_name),
+            },
+            nicknames: person
+                .nicknames
+                .iter()
+                .flatten()
+                .filter_map(|n| n.value.clone())
+                .collect(),
+            emails: person
+                .email_addresses
+                .iter()
+                .flatten()
+                .filter_map(|e| {
+                    let address = e.value.clone()?;
+                    let row = rows.get(&address.to_lowercase());
+                    Some(ContactEmail {
+                        r#type: e.type_.clone(),
+                        primary: is_primary(&e.metadata),
+                        nickname: row.map(|(nickname, _)| nickname.clone()),
+                        fcc: row.map(|(_, fcc)| fcc.clone()),
+                        address,
+                    })
+                })
+                .collect(),
+            phones: person
+                .phone_numbers
+                .iter()
+                .flatten()
+                .filter_map(|p| {
+                    Some(ContactPhone {
+                        number: p.value.clone()?,
+                        r#type: p.type_.clone(),
+                        primary: is_primary(&p.metadata),
+                    })
+                })
+                .collect(),
+            addresses: person
+                .addresses
+                .iter()
+                .flatten()
+                .map(|a| ContactAddress {
+                    r#type: a.type_.clone(),
+                    primary: is_primary(&a.metadata),
+                    po_box: a.po_box.clone(),
+                    extended_address: a.extended_address.clone(),
+                    street_address: a.street_address.clone(),
+                    city: a.city.clone(),
+                    region: a.region.clone(),
+                    postal_code: a.postal_code.clone(),
+                    country: a.country.clone(),
+                })
+                .collect(),
+            organization: person.organizations.iter().flatten().next().map(|o| {
+                ContactOrganization {
+                    name: o.name.clone(),
+                    department: o.department.clone(),
+                    title: o.title.clone(),
+                }
+            }),
+            birthday: person
+                .birthdays
+                .iter()
+                .flatten()
+                .next()
+                .and_then(mod_vcard::format_birthday),
+            note: person
+                .biographies
+                .iter()
+                .flatten()
+                .find_map(|b| b.value.clone()),
+            labels: person
+                .memberships
+                .iter()
+                .flatten()
+                .filter_map(|m| {
+                    let group = m.contact_group_membership.as_ref()?;
+                    labels.get(group.contact_group_resource_name.as_ref()?)
+                })
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// 読み込んだ連絡先を、取り込む連絡先に変換する。
+    ///
+    /// メインの値（`primary`が`true`）を先頭にし、メールアドレスの`nickname`と`fcc`を.addressbookの行の値とします。
+    fn into_card(self) -> Card {
+        let mut person = Person {
+            resource_name: self.resource_name.filter(|r| !r.is_empty()),
+            ..Default::default()
+        };
+
+        let n = self.name;
+        let name = Name {
+            display_name: self.display_name,
+            given_name: n.given,
+            middle_name: n.middle,
+            family_name: n.family,
+            honorific_prefix: n.prefix,
+            honorific_suffix: n.suffix,
+            phonetic_given_name: n.phonetic_given,
+            phonetic_middle_name: n.phonetic_middle,
+            phonetic_family_name: n.phonetic_family,
+            ..Default::default()
+        };
+        let has_name = [
+            &name.display_name,
+            &name.given_name,
+            &name.middle_name,
+            &name.family_name,
+            &name.honorific_prefix,
+            &name.honorific_suffix,
+        ]
+        .iter()
+        .any(|v| v.is_some());
+        if has_name {
+            let display_name = name
+                .display_name
+                .clone()
+                .or_else(|| mod_vcard::compose_display_name(&name));
+            person.names = Some(vec![Name {
+                display_name,
+                ..name
+            }]);
+        }
+
+        person.nicknames = Some(
+            self.nicknames
+                .into_iter()
+                .map(|value| Nickname {
+                    value: Some(value),
+                    ..Default::default()
+                })
+                .collect(),
+        )
+        .filter(|v: &Vec<Nickname>| !v.is_empty());
+
+        let mut rows = HashMap::new();
+        let emails = primary_first(self.emails, |e| e.primary);
+        person.email_addresses = Some(
+            emails
+                .into_iter()
+                .filter(|e| !e.address.trim().is_empty())
+                .map(|e| {
+                    let address = e.address.trim().to_string();
+                    rows.insert(
+                        address.to_lowercase(),
+                        RowValues {
+                            nickname: e.nickname.filter(|n| !n.is_empty()),
+                            fcc: e.fcc,
+                        },
+                    );
+                    EmailAddress {
+                        value: Some(address),
+                        type_: e.r#type,
+                        ..Default::default()
+                    }
+                })
+                .collect(),
+        )
+        .filter(|v: &Vec<EmailAddress>| !v.is_empty());
+
+        person.phone_numbers = Some(
+            primary_first(self.phones, |p| p.primary)
+                .into_iter()
+                .filter(|p| !p.number.trim().is_empty())
+                .map(|p| PhoneNumber {
+                    value: Some(p.number),
+                    type_: p.r#type,
+                    ..Default::default()
+                })
+                .collect(),
+        )
+        .filter(|v: &Vec<PhoneNumber>| !v.is_empty());
+
+        person.addresses = Some(
+            primary_first(self.addresses, |a| a.primary)
+                .into_iter()
+                .map(|a| Address {
+                    type_: a.r#type,
+                    po_box: a.po_box,
+                    extended_address: a.extended_address,
+                    street_address: a.street_address,
+                    city: a.city,
+                    region: a.region,
+                    postal_code: a.postal_code,
+                    country: a.country,
+                    ..Default::default()
+                })
+                .collect(),
+        )
+        .filter(|v: &Vec<Address>| !v.is_empty());
+
+        person.organizations = self.organization.map(|o| {
+            vec![Organization {
+                name: o.name,
+                department: o.department,
+                title: o.title,
+                ..Default::default()
+            }]
+        });
+        person.birthdays = self
+            .birthday
+            .filter(|b| !b.is_empty())
+            .map(|b| vec![mod_vcard::parse_birthday(&b)]);
+        person.biographies = self.note.filter(|n| !n.is_empty()).map(|value| {
+            vec![Biography {
+                value: Some(value),
+                content_type: Some("TEXT_PLAIN".to_string()),
+                ..Default::default()
+            }]
+        });
+
+        Card {
+            person,
+            categories: self.labels,
+            rows,
+        }
+    }
+}
+
+/// 連絡先を`--format json`のドキュメントとして書き出す関数。
+///
+/// # 引数
+/// * `contacts` - 書き出す連絡先。
+///
+/// # 戻り値
+/// 成功した場合は改行で終わるJSON、変換に失敗した場合はエラー。
+pub fn write_json(contacts: Vec<Contact>) -> serde_json::Result<String> {
+    let document = Document {
+        version: SCHEMA_VERSION,
+        contacts,
+    };
+    Ok(serde_json::to_string_pretty(&document)? + "\n")
+}
+
+/// 連絡先を1行に1つのJSON（NDJSON）として書き出す関数。
+///
+/// # 引数
+/// * `contacts` - 書き出す連絡先。
+///
+/// # 戻り値
+/// 成功した場合はNDJSON、変換に失敗した場合はエラー。
+pub fn write_ndjson(contacts: &[Contact]) -> serde_json::Result<String> {
+    let mut data = String::new();
+    for contact in contacts {
+        data.push_str(&serde_json::to_string(contact)?);
+        data.push('\n');
+    }
+    Ok(data)
+}
+
+/// JSONの解析エラーを、行番号を含む解析エラーに変換する。
+fn parse_error(e: serde_json::Error, line: usize) -> ParseError {
+    ParseError {
+        line,
+        message: e.to_string(),
+    }
+}
+
+/// `--format json`のドキュメントを読み込む関数。
+///
+/// # 引数
+/// * `data` - JSONファイルの内容。
+///
+/// # 戻り値
+/// 成功した場合は読み込んだ連絡先のベクター、形式が正しくない場合やバージョンが異なる場合はエラー。
+pub fn parse(data: &str) -> Result<Vec<Card>, ParseError> {
+    let document: Document = serde_json::from_str(data).map_err(|e| {
+        let line = e.line();
+        parse_error(e, line)
+    })?;
+    if document.version != SCHEMA_VERSION {
+        return Err(ParseError {
+            line: 1,
+            message: format!(
+                "unsupported version {} (expected {})",
+                document.version, SCHEMA_VERSION
+            ),
+        });
+    }
+    Ok(document
+        .contacts
+        .into_iter()
+        .map(Contact::into_card)
+        .collect())
+}
+
+/// NDJSON（1行に1つの連絡先）を読み込む関数。空行は無視します。
+///
+/// # 引数
+/// * `data` - NDJSONファイルの内容。
+///
+/// # 戻り値
+/// 成功した場合は読み込んだ連絡先のベクター、形式が正しくない行がある場合はエラー。
+pub fn parse_ndjson(data: &str) -> Result<Vec<Card>, ParseError> {
+    data.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str::<Contact>(line)
+                .map(Contact::into_card)
+                .map_err(|e| parse_error(e, i + 1))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mod_test;
+
+    /// テスト用の連絡先を作成する。
+    fn person() -> Person {
+        mod_test::person()
+            .resource_name("people/c123")
+            .full_name("Taro Tanaka", "Tanaka", "Taro")
+            .typed_email("taro@example.org", "home")
+            .primary_email("Taro@Example.com", "work")
+            .phone("03-1234-5678", "home")
+            .primary_phone("090-1234-5678", "mobile")
+            .birthday(4, 1)
+            .biography("memo\nline 2")
+            .group("contactGroups/abc")
+            .build()
+    }
+
+    /// 書き出す連絡先を作成する（taro@example.comだけに.addressbookの行がある）。
+    fn contacts() -> Vec<Contact> {
+        let labels = BTreeMap::from([("contactGroups/abc".to_string(), "Friends".to_string())]);
+        let rows = HashMap::from([(
+            "taro@example.com".to_string(),
+            ("taro".to_string(), "=sent".to_string()),
+        )]);
+        vec![Contact::from_person(&person(), &labels, &rows)]
+    }
+
+    /// 読み込んだ連絡先が、書き出した連絡先と同じ値であることを確認する。
+    fn assert_round_trip(cards: &[Card]) {
+        assert_eq!(cards.len(), 1);
+        let card = &cards[0];
+        assert_eq!(card.person.resource_name.as_deref(), Some("people/c123"));
+        let name = &card.person.names.as_ref().unwrap()[0];
+        assert_eq!(name.display_name.as_deref(), Some("Taro Tanaka"));
+        assert_eq!(name.family_name.as_deref(), Some("Tanaka"));
+        assert_eq!(card.categories, vec!["Friends"]);
+        let note = card.person.biographies.as_ref().unwrap()[0]
+            .value
+            .as_deref();
+        assert_eq!(note, Some("memo\nline 2"));
+        let birthday = &card.person.birthdays.as_ref().unwrap()[0];
+        assert_eq!(
+            mod_vcard::format_birthday(birthday).as_deref(),
+            Some("--04-01")
+        );
+
+        // メインの値を先頭にする
+        assert_eq!(card.emails(), vec!["Taro@Example.com", "taro@example.org"]);
+        let phones: Vec<&str> = card
+            .person
+            .phone_numbers
+            .iter()
+            .flatten()
+            .filter_map(|p| p.value.as_deref())
+            .collect();
+        assert_eq!(phones, vec!["090-1234-5678", "03-1234-5678"]);
+
+        // .addressbookの行のニックネームとFccは、小文字のメールアドレスに対応する
+        let row = &card.rows["taro@example.com"];
+        assert_eq!(row.nickname.as_deref(), Some("taro"));
+        assert_eq!(row.fcc.as_deref(), Some("=sent"));
+        let row = &card.rows["taro@example.org"];
+        assert_eq!(row.nickname, None);
+        assert_eq!(row.fcc, None);
+    }
+
+    #[test]
+    fn round_trips_a_json_document() {
+        let data = write_json(contacts()).unwrap();
+        let document: serde_json::Value = serde_json::from_str(&data).unwrap();
+        assert_eq!(document["version"], SCHEMA_VERSION);
+        let emails = &document["contacts"][0]["emails"];
+        assert_eq!(emails[1]["nickname"], "taro");
+        assert_eq!(emails[1]["primary"], true);
+        assert!(emails[0]["nickname"].is_null());
+
+        assert_round_trip(&parse(&data).unwrap());
+    }
+
+    #[test]
+    fn round_trips_ndjson() {
+        let mut contacts = contacts();
+        contacts.push(Contact {
+            display_name: Some("Hanako".to_string()),
+            ..Default::default()
+        });
+        let data = write_ndjson(&contacts).unwrap();
+        assert_eq!(data.lines().count(), 2);
+
+        let cards = parse_ndjson(&data).unwrap();
+        assert_eq!(cards.len(), 2);
+        assert_round_trip(&cards[..1]);
+        assert!(cards[1].emails().is_empty());
+    }
+
+    #[test]
+    fn rejects_another_schema_version() {
+        let data = format!("{{\"version\": {}, \"contacts\": []}}", SCHEMA_VERSION + 1);
+        let error = parse(&data).err().unwrap();
+        assert_eq!(error.line, 1);
+        assert!(error.message.contains("unsupported version"));
+        assert!(parse(&format!(
+            "{{\"version\": {}, \"contacts\": []}}",
+            SCHEMA_VERSION
+        ))
+        .is_ok());
+    }
+
+    #[test]
+    fn reports_the_line_of_an_invalid_ndjson_record() {
+        let data =
+            "{\"displayName\": \"Taro\"}\n\n{\"displayName\": \"Hanako\"}\n{\"emails\": 1}\n";
+        let error = parse_ndjson(data).err().unwrap();
+        assert_eq!(error.line, 4);
+
+        let error = parse("{\n  \"version\": 1,\n  \"contacts\": [\n    {\"emails\": 1}\n  ]\n}")
+            .err()
+            .unwrap();
+        assert_eq!(error.line, 4);
+    }
+}
@@ -0,0 +1,388 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! 連絡先をLDIF（RFC 2849）の`inetOrgPerson`/`mozillaAbPersonAlpha`のエントリーとして書き出す
+
+use crate::mod_vcard; // 連絡先ごとに変わらないUIDを作成するため
+use base64::{engine::general_purpose, Engine as _}; // ASCII以外の文字を含む値をBase64で書き出すため
+use google_people1::api::Person; // Google Contactsの連絡先を扱うため
+
+/// ファイルの先頭に書き出すLDIFのバージョン（RFC 2849のversion-spec）
+const HEADER: &str = "version: 1\n\n";
+
+/// 1行の最大の長さ（これを超える行は折り返す）
+const MAX_LINE_LENGTH: usize = 76;
+
+/// エントリーのオブジェクトクラス
+const OBJECT_CLASSES: [&str; 5] = [
+    "top",
+    "person",
+    "organizationalPerson",
+    "inetOrgPerson",
+    "mozillaAbPersonAlpha",
+];
+
+/// 値をそのまま書き出せる（SAFE-STRINGである）かどうかを返す。
+///
+/// ASCII以外の文字や制御文字を含む値、空白・`:`・`<`で始まる値、空白で終わる値はBase64で書き出す必要があります。
+fn is_safe(value: &str) -> bool {
+    value.chars().all(|c| c.is_ascii() && !c.is_ascii_control())
+        && !value.starts_with([' ', ':', '<'])
+        && !value.ends_with(' ')
+}
+
+/// 属性と値の行を、必要に応じてBase64にして折り返して追加する。
+fn push_attribute(out: &mut String, attribute: &str, value: &str) {
+    let line = if is_safe(value) {
+        format!("{}: {}", attribute, value)
+    } else {
+        format!(
+            "{}:: {}",
+            attribute,
+            general_purpose::STANDARD.encode(value.as_bytes())
+        )
+    };
+
+    // 折り返した行は空白1文字で始める（行はASCIIのみのため、バイト数で区切ってよい）
+    let mut rest = line.as_str();
+    let mut width = MAX_LINE_LENGTH;
+    while rest.len() > width {
+        let (head, tail) = rest.split_at(width);
+        out.push_str(head);
+        out.push_str("\n ");
+        rest = tail;
+        width = MAX_LINE_LENGTH - 1;
+    }
+    out.push_str(rest);
+    out.push('\n');
+}
+
+/// DNの属性値として使用できるように特殊文字をエスケープする（RFC 4514）。
+fn escape_dn_value(value: &str) -> String {
+    let mut escaped = String::new();
+    let last = value.chars().count().saturating_sub(1);
+    for (i, c) in value.chars().enumerate() {
+        let special = matches!(c, ',' | '+' | '"' | '\\' | '<' | '>' | ';' | '=')
+            || (i == 0 && matches!(c, ' ' | '#'))
+            || (i == last && c == ' ');
+        if special {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// テンプレートからエントリーのDNを作成する。
+///
+/// 値が空になるプレースホルダーを含むRDN（`,`で区切った各部分）は省略します。
+/// すべてのRDNが省略された場合は`uid`を使用します。
+fn build_dn(template: &str, cn: &str, mail: &str, uid: &str) -> String {
+    let rdns: Vec<String> = template
+        .split(',')
+        .filter_map(|rdn| {
+            let mut rdn = rdn.trim().to_string();
+            for (placeholder, value) in [("{cn}", cn), ("{mail}", mail), ("{uid}", uid)] {
+                if rdn.contains(placeholder) {
+                    if value.is_empty() {
+                        return None;
+                    }
+                    rdn = rdn.replace(placeholder, &escape_dn_value(value));
+                }
+            }
+            Some(rdn)
+        })
+        .collect();
+    if rdns.is_empty() {
+        format!("uid={}", escape_dn_value(uid))
+    } else {
+        rdns.join(",")
+    }
+}
+
+/// Google Contactsの電話番号の種類を、LDIFの属性名に変換する。
+fn phone_attribute(type_: Option<&str>) -> &'static str {
+    match type_ {
+        Some("home") => "homePhone",
+        Some("mobile") | Some("workMobile") => "mobile",
+        Some("homeFax") | Some("workFax") | Some("otherFax") => "facsimileTelephoneNumber",
+        Some("pager") | Some("workPager") => "pager",
+        _ => "telephoneNumber",
+    }
+}
+
+/// 1つの連絡先のエントリーを作成する。
+///
+/// # 引数
+/// * `person` - Googleの連絡先。
+/// * `nickname` - 書き出すニックネーム（`mozillaNickname`）。空の場合は書き出しません。
+/// * `dn_template` - DNのテンプレート（`{cn}`、`{mail}`、`{uid}`を使用できる）。
+///
+/// # 戻り値
+/// 空行で終わるエントリー。名前として書き出せる値が無い場合は`None`。
+fn write_entry(person: &Person, nickname: &str, dn_template: &str) -> Option<String> {
+    let name = person.names.iter().flatten().next();
+    let organization = person.organizations.iter().flatten().next();
+    let emails: Vec<&str> = person
+        .email_addresses
+        .iter()
+        .flatten()
+        .filter_map(|e| e.value.as_deref())
+        .filter(|v| !v.is_empty())
+        .collect();
+    let mail = emails.first().copied().unwrap_or_default();
+
+    // cnとsnは必須のため、名前が無い場合は所属組織名かメールアドレスを使用する
+    let cn = name
+        .and_then(|n| n.display_name.as_deref())
+        .or_else(|| organization.and_then(|o| o.name.as_deref()))
+        .or(emails.first().copied())
+        .map(|v| v.trim().replace('\n', " "))
+        .filter(|v| !v.is_empty())?;
+    let sn = name
+        .and_then(|n| n.family_name.as_deref())
+        .filter(|v| !v.is_empty())
+        .unwrap_or(&cn);
+
+    let mut attributes: Vec<(&str, String)> = Vec::new();
+    let mut push = |attribute: &'static str, value: &str| {
+        let value = value.trim();
+        if !value.is_empty() {
+            attributes.push((attribute, value.to_string()));
+        }
+    };
+
+    for class in OBJECT_CLASSES {
+        push("objectclass", class);
+    }
+    if let Some(given_name) = name.and_then(|n| n.given_name.as_deref()) {
+        push("givenName", given_name);
+    }
+    push("sn", sn);
+    push("cn", &cn);
+    push("mozillaNickname", nickname);
+    push("uid", &mod_vcard::uid(person));
+
+    // メールアドレス（Thunderbirdは2つまで扱う）
+    push("mail", mail);
+    if let Some(second) = emails.get(1) {
+        push("mozillaSecondEmail", second);
+    }
+
+    // 電話番号
+    for p in person.phone_numbers.iter().flatten() {
+        if let Some(number) = p.value.as_deref() {
+            push(phone_attribute(p.type_.as_deref()), number);
+        }
+    }
+
+    // 住所（自宅と勤務先の最初の住所。番地の2行目以降はStreet2に書き出す）
+    let mut home_written = false;
+    let mut work_written = false;
+    for a in person.addresses.iter().flatten() {
+        let street = a.street_address.as_deref().unwrap_or_default();
+        let (street, street2) = street.split_once('\n').unwrap_or((street, ""));
+        let attribute_names = match a.type_.as_deref() {
+            Some("work") if !work_written => {
+                work_written = true;
+                ["street", "mozillaWorkStreet2", "l", "st", "postalCode", "c"]
+            }
+            Some("work") => continue,
+            _ if !home_written => {
+                home_written = true;
+                [
+                    "mozillaHomeStreet",
+                    "mozillaHomeStreet2",
+                    "mozillaHomeLocalityName",
+                    "mozillaHomeState",
+                    "mozillaHomePostalCode",
+                    "mozillaHomeCountryName",
+                ]
+            }
+            _ => continue,
+        };
+        let street2 = street2.replace('\n', " ");
+        let values = [
+            street,
+            street2.as_str(),
+            a.city.as_deref().unwrap_or_default(),
+            a.region.as_deref().unwrap_or_default(),
+            a.postal_code.as_deref().unwrap_or_default(),
+            a.country.as_deref().unwrap_or_default(),
+        ];
+        for (attribute, value) in attribute_names.into_iter().zip(values) {
+            push(attribute, value);
+        }
+    }
+
+    // 所属組織と役職
+    if let Some(o) = organization {
+        push("o", o.name.as_deref().unwrap_or_default());
+        push("ou", o.department.as_deref().unwrap_or_default());
+        push("title", o.title.as_deref().unwrap_or_default());
+    }
+
+    // 誕生日（Thunderbirdの形式で年、月、日を分けて書き出す）
+    if let Some(date) = person
+        .birthdays
+        .iter()
+        .flatten()
+        .find_map(|b| b.date.as_ref())
+    {
+        if let Some(year) = date.year {
+            push("birthyear", &year.to_string());
+        }
+        if let Some(month) = date.month {
+            push("birthmonth", &format!("{:02}", month));
+        }
+        if let Some(day) = date.day {
+            push("birthday", &format!("{:02}", day));
+        }
+    }
+
+    // メモ（改行を含む場合はBase64で書き出す）
+    if let Some(note) = person
+        .biographies
+        .iter()
+        .flatten()
+        .find_map(|b| b.value.as_deref())
+    {
+        push("description", note);
+    }
+
+    let mut entry = String::new();
+    let dn = build_dn(dn_template, &cn, mail, &mod_vcard::uid(person));
+    push_attribute(&mut entry, "dn", &dn);
+    for (attribute, value) in &attributes {
+        push_attribute(&mut entry, attribute, value);
+    }
+    entry.push('\n');
+    Some(entry)
+}
+
+/// Googleの連絡先から、LDIFファイルの内容を作成する関数。
+///
+/// ファイルは`version: 1`の行で始めます。名前、所属組織名、メールアドレスのいずれも無い連絡先は書き出しません。
+///
+/// # 引数
+/// * `entries` - Googleの連絡先と、書き出すニックネームのタプルのスライス。
+/// * `dn_template` - DNのテンプレート（設定ファイルの`[ldif] dn`）。
+///
+/// # 戻り値
+/// LDIFファイルの内容。
+pub fn write_ldif(entries: &[(&Person, String)], dn_template: &str) -> String {
+    let mut data = HEADER.to_string();
+    for (person, nickname) in entries {
+        if let Some(entry) = write_entry(person, nickname, dn_template) {
+            data.push_str(&entry);
+        }
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mod_test;
+
+    /// テスト用の連絡先を作成する。
+    fn person() -> Person {
+        mod_test::person()
+            .full_name("田中 太郎", "田中", "太郎")
+            .email("taro@example.com")
+            .build()
+    }
+
+    /// 折り返された行を戻し、属性と値の行のベクターを返す。
+    fn unfold(data: &str) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        for line in data.lines() {
+            match line.strip_prefix(' ') {
+                Some(rest) => lines.last_mut().unwrap().push_str(rest),
+                None => lines.push(line.to_string()),
+            }
+        }
+        lines
+    }
+
+    fn decode(line: &str, attribute: &str) -> String {
+        let encoded = line.strip_prefix(&format!("{}:: ", attribute)).unwrap();
+        String::from_utf8(general_purpose::STANDARD.decode(encoded).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn starts_with_the_version_line() {
+        let person = person();
+        let data = write_ldif(&[(&person, String::new())], "cn={cn},mail={mail}");
+        assert!(data.starts_with("version: 1\n\ndn"));
+        assert_eq!(write_ldif(&[], "cn={cn}"), "version: 1\n\n");
+    }
+
+    #[test]
+    fn encodes_non_ascii_values_in_base64() {
+        let person = person();
+        let data = write_ldif(&[(&person, String::new())], "cn={cn},mail={mail}");
+        let lines = unfold(&data);
+        let cn = lines.iter().find(|l| l.starts_with("cn::")).unwrap();
+        assert_eq!(decode(cn, "cn"), "田中 太郎");
+        let dn = lines.iter().find(|l| l.starts_with("dn::")).unwrap();
+        assert_eq!(decode(dn, "dn"), "cn=田中 太郎,mail=taro@example.com");
+        assert!(lines.contains(&"mail: taro@example.com".to_string()));
+    }
+
+    #[test]
+    fn encodes_values_starting_with_a_space_or_colon() {
+        for value in [" memo", ":memo", "<memo", "memo "] {
+            let mut out = String::new();
+            push_attribute(&mut out, "description", value);
+            assert_eq!(decode(out.trim_end(), "description"), value);
+        }
+        let mut out = String::new();
+        push_attribute(&mut out, "description", "memo: ok");
+        assert_eq!(out, "description: memo: ok\n");
+    }
+
+    #[test]
+    fn folds_lines_longer_than_76_bytes() {
+        let value = "x".repeat(200);
+        let mut out = String::new();
+        push_attribute(&mut out, "description", &value);
+        let lines: Vec<&str> = out.lines().collect();
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|l| l.len() <= MAX_LINE_LENGTH));
+        assert!(lines[1..].iter().all(|l| l.starts_with(' ')));
+        assert_eq!(unfold(&out), [format!("description: {}", value)]);
+    }
+
+    #[test]
+    fn builds_the_dn_from_the_template() {
+        assert_eq!(
+            build_dn(
+                "cn={cn},mail={mail}",
+                "Tanaka, Taro",
+                "taro@example.com",
+                "u1"
+            ),
+            "cn=Tanaka\\, Taro,mail=taro@example.com"
+        );
+        assert_eq!(
+            build_dn("uid={uid},ou=contacts,dc=example,dc=com", "Taro", "", "u1"),
+            "uid=u1,ou=contacts,dc=example,dc=com"
+        );
+        // 値が空になるRDNは省略し、すべて省略された場合はuidを使用する
+        assert_eq!(build_dn("cn={cn},mail={mail}", "Taro", "", "u1"), "cn=Taro");
+        assert_eq!(build_dn("mail={mail}", "Taro", "", "u1"), "uid=u1");
+        assert_eq!(build_dn("cn={cn}", "#Taro ", "", "u1"), "cn=\\#Taro\\ ");
+    }
+}
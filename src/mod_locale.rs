@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-/// システムの環境変数からロケール設定を取得する
+//! システムの環境変数からロケール設定を取得する
 
 use std::env; // 環境変数を扱うための 'env' モジュールをインポート
 
@@ -62,7 +62,7 @@ pub fn get_locale_from_env() -> String {
 ///
 /// # 戻り値
 /// ロケールコードの形式が有効であれば `true`、そうでなければ `false`。
-fn is_valid_locale_format(code: &str) -> bool {
+pub fn is_valid_locale_format(code: &str) -> bool {
     // ロケールコードを '-' で分割して部分文字列のベクトルを生成
     let parts: Vec<&str> = code.split('-').collect();
     // ロケールコードが2つの部分から成り、各部分が英数字のみで構成されているかをチェック
@@ -0,0 +1,74 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! 連絡先をmuttのエイリアスファイル（`alias nick Name <email>`）の形式で書き出す
+
+/// 引用符で囲む必要がある文字（RFC 5322のspecials）
+const SPECIALS: &[char] = &[
+    '(', ')', '<', '>', '[', ']', ':', ';', '@', '\\', ',', '.', '"',
+];
+
+/// 名前を表示名として書き出す形式に変換する。
+///
+/// 特殊文字を含む名前は二重引用符で囲み、`\`と`"`をエスケープします。
+fn quote_name(name: &str) -> String {
+    if !name.contains(SPECIALS) {
+        return name.to_string();
+    }
+    let escaped = name.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("\"{}\"", escaped)
+}
+
+/// 名前とメールアドレスから、エイリアスに書き出すアドレス（`Name <email>`）を作成する。
+///
+/// # 引数
+/// * `name` - 名前。空の場合は`<email>`のみ。
+/// * `email` - メールアドレス。
+pub fn address(name: &str, email: &str) -> String {
+    let name = name.trim();
+    if name.is_empty() {
+        format!("<{}>", email)
+    } else {
+        format!("{} <{}>", quote_name(name), email)
+    }
+}
+
+/// ニックネームやラベル名から、エイリアス名として使用できる文字列を作成する。
+///
+/// 空白、カンマ、引用符などは`_`に置き換えます（英数字と`-_.+`以外の文字）。
+pub fn alias_name(label: &str) -> String {
+    label
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '+') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// 1つのエイリアスの行を作成する。
+///
+/// # 引数
+/// * `nickname` - エイリアス名（`alias_name`で使用できない文字を置き換える）。
+/// * `addresses` - `address`で作成したアドレス。複数の場合はカンマで区切る。
+///
+/// # 戻り値
+/// 改行を含むエイリアスの行。
+pub fn alias_line(nickname: &str, addresses: &[String]) -> String {
+    format!("alias {} {}\n", alias_name(nickname), addresses.join(", "))
+}
@@ -0,0 +1,780 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! オフラインで行った連絡先の変更を、次にGoogle Contactsに接続したときに反映するまで保存する（送信待ちの変更）
+
+use crate::mod_dirs; // 状態ディレクトリに保存するため
+use crate::mod_perm; // 連絡先を含むファイルをパーミッション0600で書き込むため
+use crate::mod_report::Action; // 反映した操作を報告するため
+use crate::mod_state::Entry; // 変更後の連絡先の値
+use async_trait::async_trait; // 反映の処理を非同期のトレイトとして定義するため
+use google_people1::api::Person; // 現在のGoogle Contactsの連絡先と照合するため
+use serde::{Deserialize, Serialize}; // JSON形式で保存するため
+use std::error::Error; // 反映に失敗した理由を返すため
+use std::fs; // ファイルシステム操作のための標準ライブラリのモジュール
+use std::io; // エラーを返すための 'io' モジュール
+use std::path::{Path, PathBuf}; // ファイルパスを扱うため
+
+/// オフラインで作成した連絡先に付ける仮のリソース名の接頭辞。
+///
+/// 連絡先のキャッシュでは`outbox/<id>`をリソース名として使用し、後の変更を同じ連絡先の作成にまとめます。
+pub const PLACEHOLDER_PREFIX: &str = "outbox/";
+
+/// Google Contactsに行う変更。
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Mutation {
+    /// 連絡先を作成する（`update_google_contacts`に既存の連絡先を渡さない場合と同じ）
+    Create { id: u64, contact: Entry },
+    /// 既存の連絡先を更新する（`update_google_contacts`に既存の連絡先を渡す場合と同じ）
+    Update {
+        resource_name: String,
+        etag: String,
+        /// 変更前のメールアドレス（連絡先のこのメールアドレスだけを置き換える）
+        #[serde(default)]
+        email: String,
+        contact: Entry,
+    },
+    /// 既存の連絡先から1つのメールアドレスを取り除く（`remove_gperson_email`と同じ）
+    RemoveEmail {
+        resource_name: String,
+        etag: String,
+        email: String,
+    },
+    /// 既存の連絡先を削除する（`remove_related_gperson`と同じ）
+    Delete {
+        resource_name: String,
+        etag: String,
+        email: String,
+    },
+}
+
+impl Mutation {
+    /// 報告やメッセージに使用するメールアドレスを返す。
+    pub fn email(&self) -> &str {
+        match self {
+            Mutation::Create { contact, .. } | Mutation::Update { contact, .. } => &contact.email,
+            Mutation::RemoveEmail { email, .. } | Mutation::Delete { email, .. } => email,
+        }
+    }
+}
+
+/// 送信待ちの変更。
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Pending {
+    pub time: String, // 変更した時刻（RFC 3339）
+    #[serde(flatten)]
+    pub mutation: Mutation, // Google Contactsに行う変更
+}
+
+/// オフラインで作成した連絡先の仮のリソース名を返す。
+pub fn placeholder(id: u64) -> String {
+    format!("{}{}", PLACEHOLDER_PREFIX, id)
+}
+
+/// 仮のリソース名から、送信待ちの作成の番号を取得する。
+///
+/// # 戻り値
+/// 仮のリソース名の場合は番号、Google Contactsのリソース名の場合は`None`。
+pub fn placeholder_id(resource_name: &str) -> Option<u64> {
+    resource_name.strip_prefix(PLACEHOLDER_PREFIX)?.parse().ok()
+}
+
+/// 次にオフラインで作成する連絡先の番号を返す。
+pub fn next_id(outbox: &[Pending]) -> u64 {
+    outbox
+        .iter()
+        .filter_map(|p| match p.mutation {
+            Mutation::Create { id, .. } => Some(id),
+            _ => None,
+        })
+        .max()
+        .map_or(1, |id| id + 1)
+}
+
+/// 送信待ちの変更を追加する関数。
+///
+/// 同じ連絡先への変更はまとめます。オフラインで作成した連絡先の更新は作成の値を置き換え、削除は作成を取り消します。
+/// 既存の連絡先の更新は、同じ行（変更後のメールアドレスが一致する）の送信待ちの更新の値を置き換えます。
+/// メールアドレスの削除は同じ行の送信待ちの更新を、連絡先の削除はその連絡先の送信待ちの変更をすべて取り消します。
+/// 競合の検出には、最初に変更したときのetagを使用します。
+///
+/// # 引数
+/// * `outbox` - 送信待ちの変更。
+/// * `mutation` - 追加する変更。
+pub fn record(outbox: &mut Vec<Pending>, mutation: Mutation) {
+    let time = chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false);
+    let created = |outbox: &Vec<Pending>, target: u64| {
+        outbox
+            .iter()
+            .position(|p| matches!(p.mutation, Mutation::Create { id, .. } if id == target))
+    };
+    // 変更後のメールアドレスが`email`である、送信待ちの更新の位置
+    let updated = |outbox: &Vec<Pending>, target: &str, email: &str| {
+        outbox.iter().position(|p| {
+            matches!(&p.mutation, Mutation::Update { resource_name, contact, .. }
+                if resource_name == target && contact.email.eq_ignore_ascii_case(email))
+        })
+    };
+
+    match mutation {
+        Mutation::Update {
+            resource_name,
+            etag,
+            email,
+            contact,
+        } => {
+            if let Some(index) = placeholder_id(&resource_name).and_then(|id| created(outbox, id)) {
+                if let Mutation::Create { contact: c, .. } = &mut outbox[index].mutation {
+                    *c = contact;
+                }
+                outbox[index].time = time;
+            } else if let Some(index) = updated(outbox, &resource_name, &email) {
+                if let Mutation::Update { contact: c, .. } = &mut outbox[index].mutation {
+                    *c = contact;
+                }
+                outbox[index].time = time;
+            } else {
+                outbox.push(Pending {
+                    time,
+                    mutation: Mutation::Update {
+                        resource_name,
+                        etag,
+                        email,
+                        contact,
+                    },
+                });
+            }
+        }
+        Mutation::RemoveEmail {
+            resource_name,
+            etag,
+            email,
+        } => {
+            // 送信待ちの更新を取り消し、Google Contactsにある変更前のメールアドレスを取り除く
+            let index = updated(outbox, &resource_name, &email);
+            let (etag, email) = match index.map(|i| outbox.remove(i).mutation) {
+                Some(Mutation::Update {
+                    etag,
+                    email: previous,
+                    ..
+                }) if !previous.is_empty() => (etag, previous),
+                Some(Mutation::Update { etag, .. }) => (etag, email),
+                _ => (etag, email),
+            };
+            outbox.push(Pending {
+                time,
+                mutation: Mutation::RemoveEmail {
+                    resource_name,
+                    etag,
+                    email,
+                },
+            });
+        }
+        Mutation::Delete {
+            resource_name,
+            etag,
+            email,
+        } => {
+            if let Some(index) = placeholder_id(&resource_name).and_then(|id| created(outbox, id)) {
+                outbox.remove(index);
+                return;
+            }
+            // 送信待ちの更新とメールアドレスの削除を取り消し、最初に変更したときのetagで削除する
+            let mut first_etag = None;
+            outbox.retain(|p| match &p.mutation {
+                Mutation::Update {
+                    resource_name: r,
+                    etag,
+                    ..
+                }
+                | Mutation::RemoveEmail {
+                    resource_name: r,
+                    etag,
+                    ..
+                } if *r == resource_name => {
+                    first_etag.get_or_insert_with(|| etag.clone());
+                    false
+                }
+                _ => true,
+            });
+            outbox.push(Pending {
+                time,
+                mutation: Mutation::Delete {
+                    resource_name,
+                    etag: first_etag.unwrap_or(etag),
+                    email,
+                },
+            });
+        }
+        mutation => outbox.push(Pending { time, mutation }),
+    }
+}
+
+/// 送信待ちの変更を保存するファイルのパスを取得する関数。
+///
+/// トークンキャッシュと同じく、アカウントごとに別のファイル（`outbox.<account>.json`）に保存します。
+///
+/// # 引数
+/// * `account` - アカウント名。空の場合は既定のアカウント。
+///
+/// # 戻り値
+/// 成功した場合はファイルのパス、ホームディレクトリが見つからない場合はエラー。
+pub fn outbox_path(account: &str) -> io::Result<PathBuf> {
+    let file_name = if account.is_empty() {
+        "outbox.json".to_string()
+    } else {
+        format!("outbox.{}.json", account)
+    };
+    Ok(mod_dirs::state_dir()?.join(file_name))
+}
+
+/// 送信待ちの変更を読み込む関数。
+///
+/// # 引数
+/// * `account` - アカウント名。
+///
+/// # 戻り値
+/// 成功した場合は送信待ちの変更（無い場合は空）、読み込みや解析に失敗した場合はエラー。
+pub fn load(account: &str) -> io::Result<Vec<Pending>> {
+    load_from(&outbox_path(account)?)
+}
+
+/// 指定したファイルから送信待ちの変更を読み込む関数。
+///
+/// # 引数
+/// * `path` - 送信待ちの変更を保存したファイルのパス。
+///
+/// # 戻り値
+/// 成功した場合は送信待ちの変更（ファイルが無い場合は空）、読み込みや解析に失敗した場合はエラー。
+fn load_from(path: &Path) -> io::Result<Vec<Pending>> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    serde_json::from_slice(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// 送信待ちの変更を保存する関数。
+///
+/// 途中で中断しても変更を失わないように、一時ファイルに書き込んでから置き換えます。
+/// 送信待ちの変更が無い場合はファイルを削除します。
+///
+/// # 引数
+/// * `account` - アカウント名。
+/// * `outbox` - 保存する送信待ちの変更。
+///
+/// # 戻り値
+/// 成功した場合はOk(())、書き込みに失敗した場合はエラー。
+pub fn save(account: &str, outbox: &[Pending]) -> io::Result<()> {
+    let path = outbox_path(account)?;
+    if !outbox.is_empty() {
+        mod_dirs::ensure_dirs()?;
+    }
+    save_to(&path, outbox)
+}
+
+/// 指定したファイルに送信待ちの変更を保存する関数。
+///
+/// # 引数
+/// * `path` - 保存するファイルのパス。
+/// * `outbox` - 保存する送信待ちの変更。空の場合はファイルを削除する。
+///
+/// # 戻り値
+/// 成功した場合はOk(())、書き込みに失敗した場合はエラー。
+fn save_to(path: &Path, outbox: &[Pending]) -> io::Result<()> {
+    if outbox.is_empty() {
+        return match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+
+    let data = serde_json::to_vec_pretty(outbox)?;
+    let staged = path.with_extension("json.tmp");
+    mod_perm::write_private_file(&staged, &data)?;
+    if let Err(e) = fs::rename(&staged, path) {
+        let _ = fs::remove_file(&staged);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// 送信待ちの変更をGoogle Contactsに反映する処理。
+#[async_trait(?Send)]
+pub trait Target {
+    /// 連絡先を作成する。
+    async fn create(&self, contact: &Entry) -> Result<(), Box<dyn Error>>;
+    /// 既存の連絡先の、変更前のメールアドレスの値を更新する。
+    async fn update(
+        &self,
+        gperson: &Person,
+        previous_email: &str,
+        contact: &Entry,
+    ) -> Result<(), Box<dyn Error>>;
+    /// 既存の連絡先から1つのメールアドレスを取り除く。
+    async fn remove_email(&self, gperson: &Person, email: &str) -> Result<(), Box<dyn Error>>;
+    /// 既存の連絡先を削除する。
+    async fn delete(&self, gperson: &Person) -> Result<(), Box<dyn Error>>;
+}
+
+/// 送信待ちの変更を1つ反映した結果。
+pub enum Replayed {
+    Applied(Action),        // 反映した操作
+    Conflict(&'static str), // 競合したため破棄した（警告のメッセージID）
+}
+
+/// 反映に失敗した変更の操作と理由。
+pub type Failed = (Action, Box<dyn Error>);
+
+/// 最初の送信待ちの変更をGoogle Contactsに反映する非同期関数。
+///
+/// 変更を現在のGoogle Contactsの連絡先と照合して競合を検出します。更新と削除は連絡先が削除されているか
+/// etagがオフラインで変更した時点から変わっている場合、作成は同じメールアドレスの連絡先が既にある場合に
+/// 競合とし、反映せずに破棄します。反映した変更と競合した変更は送信待ちから取り除き、
+/// 反映に失敗した変更は次の同期まで送信待ちに残します。
+///
+/// # 引数
+/// * `outbox` - 送信待ちの変更。
+/// * `gpersons` - 現在のGoogle Contactsのすべての連絡先。
+/// * `target` - 変更を反映する処理。
+///
+/// # 戻り値
+/// 送信待ちの変更が無い場合は`None`、それ以外は変更のメールアドレスと反映の結果。
+pub async fn replay_first(
+    outbox: &mut Vec<Pending>,
+    gpersons: &[Person],
+    target: &dyn Target,
+) -> Option<(String, Result<Replayed, Failed>)> {
+    let mutation = &outbox.first()?.mutation;
+    let email = mutation.email().to_string();
+    let find = |name: &str| {
+        gpersons
+            .iter()
+            .find(|p| p.resource_name.as_deref() == Some(name))
+    };
+    let current = |resource_name: &str, etag: &str| match find(resource_name) {
+        None => Err("outbox-conflict-deleted"),
+        Some(gperson) if gperson.etag.as_deref() != Some(etag) => Err("outbox-conflict-changed"),
+        Some(gperson) => Ok(gperson),
+    };
+
+    let replayed = match mutation {
+        Mutation::Create { contact, .. } => {
+            let exists = gpersons.iter().any(|p| {
+                p.email_addresses
+                    .iter()
+                    .flatten()
+                    .filter_map(|e| e.value.as_deref())
+                    .any(|v| v.eq_ignore_ascii_case(&contact.email))
+            });
+            if exists {
+                Err("outbox-conflict-exists")
+            } else {
+                Ok((Action::Add, target.create(contact).await))
+            }
+        }
+        Mutation::Update {
+            resource_name,
+            etag,
+            email: previous_email,
+            contact,
+        } => match current(resource_name, etag) {
+            Ok(gperson) => {
+                // 以前の形式の送信待ちの更新には、変更前のメールアドレスが無い
+                let previous_email = match previous_email.as_str() {
+                    "" => contact.email.as_str(),
+                    previous_email => previous_email,
+                };
+                let result = target.update(gperson, previous_email, contact).await;
+                Ok((Action::Update, result))
+            }
+            Err(message_id) => Err(message_id),
+        },
+        Mutation::RemoveEmail {
+            resource_name,
+            etag,
+            email,
+        } => match current(resource_name, etag) {
+            Ok(gperson) => Ok((Action::Update, target.remove_email(gperson, email).await)),
+            Err(message_id) => Err(message_id),
+        },
+        Mutation::Delete {
+            resource_name,
+            etag,
+            ..
+        } => match current(resource_name, etag) {
+            Ok(gperson) => Ok((Action::Delete, target.delete(gperson).await)),
+            Err(message_id) => Err(message_id),
+        },
+    };
+
+    let result = match replayed {
+        Ok((action, Err(e))) => return Some((email, Err((action, e)))),
+        Ok((action, Ok(()))) => Replayed::Applied(action),
+        Err(message_id) => Replayed::Conflict(message_id),
+    };
+    outbox.remove(0);
+    Some((email, Ok(result)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mod_test::temp_path;
+    use google_people1::api::EmailAddress;
+    use std::cell::RefCell;
+
+    fn entry(email: &str, name: &str) -> Entry {
+        Entry {
+            nickname: "taro".to_string(),
+            name: name.to_string(),
+            email: email.to_string(),
+            fcc: String::new(),
+            comment: String::new(),
+        }
+    }
+
+    fn gperson(resource_name: &str, etag: &str, email: &str) -> Person {
+        Person {
+            resource_name: Some(resource_name.to_string()),
+            etag: Some(etag.to_string()),
+            email_addresses: Some(vec![EmailAddress {
+                value: Some(email.to_string()),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        }
+    }
+
+    fn update(resource_name: &str, etag: &str, name: &str) -> Mutation {
+        Mutation::Update {
+            resource_name: resource_name.to_string(),
+            etag: etag.to_string(),
+            email: "taro@example.com".to_string(),
+            contact: entry("taro@example.com", name),
+        }
+    }
+
+    fn delete(resource_name: &str, etag: &str) -> Mutation {
+        Mutation::Delete {
+            resource_name: resource_name.to_string(),
+            etag: etag.to_string(),
+            email: "taro@example.com".to_string(),
+        }
+    }
+
+    /// 呼び出された操作を記録し、`fail`の場合は失敗する反映先。
+    #[derive(Default)]
+    struct FakeTarget {
+        calls: RefCell<Vec<String>>,
+        fail: bool,
+    }
+
+    impl FakeTarget {
+        fn call(&self, call: String) -> Result<(), Box<dyn Error>> {
+            self.calls.borrow_mut().push(call);
+            if self.fail {
+                Err("network is unreachable".into())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl Target for FakeTarget {
+        async fn create(&self, contact: &Entry) -> Result<(), Box<dyn Error>> {
+            self.call(format!("create {}", contact.name))
+        }
+
+        async fn update(
+            &self,
+            gperson: &Person,
+            previous_email: &str,
+            contact: &Entry,
+        ) -> Result<(), Box<dyn Error>> {
+            let name = gperson.resource_name.as_deref().unwrap_or_default();
+            self.call(format!(
+                "update {} {} {}",
+                name, previous_email, contact.name
+            ))
+        }
+
+        async fn remove_email(&self, gperson: &Person, email: &str) -> Result<(), Box<dyn Error>> {
+            let name = gperson.resource_name.as_deref().unwrap_or_default();
+            self.call(format!("remove-email {} {}", name, email))
+        }
+
+        async fn delete(&self, gperson: &Person) -> Result<(), Box<dyn Error>> {
+            let name = gperson.resource_name.as_deref().unwrap_or_default();
+            self.call(format!("delete {}", name))
+        }
+    }
+
+    #[test]
+    fn record_keeps_order_and_merges_changes_to_the_same_contact() {
+        let mut outbox = Vec::new();
+        record(
+            &mut outbox,
+            Mutation::Create {
+                id: 1,
+                contact: entry("hanako@example.com", "Hanako"),
+            },
+        );
+        record(&mut outbox, update("people/c1", "etag-1", "Taro"));
+        record(&mut outbox, update("people/c1", "etag-2", "Taro Yamada"));
+        record(&mut outbox, update(&placeholder(1), "", "Hanako Yamada"));
+        assert_eq!(next_id(&outbox), 2);
+        assert_eq!(outbox.len(), 2);
+        assert!(matches!(
+            &outbox[0].mutation,
+            Mutation::Create { id: 1, contact } if contact.name == "Hanako Yamada"
+        ));
+        assert!(matches!(
+            &outbox[1].mutation,
+            Mutation::Update { etag, contact, .. } if etag == "etag-1" && contact.name == "Taro Yamada"
+        ));
+
+        // 既存の連絡先の削除は、最初に変更したときのetagで送信待ちの更新を置き換える
+        record(&mut outbox, delete("people/c1", "etag-2"));
+        assert_eq!(outbox.len(), 2);
+        assert!(matches!(
+            &outbox[1].mutation,
+            Mutation::Delete { etag, .. } if etag == "etag-1"
+        ));
+
+        // オフラインで作成した連絡先の削除は、作成を取り消す
+        record(&mut outbox, delete(&placeholder(1), ""));
+        assert_eq!(outbox.len(), 1);
+        assert!(matches!(&outbox[0].mutation, Mutation::Delete { .. }));
+    }
+
+    #[test]
+    fn record_merges_changes_to_each_row_of_a_contact() {
+        let mut outbox = Vec::new();
+        // 行のメールアドレスを変更し、続けて同じ行を編集する
+        record(
+            &mut outbox,
+            Mutation::Update {
+                resource_name: "people/c1".to_string(),
+                etag: "etag-1".to_string(),
+                email: "taro@example.com".to_string(),
+                contact: entry("taro@example.net", "Taro"),
+            },
+        );
+        record(
+            &mut outbox,
+            Mutation::Update {
+                resource_name: "people/c1".to_string(),
+                etag: "etag-1".to_string(),
+                email: "taro@example.net".to_string(),
+                contact: entry("taro@example.net", "Taro Tanaka"),
+            },
+        );
+        assert_eq!(outbox.len(), 1);
+        assert!(matches!(
+            &outbox[0].mutation,
+            Mutation::Update { email, contact, .. }
+                if email == "taro@example.com" && contact.name == "Taro Tanaka"
+        ));
+
+        // 行を削除すると、Google Contactsにある変更前のメールアドレスを取り除く
+        let remove = |email: &str| Mutation::RemoveEmail {
+            resource_name: "people/c1".to_string(),
+            etag: "etag-2".to_string(),
+            email: email.to_string(),
+        };
+        record(&mut outbox, remove("taro@example.net"));
+        assert_eq!(outbox.len(), 1);
+        assert!(matches!(
+            &outbox[0].mutation,
+            Mutation::RemoveEmail { etag, email, .. }
+                if etag == "etag-1" && email == "taro@example.com"
+        ));
+
+        // 連絡先の削除は、その連絡先の送信待ちの変更をすべて取り消す
+        record(&mut outbox, remove("taro@example.org"));
+        record(&mut outbox, delete("people/c1", "etag-3"));
+        assert_eq!(outbox.len(), 1);
+        assert!(matches!(
+            &outbox[0].mutation,
+            Mutation::Delete { etag, .. } if etag == "etag-1"
+        ));
+    }
+
+    #[test]
+    fn placeholder_ids_round_trip() {
+        assert_eq!(placeholder_id(&placeholder(7)), Some(7));
+        assert_eq!(placeholder_id("people/c1"), None);
+        assert_eq!(next_id(&[]), 1);
+    }
+
+    #[test]
+    fn saved_outbox_loads_in_the_same_order() {
+        let path = temp_path("outbox.json");
+        assert!(load_from(&path).unwrap().is_empty());
+
+        let mut outbox = Vec::new();
+        record(
+            &mut outbox,
+            Mutation::Create {
+                id: 1,
+                contact: entry("hanako@example.com", "Hanako"),
+            },
+        );
+        record(&mut outbox, update("people/c1", "etag-1", "Taro"));
+        record(&mut outbox, delete("people/c2", "etag-2"));
+        save_to(&path, &outbox).unwrap();
+
+        let loaded = load_from(&path).unwrap();
+        assert_eq!(loaded.len(), 3);
+        assert!(matches!(
+            &loaded[0].mutation,
+            Mutation::Create { id: 1, .. }
+        ));
+        assert!(matches!(
+            &loaded[1].mutation,
+            Mutation::Update { resource_name, .. } if resource_name == "people/c1"
+        ));
+        assert!(matches!(
+            &loaded[2].mutation,
+            Mutation::Delete { resource_name, .. } if resource_name == "people/c2"
+        ));
+        assert_eq!(loaded[0].time, outbox[0].time);
+
+        // 空になった送信待ちはファイルを削除する
+        save_to(&path, &[]).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn replay_applies_add_edit_and_rm_in_order() {
+        let gpersons = vec![
+            gperson("people/c1", "etag-1", "taro@example.com"),
+            gperson("people/c2", "etag-2", "jiro@example.com"),
+            gperson("people/c3", "etag-3", "saburo@example.com"),
+        ];
+        let mut outbox = Vec::new();
+        record(
+            &mut outbox,
+            Mutation::Create {
+                id: 1,
+                contact: entry("hanako@example.com", "Hanako"),
+            },
+        );
+        record(&mut outbox, update("people/c1", "etag-1", "Taro"));
+        record(
+            &mut outbox,
+            Mutation::RemoveEmail {
+                resource_name: "people/c3".to_string(),
+                etag: "etag-3".to_string(),
+                email: "saburo@example.org".to_string(),
+            },
+        );
+        record(&mut outbox, delete("people/c2", "etag-2"));
+
+        let target = FakeTarget::default();
+        let mut results = Vec::new();
+        while let Some((email, replayed)) = replay_first(&mut outbox, &gpersons, &target).await {
+            match replayed {
+                Ok(Replayed::Applied(action)) => results.push((email, action)),
+                _ => panic!("unexpected result for {}", email),
+            }
+        }
+        assert!(outbox.is_empty());
+        assert_eq!(
+            results,
+            [
+                ("hanako@example.com".to_string(), Action::Add),
+                ("taro@example.com".to_string(), Action::Update),
+                ("saburo@example.org".to_string(), Action::Update),
+                ("taro@example.com".to_string(), Action::Delete),
+            ]
+        );
+        assert_eq!(
+            *target.calls.borrow(),
+            [
+                "create Hanako",
+                "update people/c1 taro@example.com Taro",
+                "remove-email people/c3 saburo@example.org",
+                "delete people/c2"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn replay_drops_conflicting_changes() {
+        let gpersons = vec![gperson("people/c1", "etag-2", "taro@example.com")];
+        let mut outbox = Vec::new();
+        // メールアドレスは大文字と小文字を区別せずに比較する
+        record(
+            &mut outbox,
+            Mutation::Create {
+                id: 1,
+                contact: entry("Taro@Example.com", "Taro"),
+            },
+        );
+        record(&mut outbox, update("people/c1", "etag-1", "Taro"));
+        record(&mut outbox, delete("people/c9", "etag-9"));
+
+        let target = FakeTarget::default();
+        let mut conflicts = Vec::new();
+        while let Some((_, replayed)) = replay_first(&mut outbox, &gpersons, &target).await {
+            match replayed {
+                Ok(Replayed::Conflict(message_id)) => conflicts.push(message_id),
+                _ => panic!("expected a conflict"),
+            }
+        }
+        assert_eq!(
+            conflicts,
+            [
+                "outbox-conflict-exists",
+                "outbox-conflict-changed",
+                "outbox-conflict-deleted"
+            ]
+        );
+        assert!(target.calls.borrow().is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_replay_keeps_the_entry() {
+        let gpersons = vec![
+            gperson("people/c1", "etag-1", "taro@example.com"),
+            gperson("people/c2", "etag-2", "jiro@example.com"),
+        ];
+        let mut outbox = Vec::new();
+        record(&mut outbox, update("people/c1", "etag-1", "Taro"));
+        record(&mut outbox, delete("people/c2", "etag-2"));
+        let path = temp_path("failed-outbox.json");
+        save_to(&path, &outbox).unwrap();
+
+        let target = FakeTarget {
+            fail: true,
+            ..Default::default()
+        };
+        let (email, replayed) = replay_first(&mut outbox, &gpersons, &target).await.unwrap();
+        assert_eq!(email, "taro@example.com");
+        assert!(matches!(replayed, Err((Action::Update, _))));
+        assert_eq!(outbox.len(), 2);
+        assert!(matches!(&outbox[0].mutation, Mutation::Update { .. }));
+
+        // 保存した送信待ちも、次の同期で同じ変更から反映し直せる
+        let loaded = load_from(&path).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert!(matches!(&loaded[0].mutation, Mutation::Update { .. }));
+        save_to(&path, &[]).unwrap();
+    }
+}
@@ -0,0 +1,231 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! 認証情報やキャッシュを保存するファイルとディレクトリのパーミッションを管理する
+
+use std::fs; // ファイルシステム操作のための標準ライブラリのモジュール
+use std::io::{self, Write}; // エラーを返すための 'io' モジュール
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt}; // Unixのパーミッションと所有者を扱うため
+use std::path::Path; // ファイルパスを扱うための 'Path' モジュール
+
+/// 秘密情報を保存するディレクトリのパーミッション
+pub const PRIVATE_DIR_MODE: u32 = 0o700;
+/// 秘密情報を保存するファイルのパーミッション
+pub const PRIVATE_FILE_MODE: u32 = 0o600;
+
+/// 所有者以外がアクセスできないディレクトリを作成する関数。
+///
+/// 親ディレクトリも含めて、パーミッション0700で作成します。
+/// 既に存在する場合は何もしません。
+///
+/// # 引数
+/// * `path` - 作成するディレクトリのパス。
+///
+/// # 戻り値
+/// 成功した場合はOk(())、作成に失敗した場合はエラー。
+pub fn create_private_dir(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(PRIVATE_DIR_MODE)
+            .create(path)
+    }
+    #[cfg(not(unix))]
+    {
+        fs::create_dir_all(path)
+    }
+}
+
+/// 所有者以外がアクセスできないファイルにデータを書き込む関数。
+///
+/// ファイルが存在しない場合はパーミッション0600で作成し、存在する場合は内容を置き換えます。
+///
+/// # 引数
+/// * `path` - 書き込むファイルのパス。
+/// * `data` - 書き込むデータ。
+///
+/// # 戻り値
+/// 成功した場合はOk(())、書き込みに失敗した場合はエラー。
+pub fn write_private_file(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(PRIVATE_FILE_MODE);
+
+    let mut file = options.open(path)?;
+    file.write_all(data)?;
+    file.sync_all()
+}
+
+/// ツールが管理するファイルまたはディレクトリのパーミッションを`mode`に変更する関数。
+///
+/// 作成時に指定したパーミッションは既存のファイルには適用されないため、書き込みの後などに呼び出して
+/// グループやその他のユーザーがアクセスできない状態に戻します。
+/// 他のユーザーが所有している場合は変更できないため何もしません（`check_private`で警告します）。
+/// パスが存在しない場合も何もしません。
+///
+/// # 引数
+/// * `path` - パーミッションを変更するパス。
+/// * `mode` - 設定するパーミッション（`PRIVATE_DIR_MODE`または`PRIVATE_FILE_MODE`）。
+///
+/// # 戻り値
+/// 成功した場合はOk(())、変更に失敗した場合はエラー。
+#[cfg(unix)]
+pub fn restrict_private(path: &Path, mode: u32) -> io::Result<()> {
+    let metadata = match fs::metadata(path) {
+        Ok(m) => m,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    // geteuidは失敗しないシステムコール
+    let euid = unsafe { libc::geteuid() };
+    if metadata.uid() != euid || metadata.mode() & 0o777 == mode {
+        return Ok(());
+    }
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+/// Unix以外の環境ではパーミッションを変更しない。
+#[cfg(not(unix))]
+pub fn restrict_private(_path: &Path, _mode: u32) -> io::Result<()> {
+    Ok(())
+}
+
+/// ファイルまたはディレクトリの所有者とパーミッションを検査する関数。
+///
+/// 他のユーザーが所有している場合や、グループ・その他のユーザーがアクセスできる場合は警告を表示します。
+/// パーミッションは変更せず、アクセスできる範囲が広すぎる場合は`mode`に変更する方法を警告に含めます。
+/// `strict`が`true`の場合は、警告の代わりにエラーを返します。
+/// パスが存在しない場合は何もしません。
+///
+/// # 引数
+/// * `path` - 検査するパス。
+/// * `mode` - 期待するパーミッション（`PRIVATE_DIR_MODE`または`PRIVATE_FILE_MODE`）。警告に表示します。
+/// * `strict` - 問題があった場合にエラーとするかどうか。
+///
+/// # 戻り値
+/// 問題が無いか`strict`でない場合はOk(())、`strict`で問題が見つかった場合や検査に失敗した場合はエラー。
+#[cfg(unix)]
+pub fn check_private(path: &Path, mode: u32, strict: bool) -> io::Result<()> {
+    // パスが存在しない場合は検査不要
+    let metadata = match fs::metadata(path) {
+        Ok(m) => m,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    // 所有者が実行ユーザーと異なる場合
+    // geteuidは失敗しないシステムコール
+    let euid = unsafe { libc::geteuid() };
+    let owned_by_other = metadata.uid() != euid;
+    if owned_by_other {
+        let message = format!(
+            "{} is owned by another user (uid {})",
+            path.display(),
+            metadata.uid()
+        );
+        if strict {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, message));
+        }
+        eprintln!("Warning: {}", message);
+    }
+
+    // グループやその他のユーザーがアクセスできる場合
+    let current_mode = metadata.mode() & 0o777;
+    if current_mode & 0o077 != 0 {
+        let message = format!(
+            "{} is accessible by group or others (mode {:o})",
+            path.display(),
+            current_mode
+        );
+        if strict {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, message));
+        }
+        if owned_by_other {
+            // 他のユーザーのファイルのパーミッションは変更できない
+            eprintln!("Warning: {}", message);
+        } else {
+            // ユーザーのファイルを勝手に変更せず、修正方法を示すだけにする
+            eprintln!(
+                "Warning: {}; run `chmod {:o} {}` to fix it",
+                message,
+                mode,
+                path.display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Unix以外の環境ではパーミッションの検査を行わない。
+#[cfg(not(unix))]
+pub fn check_private(_path: &Path, _mode: u32, _strict: bool) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::mod_test::temp_path;
+
+    #[test]
+    fn warns_without_changing_the_mode() {
+        let path = temp_path("perm-warn");
+        fs::write(&path, "secret").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        check_private(&path, PRIVATE_FILE_MODE, false).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        fs::remove_file(&path).unwrap();
+        assert_eq!(mode, 0o644);
+    }
+
+    #[test]
+    fn restricts_an_existing_file() {
+        let path = temp_path("perm-restrict");
+        fs::write(&path, "secret").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        restrict_private(&path, PRIVATE_FILE_MODE).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        fs::remove_file(&path).unwrap();
+        assert_eq!(mode, 0o600);
+        assert!(restrict_private(&path, PRIVATE_FILE_MODE).is_ok());
+    }
+
+    #[test]
+    fn fails_in_strict_mode() {
+        let path = temp_path("perm-strict");
+        fs::write(&path, "secret").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        let error = check_private(&path, PRIVATE_FILE_MODE, true).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn accepts_a_private_file_and_a_missing_path() {
+        let path = temp_path("perm-ok");
+        write_private_file(&path, b"secret").unwrap();
+        let result = check_private(&path, PRIVATE_FILE_MODE, true);
+        fs::remove_file(&path).unwrap();
+        assert!(result.is_ok());
+        assert!(check_private(&path, PRIVATE_FILE_MODE, true).is_ok());
+    }
+}
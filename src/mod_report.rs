@@ -0,0 +1,328 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! 実行結果の報告（`--report`）を集計して出力する
+
+use crate::mod_exit::{self, ExitCode}; // 終了コードを決定するため
+use serde_json::{json, Value}; // JSON形式で出力するため
+use std::error::Error; // 連絡先ごとのエラーを記録するため
+use std::path::PathBuf; // 報告を書き込むファイルのパスを保持するため
+use std::str::FromStr; // 文字列から報告の形式に変換するため
+
+/// 実行結果の報告の形式。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReportFormat {
+    Json, // JSON
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(s.to_string()),
+        }
+    }
+}
+
+/// 変更の対象。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Side {
+    Google,      // Google Contacts
+    AddressBook, // .addressbook
+}
+
+impl Side {
+    /// 報告に使用する名前を返す。
+    fn as_str(self) -> &'static str {
+        match self {
+            Side::Google => "google",
+            Side::AddressBook => "addressbook",
+        }
+    }
+}
+
+/// 変更の種類。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Add,    // 追加
+    Update, // 更新
+    Delete, // 削除
+}
+
+impl Action {
+    /// 報告に使用する名前を返す。
+    fn as_str(self) -> &'static str {
+        match self {
+            Action::Add => "add",
+            Action::Update => "update",
+            Action::Delete => "delete",
+        }
+    }
+}
+
+/// 追加、更新、削除の件数。
+#[derive(Default)]
+struct Counts {
+    added: usize,   // 追加した件数
+    updated: usize, // 更新した件数
+    deleted: usize, // 削除した件数
+}
+
+impl Counts {
+    /// 変更の種類に対応する件数を1増やす。
+    fn increment(&mut self, action: Action) {
+        match action {
+            Action::Add => self.added += 1,
+            Action::Update => self.updated += 1,
+            Action::Delete => self.deleted += 1,
+        }
+    }
+
+    /// 変更が1件でもあるかどうかを返す。
+    fn any(&self) -> bool {
+        self.added + self.updated + self.deleted > 0
+    }
+
+    /// JSONの値に変換する。
+    fn to_json(&self) -> Value {
+        json!({
+            "added": self.added,
+            "updated": self.updated,
+            "deleted": self.deleted,
+        })
+    }
+}
+
+/// 連絡先ごとのエラー。
+struct ContactError {
+    contact: String, // 連絡先のメールアドレス
+    side: Side,      // 変更の対象
+    action: Action,  // 変更の種類
+    message: String, // エラーの内容
+}
+
+/// 実行結果の報告。
+pub struct Report {
+    format: Option<ReportFormat>, // 報告の形式（出力しない場合は`None`）
+    path: Option<PathBuf>,        // 報告を書き込むファイル（標準エラー出力の場合は`None`）
+    command: &'static str,        // 実行したコマンド
+    google: Counts,               // Google Contactsの変更の件数
+    addressbook: Counts,          // .addressbookの変更の件数
+    unresolved: usize,            // 未解決の競合の件数
+    errors: Vec<ContactError>,    // 連絡先ごとのエラー
+    error_code: Option<ExitCode>, // 最初の連絡先ごとのエラーの終了コード
+}
+
+impl Report {
+    /// 新しい報告を作成する。
+    ///
+    /// # 引数
+    /// * `command` - 実行したコマンドの名前。
+    /// * `format` - 報告の形式。出力しない場合は`None`。
+    /// * `path` - 報告を書き込むファイル。`None`の場合は標準エラー出力に出力する。
+    pub fn new(command: &'static str, format: Option<ReportFormat>, path: Option<PathBuf>) -> Self {
+        Report {
+            format,
+            path,
+            command,
+            google: Counts::default(),
+            addressbook: Counts::default(),
+            unresolved: 0,
+            errors: Vec::new(),
+            error_code: None,
+        }
+    }
+
+    /// 成功した変更を記録する。
+    pub fn record(&mut self, side: Side, action: Action) {
+        match side {
+            Side::Google => self.google.increment(action),
+            Side::AddressBook => self.addressbook.increment(action),
+        }
+    }
+
+    /// 失敗した変更を記録する。
+    ///
+    /// # 引数
+    /// * `contact` - 連絡先のメールアドレス。
+    /// * `side` - 変更の対象。
+    /// * `action` - 変更の種類。
+    /// * `error` - 発生したエラー。
+    pub fn record_error(
+        &mut self,
+        contact: &str,
+        side: Side,
+        action: Action,
+        error: &(dyn Error + 'static),
+    ) {
+        self.error_code
+            .get_or_insert_with(|| mod_exit::classify_error(error));
+        self.errors.push(ContactError {
+            contact: contact.to_string(),
+            side,
+            action,
+            message: error.to_string(),
+        });
+    }
+
+    /// 解決されなかった競合を記録する。
+    pub fn record_unresolved(&mut self) {
+        self.unresolved += 1;
+    }
+
+    /// 記録した内容から終了コードを決定する。
+    ///
+    /// 連絡先ごとのエラーがある場合は、成功した変更があれば`PartialSuccess`、
+    /// 無ければ最初のエラーに対応する終了コードとします。
+    /// エラーが無く未解決の競合がある場合は`Unresolved`とします。
+    pub fn outcome(&self) -> ExitCode {
+        if let Some(code) = self.error_code {
+            if self.google.any() || self.addressbook.any() {
+                return ExitCode::PartialSuccess;
+            }
+            return code;
+        }
+        if self.unresolved > 0 {
+            return ExitCode::Unresolved;
+        }
+        ExitCode::Success
+    }
+
+    /// JSONの値に変換する。
+    ///
+    /// # 引数
+    /// * `code` - 終了コード。
+    /// * `error` - 処理を中断したエラーのメッセージ。
+    fn to_json(&self, code: ExitCode, error: Option<&str>) -> Value {
+        let errors: Vec<Value> = self
+            .errors
+            .iter()
+            .map(|e| {
+                json!({
+                    "contact": e.contact,
+                    "side": e.side.as_str(),
+                    "action": e.action.as_str(),
+                    "message": e.message,
+                })
+            })
+            .collect();
+        json!({
+            "command": self.command,
+            "status": code.as_str(),
+            "exit_code": code.code(),
+            "google": self.google.to_json(),
+            "addressbook": self.addressbook.to_json(),
+            "unresolved": self.unresolved,
+            "errors": errors,
+            "error": error,
+        })
+    }
+
+    /// 報告を出力し、指定した終了コードで終了する。
+    ///
+    /// 確認のメッセージや進行状況と混ざらないように、報告はファイルか標準エラー出力に出力します。
+    /// ファイルに書き込めなかった場合は、成功の終了コードを失敗に変えます。
+    ///
+    /// # 引数
+    /// * `code` - 終了コード。
+    /// * `error` - 処理を中断したエラーのメッセージ。
+    pub fn exit(&self, code: ExitCode, error: Option<&str>) -> ! {
+        let mut code = code;
+        if let Some(ReportFormat::Json) = self.format {
+            let json = self.to_json(code, error);
+            match &self.path {
+                Some(path) => {
+                    if let Err(e) = std::fs::write(path, format!("{}\n", json)) {
+                        eprintln!("{}: {}", path.display(), e);
+                        if code == ExitCode::Success {
+                            code = ExitCode::Failure;
+                        }
+                    }
+                }
+                None => eprintln!("{}", json),
+            }
+        }
+        code.exit()
+    }
+
+    /// 報告を出力し、記録した内容に応じた終了コードで終了する。
+    pub fn finish(&self) -> ! {
+        self.exit(self.outcome(), None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 連絡先ごとのエラーとして記録する、HTTPステータスで失敗したエラーを作成する。
+    fn failure(status: u16) -> google_people1::Error {
+        google_people1::Error::Failure(
+            hyper::Response::builder()
+                .status(status)
+                .body(hyper::Body::empty())
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn outcome_reflects_changes_errors_and_conflicts() {
+        let mut report = Report::new("sync", None, None);
+        assert_eq!(report.outcome(), ExitCode::Success);
+
+        report.record(Side::Google, Action::Add);
+        assert_eq!(report.outcome(), ExitCode::Success);
+
+        report.record_unresolved();
+        assert_eq!(report.outcome(), ExitCode::Unresolved);
+
+        // エラーがあっても成功した変更があれば一部成功
+        report.record_error("a@example.com", Side::Google, Action::Update, &failure(500));
+        assert_eq!(report.outcome(), ExitCode::PartialSuccess);
+    }
+
+    #[test]
+    fn outcome_uses_the_first_error_when_nothing_succeeded() {
+        let mut report = Report::new("import", None, None);
+        report.record_error("a@example.com", Side::Google, Action::Add, &failure(401));
+        report.record_error("b@example.com", Side::Google, Action::Add, &failure(503));
+        assert_eq!(report.outcome(), ExitCode::Auth);
+    }
+
+    #[test]
+    fn json_report_contains_counts_and_errors() {
+        let mut report = Report::new("rm", Some(ReportFormat::Json), None);
+        report.record(Side::AddressBook, Action::Delete);
+        report.record_error("a@example.com", Side::Google, Action::Delete, &failure(404));
+
+        let json = report.to_json(report.outcome(), None);
+        assert_eq!(json["command"], "rm");
+        assert_eq!(json["status"], "partial_success");
+        assert_eq!(json["exit_code"], 7);
+        assert_eq!(json["addressbook"]["deleted"], 1);
+        assert_eq!(json["google"]["deleted"], 0);
+        assert_eq!(json["errors"][0]["contact"], "a@example.com");
+        assert_eq!(json["errors"][0]["side"], "google");
+        assert_eq!(json["errors"][0]["action"], "delete");
+        assert!(json["error"].is_null());
+    }
+
+    #[test]
+    fn report_format_is_parsed_from_its_name() {
+        assert_eq!("json".parse::<ReportFormat>(), Ok(ReportFormat::Json));
+        assert!("yaml".parse::<ReportFormat>().is_err());
+    }
+}
@@ -0,0 +1,188 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! 最後の同期の時刻と、その時点のアドレス帳の内容（スナップショット）を保存する
+
+use crate::mod_dirs; // 状態ディレクトリに保存するため
+use crate::mod_perm; // 連絡先を含むファイルをパーミッション0600で書き込むため
+use serde::{Deserialize, Serialize}; // JSON形式で保存するため
+use std::collections::HashMap; // メールアドレスで行を照合するため
+use std::fs; // ファイルシステム操作のための標準ライブラリのモジュール
+use std::io; // エラーを返すための 'io' モジュール
+use std::path::PathBuf; // ファイルパスを扱うため
+
+/// スナップショットに保存するアドレス帳の1行。
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub nickname: String, // ニックネーム
+    pub name: String,     // 名前
+    pub email: String,    // メールアドレス（リストの場合は括弧で囲まれたアドレスの一覧）
+    pub fcc: String,      // Fcc
+    pub comment: String,  // コメント
+}
+
+/// 最後の同期の状態。
+#[derive(Serialize, Deserialize)]
+pub struct SyncState {
+    pub time: String,         // 同期した時刻（RFC 3339）
+    pub command: String,      // 実行したコマンド（initまたはsync）
+    pub addressbook: PathBuf, // 同期したアドレス帳のパス
+    pub entries: Vec<Entry>,  // 同期した直後のアドレス帳の内容
+}
+
+/// スナップショットから変更された行の数。
+pub struct PendingEdits {
+    pub added: usize,    // 追加された行
+    pub modified: usize, // 変更された行
+    pub removed: usize,  // 削除された行
+}
+
+impl PendingEdits {
+    /// 変更された行の合計を返す。
+    pub fn total(&self) -> usize {
+        self.added + self.modified + self.removed
+    }
+}
+
+/// 同期の状態を保存するファイルのパスを取得する関数。
+///
+/// トークンキャッシュと同じく、アカウントごとに別のファイル（`sync_state.<account>.json`）に保存します。
+///
+/// # 引数
+/// * `account` - アカウント名。空の場合は既定のアカウント。
+///
+/// # 戻り値
+/// 成功した場合はファイルのパス、ホームディレクトリが見つからない場合はエラー。
+pub fn state_path(account: &str) -> io::Result<PathBuf> {
+    let file_name = if account.is_empty() {
+        "sync_state.json".to_string()
+    } else {
+        format!("sync_state.{}.json", account)
+    };
+    Ok(mod_dirs::state_dir()?.join(file_name))
+}
+
+/// 最後の同期の状態を読み込む関数。
+///
+/// # 引数
+/// * `account` - アカウント名。
+///
+/// # 戻り値
+/// 成功した場合は同期の状態（まだ同期していない場合は`None`）、読み込みや解析に失敗した場合はエラー。
+pub fn load(account: &str) -> io::Result<Option<SyncState>> {
+    let data = match fs::read(state_path(account)?) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    serde_json::from_slice(&data)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// 同期の状態を保存する関数。
+///
+/// 連絡先を含むため、所有者以外が読めないパーミッション（0600）で書き込みます。
+///
+/// # 引数
+/// * `account` - アカウント名。
+/// * `state` - 保存する同期の状態。
+///
+/// # 戻り値
+/// 成功した場合はOk(())、書き込みに失敗した場合はエラー。
+pub fn save(account: &str, state: &SyncState) -> io::Result<()> {
+    mod_dirs::ensure_dirs()?;
+    let data = serde_json::to_vec_pretty(state)?;
+    mod_perm::write_private_file(&state_path(account)?, &data)
+}
+
+/// スナップショットと現在のアドレス帳を比較し、変更された行を数える関数。
+///
+/// 同期と同じく行をメールアドレスで照合します。同じメールアドレスの行が複数ある場合は最初の行を使用します。
+///
+/// # 引数
+/// * `snapshot` - 最後の同期の直後のアドレス帳の内容。
+/// * `current` - 現在のアドレス帳の内容。
+///
+/// # 戻り値
+/// 追加、変更、削除された行の数。
+pub fn pending_edits(snapshot: &[Entry], current: &[Entry]) -> PendingEdits {
+    let by_email = |entries: &[Entry]| {
+        let mut map: HashMap<String, Entry> = HashMap::new();
+        for entry in entries {
+            map.entry(entry.email.clone())
+                .or_insert_with(|| entry.clone());
+        }
+        map
+    };
+    let before = by_email(snapshot);
+    let after = by_email(current);
+
+    PendingEdits {
+        added: after.keys().filter(|k| !before.contains_key(*k)).count(),
+        modified: after
+            .iter()
+            .filter(|(k, v)| before.get(*k).is_some_and(|b| b != *v))
+            .count(),
+        removed: before.keys().filter(|k| !after.contains_key(*k)).count(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// テスト用の行を作成する。
+    fn entry(email: &str, name: &str) -> Entry {
+        Entry {
+            nickname: "Tanaka".to_string(),
+            name: name.to_string(),
+            email: email.to_string(),
+            fcc: String::new(),
+            comment: String::new(),
+        }
+    }
+
+    #[test]
+    fn counts_added_modified_and_removed_rows() {
+        let snapshot = vec![
+            entry("taro@example.com", "Taro"),
+            entry("hanako@example.com", "Hanako"),
+            entry("jiro@example.com", "Jiro"),
+        ];
+        let current = vec![
+            entry("taro@example.com", "Taro"),
+            entry("hanako@example.com", "Hanako Tanaka"),
+            entry("saburo@example.com", "Saburo"),
+            entry("shiro@example.com", "Shiro"),
+        ];
+
+        let pending = pending_edits(&snapshot, &current);
+        assert_eq!(pending.added, 2);
+        assert_eq!(pending.modified, 1);
+        assert_eq!(pending.removed, 1);
+        assert_eq!(pending.total(), 4);
+    }
+
+    #[test]
+    fn uses_the_first_row_of_each_email() {
+        let snapshot = vec![entry("taro@example.com", "Taro")];
+        let current = vec![
+            entry("taro@example.com", "Taro"),
+            entry("taro@example.com", "Taro (work)"),
+        ];
+        assert_eq!(pending_edits(&snapshot, &current).total(), 0);
+        assert_eq!(pending_edits(&snapshot, &snapshot).total(), 0);
+    }
+}
@@ -0,0 +1,148 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! テストで共通して使用する連絡先の作成と一時ファイルの補助関数
+
+use google_people1::api::Person; // Google Contactsの連絡先を扱うため
+use serde_json::{json, Map, Value}; // 連絡先のフィールドをJSONで組み立てるため
+use std::path::PathBuf; // 一時ファイルのパスを扱うため
+
+/// テスト用の一時ファイルのパスを返す。
+///
+/// 並行して実行される他のテストプロセスと衝突しないように、プロセスIDを含めます。
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "{}-test-{}-{}",
+        env!("CARGO_PKG_NAME"),
+        std::process::id(),
+        name
+    ))
+}
+
+/// テスト用の連絡先を組み立てる。
+///
+/// 指定したフィールドだけを持つ`Person`を作成します。
+pub fn person() -> PersonBuilder {
+    PersonBuilder::default()
+}
+
+/// テスト用の連絡先のフィールドを保持する構造体。
+#[derive(Default)]
+pub struct PersonBuilder {
+    fields: Map<String, Value>, // People APIのJSON表現のフィールド
+}
+
+impl PersonBuilder {
+    pub fn resource_name(mut self, resource_name: &str) -> Self {
+        self.fields
+            .insert("resourceName".to_string(), json!(resource_name));
+        self
+    }
+
+    /// 表示名だけの名前を追加する。
+    pub fn name(self, display_name: &str) -> Self {
+        self.push("names", json!({"displayName": display_name}))
+    }
+
+    /// 表示名、姓、名を持つ名前を追加する。
+    pub fn full_name(self, display_name: &str, family_name: &str, given_name: &str) -> Self {
+        self.push(
+            "names",
+            json!({
+                "displayName": display_name,
+                "familyName": family_name,
+                "givenName": given_name,
+            }),
+        )
+    }
+
+    pub fn nickname(self, value: &str) -> Self {
+        self.push("nicknames", json!({"value": value}))
+    }
+
+    /// 種類の無いメールアドレスを追加する。
+    pub fn email(self, value: &str) -> Self {
+        self.push("emailAddresses", json!({"value": value}))
+    }
+
+    pub fn typed_email(self, value: &str, type_: &str) -> Self {
+        self.push("emailAddresses", json!({"value": value, "type": type_}))
+    }
+
+    /// メインのメールアドレスを追加する。
+    pub fn primary_email(self, value: &str, type_: &str) -> Self {
+        self.push("emailAddresses", primary(value, type_))
+    }
+
+    pub fn phone(self, value: &str, type_: &str) -> Self {
+        self.push("phoneNumbers", json!({"value": value, "type": type_}))
+    }
+
+    /// メインの電話番号を追加する。
+    pub fn primary_phone(self, value: &str, type_: &str) -> Self {
+        self.push("phoneNumbers", primary(value, type_))
+    }
+
+    pub fn address(self, street_address: &str, city: &str, postal_code: &str) -> Self {
+        self.push(
+            "addresses",
+            json!({
+                "streetAddress": street_address,
+                "city": city,
+                "postalCode": postal_code,
+            }),
+        )
+    }
+
+    pub fn organization(self, name: &str) -> Self {
+        self.push("organizations", json!({"name": name}))
+    }
+
+    /// 年の無い誕生日を追加する。
+    pub fn birthday(self, month: i32, day: i32) -> Self {
+        self.push("birthdays", json!({"date": {"month": month, "day": day}}))
+    }
+
+    pub fn biography(self, value: &str) -> Self {
+        self.push("biographies", json!({"value": value}))
+    }
+
+    /// 連絡先グループのメンバーシップを追加する。
+    pub fn group(self, resource_name: &str) -> Self {
+        self.push(
+            "memberships",
+            json!({"contactGroupMembership": {"contactGroupResourceName": resource_name}}),
+        )
+    }
+
+    /// 組み立てたフィールドから`Person`を作成する。
+    pub fn build(self) -> Person {
+        serde_json::from_value(Value::Object(self.fields)).unwrap()
+    }
+
+    /// 配列のフィールドに値を追加する。
+    fn push(mut self, field: &str, value: Value) -> Self {
+        let values = self
+            .fields
+            .entry(field)
+            .or_insert_with(|| Value::Array(Vec::new()));
+        values.as_array_mut().unwrap().push(value);
+        self
+    }
+}
+
+/// メインの値であることを示すメタデータを持つ値を作成する。
+fn primary(value: &str, type_: &str) -> Value {
+    json!({"value": value, "type": type_, "metadata": {"primary": true}})
+}
@@ -0,0 +1,650 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! OAuth2トークンの保存先（バックエンド）を切り替えて使用できるようにする
+
+use crate::mod_perm; // トークンファイルをパーミッション0600で書き込むため
+use async_trait::async_trait; // yup-oauth2のTokenStorageトレイトを実装するため
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN}; // トークンの暗号化用
+use ring::rand::{SecureRandom, SystemRandom}; // ソルトとノンスの生成用
+use serde::{Deserialize, Serialize}; // トークンキャッシュのシリアライズ用
+use std::io::{self, Write}; // 入出力機能のための 'io' モジュールをインポート
+use std::num::NonZeroU32; // PBKDF2の反復回数を指定するため
+use std::path::PathBuf; // ファイルパスを扱うため
+use std::str::FromStr; // 文字列からバックエンドの種類に変換するため
+use std::sync::Mutex; // メモリ上のトークンを保護するため
+use yup_oauth2::storage::{TokenInfo, TokenStorage}; // カスタムトークンストレージのトレイト
+
+/// 暗号化されたトークンファイルの先頭に付けるマジックバイト
+const ENCRYPTED_MAGIC: &[u8] = b"GCTE1";
+/// 鍵導出に使用するソルトの長さ
+const SALT_LEN: usize = 16;
+/// PBKDF2の反復回数
+const PBKDF2_ITERATIONS: u32 = 200_000;
+/// Secret Serviceに保存する項目のユーザー名
+const KEYRING_ITEM: &str = "token-cache";
+
+/// トークンキャッシュに保存されている1件分のトークン。
+///
+/// yup-oauth2が`token_cache.json`に書き込む`{"scopes": [...], "token": {...}}`形式に対応します。
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CachedToken {
+    pub scopes: Vec<String>, // このトークンに許可されているスコープ
+    pub token: TokenInfo,    // アクセストークン、リフレッシュトークンと有効期限
+}
+
+/// トークンの保存先の種類。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TokenStorageKind {
+    File,      // 平文のJSONファイル（token_cache.json）
+    Encrypted, // パスフレーズで暗号化したファイル（token_cache.enc）
+    Keyring,   // Secret Serviceのキーリング
+    Memory,    // メモリ上のみ（永続化しない。テスト用）
+}
+
+impl FromStr for TokenStorageKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "file" => Ok(TokenStorageKind::File),
+            "encrypted" => Ok(TokenStorageKind::Encrypted),
+            "keyring" => Ok(TokenStorageKind::Keyring),
+            "memory" => Ok(TokenStorageKind::Memory),
+            _ => Err(format!(
+                "unknown token storage \"{}\" (expected file, encrypted, keyring or memory)",
+                s
+            )),
+        }
+    }
+}
+
+impl TokenStorageKind {
+    /// 設定ファイルや環境変数で使用する名前を返す。
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenStorageKind::File => "file",
+            TokenStorageKind::Encrypted => "encrypted",
+            TokenStorageKind::Keyring => "keyring",
+            TokenStorageKind::Memory => "memory",
+        }
+    }
+
+    /// トークンを保存するファイル名を返す。ファイルを使用しない場合は`None`。
+    ///
+    /// `account`が空でない場合は、アカウントごとに別のファイル（例: `token_cache.work.json`）を使用します。
+    pub fn file_name(&self, account: &str) -> Option<String> {
+        let extension = match self {
+            TokenStorageKind::File => "json",
+            TokenStorageKind::Encrypted => "enc",
+            TokenStorageKind::Keyring | TokenStorageKind::Memory => return None,
+        };
+        if account.is_empty() {
+            Some(format!("token_cache.{}", extension))
+        } else {
+            Some(format!("token_cache.{}.{}", account, extension))
+        }
+    }
+}
+
+/// トークンキャッシュの中身（バイト列）を読み書きするバックエンドのトレイト。
+pub trait BlobStore: Send + Sync {
+    /// 保存されているデータを読み込む。保存されていない場合は`None`を返す。
+    fn read(&self) -> io::Result<Option<Vec<u8>>>;
+    /// データを保存する。既存のデータは置き換える。
+    fn write(&self, data: &[u8]) -> io::Result<()>;
+    /// 保存されているデータを削除する。
+    fn remove(&self) -> io::Result<()>;
+}
+
+/// 平文のファイルに保存するバックエンド。
+pub struct FileStore {
+    path: PathBuf, // トークンファイルのパス
+}
+
+impl FileStore {
+    pub fn new(path: PathBuf) -> Self {
+        FileStore { path }
+    }
+}
+
+impl BlobStore for FileStore {
+    fn read(&self) -> io::Result<Option<Vec<u8>>> {
+        match std::fs::read(&self.path) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write(&self, data: &[u8]) -> io::Result<()> {
+        mod_perm::write_private_file(&self.path, data)?;
+        // 既存のファイルには作成時のパーミッションが適用されないため、書き込むたびに0600にする
+        mod_perm::restrict_private(&self.path, mod_perm::PRIVATE_FILE_MODE)
+    }
+
+    fn remove(&self) -> io::Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// パスフレーズから導出した鍵でAES-256-GCM暗号化してファイルに保存するバックエンド。
+///
+/// ファイルの形式は`マジックバイト || ソルト || ノンス || 暗号文とタグ`です。
+/// ソルトとノンスは書き込みのたびに生成します。
+/// PBKDF2による鍵導出は時間がかかるため、導出した鍵はソルトとともに保持し、同じソルトの読み込みに再利用します。
+pub struct EncryptedFileStore {
+    file: FileStore,                                // 暗号化したデータを保存するファイル
+    passphrase: String,                             // 鍵導出に使用するパスフレーズ
+    key: Mutex<Option<([u8; SALT_LEN], [u8; 32])>>, // 導出済みの鍵とそのソルト
+}
+
+impl EncryptedFileStore {
+    pub fn new(path: PathBuf, passphrase: String) -> Self {
+        EncryptedFileStore {
+            file: FileStore::new(path),
+            passphrase,
+            key: Mutex::new(None),
+        }
+    }
+
+    /// パスフレーズとソルトからAES-256-GCMの鍵を導出する。導出済みの鍵があれば再利用する。
+    fn derive_key(&self, salt: &[u8; SALT_LEN]) -> LessSafeKey {
+        let mut cached = self.key.lock().unwrap();
+        let key = match *cached {
+            Some((cached_salt, key)) if cached_salt == *salt => key,
+            _ => {
+                let mut key = [0u8; 32];
+                ring::pbkdf2::derive(
+                    ring::pbkdf2::PBKDF2_HMAC_SHA256,
+                    NonZeroU32::new(PBKDF2_ITERATIONS).unwrap(),
+                    salt,
+                    self.passphrase.as_bytes(),
+                    &mut key,
+                );
+                *cached = Some((*salt, key));
+                key
+            }
+        };
+        // 鍵の長さはAES_256_GCMに合わせているので失敗しない
+        LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &key).unwrap())
+    }
+}
+
+impl BlobStore for EncryptedFileStore {
+    fn read(&self) -> io::Result<Option<Vec<u8>>> {
+        let data = match self.file.read()? {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+
+        // ヘッダーを検証して分割
+        let header_len = ENCRYPTED_MAGIC.len() + SALT_LEN + NONCE_LEN;
+        if data.len() < header_len || !data.starts_with(ENCRYPTED_MAGIC) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "encrypted token cache has an invalid header",
+            ));
+        }
+        let mut salt = [0u8; SALT_LEN];
+        salt.copy_from_slice(&data[ENCRYPTED_MAGIC.len()..ENCRYPTED_MAGIC.len() + SALT_LEN]);
+        let nonce =
+            Nonce::try_assume_unique_for_key(&data[ENCRYPTED_MAGIC.len() + SALT_LEN..header_len])
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid nonce"))?;
+
+        // 復号する。パスフレーズが違う場合はタグの検証に失敗する
+        let mut in_out = data[header_len..].to_vec();
+        let plain = self
+            .derive_key(&salt)
+            .open_in_place(nonce, Aad::from(ENCRYPTED_MAGIC), &mut in_out)
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "failed to decrypt the token cache (wrong passphrase?)",
+                )
+            })?;
+
+        Ok(Some(plain.to_vec()))
+    }
+
+    fn write(&self, data: &[u8]) -> io::Result<()> {
+        // ソルトとノンスは書き込みのたびに新しく生成する
+        let rng = SystemRandom::new();
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        rng.fill(&mut salt)
+            .and_then(|_| rng.fill(&mut nonce))
+            .map_err(|_| io::Error::other("failed to generate random bytes"))?;
+
+        let mut in_out = data.to_vec();
+        self.derive_key(&salt)
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(ENCRYPTED_MAGIC),
+                &mut in_out,
+            )
+            .map_err(|_| io::Error::other("failed to encrypt the token cache"))?;
+
+        let mut output =
+            Vec::with_capacity(ENCRYPTED_MAGIC.len() + SALT_LEN + NONCE_LEN + in_out.len());
+        output.extend_from_slice(ENCRYPTED_MAGIC);
+        output.extend_from_slice(&salt);
+        output.extend_from_slice(&nonce);
+        output.extend_from_slice(&in_out);
+        self.file.write(&output)
+    }
+
+    fn remove(&self) -> io::Result<()> {
+        self.file.remove()
+    }
+}
+
+/// Secret Serviceのキーリングに保存するバックエンド。
+///
+/// `keyring`クレートを使用し、D-Bus経由でSecret Serviceに保存します。
+/// アカウント名が空でない場合は、アカウントごとに別の項目に保存します。
+pub struct KeyringStore {
+    entry: keyring::Entry, // キーリングの項目
+}
+
+impl KeyringStore {
+    /// キーリングの項目を開く。キーリングへのアクセスは読み書きの時点で行う。
+    pub fn new(account: &str) -> io::Result<Self> {
+        let user = if account.is_empty() {
+            KEYRING_ITEM.to_string()
+        } else {
+            format!("{}.{}", KEYRING_ITEM, account)
+        };
+        let entry = keyring::Entry::new(env!("CARGO_PKG_NAME"), &user).map_err(keyring_error)?;
+        Ok(KeyringStore { entry })
+    }
+}
+
+/// キーリングのエラーを`io::Error`に変換する。
+///
+/// キーリングがロックされている場合やD-Busに接続できない場合もエラーとして返し、
+/// トークンが保存されていない場合と区別します。
+fn keyring_error(error: keyring::Error) -> io::Error {
+    io::Error::other(format!("keyring error: {}", error))
+}
+
+impl BlobStore for KeyringStore {
+    fn read(&self) -> io::Result<Option<Vec<u8>>> {
+        match self.entry.get_secret() {
+            Ok(data) => Ok(Some(data)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(keyring_error(e)),
+        }
+    }
+
+    fn write(&self, data: &[u8]) -> io::Result<()> {
+        self.entry.set_secret(data).map_err(keyring_error)
+    }
+
+    fn remove(&self) -> io::Result<()> {
+        // 該当する項目が無い場合は成功として扱う
+        match self.entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(keyring_error(e)),
+        }
+    }
+}
+
+/// メモリ上にのみ保存するバックエンド。
+///
+/// 実行のたびに認証が必要になるため、テストやCIでの使用を想定しています。
+#[derive(Default)]
+pub struct MemoryStore {
+    data: Mutex<Option<Vec<u8>>>, // 保存されているデータ
+}
+
+impl BlobStore for MemoryStore {
+    fn read(&self) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.data.lock().unwrap().clone())
+    }
+
+    fn write(&self, data: &[u8]) -> io::Result<()> {
+        *self.data.lock().unwrap() = Some(data.to_vec());
+        Ok(())
+    }
+
+    fn remove(&self) -> io::Result<()> {
+        *self.data.lock().unwrap() = None;
+        Ok(())
+    }
+}
+
+/// トークンキャッシュ。
+///
+/// `BlobStore`にトークンのリストをyup-oauth2と同じJSON形式で保存し、
+/// yup-oauth2の`TokenStorage`として使用できるようにします。
+/// yup-oauth2はリクエストのたびにトークンを問い合わせるため、読み込んだトークンのリストはメモリ上に保持し、
+/// 保存先（暗号化ファイルの復号やキーリングへのアクセス）はプロセスごとに最初の1回だけ読み込みます。
+pub struct TokenCache {
+    store: Box<dyn BlobStore>,               // トークンの保存先
+    tokens: Mutex<Option<Vec<CachedToken>>>, // 読み込み済みのトークンのリスト
+}
+
+impl TokenCache {
+    pub fn new(store: Box<dyn BlobStore>) -> Self {
+        TokenCache {
+            store,
+            tokens: Mutex::new(None),
+        }
+    }
+
+    /// 保存されているすべてのトークンを読み込む。保存されていない場合は空のリストを返す。
+    pub fn load_tokens(&self) -> io::Result<Vec<CachedToken>> {
+        let mut cached = self.tokens.lock().unwrap();
+        if let Some(tokens) = cached.as_ref() {
+            return Ok(tokens.clone());
+        }
+        let tokens: Vec<CachedToken> = match self.store.read()? {
+            Some(data) => serde_json::from_slice(&data)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            None => Vec::new(),
+        };
+        *cached = Some(tokens.clone());
+        Ok(tokens)
+    }
+
+    /// トークンのリストを保存する。
+    pub fn save_tokens(&self, tokens: &[CachedToken]) -> io::Result<()> {
+        let data = serde_json::to_vec(tokens)?;
+        self.store.write(&data)?;
+        *self.tokens.lock().unwrap() = Some(tokens.to_vec());
+        Ok(())
+    }
+
+    /// 保存されているトークンを削除する。
+    pub fn clear(&self) -> io::Result<()> {
+        self.store.remove()?;
+        *self.tokens.lock().unwrap() = Some(Vec::new());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TokenStorage for TokenCache {
+    async fn set(&self, scopes: &[&str], token: TokenInfo) -> anyhow::Result<()> {
+        let mut tokens = self.load_tokens()?;
+
+        // 同じスコープのトークンは置き換え、それ以外は追加する
+        let scopes: Vec<String> = scopes.iter().map(|s| s.to_string()).collect();
+        match tokens.iter_mut().find(|t| same_scopes(&t.scopes, &scopes)) {
+            Some(existing) => existing.token = token,
+            None => tokens.push(CachedToken { scopes, token }),
+        }
+
+        self.save_tokens(&tokens)?;
+        Ok(())
+    }
+
+    async fn get(&self, scopes: &[&str]) -> Option<TokenInfo> {
+        let tokens = self.load_tokens().ok()?;
+
+        // 要求されたスコープをすべて含むトークンを探す
+        tokens
+            .iter()
+            .find(|t| {
+                scopes
+                    .iter()
+                    .all(|s| t.scopes.iter().any(|ts| scope_covers(ts, s)))
+            })
+            .map(|t| t.token.clone())
+    }
+}
+
+/// 許可されたスコープが、要求されたスコープの権限を含むかどうかを判定する関数。
+///
+/// Googleの読み書きスコープ（例: `.../auth/contacts`）は、対応する読み取り専用スコープ
+/// （`.../auth/contacts.readonly`）の権限を含みます。
+///
+/// # 引数
+/// * `granted` - トークンに許可されているスコープ。
+/// * `requested` - 要求されたスコープ。
+///
+/// # 戻り値
+/// 要求されたスコープの権限を含む場合は`true`。
+pub fn scope_covers(granted: &str, requested: &str) -> bool {
+    granted == requested || requested.strip_suffix(".readonly") == Some(granted)
+}
+
+/// 2つのスコープのリストが同じ集合かどうかを判定する。
+fn same_scopes(a: &[String], b: &[String]) -> bool {
+    a.len() == b.len() && a.iter().all(|s| b.contains(s))
+}
+
+/// 端末からエコーなしでパスフレーズを読み込む関数。
+///
+/// 標準入力が端末でない場合は、そのまま1行読み込みます。
+///
+/// # 引数
+/// * `prompt` - 標準エラー出力に表示するプロンプト。
+///
+/// # 戻り値
+/// 入力されたパスフレーズ（末尾の改行は除く）。
+pub fn read_passphrase(prompt: &str) -> io::Result<String> {
+    eprint!("{}", prompt);
+    io::stderr().flush()?;
+
+    #[cfg(unix)]
+    let saved = {
+        // 端末の場合はエコーを無効にする
+        let mut term: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::isatty(0) } == 1 && unsafe { libc::tcgetattr(0, &mut term) } == 0 {
+            let saved = term;
+            term.c_lflag &= !libc::ECHO;
+            unsafe { libc::tcsetattr(0, libc::TCSANOW, &term) };
+            Some(saved)
+        } else {
+            None
+        }
+    };
+
+    let mut input = String::new();
+    let result = io::stdin().read_line(&mut input);
+
+    #[cfg(unix)]
+    if let Some(saved) = saved {
+        // 端末の設定を元に戻す
+        unsafe { libc::tcsetattr(0, libc::TCSANOW, &saved) };
+        eprintln!();
+    }
+
+    result?;
+    Ok(input.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mod_test::temp_path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// テスト用のトークンを作成する。
+    fn token_info(access_token: &str) -> TokenInfo {
+        serde_json::from_value(serde_json::json!({
+            "access_token": access_token,
+            "refresh_token": null,
+            "expires_at": null,
+            "id_token": null,
+        }))
+        .unwrap()
+    }
+
+    /// 読み込みの回数を数えるバックエンド。
+    struct CountingStore {
+        inner: MemoryStore,
+        reads: Arc<AtomicUsize>,
+    }
+
+    impl BlobStore for CountingStore {
+        fn read(&self) -> io::Result<Option<Vec<u8>>> {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            self.inner.read()
+        }
+
+        fn write(&self, data: &[u8]) -> io::Result<()> {
+            self.inner.write(data)
+        }
+
+        fn remove(&self) -> io::Result<()> {
+            self.inner.remove()
+        }
+    }
+
+    #[tokio::test]
+    async fn get_returns_a_token_covering_the_requested_scopes() {
+        let cache = TokenCache::new(Box::new(MemoryStore::default()));
+        cache.set(&["a", "b"], token_info("ab")).await.unwrap();
+        cache.set(&["c"], token_info("c")).await.unwrap();
+
+        assert_eq!(
+            cache.get(&["b"]).await.and_then(|t| t.access_token),
+            Some("ab".to_string())
+        );
+        assert_eq!(
+            cache.get(&["c"]).await.and_then(|t| t.access_token),
+            Some("c".to_string())
+        );
+        assert!(cache.get(&["a", "c"]).await.is_none());
+
+        // 同じスコープのトークンは置き換える
+        cache.set(&["b", "a"], token_info("ab2")).await.unwrap();
+        assert_eq!(cache.load_tokens().unwrap().len(), 2);
+        assert_eq!(
+            cache.get(&["a"]).await.and_then(|t| t.access_token),
+            Some("ab2".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn read_write_scope_covers_the_readonly_scope() {
+        let contacts = "https://www.googleapis.com/auth/contacts";
+        let readonly = "https://www.googleapis.com/auth/contacts.readonly";
+        assert!(scope_covers(contacts, readonly));
+        assert!(scope_covers(readonly, readonly));
+        assert!(!scope_covers(readonly, contacts));
+        assert!(!scope_covers(
+            "https://www.googleapis.com/auth/con",
+            readonly
+        ));
+
+        let cache = TokenCache::new(Box::new(MemoryStore::default()));
+        cache.set(&[contacts], token_info("rw")).await.unwrap();
+        assert_eq!(
+            cache.get(&[readonly]).await.and_then(|t| t.access_token),
+            Some("rw".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn store_is_read_only_once() {
+        let reads = Arc::new(AtomicUsize::new(0));
+        let cache = TokenCache::new(Box::new(CountingStore {
+            inner: MemoryStore::default(),
+            reads: reads.clone(),
+        }));
+        cache.set(&["a"], token_info("a")).await.unwrap();
+        for _ in 0..3 {
+            assert!(cache.get(&["a"]).await.is_some());
+        }
+        assert_eq!(reads.load(Ordering::SeqCst), 1);
+
+        cache.clear().unwrap();
+        assert!(cache.get(&["a"]).await.is_none());
+    }
+
+    #[test]
+    fn encrypted_store_round_trips_with_a_new_salt_per_write() {
+        let path = temp_path("encrypted");
+        let store = EncryptedFileStore::new(path.clone(), "secret".to_string());
+        store.write(b"first").unwrap();
+        let first = std::fs::read(&path).unwrap();
+        store.write(b"second").unwrap();
+        let second = std::fs::read(&path).unwrap();
+
+        // 書き込みのたびにソルトとノンスを変える
+        let salt = ENCRYPTED_MAGIC.len()..ENCRYPTED_MAGIC.len() + SALT_LEN;
+        let nonce = salt.end..salt.end + NONCE_LEN;
+        assert_ne!(first[salt.clone()], second[salt]);
+        assert_ne!(first[nonce.clone()], second[nonce]);
+
+        // 新しいプロセスと同じく、導出済みの鍵が無い状態から復号する
+        let reopened = EncryptedFileStore::new(path.clone(), "secret".to_string());
+        assert_eq!(reopened.read().unwrap().as_deref(), Some(&b"second"[..]));
+
+        let wrong = EncryptedFileStore::new(path.clone(), "wrong".to_string());
+        assert_eq!(wrong.read().unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        wrong.remove().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_store_makes_an_existing_cache_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = temp_path("file-store");
+        std::fs::write(&path, "old").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        FileStore::new(path.clone()).write(b"new").unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(mode, 0o600);
+        assert_eq!(data, b"new");
+    }
+
+    #[test]
+    fn keyring_store_round_trips_and_reports_backend_errors() {
+        keyring::set_default_credential_builder(keyring::mock::default_credential_builder());
+        let store = KeyringStore::new("work").unwrap();
+        assert_eq!(store.read().unwrap(), None);
+        store.write(b"token").unwrap();
+        assert_eq!(store.read().unwrap().as_deref(), Some(&b"token"[..]));
+        store.remove().unwrap();
+        assert_eq!(store.read().unwrap(), None);
+        store.remove().unwrap();
+
+        // ロックされたキーリングなどのエラーは「トークンが無い」とは区別する
+        let mock = store
+            .entry
+            .get_credential()
+            .downcast_ref::<keyring::mock::MockCredential>()
+            .unwrap();
+        mock.set_error(keyring::Error::NoStorageAccess("locked".into()));
+        assert!(store.read().is_err());
+    }
+
+    #[test]
+    fn storage_kind_is_parsed_from_its_name() {
+        for kind in [
+            TokenStorageKind::File,
+            TokenStorageKind::Encrypted,
+            TokenStorageKind::Keyring,
+            TokenStorageKind::Memory,
+        ] {
+            assert_eq!(kind.as_str().parse::<TokenStorageKind>(), Ok(kind));
+        }
+        assert!("plain".parse::<TokenStorageKind>().is_err());
+    }
+}
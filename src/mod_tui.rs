@@ -0,0 +1,667 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! 同期で変更する連絡先を全画面で一覧表示し、項目ごとに解決方法を選択させる（`sync --tui`）
+
+use crate::mod_diff::{self, ContactDiff}; // 差分のプレビューを表示するため
+use crate::mod_fluent; // 表示する文字列を取得するため
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers}; // キー入力と端末の大きさの変更を読み込むため
+use crossterm::{cursor, execute, queue, terminal}; // 端末を全画面の入力モードにするため
+use fluent::{bundle::FluentBundle, FluentResource}; // ローカライズされた文字列を保持するため
+use intl_memoizer::concurrent::IntlLangMemoizer; // FluentBundleの型引数
+use std::io::{self, Write}; // 端末への書き込みのため
+
+/// 項目ごとの解決方法。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Decision {
+    Undecided,   // まだ選択していない（変更しない）
+    Reject,      // 変更しない
+    Google,      // Google Contactsの状態に合わせる
+    AddressBook, // .addressbookの状態に合わせる
+}
+
+/// 一覧に表示する項目。
+pub struct Item<'a> {
+    pub diff: &'a ContactDiff, // 連絡先の差分
+    pub proposed: Decision,    // 承認（`y`）した場合に使用する解決方法
+}
+
+/// 押されたキー。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Key {
+    Up,         // ↑、k
+    Down,       // ↓、j
+    PageUp,     // Page Up
+    PageDown,   // Page Down
+    Home,       // Home
+    End,        // End
+    Enter,      // Enter
+    Escape,     // Esc
+    Char(char), // その他の文字
+}
+
+/// 表示している画面。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Screen {
+    List,    // 一覧とプレビュー
+    Confirm, // 変更の確認
+}
+
+/// キー入力を処理した結果。
+#[derive(PartialEq, Eq, Debug)]
+enum Outcome {
+    Continue,  // 選択を続ける
+    Confirmed, // 選択を確定した
+    Cancelled, // 中断した
+}
+
+/// 端末を全画面の入力モードにし、終了時（パニックを含む）に元に戻すためのガード。
+struct RawTerminal;
+
+impl RawTerminal {
+    /// 端末をrawモードにし、代替画面に切り替えてカーソルを隠す。
+    fn enable() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        if let Err(e) = execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide) {
+            let _ = terminal::disable_raw_mode();
+            return Err(e);
+        }
+        Ok(RawTerminal)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        // カーソルを表示し、元の画面と端末の設定に戻す
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// 端末の大きさ（行数、桁数）を取得する。取得できない場合は24行80桁とする。
+fn terminal_size() -> (usize, usize) {
+    match terminal::size() {
+        Ok((cols, rows)) if rows > 0 && cols > 0 => (rows as usize, cols as usize),
+        _ => (24, 80),
+    }
+}
+
+/// 端末のキー入力のイベントを、TUIで使用するキーに変換する。
+///
+/// キーを離したイベント（Windows）や、割り当てていないキーは`None`とします。
+fn decode_key(event: &KeyEvent) -> Option<Key> {
+    if event.kind == KeyEventKind::Release {
+        return None;
+    }
+    let key = match event.code {
+        // Ctrl-Cは中断として扱う
+        KeyCode::Char('c') if event.modifiers.contains(KeyModifiers::CONTROL) => Key::Char('q'),
+        KeyCode::Up | KeyCode::Char('k') => Key::Up,
+        KeyCode::Down | KeyCode::Char('j') => Key::Down,
+        KeyCode::PageUp => Key::PageUp,
+        KeyCode::PageDown => Key::PageDown,
+        KeyCode::Home => Key::Home,
+        KeyCode::End => Key::End,
+        KeyCode::Enter => Key::Enter,
+        KeyCode::Esc => Key::Escape,
+        KeyCode::Char(c) => Key::Char(c),
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// 端末から次のキーを読み込む。
+///
+/// 端末の大きさが変わった場合は、再描画できるように`None`を返します。
+fn read_key() -> io::Result<Option<Key>> {
+    loop {
+        match event::read()? {
+            Event::Key(key) => {
+                if let Some(key) = decode_key(&key) {
+                    return Ok(Some(key));
+                }
+            }
+            Event::Resize(_, _) => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+/// 文字の表示幅（全角文字は2桁）を返す。
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x1100..=0x115F
+        | 0x2E80..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+/// 文字列を表示幅`width`に切り詰め、足りない場合は空白で埋める。
+fn fit(s: &str, width: usize) -> String {
+    let mut result = String::new();
+    let mut used = 0;
+    for c in s.chars() {
+        // 制御文字（タブなど）は空白として表示する
+        let c = if c.is_control() { ' ' } else { c };
+        let w = char_width(c);
+        if used + w > width {
+            break;
+        }
+        result.push(c);
+        used += w;
+    }
+    result.push_str(&" ".repeat(width - used));
+    result
+}
+
+/// 項目ごとの解決方法と、一覧で選択している項目。
+struct Selection {
+    decisions: Vec<Decision>, // 項目ごとの解決方法
+    cursor: usize,            // 選択している項目
+    offset: usize,            // 一覧の先頭に表示している項目
+    screen: Screen,           // 表示している画面
+}
+
+impl Selection {
+    /// `len`個の項目をすべて未選択とした状態を作成する。
+    fn new(len: usize) -> Self {
+        Selection {
+            decisions: vec![Decision::Undecided; len],
+            cursor: 0,
+            offset: 0,
+            screen: Screen::List,
+        }
+    }
+
+    /// 一覧に表示できる行数を返す。
+    fn list_height(rows: usize) -> usize {
+        // タイトル、見出し、区切り、ヘルプの4行を除いた半分を一覧に使う
+        (rows.saturating_sub(4) / 2).max(1)
+    }
+
+    /// 選択している項目が一覧に表示されるように先頭の位置を調整する。
+    fn scroll(&mut self, height: usize) {
+        if self.cursor < self.offset {
+            self.offset = self.cursor;
+        } else if self.cursor >= self.offset + height {
+            self.offset = self.cursor + 1 - height;
+        }
+    }
+
+    /// 選択している項目を移動する。
+    fn move_cursor(&mut self, delta: isize) {
+        let last = self.decisions.len() as isize - 1;
+        self.cursor = (self.cursor as isize + delta).clamp(0, last) as usize;
+    }
+
+    /// 選択している項目の解決方法を設定し、次の項目に移動する。
+    fn decide(&mut self, decision: Decision) {
+        self.decisions[self.cursor] = decision;
+        self.move_cursor(1);
+    }
+
+    /// キー入力を処理する。
+    ///
+    /// # 引数
+    /// * `key` - 押されたキー。
+    /// * `items` - 一覧に表示している項目。
+    /// * `page` - Page Up/Page Downで移動する項目の数。
+    ///
+    /// # 戻り値
+    /// 選択を続けるか、確定したか、中断したか。
+    fn handle_key(&mut self, key: Key, items: &[Item], page: isize) -> Outcome {
+        if self.screen == Screen::Confirm {
+            // 確認画面: yで確定、それ以外は一覧に戻る
+            match key {
+                Key::Char('y') => return Outcome::Confirmed,
+                Key::Char('q') => return Outcome::Cancelled,
+                _ => self.screen = Screen::List,
+            }
+            return Outcome::Continue;
+        }
+
+        match key {
+            Key::Up => self.move_cursor(-1),
+            Key::Down => self.move_cursor(1),
+            Key::PageUp => self.move_cursor(-page),
+            Key::PageDown => self.move_cursor(page),
+            Key::Home => self.cursor = 0,
+            Key::End => self.cursor = items.len() - 1,
+            Key::Enter => self.screen = Screen::Confirm,
+            Key::Escape | Key::Char('q') => return Outcome::Cancelled,
+            Key::Char('y') => self.decide(items[self.cursor].proposed),
+            Key::Char('n') => self.decide(Decision::Reject),
+            Key::Char('g') => self.decide(Decision::Google),
+            Key::Char('a') => self.decide(Decision::AddressBook),
+            Key::Char('u') => self.decide(Decision::Undecided),
+            Key::Char('Y') => {
+                for (decision, item) in self.decisions.iter_mut().zip(items) {
+                    if *decision == Decision::Undecided {
+                        *decision = item.proposed;
+                    }
+                }
+            }
+            Key::Char(_) => {}
+        }
+        Outcome::Continue
+    }
+}
+
+/// 解決方法により行われる変更のメッセージIDを返す。
+fn action_id(diff: &ContactDiff, decision: Decision) -> &'static str {
+    let conflict = diff.addressbook.is_some() && diff.google.is_some();
+    match (decision, conflict, diff.google.is_some()) {
+        (Decision::Undecided, _, _) => "tui-action-undecided",
+        (Decision::Reject, _, _) => "tui-action-skip",
+        (Decision::Google, true, _) => "tui-action-update-addressbook",
+        (Decision::AddressBook, true, _) => "tui-action-update-google",
+        // Google Contactsにのみ存在する
+        (Decision::Google, false, true) => "tui-action-add-addressbook",
+        (Decision::AddressBook, false, true) => "tui-action-delete-google",
+        // .addressbookにのみ存在する
+        (Decision::Google, false, false) => "tui-action-delete-addressbook",
+        (Decision::AddressBook, false, false) => "tui-action-add-google",
+    }
+}
+
+/// TUIの画面。
+struct View<'a, 'b> {
+    bundle: &'a FluentBundle<FluentResource, IntlLangMemoizer>, // 表示する文字列
+    items: &'a [Item<'b>],                                      // 一覧に表示する項目
+}
+
+impl View<'_, '_> {
+    /// 翻訳された文字列を取得する。
+    fn tr(&self, message_id: &str) -> String {
+        mod_fluent::get_translation(self.bundle, message_id)
+    }
+
+    /// 項目の種類の表示名を返す。
+    fn kind_label(&self, diff: &ContactDiff) -> String {
+        match (&diff.addressbook, &diff.google) {
+            (Some(_), Some(_)) => self.tr("tui-kind-conflict"),
+            (None, _) => self.tr("tui-kind-google-only"),
+            (_, None) => self.tr("tui-kind-addressbook-only"),
+        }
+    }
+
+    /// 端末の大きさに合わせて画面全体の行を作成する。
+    fn render(&self, selection: &mut Selection, rows: usize, cols: usize) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        match selection.screen {
+            Screen::List => self.render_list(selection, &mut lines, rows, cols),
+            Screen::Confirm => self.render_confirm(selection, &mut lines, cols),
+        }
+        lines.truncate(rows);
+        lines
+    }
+
+    /// 画面全体を描画する。
+    fn draw(&self, selection: &mut Selection, out: &mut impl Write) -> io::Result<()> {
+        let (rows, cols) = terminal_size();
+        let lines = self.render(selection, rows, cols);
+        queue!(
+            out,
+            cursor::MoveTo(0, 0),
+            terminal::Clear(terminal::ClearType::All)
+        )?;
+        write!(out, "{}", lines.join("\r\n"))?;
+        out.flush()
+    }
+
+    /// 一覧とプレビューの画面の行を作成する。
+    fn render_list(
+        &self,
+        selection: &mut Selection,
+        lines: &mut Vec<String>,
+        rows: usize,
+        cols: usize,
+    ) {
+        let height = Selection::list_height(rows);
+        selection.scroll(height);
+
+        let title = format!(
+            "{} ({}/{})",
+            self.tr("tui-title"),
+            selection.cursor + 1,
+            self.items.len()
+        );
+        lines.push(format!("\x1b[1m{}\x1b[0m", fit(&title, cols)));
+
+        // 見出し: 選択、種類、変更、メールアドレス
+        let header = format!(
+            "  {} {} {}",
+            fit(&self.tr("tui-column-kind"), 18),
+            fit(&self.tr("tui-column-action"), 26),
+            self.tr("tui-column-email")
+        );
+        lines.push(format!("\x1b[4m{}\x1b[0m", fit(&header, cols)));
+
+        for index in selection.offset..(selection.offset + height).min(self.items.len()) {
+            let item = &self.items[index];
+            let decision = selection.decisions[index];
+            let row = format!(
+                "{} {} {} {}",
+                if index == selection.cursor { '>' } else { ' ' },
+                fit(&self.kind_label(item.diff), 18),
+                fit(&self.tr(action_id(item.diff, decision)), 26),
+                item.diff.email
+            );
+            let row = fit(&row, cols);
+            // 選択している行は反転表示、未選択の項目は暗く表示する
+            lines.push(match (index == selection.cursor, decision) {
+                (true, _) => format!("\x1b[7m{}\x1b[0m", row),
+                (false, Decision::Undecided) => format!("\x1b[2m{}\x1b[0m", row),
+                (false, _) => row,
+            });
+        }
+        while lines.len() < height + 2 {
+            lines.push(String::new());
+        }
+
+        // 選択している項目の差分のプレビュー
+        lines.push(format!("\x1b[2m{}\x1b[0m", "-".repeat(cols)));
+        let preview_height = rows.saturating_sub(height + 4);
+        let mut preview = Vec::new();
+        let item = &self.items[selection.cursor];
+        let _ = mod_diff::write_unified(
+            &mut preview,
+            std::slice::from_ref(item.diff),
+            ".addressbook",
+            false,
+        );
+        for line in String::from_utf8_lossy(&preview)
+            .lines()
+            .skip(2) // ---/+++の見出しは省略する
+            .take(preview_height)
+        {
+            let text = fit(line, cols);
+            lines.push(match line.chars().next() {
+                Some('-') => format!("\x1b[31m{}\x1b[0m", text),
+                Some('+') => format!("\x1b[32m{}\x1b[0m", text),
+                Some('@') => format!("\x1b[36m{}\x1b[0m", text),
+                _ => text,
+            });
+        }
+        while lines.len() < rows.saturating_sub(1) {
+            lines.push(String::new());
+        }
+
+        lines.push(format!("\x1b[7m{}\x1b[0m", fit(&self.tr("tui-help"), cols)));
+    }
+
+    /// 変更の確認画面の行を作成する。
+    fn render_confirm(&self, selection: &Selection, lines: &mut Vec<String>, cols: usize) {
+        lines.push(format!(
+            "\x1b[1m{}\x1b[0m",
+            fit(&self.tr("tui-confirm-title"), cols)
+        ));
+        lines.push(String::new());
+
+        // 変更の種類ごとの件数（行われる変更のメッセージIDごとに数える）
+        let ids = [
+            "tui-action-add-google",
+            "tui-action-update-google",
+            "tui-action-delete-google",
+            "tui-action-add-addressbook",
+            "tui-action-update-addressbook",
+            "tui-action-delete-addressbook",
+            "tui-action-skip",
+            "tui-action-undecided",
+        ];
+        for id in ids {
+            let count = self
+                .items
+                .iter()
+                .zip(&selection.decisions)
+                .filter(|(item, decision)| action_id(item.diff, **decision) == id)
+                .count();
+            lines.push(fit(&format!("  {}: {}", self.tr(id), count), cols));
+        }
+
+        lines.push(String::new());
+        lines.push(fit(&self.tr("tui-confirm-prompt"), cols));
+    }
+}
+
+/// 同期で変更する連絡先を全画面で一覧表示し、項目ごとに解決方法を選択させる関数。
+///
+/// `y`で提案された解決方法を承認、`n`で拒否（変更しない）、`g`/`a`でGoogle Contacts/.addressbookの状態に合わせます。
+/// `Y`はまだ選択していないすべての項目を承認します。Enterで確認画面を表示し、確認画面で`y`を入力すると選択を確定します。
+/// 選択していない項目は変更しません。端末の大きさが変わった場合は再描画します。
+/// この関数はGoogle Contactsも.addressbookも変更しません。
+///
+/// # 引数
+/// * `bundle` - ローカライズされた文字列と国際化の詳細を含むFluentBundleへの参照。
+/// * `items` - 一覧に表示する項目（空でないこと）。
+///
+/// # 戻り値
+/// 確定した場合は項目ごとの解決方法、`q`やEscで中断した場合は`None`、端末の操作に失敗した場合はエラー。
+pub fn run(
+    bundle: &FluentBundle<FluentResource, IntlLangMemoizer>,
+    items: &[Item],
+) -> io::Result<Option<Vec<Decision>>> {
+    let view = View { bundle, items };
+    let mut selection = Selection::new(items.len());
+
+    let _terminal = RawTerminal::enable()?;
+    let mut out = io::stdout();
+    loop {
+        view.draw(&mut selection, &mut out)?;
+        let key = match read_key()? {
+            Some(key) => key,
+            // 端末の大きさが変わった
+            None => continue,
+        };
+        let page = Selection::list_height(terminal_size().0) as isize;
+
+        match selection.handle_key(key, items, page) {
+            Outcome::Continue => {}
+            Outcome::Confirmed => return Ok(Some(selection.decisions)),
+            Outcome::Cancelled => return Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mod_diff::Record;
+
+    /// テスト用の値を作成する。
+    fn record(name: &str) -> Record {
+        Record {
+            nickname: "Tanaka".to_string(),
+            name: name.to_string(),
+            email: "taro@example.com".to_string(),
+            fcc: String::new(),
+            comment: String::new(),
+        }
+    }
+
+    /// 値が異なる連絡先、Google Contactsにのみ存在する連絡先、.addressbookにのみ存在する連絡先の差分。
+    fn diffs() -> Vec<ContactDiff> {
+        vec![
+            ContactDiff {
+                email: "taro@example.com".to_string(),
+                addressbook: Some(record("Tanaka Taro")),
+                google: Some(record("Taro Tanaka")),
+                changed: vec!["name"],
+            },
+            ContactDiff {
+                email: "hanako@example.com".to_string(),
+                addressbook: None,
+                google: Some(record("Hanako")),
+                changed: Vec::new(),
+            },
+            ContactDiff {
+                email: "jiro@example.com".to_string(),
+                addressbook: Some(record("Jiro")),
+                google: None,
+                changed: Vec::new(),
+            },
+        ]
+    }
+
+    /// 差分から一覧の項目を作成する。
+    fn items(diffs: &[ContactDiff]) -> Vec<Item<'_>> {
+        diffs
+            .iter()
+            .map(|diff| Item {
+                diff,
+                proposed: Decision::Google,
+            })
+            .collect()
+    }
+
+    /// 修飾キーなしのキー入力のイベントを作成する。
+    fn press(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn decodes_keys() {
+        assert_eq!(decode_key(&press(KeyCode::Char('j'))), Some(Key::Down));
+        assert_eq!(decode_key(&press(KeyCode::Down)), Some(Key::Down));
+        assert_eq!(decode_key(&press(KeyCode::Char('k'))), Some(Key::Up));
+        assert_eq!(decode_key(&press(KeyCode::Up)), Some(Key::Up));
+        assert_eq!(decode_key(&press(KeyCode::PageDown)), Some(Key::PageDown));
+        assert_eq!(decode_key(&press(KeyCode::Enter)), Some(Key::Enter));
+        assert_eq!(decode_key(&press(KeyCode::Esc)), Some(Key::Escape));
+        assert_eq!(decode_key(&press(KeyCode::Char('Y'))), Some(Key::Char('Y')));
+        assert_eq!(
+            decode_key(&KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)),
+            Some(Key::Char('q'))
+        );
+        assert_eq!(decode_key(&press(KeyCode::F(1))), None);
+
+        let mut release = press(KeyCode::Char('j'));
+        release.kind = KeyEventKind::Release;
+        assert_eq!(decode_key(&release), None);
+    }
+
+    #[test]
+    fn repeated_keys_are_each_handled() {
+        let diffs = diffs();
+        let items = items(&diffs);
+        let mut selection = Selection::new(items.len());
+
+        // キーを押し続けた場合も1つずつ処理し、最後の項目で止まる
+        for _ in 0..3 {
+            let key = decode_key(&press(KeyCode::Char('j'))).unwrap();
+            assert_eq!(selection.handle_key(key, &items, 10), Outcome::Continue);
+        }
+        assert_eq!(selection.cursor, 2);
+        selection.handle_key(Key::Up, &items, 10);
+        selection.handle_key(Key::Up, &items, 10);
+        selection.handle_key(Key::Up, &items, 10);
+        assert_eq!(selection.cursor, 0);
+        selection.handle_key(Key::PageDown, &items, 10);
+        assert_eq!(selection.cursor, 2);
+        selection.handle_key(Key::Home, &items, 10);
+        assert_eq!(selection.cursor, 0);
+    }
+
+    #[test]
+    fn decisions_are_set_per_item_and_confirmed() {
+        let diffs = diffs();
+        let items = items(&diffs);
+        let mut selection = Selection::new(items.len());
+
+        selection.handle_key(Key::Char('a'), &items, 10);
+        assert_eq!(selection.cursor, 1);
+        selection.handle_key(Key::Char('n'), &items, 10);
+        // Yは未選択の項目だけを承認する
+        selection.handle_key(Key::Char('Y'), &items, 10);
+        assert_eq!(
+            selection.decisions,
+            vec![Decision::AddressBook, Decision::Reject, Decision::Google]
+        );
+
+        // 確認画面でy以外を押すと一覧に戻る
+        selection.handle_key(Key::Enter, &items, 10);
+        assert_eq!(
+            selection.handle_key(Key::Char('x'), &items, 10),
+            Outcome::Continue
+        );
+        assert_eq!(selection.screen, Screen::List);
+        selection.handle_key(Key::Enter, &items, 10);
+        assert_eq!(
+            selection.handle_key(Key::Char('y'), &items, 10),
+            Outcome::Confirmed
+        );
+
+        let mut selection = Selection::new(items.len());
+        assert_eq!(
+            selection.handle_key(Key::Escape, &items, 10),
+            Outcome::Cancelled
+        );
+    }
+
+    #[test]
+    fn list_scrolls_to_keep_the_cursor_visible() {
+        let mut selection = Selection::new(20);
+        let height = Selection::list_height(12);
+        assert_eq!(height, 4);
+
+        selection.move_cursor(6);
+        selection.scroll(height);
+        assert_eq!(selection.offset, 3);
+        selection.move_cursor(-5);
+        selection.scroll(height);
+        assert_eq!(selection.offset, 1);
+    }
+
+    #[test]
+    fn actions_depend_on_the_kind_of_item() {
+        let diffs = diffs();
+        assert_eq!(
+            action_id(&diffs[0], Decision::Google),
+            "tui-action-update-addressbook"
+        );
+        assert_eq!(
+            action_id(&diffs[1], Decision::AddressBook),
+            "tui-action-delete-google"
+        );
+        assert_eq!(
+            action_id(&diffs[2], Decision::AddressBook),
+            "tui-action-add-google"
+        );
+        assert_eq!(action_id(&diffs[2], Decision::Reject), "tui-action-skip");
+    }
+
+    #[test]
+    fn render_fits_the_terminal_size() {
+        let bundle = mod_fluent::init_fluent_bundle("en-US");
+        let diffs = diffs();
+        let items = items(&diffs);
+        let view = View {
+            bundle: &bundle,
+            items: &items,
+        };
+        let mut selection = Selection::new(items.len());
+
+        for (rows, cols) in [(24, 80), (10, 40)] {
+            let lines = view.render(&mut selection, rows, cols);
+            assert_eq!(lines.len(), rows);
+        }
+    }
+}
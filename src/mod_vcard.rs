@@ -0,0 +1,1005 @@
+// Copyright 2023 Takahiro Yoshizawa
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Google Contactsの連絡先をvCard（4.0はRFC 6350、3.0はRFC 2426）形式で書き出し、vCard（2.1、3.0、4.0）を読み込む
+
+use base64::{engine::general_purpose, Engine as _}; // ENCODING=BASE64の値をデコードするため
+use encoding_rs::{Encoding, UTF_8}; // CHARSETパラメータの文字コードでデコードするため
+use google_people1::api::{
+    Address, Biography, Birthday, Date, EmailAddress, FieldMetadata, Name, Nickname, Organization,
+    Person, PhoneNumber,
+}; // 書き出す連絡先と読み込んだ連絡先
+use std::collections::{BTreeMap, HashMap}; // 連絡先グループのリソース名とラベル名の対応
+use std::fmt; // エラーメッセージの表示のため
+use std::str::FromStr; // コマンドラインの値を解析するため
+
+/// 1行の最大の長さ（バイト数、改行を除く）。これを超える行は折り返す。
+const MAX_LINE_OCTETS: usize = 75;
+
+/// vCardのバージョン。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Version {
+    V3, // vCard 3.0（RFC 2426）
+    V4, // vCard 4.0（RFC 6350）
+}
+
+impl FromStr for Version {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "3.0" | "3" => Ok(Version::V3),
+            "4.0" | "4" => Ok(Version::V4),
+            _ => Err(s.to_string()),
+        }
+    }
+}
+
+impl Version {
+    /// VERSIONプロパティの値を返す。
+    fn as_str(self) -> &'static str {
+        match self {
+            Version::V3 => "3.0",
+            Version::V4 => "4.0",
+        }
+    }
+}
+
+/// 連絡先のUIDを返す。
+///
+/// Google Contactsのリソース名（`people/c123`）の`/`を`-`に置き換えた値で、ファイル名にも使用できます。
+pub fn uid(person: &Person) -> String {
+    person
+        .resource_name
+        .as_deref()
+        .unwrap_or_default()
+        .replace('/', "-")
+}
+
+/// テキストの値をエスケープする（バックスラッシュ、カンマ、セミコロン、改行）。
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ',' => escaped.push_str("\\,"),
+            ';' => escaped.push_str("\\;"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// 1行を75バイトごとに折り返して追加する（UTF-8の文字の途中では折り返さない）。
+fn push_folded(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > MAX_LINE_OCTETS {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+/// 主な値として設定されているかどうかを返す。
+fn is_primary(metadata: &Option<FieldMetadata>) -> bool {
+    metadata
+        .as_ref()
+        .and_then(|m| m.primary)
+        .unwrap_or_default()
+}
+
+/// TYPEとPREFのパラメータを返す。
+///
+/// # 引数
+/// * `version` - vCardのバージョン。3.0では大文字で、PREFをTYPEの値として書き出す。
+/// * `types` - TYPEの値（小文字）。
+/// * `primary` - 主な値かどうか。
+fn type_params(version: Version, types: &[&str], primary: bool) -> String {
+    let mut types: Vec<String> = types.iter().map(|t| t.to_string()).collect();
+    if version == Version::V3 {
+        if primary {
+            types.push("pref".to_string());
+        }
+        types.iter_mut().for_each(|t| *t = t.to_uppercase());
+    }
+
+    let mut params = String::new();
+    if !types.is_empty() {
+        params.push_str(";TYPE=");
+        params.push_str(&types.join(","));
+    }
+    if version == Version::V4 && primary {
+        params.push_str(";PREF=1");
+    }
+    params
+}
+
+/// Google Contactsのメールアドレスの種類をvCardのTYPEの値に変換する。
+fn email_types(type_: Option<&str>) -> Vec<&'static str> {
+    match type_ {
+        Some("home") => vec!["home"],
+        Some("work") => vec!["work"],
+        _ => Vec::new(),
+    }
+}
+
+/// Google Contactsの電話番号の種類をvCardのTYPEの値に変換する。
+fn phone_types(type_: Option<&str>) -> Vec<&'static str> {
+    match type_ {
+        Some("home") => vec!["home", "voice"],
+        Some("work") => vec!["work", "voice"],
+        Some("mobile") => vec!["cell"],
+        Some("homeFax") => vec!["home", "fax"],
+        Some("workFax") => vec!["work", "fax"],
+        Some("otherFax") => vec!["fax"],
+        Some("pager") => vec!["pager"],
+        Some("workMobile") => vec!["work", "cell"],
+        Some("workPager") => vec!["work", "pager"],
+        _ => vec!["voice"],
+    }
+}
+
+/// Google Contactsの住所の種類をvCardのTYPEの値に変換する。
+fn address_types(type_: Option<&str>) -> Vec<&'static str> {
+    match type_ {
+        Some("home") => vec!["home"],
+        Some("work") => vec!["work"],
+        _ => Vec::new(),
+    }
+}
+
+/// 連絡先を1つのvCardに変換する関数。
+///
+/// 名前（読み仮名を含む）、ニックネーム、種類付きのすべてのメールアドレス、電話番号、住所、所属組織、誕生日、
+/// メモ、連絡先グループ（CATEGORIES）を書き出します。読み仮名は多くのアプリケーションが対応している
+/// `X-PHONETIC-*`プロパティを使用します。3.0では年の無い誕生日を書き出しません。
+///
+/// # 引数
+/// * `person` - 変換する連絡先。
+/// * `labels` - 連絡先グループのリソース名とラベル名の対応（ユーザーが作成したグループのみ）。
+/// * `version` - vCardのバージョン。
+///
+/// # 戻り値
+/// 改行をCRLFとし、75バイトで折り返したvCard。
+pub fn write_card(person: &Person, labels: &BTreeMap<String, String>, version: Version) -> String {
+    let mut out = String::new();
+    let mut push = |name: &str, value: &str| push_folded(&mut out, &format!("{}:{}", name, value));
+    let join = |values: &[Option<&String>]| {
+        values
+            .iter()
+            .map(|v| escape(v.map_or("", |s| s.as_str())))
+            .collect::<Vec<_>>()
+            .join(";")
+    };
+
+    push("BEGIN", "VCARD");
+    push("VERSION", version.as_str());
+    push("PRODID", "-//gcontacts//EN");
+
+    // 名前（FNは必須のため、名前が無い場合は所属組織名かメールアドレスを使用する）
+    let name = person.names.iter().flatten().next();
+    let organization = person.organizations.iter().flatten().next();
+    let email = person
+        .email_addresses
+        .iter()
+        .flatten()
+        .find_map(|e| e.value.as_ref());
+    let formatted_name = name
+        .and_then(|n| n.display_name.as_ref())
+        .or_else(|| organization.and_then(|o| o.name.as_ref()))
+        .or(email)
+        .map_or(String::new(), |s| escape(s));
+    push("FN", &formatted_name);
+    match name {
+        Some(n) => push(
+            "N",
+            &join(&[
+                n.family_name.as_ref(),
+                n.given_name.as_ref(),
+                n.middle_name.as_ref(),
+                n.honorific_prefix.as_ref(),
+                n.honorific_suffix.as_ref(),
+            ]),
+        ),
+        None => push("N", ";;;;"),
+    }
+    if let Some(n) = name {
+        for (property, value) in [
+            ("X-PHONETIC-FIRST-NAME", &n.phonetic_given_name),
+            ("X-PHONETIC-MIDDLE-NAME", &n.phonetic_middle_name),
+            ("X-PHONETIC-LAST-NAME", &n.phonetic_family_name),
+        ] {
+            if let Some(value) = value.as_deref().filter(|v| !v.is_empty()) {
+                push(property, &escape(value));
+            }
+        }
+    }
+
+    // ニックネーム
+    let nicknames: Vec<String> = person
+        .nicknames
+        .iter()
+        .flatten()
+        .filter_map(|n| n.value.as_deref())
+        .filter(|v| !v.is_empty())
+        .map(escape)
+        .collect();
+    if !nicknames.is_empty() {
+        push("NICKNAME", &nicknames.join(","));
+    }
+
+    // メールアドレス（3.0ではINTERNETを付ける）
+    for e in person.email_addresses.iter().flatten() {
+        let Some(value) = e.value.as_deref().filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        let mut types = email_types(e.type_.as_deref());
+        if version == Version::V3 {
+            types.insert(0, "internet");
+        }
+        let params = type_params(version, &types, is_primary(&e.metadata));
+        push(&format!("EMAIL{}", params), &escape(value));
+    }
+
+    // 電話番号
+    for p in person.phone_numbers.iter().flatten() {
+        let Some(value) = p.value.as_deref().filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        let types = phone_types(p.type_.as_deref());
+        let params = type_params(version, &types, is_primary(&p.metadata));
+        push(&format!("TEL{}", params), &escape(value));
+    }
+
+    // 住所（私書箱;拡張住所;番地;市区町村;都道府県;郵便番号;国）
+    for a in person.addresses.iter().flatten() {
+        let types = address_types(a.type_.as_deref());
+        let params = type_params(version, &types, is_primary(&a.metadata));
+        let value = join(&[
+            a.po_box.as_ref(),
+            a.extended_address.as_ref(),
+            a.street_address.as_ref(),
+            a.city.as_ref(),
+            a.region.as_ref(),
+            a.postal_code.as_ref(),
+            a.country.as_ref(),
+        ]);
+        push(&format!("ADR{}", params), &value);
+    }
+
+    // 所属組織と役職
+    if let Some(o) = organization {
+        if o.name.is_some() || o.department.is_some() {
+            push("ORG", &join(&[o.name.as_ref(), o.department.as_ref()]));
+        }
+        if let Some(title) = o.title.as_deref().filter(|v| !v.is_empty()) {
+            push("TITLE", &escape(title));
+        }
+    }
+
+    // 誕生日
+    if let Some(birthday) = person.birthdays.iter().flatten().next() {
+        let date = birthday.date.as_ref();
+        match (
+            date.and_then(|d| d.year),
+            date.and_then(|d| d.month),
+            date.and_then(|d| d.day),
+        ) {
+            (Some(y), Some(m), Some(d)) if version == Version::V3 => {
+                push("BDAY", &format!("{:04}-{:02}-{:02}", y, m, d))
+            }
+            (Some(y), Some(m), Some(d)) => push("BDAY", &format!("{:04}{:02}{:02}", y, m, d)),
+            (None, Some(m), Some(d)) if version == Version::V4 => {
+                push("BDAY", &format!("--{:02}{:02}", m, d))
+            }
+            _ => {
+                if let Some(text) = birthday.text.as_deref().filter(|_| version == Version::V4) {
+                    push("BDAY;VALUE=text", &escape(text));
+                }
+            }
+        }
+    }
+
+    // メモ
+    if let Some(note) = person
+        .biographies
+        .iter()
+        .flatten()
+        .find_map(|b| b.value.as_deref())
+        .filter(|v| !v.is_empty())
+    {
+        push("NOTE", &escape(note));
+    }
+
+    // 連絡先グループ（ユーザーが作成したグループのみ）
+    let categories: Vec<String> = person
+        .memberships
+        .iter()
+        .flatten()
+        .filter_map(|m| {
+            let group = m.contact_group_membership.as_ref()?;
+            labels.get(group.contact_group_resource_name.as_ref()?)
+        })
+        .map(|label| escape(label))
+        .collect();
+    if !categories.is_empty() {
+        push("CATEGORIES", &categories.join(","));
+    }
+
+    let uid = uid(person);
+    if !uid.is_empty() {
+        push("UID", &escape(&uid));
+    }
+    push("END", "VCARD");
+    out
+}
+
+/// 解析エラー。
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,     // エラーが発生した行番号
+    pub message: String, // エラーの内容
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// .addressbookに追加する行に使用する値。
+#[derive(Clone, Default)]
+pub struct RowValues {
+    pub nickname: Option<String>, // ニックネーム（`None`の場合は作成する）
+    pub fcc: Option<String>,      // Fcc（`None`の場合は設定ファイルの値）
+}
+
+/// 読み込んだvCard（Google CSVやJSONから読み込んだ連絡先も同じ形で扱う）。
+#[derive(Default)]
+pub struct Card {
+    pub person: Person, // Google Contactsに作成する連絡先（リソース名はJSONから読み込んだ場合のみ。etagは無い）
+    pub categories: Vec<String>, // CATEGORIESのラベル名
+    pub rows: HashMap<String, RowValues>, // 小文字のメールアドレスごとの.addressbookの行の値（JSONのみ）
+}
+
+impl Card {
+    /// カードのすべてのメールアドレスを返す（PREFが指定されたものが先頭）。
+    pub fn emails(&self) -> Vec<&str> {
+        self.person
+            .email_addresses
+            .iter()
+            .flatten()
+            .filter_map(|e| e.value.as_deref())
+            .collect()
+    }
+}
+
+/// 1つのプロパティ（折り返しを戻した1行）。
+struct ContentLine {
+    name: String,                  // プロパティ名（大文字、グループを除く）
+    params: Vec<(String, String)>, // パラメータ名（大文字）と値
+    value: String,                 // エンコーディングをデコードした値（エスケープはそのまま）
+}
+
+impl ContentLine {
+    /// TYPEパラメータの値を小文字で返す（2.1の値だけのパラメータを含む）。
+    fn types(&self) -> Vec<String> {
+        self.params
+            .iter()
+            .filter(|(name, _)| name == "TYPE")
+            .flat_map(|(_, value)| value.split(','))
+            .map(|t| t.trim().to_ascii_lowercase())
+            .collect()
+    }
+
+    /// 主な値として指定されているかどうか（3.0以前はTYPE=PREF、4.0はPREFパラメータ）を返す。
+    fn is_pref(&self) -> bool {
+        self.types().iter().any(|t| t == "pref") || self.params.iter().any(|(n, _)| n == "PREF")
+    }
+
+    /// テキストの値のエスケープを戻して返す。
+    fn text(&self) -> String {
+        split_components(&self.value, None).concat()
+    }
+
+    /// 構造化された値（`;`区切り）の各要素を返す。
+    fn components(&self) -> Vec<String> {
+        split_components(&self.value, Some(';'))
+    }
+
+    /// 値のリスト（`,`区切り）を返す。空の要素は除く。
+    fn list(&self) -> Vec<String> {
+        split_components(&self.value, Some(','))
+            .into_iter()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .collect()
+    }
+}
+
+/// 値を区切り文字で分け、各要素のエスケープ（`\n`、`\,`、`\;`、`\\`）を戻す。
+///
+/// # 引数
+/// * `value` - 分ける値。
+/// * `separator` - 区切り文字。`None`の場合は分けない。
+fn split_components(value: &str, separator: Option<char>) -> Vec<String> {
+    let mut components = vec![String::new()];
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        let current = components.last_mut().unwrap();
+        match c {
+            '\\' => match chars.next() {
+                Some('n') | Some('N') => current.push('\n'),
+                Some(escaped) => current.push(escaped),
+                None => current.push('\\'),
+            },
+            c if Some(c) == separator => components.push(String::new()),
+            c => current.push(c),
+        }
+    }
+    components
+}
+
+/// 要素を取得する。空の場合は`None`を返す。
+fn component(components: &[String], index: usize) -> Option<String> {
+    components
+        .get(index)
+        .map(|c| c.trim())
+        .filter(|c| !c.is_empty())
+        .map(|c| c.to_string())
+}
+
+/// 行がQuoted-Printableの値で、次の行に続く（ソフト改行で終わる）かどうかを返す。
+fn continues_quoted_printable(line: &[u8]) -> bool {
+    let head = line.split(|&b| b == b':').next().unwrap_or_default();
+    String::from_utf8_lossy(head)
+        .to_ascii_uppercase()
+        .contains("QUOTED-PRINTABLE")
+        && line.ends_with(b"=")
+}
+
+/// 折り返された行を戻す関数。
+///
+/// 空白またはタブで始まる行は前の行の続きとします。2.1のQuoted-Printableの値は、`=`で終わる行を次の行に続けます。
+/// 値の文字コードは行ごとに異なる場合があるため、行はデコードせずにバイト列のまま扱います。
+///
+/// # 戻り値
+/// 行番号（元の行の1から始まる番号）と、戻した行のタプルのベクター。空行は含まない。
+fn unfold(data: &[u8]) -> Vec<(usize, Vec<u8>)> {
+    let mut lines: Vec<(usize, Vec<u8>)> = Vec::new();
+    for (index, raw) in data.split(|&b| b == b'\n').enumerate() {
+        let raw = raw.strip_suffix(b"\r").unwrap_or(raw);
+        if let Some((_, last)) = lines.last_mut() {
+            if continues_quoted_printable(last) {
+                last.pop();
+                let start = raw.iter().position(|b| !b.is_ascii_whitespace());
+                last.extend_from_slice(&raw[start.unwrap_or(raw.len())..]);
+                continue;
+            }
+            if raw.starts_with(b" ") || raw.starts_with(b"\t") {
+                last.extend_from_slice(&raw[1..]);
+                continue;
+            }
+        }
+        if !raw.iter().all(|b| b.is_ascii_whitespace()) {
+            lines.push((index + 1, raw.to_vec()));
+        }
+    }
+    lines
+}
+
+/// 1行をプロパティ名、パラメータ、値に分ける関数。
+///
+/// 2.1の値だけのパラメータ（`TEL;HOME;VOICE:`や`NOTE;QUOTED-PRINTABLE:`）はTYPEまたはENCODINGとして扱います。
+/// 値はQuoted-PrintableとBase64の場合はデコードしてから、CHARSETパラメータの文字コード（無い場合はUTF-8）として
+/// 解釈します。対応していない文字コードの場合はエラーを返します。
+fn parse_line(number: usize, line: &[u8]) -> Result<ContentLine, ParseError> {
+    let error = |message: &str| ParseError {
+        line: number,
+        message: message.to_string(),
+    };
+
+    // 引用符の外にある最初の':'で、プロパティ名とパラメータを値から分ける
+    let mut quoted = false;
+    let colon = line.iter().position(|&b| match b {
+        b'"' => {
+            quoted = !quoted;
+            false
+        }
+        b':' => !quoted,
+        _ => false,
+    });
+    let Some(colon) = colon else {
+        return Err(error("expected ':' after the property name"));
+    };
+    let (head, value) = (String::from_utf8_lossy(&line[..colon]), &line[colon + 1..]);
+
+    let mut parts = head.split(';');
+    let name = parts.next().unwrap_or_default();
+    let name = name
+        .rsplit('.')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_uppercase();
+    if name.is_empty() {
+        return Err(error("missing property name"));
+    }
+    let params: Vec<(String, String)> = parts
+        .map(|param| match param.split_once('=') {
+            Some((n, v)) => (
+                n.trim().to_ascii_uppercase(),
+                v.trim().trim_matches('"').to_string(),
+            ),
+            None => {
+                let value = param.trim().to_string();
+                match value.to_ascii_uppercase().as_str() {
+                    "QUOTED-PRINTABLE" | "BASE64" | "8BIT" | "7BIT" => {
+                        ("ENCODING".to_string(), value)
+                    }
+                    _ => ("TYPE".to_string(), value),
+                }
+            }
+        })
+        .collect();
+
+    let encoding = params
+        .iter()
+        .find(|(n, _)| n == "ENCODING")
+        .map(|(_, v)| v.to_ascii_uppercase());
+    let charset = match params.iter().find(|(n, _)| n == "CHARSET") {
+        Some((_, label)) => Encoding::for_label(label.as_bytes())
+            .ok_or_else(|| error(&format!("unsupported charset \"{}\"", label)))?,
+        None => UTF_8,
+    };
+    let value = match encoding.as_deref() {
+        Some("QUOTED-PRINTABLE") => {
+            let decoded = quoted_printable::decode(value, quoted_printable::ParseMode::Robust)
+                .map_err(|e| error(&e.to_string()))?;
+            charset
+                .decode_without_bom_handling(&decoded)
+                .0
+                .replace("\r\n", "\n")
+        }
+        Some("B") | Some("BASE64") => {
+            let decoded = general_purpose::STANDARD
+                .decode(String::from_utf8_lossy(value).trim())
+                .map_err(|e| error(&e.to_string()))?;
+            charset.decode_without_bom_handling(&decoded).0.into_owned()
+        }
+        _ => charset.decode_without_bom_handling(value).0.into_owned(),
+    };
+
+    Ok(ContentLine {
+        name,
+        params,
+        value,
+    })
+}
+
+/// vCardのTYPEの値から、Google Contactsのメールアドレスまたは住所の種類を返す。
+fn location_type(types: &[String]) -> &'static str {
+    if types.iter().any(|t| t == "home") {
+        "home"
+    } else if types.iter().any(|t| t == "work") {
+        "work"
+    } else {
+        "other"
+    }
+}
+
+/// vCardのTYPEの値から、Google Contactsの電話番号の種類を返す。
+fn phone_type(types: &[String]) -> &'static str {
+    let has = |name: &str| types.iter().any(|t| t == name);
+    match (has("fax"), has("cell"), has("pager")) {
+        (true, _, _) if has("home") => "homeFax",
+        (true, _, _) if has("work") => "workFax",
+        (true, _, _) => "otherFax",
+        (_, true, _) if has("work") => "workMobile",
+        (_, true, _) => "mobile",
+        (_, _, true) if has("work") => "workPager",
+        (_, _, true) => "pager",
+        _ => location_type(types),
+    }
+}
+
+/// BDAYの値を誕生日に変換する。
+///
+/// `YYYY-MM-DD`、`YYYYMMDD`（時刻が続いてもよい）、`--MMDD`、`--MM-DD`を日付として、それ以外はテキストとして扱います。
+pub fn parse_birthday(value: &str) -> Birthday {
+    let digits: String = value.chars().filter(|c| c.is_ascii_digit()).collect();
+    let number = |range: std::ops::Range<usize>| digits.get(range).and_then(|d| d.parse().ok());
+    let (year, month, day) = if value.starts_with("--") && digits.len() == 4 {
+        (None, number(0..2), number(2..4))
+    } else if digits.len() >= 8 {
+        (number(0..4), number(4..6), number(6..8))
+    } else {
+        (None, None, None)
+    };
+
+    match (month, day) {
+        (Some(m), Some(d)) if (1..=12).contains(&m) && (1..=31).contains(&d) => Birthday {
+            date: Some(Date {
+                year,
+                month: Some(m),
+                day: Some(d),
+            }),
+            ..Default::default()
+        },
+        _ => Birthday {
+            text: Some(value.to_string()),
+            ..Default::default()
+        },
+    }
+}
+
+/// 誕生日を`YYYY-MM-DD`（年が無い場合は`--MM-DD`）の形式に変換する関数。
+///
+/// 日付の無い誕生日はテキストをそのまま返します。
+pub fn format_birthday(birthday: &Birthday) -> Option<String> {
+    match birthday.date.as_ref().map(|d| (d.year, d.month, d.day)) {
+        Some((Some(y), Some(m), Some(d))) => Some(format!("{:04}-{:02}-{:02}", y, m, d)),
+        Some((None, Some(m), Some(d))) => Some(format!("--{:02}-{:02}", m, d)),
+        _ => birthday.text.clone(),
+    }
+}
+
+/// 要素が1つ以上あるベクターに値を追加する。
+fn push_value<T>(field: &mut Option<Vec<T>>, value: T, first: bool) {
+    let values = field.get_or_insert_with(Vec::new);
+    if first {
+        values.insert(0, value);
+    } else {
+        values.push(value);
+    }
+}
+
+/// 連絡先の最初の名前を返す（無い場合は作成する）。
+fn first_name(person: &mut Person) -> &mut Name {
+    &mut person.names.get_or_insert_with(|| vec![Name::default()])[0]
+}
+
+/// 連絡先の最初の所属組織を返す（無い場合は作成する）。
+fn first_organization(person: &mut Person) -> &mut Organization {
+    &mut person
+        .organizations
+        .get_or_insert_with(|| vec![Organization::default()])[0]
+}
+
+/// 1つのプロパティを連絡先に反映する。
+fn apply_line(card: &mut Card, line: &ContentLine) {
+    let person = &mut card.person;
+
+    match line.name.as_str() {
+        "FN" => first_name(person).display_name = Some(line.text()).filter(|v| !v.is_empty()),
+        "N" => {
+            let c = line.components();
+            let n = first_name(person);
+            n.family_name = component(&c, 0);
+            n.given_name = component(&c, 1);
+            n.middle_name = component(&c, 2);
+            n.honorific_prefix = component(&c, 3);
+            n.honorific_suffix = component(&c, 4);
+        }
+        "X-PHONETIC-FIRST-NAME" => first_name(person).phonetic_given_name = Some(line.text()),
+        "X-PHONETIC-MIDDLE-NAME" => first_name(person).phonetic_middle_name = Some(line.text()),
+        "X-PHONETIC-LAST-NAME" => first_name(person).phonetic_family_name = Some(line.text()),
+        "NICKNAME" => {
+            for value in line.list() {
+                push_value(
+                    &mut person.nicknames,
+                    Nickname {
+                        value: Some(value),
+                        ..Default::default()
+                    },
+                    false,
+                );
+            }
+        }
+        "EMAIL" => {
+            let value = line.text().trim().trim_start_matches("mailto:").to_string();
+            if !value.is_empty() {
+                let email = EmailAddress {
+                    value: Some(value),
+                    type_: Some(location_type(&line.types()).to_string()),
+                    ..Default::default()
+                };
+                push_value(&mut person.email_addresses, email, line.is_pref());
+            }
+        }
+        "TEL" => {
+            let value = line.text().trim().trim_start_matches("tel:").to_string();
+            if !value.is_empty() {
+                let phone = PhoneNumber {
+                    value: Some(value),
+                    type_: Some(phone_type(&line.types()).to_string()),
+                    ..Default::default()
+                };
+                push_value(&mut person.phone_numbers, phone, line.is_pref());
+            }
+        }
+        "ADR" => {
+            let c = line.components();
+            let address = Address {
+                po_box: component(&c, 0),
+                extended_address: component(&c, 1),
+                street_address: component(&c, 2),
+                city: component(&c, 3),
+                region: component(&c, 4),
+                postal_code: component(&c, 5),
+                country: component(&c, 6),
+                type_: Some(location_type(&line.types()).to_string()),
+                ..Default::default()
+            };
+            push_value(&mut person.addresses, address, line.is_pref());
+        }
+        "ORG" => {
+            let c = line.components();
+            let o = first_organization(person);
+            o.name = component(&c, 0);
+            o.department = component(&c, 1);
+        }
+        "TITLE" => first_organization(person).title = Some(line.text()).filter(|v| !v.is_empty()),
+        "BDAY" => person.birthdays = Some(vec![parse_birthday(line.text().trim())]),
+        "NOTE" => {
+            let note = line.text();
+            let biographies = person.biographies.get_or_insert_with(|| {
+                vec![Biography {
+                    value: Some(String::new()),
+                    content_type: Some("TEXT_PLAIN".to_string()),
+                    ..Default::default()
+                }]
+            });
+            let value = biographies[0].value.get_or_insert_with(String::new);
+            if !value.is_empty() {
+                value.push('\n');
+            }
+            value.push_str(&note);
+        }
+        "CATEGORIES" => card.categories.extend(line.list()),
+        _ => {}
+    }
+}
+
+/// 名前の要素から表示名を作成する（FNが無い場合）。
+pub fn compose_display_name(name: &Name) -> Option<String> {
+    let parts: Vec<&str> = [
+        &name.honorific_prefix,
+        &name.given_name,
+        &name.middle_name,
+        &name.family_name,
+        &name.honorific_suffix,
+    ]
+    .iter()
+    .filter_map(|part| part.as_deref())
+    .collect();
+    Some(parts.join(" ")).filter(|n| !n.is_empty())
+}
+
+/// vCardを読み込む関数。
+///
+/// 2.1、3.0、4.0の`BEGIN:VCARD`から`END:VCARD`までを1つの連絡先として読み込みます。
+/// 折り返された行、2.1のQuoted-Printable（ソフト改行を含む）とBase64の値に対応し、値は行ごとのCHARSETパラメータの
+/// 文字コード（無い場合はUTF-8）として解釈します。
+/// 名前、読み仮名（`X-PHONETIC-*`）、ニックネーム、メールアドレス、電話番号、住所、所属組織と役職、誕生日、メモ、
+/// CATEGORIESを読み込み、それ以外のプロパティ（写真など）は無視します。入れ子のvCard（2.1のAGENT）も無視します。
+///
+/// # 引数
+/// * `data` - vCardファイルの内容（デコードしていないバイト列）。
+///
+/// # 戻り値
+/// 成功した場合は読み込んだvCardのベクター、形式が正しくない場合はエラー。
+pub fn parse(data: &[u8]) -> Result<Vec<Card>, ParseError> {
+    let mut cards = Vec::new();
+    let mut current: Option<Card> = None;
+    let mut nested = 0;
+    let mut last_line = 0;
+
+    for (number, raw) in unfold(data) {
+        last_line = number;
+        let line = parse_line(number, &raw)?;
+        let is_vcard = line.value.trim().eq_ignore_ascii_case("VCARD");
+        match (line.name.as_str(), current.as_mut()) {
+            ("BEGIN", None) if is_vcard => current = Some(Card::default()),
+            ("BEGIN", Some(_)) if is_vcard => nested += 1,
+            ("END", Some(_)) if is_vcard && nested > 0 => nested -= 1,
+            ("END", Some(_)) if is_vcard => {
+                let mut card = current.take().unwrap();
+                if let Some(name) = card.person.names.as_mut().map(|n| &mut n[0]) {
+                    if name.display_name.is_none() {
+                        name.display_name = compose_display_name(name);
+                    }
+                }
+                cards.push(card);
+            }
+            (_, Some(_)) if nested > 0 => {}
+            (_, Some(card)) => apply_line(card, &line),
+            (_, None) => {
+                return Err(ParseError {
+                    line: number,
+                    message: format!("unexpected {} outside BEGIN:VCARD", line.name),
+                })
+            }
+        }
+    }
+
+    if current.is_some() {
+        return Err(ParseError {
+            line: last_line,
+            message: "missing END:VCARD".to_string(),
+        });
+    }
+    Ok(cards)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mod_test;
+
+    /// テスト用の連絡先を作成する。
+    fn person() -> Person {
+        mod_test::person()
+            .resource_name("people/c123")
+            .full_name("Taro Tanaka", "Tanaka", "Taro")
+            .nickname("taro")
+            .primary_email("taro@example.com", "work")
+            .typed_email("taro@example.org", "home")
+            .birthday(4, 1)
+            .biography("memo; line 1\nline 2, end")
+            .group("contactGroups/abc")
+            .group("contactGroups/myContacts")
+            .build()
+    }
+
+    /// 連絡先グループのリソース名とラベル名の対応。
+    fn labels() -> BTreeMap<String, String> {
+        BTreeMap::from([("contactGroups/abc".to_string(), "Friends".to_string())])
+    }
+
+    #[test]
+    fn escapes_text_values() {
+        assert_eq!(escape("a\\b,c;d\r\ne"), r"a\\b\,c\;d\ne");
+    }
+
+    #[test]
+    fn folds_long_lines_without_splitting_characters() {
+        let mut out = String::new();
+        push_folded(&mut out, &"あ".repeat(30));
+        let lines: Vec<&str> = out.trim_end_matches("\r\n").split("\r\n").collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|l| l.len() <= MAX_LINE_OCTETS));
+        assert_eq!(lines.concat().replace(' ', ""), "あ".repeat(30));
+    }
+
+    #[test]
+    fn writes_a_version_4_card() {
+        let card = write_card(&person(), &labels(), Version::V4);
+        let lines: Vec<&str> = card.split("\r\n").collect();
+        for expected in [
+            "BEGIN:VCARD",
+            "VERSION:4.0",
+            "FN:Taro Tanaka",
+            "N:Tanaka;Taro;;;",
+            "NICKNAME:taro",
+            "EMAIL;TYPE=work;PREF=1:taro@example.com",
+            "EMAIL;TYPE=home:taro@example.org",
+            "BDAY:--0401",
+            r"NOTE:memo\; line 1\nline 2\, end",
+            "CATEGORIES:Friends",
+            "UID:people-c123",
+            "END:VCARD",
+        ] {
+            assert!(lines.contains(&expected), "{}\n{}", expected, card);
+        }
+    }
+
+    #[test]
+    fn writes_a_version_3_card() {
+        let card = write_card(&person(), &labels(), Version::V3);
+        assert!(card.contains("VERSION:3.0\r\n"));
+        assert!(card.contains("EMAIL;TYPE=INTERNET,WORK,PREF:taro@example.com\r\n"));
+        // 年の無い誕生日は3.0では書き出さない
+        assert!(!card.contains("BDAY"));
+    }
+
+    #[test]
+    fn round_trips_a_written_card() {
+        for version in [Version::V3, Version::V4] {
+            let cards = parse(write_card(&person(), &labels(), version).as_bytes()).unwrap();
+            assert_eq!(cards.len(), 1);
+            let card = &cards[0];
+            assert_eq!(card.emails(), vec!["taro@example.com", "taro@example.org"]);
+            assert_eq!(card.categories, vec!["Friends"]);
+
+            let name = &card.person.names.as_ref().unwrap()[0];
+            assert_eq!(name.display_name.as_deref(), Some("Taro Tanaka"));
+            assert_eq!(name.family_name.as_deref(), Some("Tanaka"));
+            assert_eq!(name.given_name.as_deref(), Some("Taro"));
+            let note = card.person.biographies.as_ref().unwrap()[0]
+                .value
+                .as_deref();
+            assert_eq!(note, Some("memo; line 1\nline 2, end"));
+            let nickname = card.person.nicknames.as_ref().unwrap()[0].value.as_deref();
+            assert_eq!(nickname, Some("taro"));
+        }
+    }
+
+    #[test]
+    fn decodes_version_2_1_encodings_with_their_charset() {
+        let data = "BEGIN:VCARD\r\n\
+                    VERSION:2.1\r\n\
+                    N;CHARSET=SHIFT_JIS;ENCODING=QUOTED-PRINTABLE:=93=63=92=86;=91=BE=98=59\r\n\
+                    NOTE;QUOTED-PRINTABLE:line 1=0D=0A=\r\n\
+                    line 2\r\n\
+                    EMAIL;INTERNET;PREF:taro@example.com\r\n\
+                    X-MEMO;CHARSET=UTF-8;ENCODING=BASE64:44GC44GE\r\n\
+                    END:VCARD\r\n";
+        let cards = parse(data.as_bytes()).unwrap();
+        let name = &cards[0].person.names.as_ref().unwrap()[0];
+        assert_eq!(name.family_name.as_deref(), Some("田中"));
+        assert_eq!(name.given_name.as_deref(), Some("太郎"));
+        let note = cards[0].person.biographies.as_ref().unwrap()[0]
+            .value
+            .as_deref();
+        assert_eq!(note, Some("line 1\nline 2"));
+        assert_eq!(cards[0].emails(), vec!["taro@example.com"]);
+    }
+
+    #[test]
+    fn decodes_8bit_values_with_their_charset() {
+        // 「表」の2バイト目は'\\'と同じ0x5Cのため、デコードしてからエスケープを戻す必要がある
+        let (name, _, _) = encoding_rs::SHIFT_JIS.encode("N;CHARSET=Shift_JIS:山田;表子;;;\r\n");
+        let (full_name, _, _) = encoding_rs::SHIFT_JIS.encode("FN;CHARSET=Shift_JIS:山田 表子\r\n");
+        let mut data = b"BEGIN:VCARD\r\nVERSION:2.1\r\n".to_vec();
+        data.extend_from_slice(&name);
+        data.extend_from_slice(&full_name);
+        data.extend_from_slice("NOTE:メモ\r\nEMAIL:hyoko@example.com\r\nEND:VCARD\r\n".as_bytes());
+
+        let cards = parse(&data).unwrap();
+        let name = &cards[0].person.names.as_ref().unwrap()[0];
+        assert_eq!(name.family_name.as_deref(), Some("山田"));
+        assert_eq!(name.given_name.as_deref(), Some("表子"));
+        assert_eq!(name.display_name.as_deref(), Some("山田 表子"));
+        // CHARSETの無い行はUTF-8として解釈する
+        let note = cards[0].person.biographies.as_ref().unwrap()[0]
+            .value
+            .as_deref();
+        assert_eq!(note, Some("メモ"));
+    }
+
+    #[test]
+    fn rejects_an_unsupported_charset() {
+        let data = "BEGIN:VCARD\r\n\
+                    VERSION:2.1\r\n\
+                    N;CHARSET=X-UNKNOWN;ENCODING=QUOTED-PRINTABLE:=41\r\n\
+                    END:VCARD\r\n";
+        let error = parse(data.as_bytes()).err().unwrap();
+        assert_eq!(error.line, 3);
+        assert!(error.message.contains("X-UNKNOWN"));
+    }
+
+    #[test]
+    fn reports_an_unterminated_card() {
+        assert!(parse(b"BEGIN:VCARD\r\nVERSION:3.0\r\nFN:Taro\r\n").is_err());
+    }
+}